@@ -0,0 +1,109 @@
+//! Capture control driven by the host over the ring's command channel:
+//! pausing, the opcode filter and the counters reported back.
+//!
+//! The init thread ends in [`serve`], polling for commands and replying to
+//! each, until the host asks us to unload or the mapping goes away.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::RwLock;
+
+use ring_protocol::command::{CaptureStatus, Command, FilterMode, OpcodeFilter, Reply};
+
+use crate::{hooks, ipc};
+
+/// How often to look for commands, in milliseconds.
+const POLL_INTERVAL_MS: u32 = 20;
+
+// ---------------------------------------------------------------------------
+// Global state
+// ---------------------------------------------------------------------------
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+static FILTER: RwLock<OpcodeFilter> = RwLock::new(OpcodeFilter {
+    mode: FilterMode::Off,
+    opcodes: Vec::new(),
+});
+
+/// Packets written to the ring.
+static CAPTURED: AtomicU32 = AtomicU32::new(0);
+
+/// Packets the opcode filter kept out.
+static FILTERED: AtomicU32 = AtomicU32::new(0);
+
+// ---------------------------------------------------------------------------
+// Public API
+// ---------------------------------------------------------------------------
+
+/// Whether a packet with `opcode` should go to the ring, counting it if the
+/// filter keeps it out.  Called for every packet before it is written.
+pub fn should_capture(opcode: u32) -> bool {
+    if PAUSED.load(Ordering::Relaxed) {
+        return false;
+    }
+    let allowed = FILTER.read().map_or(true, |filter| filter.allows(opcode));
+    if !allowed {
+        FILTERED.fetch_add(1, Ordering::Relaxed);
+    }
+    allowed
+}
+
+/// Count a packet the ring accepted.  Packets it dropped or skipped are not
+/// captured; the ring header counts the drops.
+pub fn packet_written() {
+    CAPTURED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Answer host commands until one asks us to unload (returns `true`) or the
+/// shared memory is gone (returns `false`).
+pub fn serve() -> bool {
+    loop {
+        let Some(ring) = ipc::ring() else {
+            return false;
+        };
+        let channel = ring.commands();
+        while let Some((id, command)) = channel.next_command() {
+            let unload = command == Some(Command::Unload);
+            let reply = command.map_or(Reply::Rejected, |command| handle(&command));
+            let _ = channel.send_reply(id, &reply);
+            if unload {
+                return true;
+            }
+        }
+        unsafe { windows::Win32::System::Threading::Sleep(POLL_INTERVAL_MS) };
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+fn handle(command: &Command) -> Reply {
+    match command {
+        Command::Pause => PAUSED.store(true, Ordering::Relaxed),
+        Command::Resume => PAUSED.store(false, Ordering::Relaxed),
+        Command::SetFilter(filter) => match FILTER.write() {
+            Ok(mut current) => *current = filter.clone(),
+            Err(_) => return Reply::Rejected,
+        },
+        Command::QueryStatus => return Reply::Status(status()),
+        // The caller unloads once the reply is out.
+        Command::Unload => {}
+    }
+    Reply::Done
+}
+
+fn status() -> CaptureStatus {
+    let (filter_mode, filter_len) = FILTER
+        .read()
+        .map(|filter| (filter.mode, filter.opcodes.len() as u32))
+        .unwrap_or_default();
+    CaptureStatus {
+        paused: PAUSED.load(Ordering::Relaxed),
+        hooks_active: hooks::hooks_active(),
+        filter_mode,
+        filter_len,
+        captured: CAPTURED.load(Ordering::Relaxed),
+        filtered: FILTERED.load(Ordering::Relaxed),
+    }
+}
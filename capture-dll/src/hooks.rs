@@ -25,8 +25,16 @@
 //! WoW 32-bit uses `__thiscall`: `this` in ECX, remaining args on stack
 //! (callee-cleans like stdcall).  Our hook functions use `extern "thiscall"`
 //! which matches this ABI exactly (stable since Rust 1.73).
+//!
+//! # Unhooking
+//!
+//! WoW's main thread may be inside a detour, or inside the original function
+//! called from one, when we unhook.  Every detour (and the window procedure)
+//! counts itself in [`InFlight`] for its whole call, so [`uninstall_hooks`]
+//! restores the patched bytes, waits for the count to drain and only then
+//! frees the trampolines.
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use crate::ipc;
 use crate::version::WowOffsets;
@@ -37,7 +45,7 @@ use windows::Win32::System::Memory::{
     VirtualAlloc, VirtualFree, VirtualProtect, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE,
     PAGE_EXECUTE_READWRITE, PAGE_PROTECTION_FLAGS,
 };
-use windows::Win32::System::Threading::GetCurrentProcess;
+use windows::Win32::System::Threading::{GetCurrentProcess, Sleep};
 
 // ---------------------------------------------------------------------------
 // Constants
@@ -49,12 +57,25 @@ const MAX_HOOK_SIZE: usize = 16;
 /// Size of each trampoline allocation (overkill, but one page is fine).
 const TRAMPOLINE_SIZE: usize = 64;
 
+/// How long to wait for in-flight detour calls to return when unhooking.
+const DRAIN_TIMEOUT_MS: u32 = 5_000;
+
+/// Extra wait once no call is counted, for threads between a patched JMP and
+/// the count (or between the count and their `ret`).
+const DRAIN_GRACE_MS: u32 = 100;
+
 // ---------------------------------------------------------------------------
 // Global state
 // ---------------------------------------------------------------------------
 
 static HOOKS_INSTALLED: AtomicBool = AtomicBool::new(false);
 
+/// Set when unhooking starts; detours still running just pass through.
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// Calls currently inside our detours or the window procedure.
+static IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+
 static mut SEND_ORIG_BYTES: [u8; MAX_HOOK_SIZE] = [0; MAX_HOOK_SIZE];
 static mut RECV_ORIG_BYTES: [u8; MAX_HOOK_SIZE] = [0; MAX_HOOK_SIZE];
 
@@ -68,6 +89,23 @@ static mut RECV_TRAMPOLINE: *mut u8 = std::ptr::null_mut();
 static mut SEND_TARGET: *mut u8 = std::ptr::null_mut();
 static mut RECV_TARGET: *mut u8 = std::ptr::null_mut();
 
+/// Counts one call into our code for as long as it is alive.  Take it first
+/// thing in every detour and hold it across the call to the original.
+pub(crate) struct InFlight;
+
+impl InFlight {
+    pub(crate) fn enter() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        InFlight
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 // ---------------------------------------------------------------------------
 // CDataStore helpers
 // ---------------------------------------------------------------------------
@@ -94,9 +132,11 @@ unsafe fn cds_size(cds: usize) -> usize {
 /// Two stack parameters + return value.  Getting the param count wrong
 /// causes `ret 4` instead of `ret 8`, corrupting the caller's stack.
 unsafe extern "thiscall" fn send_hook(this: usize, data_store: usize, param2: usize) -> u32 {
+    let _call = InFlight::enter();
+
     // Capture packet data BEFORE calling original (CDataStore is definitely
     // valid here; it may be freed by the caller after Send2 returns).
-    if data_store > 0x10000 {
+    if !STOPPING.load(Ordering::Acquire) && data_store > 0x10000 {
         let data_ptr = cds_data(data_store);
         let data_size = cds_size(data_store);
 
@@ -132,8 +172,10 @@ unsafe extern "thiscall" fn recv_hook(
     param1: usize,
     data_store: usize,
 ) -> u32 {
+    let _call = InFlight::enter();
+
     // Capture incoming packet data.
-    if data_store > 0x10000 {
+    if !STOPPING.load(Ordering::Acquire) && data_store > 0x10000 {
         let data_ptr = cds_data(data_store);
         let data_size = cds_size(data_store);
 
@@ -339,18 +381,30 @@ pub unsafe fn install_hooks(offsets: &WowOffsets) -> bool {
 }
 
 /// Remove hooks and restore original function bytes.
-pub unsafe fn uninstall_hooks() {
+///
+/// Returns `true` once no thread is left in our code, so the DLL may be
+/// unloaded.  If calls are still in flight after [`DRAIN_TIMEOUT_MS`], the
+/// trampolines are left allocated and `false` is returned.
+pub unsafe fn uninstall_hooks() -> bool {
+    STOPPING.store(true, Ordering::Release);
     if !HOOKS_INSTALLED.load(Ordering::Acquire) {
-        return;
+        return wait_for_drain();
     }
 
-    // Restore original bytes
+    // Restore original bytes; new calls no longer reach the detours.
     if !RECV_TARGET.is_null() {
         restore_bytes(RECV_TARGET, &RECV_ORIG_BYTES, RECV_HOOK_SIZE);
     }
     if !SEND_TARGET.is_null() {
         restore_bytes(SEND_TARGET, &SEND_ORIG_BYTES, SEND_HOOK_SIZE);
     }
+    HOOKS_INSTALLED.store(false, Ordering::Release);
+
+    // Calls already inside a detour still return through its trampoline.
+    if !wait_for_drain() {
+        file_log("capture-dll: detour calls still in flight, keeping trampolines");
+        return false;
+    }
 
     // Free trampolines
     if !RECV_TRAMPOLINE.is_null() {
@@ -364,7 +418,24 @@ pub unsafe fn uninstall_hooks() {
 
     SEND_TARGET = std::ptr::null_mut();
     RECV_TARGET = std::ptr::null_mut();
-    HOOKS_INSTALLED.store(false, Ordering::Release);
+    true
+}
+
+/// Wait until no call is in flight, then for [`DRAIN_GRACE_MS`] more, and
+/// check again.  `false` on timeout.
+unsafe fn wait_for_drain() -> bool {
+    let mut waited = 0;
+    while waited < DRAIN_TIMEOUT_MS {
+        if IN_FLIGHT.load(Ordering::SeqCst) == 0 {
+            Sleep(DRAIN_GRACE_MS);
+            if IN_FLIGHT.load(Ordering::SeqCst) == 0 {
+                return true;
+            }
+        }
+        Sleep(10);
+        waited += 10;
+    }
+    false
 }
 
 /// Returns `true` when hooks are currently active.
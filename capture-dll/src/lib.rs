@@ -18,9 +18,15 @@
 //! 2. Creates the shared-memory ring buffer ([`ipc::init_shared_memory`]).
 //! 3. Installs inline detours on the packet functions ([`hooks::install_hooks`]).
 //! 4. Subclasses WoW's main window for host-to-DLL commands ([`wndproc::install_wndproc`]).
+//! 5. Serves commands from the host's command channel ([`control::serve`])
+//!    until the host asks it to unload.
 //!
-//! On `DLL_PROCESS_DETACH` everything is torn down in reverse order.
+//! On `DLL_PROCESS_DETACH` everything is torn down in reverse order, unless
+//! the process is exiting.  An unload requested by the host tears down the
+//! same way from the init thread, and only frees the library once no thread
+//! is left in our code.
 
+mod control;
 mod hooks;
 mod ipc;
 mod version;
@@ -31,7 +37,9 @@ use std::ffi::c_void;
 use once_cell::sync::OnceCell;
 
 use windows::Win32::Foundation::{BOOL, HINSTANCE, HMODULE, TRUE};
-use windows::Win32::System::LibraryLoader::DisableThreadLibraryCalls;
+use windows::Win32::System::LibraryLoader::{
+    DisableThreadLibraryCalls, FreeLibraryAndExitThread,
+};
 use windows::Win32::System::SystemServices::{DLL_PROCESS_ATTACH, DLL_PROCESS_DETACH};
 use windows::Win32::System::Threading::{CreateThread, THREAD_CREATION_FLAGS};
 use windows::Win32::System::Diagnostics::Debug::OutputDebugStringA;
@@ -76,7 +84,7 @@ static STATE: OnceCell<CaptureState> = OnceCell::new();
 pub unsafe extern "system" fn DllMain(
     dll: HINSTANCE,
     reason: u32,
-    _reserved: *mut c_void,
+    reserved: *mut c_void,
 ) -> BOOL {
     if reason == DLL_PROCESS_ATTACH {
         // Suppress DLL_THREAD_ATTACH / DLL_THREAD_DETACH notifications to
@@ -101,7 +109,11 @@ pub unsafe extern "system" fn DllMain(
 
         TRUE
     } else if reason == DLL_PROCESS_DETACH {
-        cleanup();
+        // A non-null `reserved` means the process is exiting: other threads
+        // are already gone, possibly mid-detour, and the memory goes anyway.
+        if reserved.is_null() {
+            cleanup();
+        }
         TRUE
     } else {
         TRUE
@@ -210,6 +222,17 @@ unsafe fn init_thread_inner(param: *mut c_void) -> u32 {
 
     file_log("capture-dll: initialisation complete\n");
     debug_log("capture-dll: initialisation complete\n");
+
+    // 5. Serve host commands on this thread until asked to unload.
+    if control::serve() {
+        file_log("capture-dll: unloading on host request\n");
+        debug_log("capture-dll: unloading on host request\n");
+        if cleanup() {
+            FreeLibraryAndExitThread(HMODULE(dll_instance.0), 0);
+        }
+        file_log("capture-dll: hooks still in use, staying loaded\n");
+        debug_log("capture-dll: hooks still in use, staying loaded\n");
+    }
     0
 }
 
@@ -217,18 +240,24 @@ unsafe fn init_thread_inner(param: *mut c_void) -> u32 {
 // Cleanup
 // ---------------------------------------------------------------------------
 
-/// Reverse-order teardown of everything the DLL installed.
-unsafe fn cleanup() {
+/// Reverse-order teardown of everything the DLL installed.  Returns `false`
+/// if a thread was still in our code, in which case the shared memory stays
+/// mapped and the DLL must stay loaded.
+unsafe fn cleanup() -> bool {
     debug_log("capture-dll: cleanup\n");
 
     // 1. Restore the original window procedure.
     wndproc::uninstall_wndproc();
 
-    // 2. Remove inline detours.
-    hooks::uninstall_hooks();
+    // 2. Remove inline detours, waiting out calls still inside them or the
+    //    window procedure.
+    if !hooks::uninstall_hooks() {
+        return false;
+    }
 
-    // 3. Unmap shared memory.
+    // 3. Unmap shared memory.  No detour can be writing to it any more.
     ipc::cleanup();
+    true
 }
 
 // ---------------------------------------------------------------------------
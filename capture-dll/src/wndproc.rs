@@ -45,6 +45,10 @@ unsafe extern "system" fn capture_wndproc(
     wparam: WPARAM,
     lparam: LPARAM,
 ) -> LRESULT {
+    // Counted so unloading waits for us, including across the call to the
+    // original procedure.
+    let _call = crate::hooks::InFlight::enter();
+
     // Check if this is a capture command.
     if wparam.0 == CAPTURE_WPARAM {
         match msg {
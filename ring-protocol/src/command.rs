@@ -0,0 +1,347 @@
+//! The command channel: two small queues of fixed-size slots after the
+//! packet ring, one carrying [`Command`]s from the host to the DLL and one
+//! carrying a [`Reply`] to each back, matched by id.
+//!
+//! Each queue has a single producer and a single consumer and works like
+//! the packet ring: `head` and `tail` are free-running slot counters, each
+//! stored by one side only, with a release store after the slot it covers
+//! is written or read.
+
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::WriteError;
+
+/// Slots in each queue.
+pub const COMMAND_SLOTS: usize = 8;
+
+/// Size of one slot: id, code and payload length, then the payload.
+pub const COMMAND_SLOT_SIZE: usize = 256;
+
+/// Bytes of a slot left for the payload.
+const SLOT_PAYLOAD_SIZE: usize = COMMAND_SLOT_SIZE - 12;
+
+/// Most opcodes a [`Command::SetFilter`] can carry.
+pub const MAX_FILTER_OPCODES: usize = (SLOT_PAYLOAD_SIZE - 8) / 4;
+
+/// Size of one queue: its counters, then the slots.
+const QUEUE_SIZE: usize = std::mem::size_of::<QueueHeader>() + COMMAND_SLOTS * COMMAND_SLOT_SIZE;
+
+/// Size of the channel in the shared mapping.
+pub const COMMAND_CHANNEL_SIZE: usize = 2 * QUEUE_SIZE;
+
+const CMD_PAUSE: u32 = 1;
+const CMD_RESUME: u32 = 2;
+const CMD_SET_FILTER: u32 = 3;
+const CMD_QUERY_STATUS: u32 = 4;
+const CMD_UNLOAD: u32 = 5;
+
+const REPLY_DONE: u32 = 1;
+const REPLY_STATUS: u32 = 2;
+const REPLY_REJECTED: u32 = 3;
+
+#[repr(C)]
+struct QueueHeader {
+    /// Slots written so far. Stored by the producer.
+    head: AtomicU32,
+    /// Slots read so far. Stored by the consumer.
+    tail: AtomicU32,
+}
+
+/// One queue in the channel: its counters and its slots.
+struct Queue<'a> {
+    header: &'a QueueHeader,
+    slots: *mut u8,
+}
+
+/// Which opcodes the DLL captures.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterMode {
+    /// Every opcode.
+    #[default]
+    Off = 0,
+    /// Only the listed opcodes.
+    Only = 1,
+    /// Every opcode but the listed ones.
+    Except = 2,
+}
+
+impl FilterMode {
+    fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(FilterMode::Off),
+            1 => Some(FilterMode::Only),
+            2 => Some(FilterMode::Except),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpcodeFilter {
+    pub mode: FilterMode,
+    pub opcodes: Vec<u32>,
+}
+
+impl OpcodeFilter {
+    pub fn allows(&self, opcode: u32) -> bool {
+        match self.mode {
+            FilterMode::Off => true,
+            FilterMode::Only => self.opcodes.contains(&opcode),
+            FilterMode::Except => !self.opcodes.contains(&opcode),
+        }
+    }
+}
+
+/// What the host asks of the DLL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Stop writing packets to the ring until resumed.
+    Pause,
+    Resume,
+    /// Replace the opcode filter. At most [`MAX_FILTER_OPCODES`] opcodes.
+    SetFilter(OpcodeFilter),
+    /// Ask for a [`Reply::Status`].
+    QueryStatus,
+    /// Remove the hooks and unload the DLL, after replying.
+    Unload,
+}
+
+/// The DLL's state and counters, as of a [`Command::QueryStatus`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureStatus {
+    pub paused: bool,
+    pub hooks_active: bool,
+    pub filter_mode: FilterMode,
+    pub filter_len: u32,
+    /// Packets written to the ring. Drops are counted in the ring header.
+    pub captured: u32,
+    /// Packets the opcode filter kept out.
+    pub filtered: u32,
+}
+
+/// The DLL's answer to a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    Done,
+    Status(CaptureStatus),
+    /// The command didn't make sense to the DLL.
+    Rejected,
+}
+
+impl Command {
+    fn encode(&self) -> (u32, Vec<u32>) {
+        match self {
+            Command::Pause => (CMD_PAUSE, Vec::new()),
+            Command::Resume => (CMD_RESUME, Vec::new()),
+            Command::SetFilter(filter) => {
+                let mut words = vec![filter.mode as u32, filter.opcodes.len() as u32];
+                words.extend(&filter.opcodes);
+                (CMD_SET_FILTER, words)
+            }
+            Command::QueryStatus => (CMD_QUERY_STATUS, Vec::new()),
+            Command::Unload => (CMD_UNLOAD, Vec::new()),
+        }
+    }
+
+    fn decode(code: u32, words: &[u32]) -> Option<Self> {
+        Some(match code {
+            CMD_PAUSE => Command::Pause,
+            CMD_RESUME => Command::Resume,
+            CMD_SET_FILTER => {
+                let (&mode, &count) = (words.first()?, words.get(1)?);
+                let opcodes = words.get(2..2 + count as usize)?.to_vec();
+                Command::SetFilter(OpcodeFilter {
+                    mode: FilterMode::from_u32(mode)?,
+                    opcodes,
+                })
+            }
+            CMD_QUERY_STATUS => Command::QueryStatus,
+            CMD_UNLOAD => Command::Unload,
+            _ => return None,
+        })
+    }
+}
+
+impl Reply {
+    fn encode(&self) -> (u32, Vec<u32>) {
+        match self {
+            Reply::Done => (REPLY_DONE, Vec::new()),
+            Reply::Status(status) => (
+                REPLY_STATUS,
+                vec![
+                    status.paused as u32,
+                    status.hooks_active as u32,
+                    status.filter_mode as u32,
+                    status.filter_len,
+                    status.captured,
+                    status.filtered,
+                ],
+            ),
+            Reply::Rejected => (REPLY_REJECTED, Vec::new()),
+        }
+    }
+
+    fn decode(code: u32, words: &[u32]) -> Option<Self> {
+        Some(match code {
+            REPLY_DONE => Reply::Done,
+            REPLY_STATUS => {
+                let [paused, hooks_active, mode, filter_len, captured, filtered] =
+                    *words.get(..6)?
+                else {
+                    return None;
+                };
+                Reply::Status(CaptureStatus {
+                    paused: paused != 0,
+                    hooks_active: hooks_active != 0,
+                    filter_mode: FilterMode::from_u32(mode)?,
+                    filter_len,
+                    captured,
+                    filtered,
+                })
+            }
+            REPLY_REJECTED => Reply::Rejected,
+            _ => return None,
+        })
+    }
+}
+
+/// A view of the command channel in a ring's mapping. The host sends
+/// commands and reads replies; the DLL does the opposite.
+pub struct CommandChannel<'a> {
+    base: NonNull<u8>,
+    _region: PhantomData<&'a [u8]>,
+}
+
+// As for `Ring`: the queues are only touched through atomics and slot
+// copies the protocol keeps disjoint.
+unsafe impl Send for CommandChannel<'_> {}
+unsafe impl Sync for CommandChannel<'_> {}
+
+impl<'a> CommandChannel<'a> {
+    /// # Safety
+    ///
+    /// `base` must point at `COMMAND_CHANNEL_SIZE` zeroed or channel bytes,
+    /// 4-byte aligned and mapped for `'a`.
+    pub(crate) unsafe fn new(base: NonNull<u8>) -> Self {
+        CommandChannel {
+            base,
+            _region: PhantomData,
+        }
+    }
+
+    /// The request queue at 0, the reply queue at 1.
+    fn queue(&self, index: usize) -> Queue<'_> {
+        // SAFETY: both queues lie within the channel, which lives for 'a.
+        unsafe {
+            let base = self.base.as_ptr().add(index * QUEUE_SIZE);
+            Queue {
+                header: &*(base as *const QueueHeader),
+                slots: base.add(std::mem::size_of::<QueueHeader>()),
+            }
+        }
+    }
+
+    fn requests(&self) -> Queue<'_> {
+        self.queue(0)
+    }
+
+    fn replies(&self) -> Queue<'_> {
+        self.queue(1)
+    }
+
+    /// Queue `command` under `id`. Host side.
+    pub fn send_command(&self, id: u32, command: &Command) -> Result<(), WriteError> {
+        let (code, words) = command.encode();
+        self.requests().push(id, code, &words)
+    }
+
+    /// The next command and its id, or `None` if there is none. The
+    /// command is `None` if it didn't decode, to be answered with
+    /// `Reply::Rejected`. DLL side.
+    pub fn next_command(&self) -> Option<(u32, Option<Command>)> {
+        let (id, code, words) = self.requests().pop()?;
+        Some((id, Command::decode(code, &words)))
+    }
+
+    /// Answer the command `id`. DLL side.
+    pub fn send_reply(&self, id: u32, reply: &Reply) -> Result<(), WriteError> {
+        let (code, words) = reply.encode();
+        self.replies().push(id, code, &words)
+    }
+
+    /// The next reply that decodes and the id of its command. Host side.
+    pub fn next_reply(&self) -> Option<(u32, Reply)> {
+        loop {
+            let (id, code, words) = self.replies().pop()?;
+            if let Some(reply) = Reply::decode(code, &words) {
+                return Some((id, reply));
+            }
+        }
+    }
+}
+
+impl Queue<'_> {
+    fn slot(&self, index: u32) -> *mut u8 {
+        // SAFETY: the index is reduced to a slot within the queue.
+        unsafe {
+            self.slots
+                .add(index as usize % COMMAND_SLOTS * COMMAND_SLOT_SIZE)
+        }
+    }
+
+    fn push(&self, id: u32, code: u32, words: &[u32]) -> Result<(), WriteError> {
+        let len = words.len() * 4;
+        if len > SLOT_PAYLOAD_SIZE {
+            return Err(WriteError::TooLarge);
+        }
+        let head = self.header.head.load(Ordering::Relaxed);
+        let tail = self.header.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) as usize >= COMMAND_SLOTS {
+            return Err(WriteError::Full);
+        }
+
+        let mut bytes = Vec::with_capacity(12 + len);
+        for word in [id, code, len as u32].iter().chain(words) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        // SAFETY: the slot at `head` is the producer's until `head` moves
+        // past it, and the bytes fit in it.
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), self.slot(head), bytes.len()) };
+        self.header
+            .head
+            .store(head.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<(u32, u32, Vec<u32>)> {
+        let head = self.header.head.load(Ordering::Acquire);
+        let tail = self.header.tail.load(Ordering::Relaxed);
+        let pending = head.wrapping_sub(tail) as usize;
+        if pending == 0 {
+            return None;
+        }
+        if pending > COMMAND_SLOTS {
+            // Counters from a scribbled-over channel; drop what's queued.
+            self.header.tail.store(head, Ordering::Release);
+            return None;
+        }
+
+        let mut bytes = [0u8; COMMAND_SLOT_SIZE];
+        // SAFETY: the slot at `tail` is the consumer's until `tail` moves
+        // past it.
+        unsafe { ptr::copy_nonoverlapping(self.slot(tail), bytes.as_mut_ptr(), bytes.len()) };
+        self.header
+            .tail
+            .store(tail.wrapping_add(1), Ordering::Release);
+
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+        let (id, code, len) = (words.next()?, words.next()?, words.next()? as usize);
+        let payload = words.take(len.min(SLOT_PAYLOAD_SIZE) / 4).collect();
+        Some((id, code, payload))
+    }
+}
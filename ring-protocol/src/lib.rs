@@ -0,0 +1,983 @@
+//! The packet ring shared by the capture DLL (producer) and the host
+//! (consumer), independent of how the memory is mapped.
+//!
+//! Layout:
+//! ```text
+//! [ PacketRingHeader (92 bytes) ][ data ring buffer (capacity bytes) ][ command channel ]
+//! ```
+//!
+//! The producer writes [`PacketEntry`] records, each followed by its
+//! payload and padded to a multiple of 4 bytes, and advances `write_pos`.
+//! The consumer reads them and advances `read_pos`. Both are byte offsets
+//! into the data area; `write_pos == read_pos` means empty, so one byte
+//! always stays free. Each side only ever stores its own cursor, so the
+//! protocol needs no locks: a cursor is published with a release store
+//! after the bytes it covers are written or read.
+//!
+//! Packets that don't fit are dropped and counted in the header. The next
+//! write that fits is preceded by a gap entry carrying the losses since the
+//! last one, so the consumer knows exactly where the stream has holes.
+//!
+//! Every entry starts with [`ENTRY_SYNC`] and carries a sequence number. If
+//! the consumer meets an entry that doesn't make sense, it scans forward
+//! to the next valid one and reports the bytes it skipped and the entries
+//! the sequence numbers say were lost, instead of stalling on it forever.
+//!
+//! Every field is 32 bits wide so the 32-bit DLL and the 64-bit host agree
+//! on the layout.
+//!
+//! The header opens with the magic, the protocol version, the header size
+//! and the producer's feature flags, which keep their place in every
+//! version. The consumer refuses a ring from another protocol version or
+//! one using features it doesn't know, rather than misreading it.
+//!
+//! The host picks the ring size, the largest packet worth keeping and the
+//! directions to capture with [`CaptureOptions`], handed to the DLL in a
+//! small mapping of their own before it creates the ring. The DLL records
+//! the values it actually used in the header.
+//!
+//! The [`command`] channel after the data area lets the host pause and
+//! resume capture, filter opcodes, query counters and unload the DLL.
+
+pub mod command;
+
+use command::{CommandChannel, COMMAND_CHANNEL_SIZE};
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// ---------------------------------------------------------------------------
+// Constants
+// ---------------------------------------------------------------------------
+
+/// Magic value written into the header so the host can verify the mapping.
+pub const RING_MAGIC: u32 = 0x57535056; // "WSPV"
+
+/// Magic of rings from before the header carried a version. They are
+/// protocol version 1.
+pub const LEGACY_RING_MAGIC: u32 = 0x57535059; // "WSPY"
+
+/// Version of the layout and rules in this crate. Bump it on any change
+/// the other side would misread.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The producer reports drops with gap entries.
+pub const FEATURE_DROP_GAPS: u32 = 1 << 0;
+
+/// Entries carry `ENTRY_SYNC` and sequence numbers.
+pub const FEATURE_SEQUENCES: u32 = 1 << 1;
+
+/// A command channel follows the data area.
+pub const FEATURE_COMMANDS: u32 = 1 << 2;
+
+/// Every feature this crate implements. A producer announces them all.
+pub const SUPPORTED_FEATURES: u32 = FEATURE_DROP_GAPS | FEATURE_SEQUENCES | FEATURE_COMMANDS;
+
+/// Ring size when the host doesn't pick one (4 MiB).
+pub const DEFAULT_RING_CAPACITY: u32 = 4 * 1024 * 1024;
+
+/// Bounds on the ring size the host may pick. The DLL lives in a 32-bit
+/// address space, so the top stays modest.
+pub const MIN_RING_CAPACITY: u32 = 64 * 1024;
+pub const MAX_RING_CAPACITY: u32 = 64 * 1024 * 1024;
+
+/// `CaptureOptions::directions` bit for server-to-client packets.
+pub const DIRECTION_SERVER: u32 = 1 << 0;
+
+/// `CaptureOptions::directions` bit for client-to-server packets.
+pub const DIRECTION_CLIENT: u32 = 1 << 1;
+
+/// Both directions.
+pub const ALL_DIRECTIONS: u32 = DIRECTION_SERVER | DIRECTION_CLIENT;
+
+/// Magic at the start of a serialized [`CaptureOptions`].
+pub const OPTIONS_MAGIC: u32 = 0x57534F50; // "WSOP"
+
+/// Size of a serialized [`CaptureOptions`].
+pub const OPTIONS_SIZE: usize = 20;
+
+/// Size of [`PacketRingHeader`].
+pub const HEADER_SIZE: usize = std::mem::size_of::<PacketRingHeader>();
+
+/// Size of the [`PacketEntry`] header, not counting the payload.
+pub const ENTRY_HEADER_SIZE: usize = std::mem::size_of::<PacketEntry>();
+
+/// Entries start on this boundary.
+pub const ENTRY_ALIGN: usize = 4;
+
+/// First field of every entry, so the consumer can find entry boundaries
+/// again after corruption.
+pub const ENTRY_SYNC: u32 = 0x434E5953; // "SYNC"
+
+/// `PacketEntry::kind` of a captured packet.
+pub const ENTRY_KIND_PACKET: u8 = 0;
+
+/// `PacketEntry::kind` of a gap marker, whose payload is a [`Drops`].
+pub const ENTRY_KIND_GAP: u8 = 1;
+
+/// Payload size of a gap entry.
+const GAP_PAYLOAD_SIZE: usize = 16;
+
+// ---------------------------------------------------------------------------
+// Shared-memory structures
+// ---------------------------------------------------------------------------
+
+/// Header at the start of the shared memory region.
+#[repr(C)]
+pub struct PacketRingHeader {
+    /// Magic value (`RING_MAGIC`). Lets the host verify the mapping.
+    pub magic: AtomicU32,
+    /// `PROTOCOL_VERSION` of the producer.
+    pub version: AtomicU32,
+    /// `HEADER_SIZE` of the producer.
+    pub header_size: AtomicU32,
+    /// `FEATURE_*` flags the producer uses.
+    pub features: AtomicU32,
+    /// Write cursor (byte offset into the data area). Updated by the DLL.
+    pub write_pos: AtomicU32,
+    /// Read cursor (byte offset into the data area). Updated by the host.
+    pub read_pos: AtomicU32,
+    /// Size of the data area in bytes.
+    pub capacity: AtomicU32,
+    /// Set to 1 once hooks are installed and the DLL is ready.
+    pub dll_ready: AtomicU32,
+    /// The detected WoW build number.
+    pub build_number: AtomicU32,
+    /// Everything dropped since the ring was created. Written by the DLL.
+    pub dropped: DropCounters,
+    /// Drops not yet reported by a gap entry. Only the DLL touches these.
+    pub unreported: DropCounters,
+    /// Sequence number of the next entry. Only the DLL touches this.
+    pub write_sequence: AtomicU32,
+    /// Sequence number the host expects next. Only the host touches this.
+    pub read_sequence: AtomicU32,
+    /// Entries the host lost to corruption. Written by the host.
+    pub lost_packets: AtomicU32,
+    /// Bytes the host skipped to resynchronize. Written by the host.
+    pub lost_bytes: AtomicU32,
+    /// Largest payload the DLL keeps; bigger packets are dropped.
+    pub max_packet_size: AtomicU32,
+    /// `DIRECTION_*` bits of the directions the DLL captures.
+    pub directions: AtomicU32,
+}
+
+/// What the host asks the DLL to capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureOptions {
+    /// Size of the ring's data area in bytes.
+    pub ring_capacity: u32,
+    /// Largest payload to keep. Bigger packets are dropped and counted.
+    pub max_packet_size: u32,
+    /// `DIRECTION_*` bits. Packets in other directions are skipped
+    /// without counting as drops.
+    pub directions: u32,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            ring_capacity: DEFAULT_RING_CAPACITY,
+            max_packet_size: u32::MAX,
+            directions: ALL_DIRECTIONS,
+        }
+    }
+}
+
+impl CaptureOptions {
+    /// The defaults, with a ring of `capacity` bytes.
+    pub fn with_capacity(capacity: u32) -> Self {
+        CaptureOptions {
+            ring_capacity: capacity,
+            ..Default::default()
+        }
+    }
+
+    /// The options the DLL actually uses: the capacity within bounds and
+    /// aligned, the packet size limit no larger than the ring can hold, and
+    /// only known direction bits.
+    pub fn effective(&self) -> Self {
+        let ring_capacity = self
+            .ring_capacity
+            .clamp(MIN_RING_CAPACITY, MAX_RING_CAPACITY)
+            & !(ENTRY_ALIGN as u32 - 1);
+        CaptureOptions {
+            ring_capacity,
+            max_packet_size: self.max_packet_size.min(max_payload(ring_capacity)),
+            directions: self.directions & ALL_DIRECTIONS,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; OPTIONS_SIZE] {
+        let mut bytes = [0u8; OPTIONS_SIZE];
+        let fields = [
+            OPTIONS_MAGIC,
+            PROTOCOL_VERSION,
+            self.ring_capacity,
+            self.max_packet_size,
+            self.directions,
+        ];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Options serialized by this protocol version, or `None`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < OPTIONS_SIZE {
+            return None;
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        if u32_at(0) != OPTIONS_MAGIC || u32_at(4) != PROTOCOL_VERSION {
+            return None;
+        }
+        Some(CaptureOptions {
+            ring_capacity: u32_at(8),
+            max_packet_size: u32_at(12),
+            directions: u32_at(16),
+        })
+    }
+}
+
+/// Name of the mapping holding the ring for the WoW process `pid`.
+pub fn ring_mapping_name(pid: u32) -> String {
+    format!("Local\\WowCapture_{}", pid)
+}
+
+/// Name of the mapping the host leaves the [`CaptureOptions`] in for the
+/// WoW process `pid`.
+pub fn options_mapping_name(pid: u32) -> String {
+    format!("Local\\WowCaptureOptions_{}", pid)
+}
+
+/// Dropped packets and payload bytes, indexed by direction. The counters
+/// wrap at 2^32.
+#[repr(C)]
+#[derive(Default)]
+pub struct DropCounters {
+    pub packets: [AtomicU32; 2],
+    pub bytes: [AtomicU32; 2],
+}
+
+impl DropCounters {
+    fn add(&self, direction: u8, bytes: usize) {
+        let direction = direction.min(1) as usize;
+        self.packets[direction].fetch_add(1, Ordering::Relaxed);
+        self.bytes[direction].fetch_add(bytes as u32, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Drops {
+        let load = |counter: &AtomicU32| counter.load(Ordering::Relaxed);
+        Drops {
+            packets: self.packets.each_ref().map(load),
+            bytes: self.bytes.each_ref().map(load),
+        }
+    }
+
+    fn subtract(&self, drops: &Drops) {
+        for direction in 0..2 {
+            self.packets[direction].fetch_sub(drops.packets[direction], Ordering::Relaxed);
+            self.bytes[direction].fetch_sub(drops.bytes[direction], Ordering::Relaxed);
+        }
+    }
+}
+
+/// A snapshot of [`DropCounters`]: index 0 is SMSG, 1 is CMSG.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Drops {
+    pub packets: [u32; 2],
+    pub bytes: [u32; 2],
+}
+
+impl Drops {
+    pub fn is_empty(&self) -> bool {
+        self.packets == [0, 0]
+    }
+
+    fn to_bytes(self) -> [u8; GAP_PAYLOAD_SIZE] {
+        let mut bytes = [0u8; GAP_PAYLOAD_SIZE];
+        let fields = [
+            self.packets[0],
+            self.packets[1],
+            self.bytes[0],
+            self.bytes[1],
+        ];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != GAP_PAYLOAD_SIZE {
+            return None;
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        Some(Drops {
+            packets: [u32_at(0), u32_at(4)],
+            bytes: [u32_at(8), u32_at(12)],
+        })
+    }
+}
+
+/// Per-packet record written into the ring buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketEntry {
+    /// Always `ENTRY_SYNC`.
+    pub sync: u32,
+    /// Total size of this entry in bytes (header fields + payload, aligned).
+    pub total_size: u32,
+    /// One more than the previous entry's, wrapping.
+    pub sequence: u32,
+    /// `timeGetTime()` timestamp.
+    pub timestamp: u32,
+    /// 0 = server-to-client (SMSG), 1 = client-to-server (CMSG).
+    pub direction: u8,
+    /// `ENTRY_KIND_PACKET` or `ENTRY_KIND_GAP`.
+    pub kind: u8,
+    pub _pad: [u8; 2],
+    /// The packet opcode.
+    pub opcode: u32,
+    /// Length of the payload bytes that follow this header.
+    pub data_len: u32,
+    // Followed by `data_len` bytes of packet payload.
+}
+
+impl PacketEntry {
+    /// An entry of `kind` for a `data_len`-byte payload, with no direction
+    /// or opcode.
+    fn new(kind: u8, sequence: u32, timestamp: u32, data_len: usize) -> Self {
+        PacketEntry {
+            sync: ENTRY_SYNC,
+            total_size: entry_size(data_len) as u32,
+            sequence,
+            timestamp,
+            direction: 0,
+            kind,
+            _pad: [0; 2],
+            opcode: 0,
+            data_len: data_len as u32,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_HEADER_SIZE] {
+        let mut bytes = [0u8; ENTRY_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&self.sync.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.total_size.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes[16] = self.direction;
+        bytes[17] = self.kind;
+        bytes[20..24].copy_from_slice(&self.opcode.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.data_len.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; ENTRY_HEADER_SIZE]) -> Self {
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        PacketEntry {
+            sync: u32_at(0),
+            total_size: u32_at(4),
+            sequence: u32_at(8),
+            timestamp: u32_at(12),
+            direction: bytes[16],
+            kind: bytes[17],
+            _pad: [0; 2],
+            opcode: u32_at(20),
+            data_len: u32_at(24),
+        }
+    }
+
+    /// Whether this looks like an entry the producer wrote, fitting in
+    /// `available` bytes.
+    fn is_valid(&self, available: usize) -> bool {
+        let payload_ok = match self.kind {
+            ENTRY_KIND_PACKET => self.direction <= 1,
+            ENTRY_KIND_GAP => self.data_len as usize == GAP_PAYLOAD_SIZE,
+            _ => false,
+        };
+        self.sync == ENTRY_SYNC
+            && payload_ok
+            && self.total_size as usize == entry_size(self.data_len as usize)
+            && self.total_size as usize <= available
+    }
+}
+
+/// A packet as the producer hands it over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawPacket {
+    pub timestamp: u32,
+    /// 0 = SMSG, 1 = CMSG.
+    pub direction: u8,
+    pub opcode: u32,
+    pub data: Vec<u8>,
+}
+
+/// What the consumer lost to a corrupt stretch of the ring.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lost {
+    /// Entries missing from the sequence. Only known once a valid entry
+    /// follows the corruption, so it may come in a later `Record::Lost`.
+    pub packets: u32,
+    /// Bytes skipped to find the next valid entry.
+    pub bytes: u32,
+}
+
+/// What the consumer reads out of the ring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    Packet(RawPacket),
+    /// Packets were dropped here, between the records either side.
+    Gap {
+        timestamp: u32,
+        drops: Drops,
+    },
+    /// The ring was corrupt here and the consumer skipped over it.
+    Lost(Lost),
+}
+
+// ---------------------------------------------------------------------------
+// Errors
+// ---------------------------------------------------------------------------
+
+/// Why a region can't hold or doesn't contain a ring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RingError {
+    /// The region doesn't start on a 4-byte boundary.
+    Misaligned,
+    /// The region is too small for the header and the requested capacity.
+    TooSmall {
+        len: usize,
+        needed: usize,
+    },
+    /// The capacity is zero or not a multiple of [`ENTRY_ALIGN`].
+    BadCapacity(u32),
+    BadMagic(u32),
+    /// The ring speaks another protocol version.
+    Incompatible {
+        ours: u32,
+        theirs: u32,
+    },
+    /// The header size doesn't match this protocol version's.
+    BadHeaderSize {
+        expected: usize,
+        found: u32,
+    },
+    /// The producer uses features this crate doesn't know.
+    UnknownFeatures(u32),
+}
+
+impl std::fmt::Display for RingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RingError::Misaligned => write!(f, "Ring region is not 4-byte aligned"),
+            RingError::TooSmall { len, needed } => {
+                write!(f, "Ring region is {} bytes, need {}", len, needed)
+            }
+            RingError::BadCapacity(capacity) => write!(
+                f,
+                "Ring capacity {} is not a non-zero multiple of {}",
+                capacity, ENTRY_ALIGN
+            ),
+            RingError::BadMagic(magic) => write!(
+                f,
+                "Bad magic in shared memory: 0x{:08X} (expected 0x{:08X})",
+                magic, RING_MAGIC
+            ),
+            RingError::Incompatible { ours, theirs } => write!(
+                f,
+                "Ring protocol version {} is not supported (this build speaks version {})",
+                theirs, ours
+            ),
+            RingError::BadHeaderSize { expected, found } => write!(
+                f,
+                "Ring header is {} bytes, protocol version {} uses {}",
+                found, PROTOCOL_VERSION, expected
+            ),
+            RingError::UnknownFeatures(features) => {
+                write!(f, "Ring uses unknown features 0x{:08X}", features)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RingError {}
+
+/// Why a packet wasn't written. Unless it was skipped, the packet is
+/// dropped and counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteError {
+    /// The packet's direction isn't captured. Not counted as a drop.
+    Skipped,
+    /// The entry is larger than the whole ring or the packet larger than
+    /// `max_packet_size`, so it can never fit.
+    TooLarge,
+    /// Not enough free space until the consumer catches up.
+    Full,
+}
+
+// ---------------------------------------------------------------------------
+// Ring
+// ---------------------------------------------------------------------------
+
+/// Bytes needed for a ring with a data area of `capacity` bytes, and the
+/// command channel after it.
+pub const fn region_size(capacity: u32) -> usize {
+    HEADER_SIZE + capacity as usize + COMMAND_CHANNEL_SIZE
+}
+
+/// An entry's size in the ring: header plus payload, padded.
+pub const fn entry_size(data_len: usize) -> usize {
+    (ENTRY_HEADER_SIZE + data_len + ENTRY_ALIGN - 1) & !(ENTRY_ALIGN - 1)
+}
+
+/// The largest payload a ring of `capacity` bytes can ever hold.
+pub const fn max_payload(capacity: u32) -> u32 {
+    capacity.saturating_sub((ENTRY_HEADER_SIZE + ENTRY_ALIGN) as u32)
+}
+
+/// A view of a ring in memory that another process may be using at the
+/// same time. Producer and consumer methods take `&self`; the protocol,
+/// not Rust's borrow rules, keeps the two sides off each other's bytes.
+pub struct Ring<'a> {
+    base: NonNull<u8>,
+    capacity: u32,
+    _region: PhantomData<&'a [u8]>,
+}
+
+// The header is only touched through atomics and the data area only
+// through raw copies the protocol keeps disjoint.
+unsafe impl Send for Ring<'_> {}
+unsafe impl Sync for Ring<'_> {}
+
+impl<'a> Ring<'a> {
+    /// Lay out a new, empty ring in `region`. The producer does this once.
+    pub fn create(
+        region: &'a mut [u8],
+        options: CaptureOptions,
+        build_number: u32,
+    ) -> Result<Self, RingError> {
+        // SAFETY: the slice is valid and exclusively ours for 'a.
+        unsafe { Self::create_raw(region.as_mut_ptr(), region.len(), options, build_number) }
+    }
+
+    /// Attach to a ring another party created in `region`.
+    pub fn attach(region: &'a mut [u8]) -> Result<Self, RingError> {
+        // SAFETY: as above.
+        unsafe { Self::attach_raw(region.as_mut_ptr(), region.len()) }
+    }
+
+    /// [`Ring::create`] over mapped memory.
+    ///
+    /// # Safety
+    ///
+    /// `base..base + len` must stay mapped and writable for `'a`, and only
+    /// be accessed through `Ring`s following this protocol.
+    pub unsafe fn create_raw(
+        base: *mut u8,
+        len: usize,
+        options: CaptureOptions,
+        build_number: u32,
+    ) -> Result<Self, RingError> {
+        let capacity = options.ring_capacity;
+        let base = check_region(base, len)?;
+        check_capacity(capacity)?;
+        let needed = region_size(capacity);
+        if len < needed {
+            return Err(RingError::TooSmall { len, needed });
+        }
+
+        // Zero the whole region, then publish the header with the magic
+        // last so a reader never sees a half-written one.
+        ptr::write_bytes(base.as_ptr(), 0, needed);
+        let ring = Ring {
+            base,
+            capacity,
+            _region: PhantomData,
+        };
+        let header = ring.header();
+        header.version.store(PROTOCOL_VERSION, Ordering::Relaxed);
+        header
+            .header_size
+            .store(HEADER_SIZE as u32, Ordering::Relaxed);
+        header.features.store(SUPPORTED_FEATURES, Ordering::Relaxed);
+        header.write_pos.store(0, Ordering::Relaxed);
+        header.read_pos.store(0, Ordering::Relaxed);
+        header.capacity.store(capacity, Ordering::Relaxed);
+        header.dll_ready.store(0, Ordering::Relaxed);
+        header.build_number.store(build_number, Ordering::Relaxed);
+        header
+            .max_packet_size
+            .store(options.max_packet_size, Ordering::Relaxed);
+        header
+            .directions
+            .store(options.directions, Ordering::Relaxed);
+        header.magic.store(RING_MAGIC, Ordering::Release);
+        Ok(ring)
+    }
+
+    /// [`Ring::attach`] over mapped memory. Fails unless the ring speaks
+    /// this crate's protocol version with features it knows.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Ring::create_raw`].
+    pub unsafe fn attach_raw(base: *mut u8, len: usize) -> Result<Self, RingError> {
+        let base = check_region(base, len)?;
+        let header = &*(base.as_ptr() as *const PacketRingHeader);
+        let magic = header.magic.load(Ordering::Acquire);
+        let version = match magic {
+            RING_MAGIC => header.version.load(Ordering::Relaxed),
+            LEGACY_RING_MAGIC => 1,
+            _ => return Err(RingError::BadMagic(magic)),
+        };
+        if version != PROTOCOL_VERSION {
+            return Err(RingError::Incompatible {
+                ours: PROTOCOL_VERSION,
+                theirs: version,
+            });
+        }
+        let header_size = header.header_size.load(Ordering::Relaxed);
+        if header_size as usize != HEADER_SIZE {
+            return Err(RingError::BadHeaderSize {
+                expected: HEADER_SIZE,
+                found: header_size,
+            });
+        }
+        let unknown = header.features.load(Ordering::Relaxed) & !SUPPORTED_FEATURES;
+        if unknown != 0 {
+            return Err(RingError::UnknownFeatures(unknown));
+        }
+        let capacity = header.capacity.load(Ordering::Relaxed);
+        check_capacity(capacity)?;
+        let needed = region_size(capacity);
+        if len < needed {
+            return Err(RingError::TooSmall { len, needed });
+        }
+        Ok(Ring {
+            base,
+            capacity,
+            _region: PhantomData,
+        })
+    }
+
+    fn header(&self) -> &PacketRingHeader {
+        // SAFETY: the region is aligned, large enough and lives for 'a.
+        unsafe { &*(self.base.as_ptr() as *const PacketRingHeader) }
+    }
+
+    fn data(&self) -> *mut u8 {
+        // SAFETY: the data area follows the header inside the region.
+        unsafe { self.base.as_ptr().add(HEADER_SIZE) }
+    }
+
+    /// The command channel after the data area.
+    pub fn commands(&self) -> CommandChannel<'a> {
+        // SAFETY: `region_size` leaves room for the channel after the data
+        // area, which is 4-byte aligned since the capacity is.
+        unsafe {
+            let base = NonNull::new_unchecked(self.data().add(self.capacity as usize));
+            CommandChannel::new(base)
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn build_number(&self) -> u32 {
+        self.header().build_number.load(Ordering::Relaxed)
+    }
+
+    /// Mark the DLL as ready (hooks installed).
+    pub fn set_ready(&self) {
+        self.header().dll_ready.store(1, Ordering::Release);
+    }
+
+    /// Returns `true` once the DLL signals that its hooks are installed.
+    pub fn is_ready(&self) -> bool {
+        self.header().dll_ready.load(Ordering::Acquire) == 1
+    }
+
+    /// The options the producer created the ring with.
+    pub fn options(&self) -> CaptureOptions {
+        let header = self.header();
+        CaptureOptions {
+            ring_capacity: self.capacity,
+            max_packet_size: header.max_packet_size.load(Ordering::Relaxed),
+            directions: header.directions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// `FEATURE_*` flags the producer uses.
+    pub fn features(&self) -> u32 {
+        self.header().features.load(Ordering::Relaxed)
+    }
+
+    /// Everything dropped since the ring was created.
+    pub fn drops(&self) -> Drops {
+        self.header().dropped.load()
+    }
+
+    /// Everything the consumer lost to corruption since the ring was
+    /// created.
+    pub fn lost(&self) -> Lost {
+        let header = self.header();
+        Lost {
+            packets: header.lost_packets.load(Ordering::Relaxed),
+            bytes: header.lost_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Bytes waiting for the consumer.
+    pub fn used(&self) -> u32 {
+        let write_pos = self.header().write_pos.load(Ordering::Acquire);
+        let read_pos = self.header().read_pos.load(Ordering::Acquire);
+        used(write_pos, read_pos, self.capacity)
+    }
+
+    // -- Producer -----------------------------------------------------------
+
+    /// Append a packet, preceded by a gap entry if anything was dropped
+    /// since the last one. Packets in directions the ring doesn't capture
+    /// are skipped. Only one producer may write at a time.
+    pub fn write_packet(
+        &self,
+        timestamp: u32,
+        direction: u8,
+        opcode: u32,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        let header = self.header();
+        let bit = if direction == 0 {
+            DIRECTION_SERVER
+        } else {
+            DIRECTION_CLIENT
+        };
+        if header.directions.load(Ordering::Relaxed) & bit == 0 {
+            return Err(WriteError::Skipped);
+        }
+
+        let result = self.try_write_packet(timestamp, direction, opcode, data);
+        if result.is_err() {
+            header.dropped.add(direction, data.len());
+            header.unreported.add(direction, data.len());
+        }
+        result
+    }
+
+    fn try_write_packet(
+        &self,
+        timestamp: u32,
+        direction: u8,
+        opcode: u32,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        let capacity = self.capacity as usize;
+        let total = entry_size(data.len());
+        let header = self.header();
+        // One byte always stays free, so an entry the size of the ring
+        // would never fit either.
+        if total >= capacity || data.len() > header.max_packet_size.load(Ordering::Relaxed) as usize
+        {
+            return Err(WriteError::TooLarge);
+        }
+
+        let write_pos = header.write_pos.load(Ordering::Relaxed);
+        let read_pos = header.read_pos.load(Ordering::Acquire);
+        if write_pos >= self.capacity || read_pos >= self.capacity {
+            // Cursors from a scribbled-over header; nothing is safe to write.
+            return Err(WriteError::Full);
+        }
+        let free = capacity - 1 - used(write_pos, read_pos, self.capacity) as usize;
+
+        let unreported = header.unreported.load();
+        let gap = !unreported.is_empty();
+        let gap_total = if gap { entry_size(GAP_PAYLOAD_SIZE) } else { 0 };
+        // The gap must land before this packet, so both fit or neither goes.
+        if gap_total + total > free {
+            return Err(WriteError::Full);
+        }
+
+        let mut write_pos = write_pos as usize;
+        let mut sequence = header.write_sequence.load(Ordering::Relaxed);
+        if gap {
+            let payload = unreported.to_bytes();
+            let entry = PacketEntry::new(ENTRY_KIND_GAP, sequence, timestamp, payload.len());
+            // SAFETY: `free` bytes from `write_pos` belong to the producer.
+            unsafe { self.write_entry(write_pos, entry, &payload) };
+            header.unreported.subtract(&unreported);
+            write_pos = (write_pos + gap_total) % capacity;
+            sequence = sequence.wrapping_add(1);
+        }
+        let entry = PacketEntry {
+            direction,
+            opcode,
+            ..PacketEntry::new(ENTRY_KIND_PACKET, sequence, timestamp, data.len())
+        };
+        // SAFETY: as above.
+        unsafe { self.write_entry(write_pos, entry, data) };
+        header
+            .write_sequence
+            .store(sequence.wrapping_add(1), Ordering::Relaxed);
+        header
+            .write_pos
+            .store(((write_pos + total) % capacity) as u32, Ordering::Release);
+        Ok(())
+    }
+
+    /// Copy an entry and its payload into the data area at `offset`.
+    unsafe fn write_entry(&self, offset: usize, entry: PacketEntry, data: &[u8]) {
+        self.copy_in(offset, &entry.to_bytes());
+        self.copy_in((offset + ENTRY_HEADER_SIZE) % self.capacity as usize, data);
+    }
+
+    // -- Consumer -----------------------------------------------------------
+
+    /// Read every complete entry and advance `read_pos` past them. Where
+    /// an entry doesn't make sense, skip ahead to the next valid one and
+    /// report what was lost with a `Record::Lost`. Only one consumer may
+    /// read at a time.
+    pub fn read_records(&self) -> Vec<Record> {
+        let mut records = Vec::new();
+        let header = self.header();
+        let capacity = self.capacity as usize;
+        let write_pos = header.write_pos.load(Ordering::Acquire);
+        let read_pos = header.read_pos.load(Ordering::Relaxed);
+        if write_pos >= self.capacity || read_pos >= self.capacity {
+            return records;
+        }
+        let mut read_pos = read_pos as usize;
+        let mut available = used(write_pos, read_pos as u32, self.capacity) as usize;
+        let mut sequence = header.read_sequence.load(Ordering::Relaxed);
+        // The most entries the ring can hold. While scanning, a sequence
+        // number further ahead than this comes from garbage that happens to
+        // look valid. On an entry boundary the structure checks are enough,
+        // and trusting the entry lets a damaged sequence number heal.
+        let max_skip = (capacity / ENTRY_HEADER_SIZE) as u32;
+        let ahead = |sequence: u32, expected: u32| {
+            Some(sequence.wrapping_sub(expected)).filter(|&n| n <= max_skip)
+        };
+        let mut skipped = 0usize;
+
+        while available > 0 {
+            let entry = (available >= ENTRY_HEADER_SIZE)
+                .then(|| {
+                    let mut entry_bytes = [0u8; ENTRY_HEADER_SIZE];
+                    // SAFETY: `available` bytes from `read_pos` belong to
+                    // the consumer.
+                    unsafe { self.copy_out(read_pos, &mut entry_bytes) };
+                    PacketEntry::from_bytes(&entry_bytes)
+                })
+                .filter(|entry| {
+                    entry.is_valid(available)
+                        && (skipped == 0 || ahead(entry.sequence, sequence).is_some())
+                });
+            let Some(entry) = entry else {
+                // Entries are aligned, so the next one starts on a boundary.
+                let step = ENTRY_ALIGN.min(available);
+                read_pos = (read_pos + step) % capacity;
+                available -= step;
+                skipped += step;
+                continue;
+            };
+
+            let missing = ahead(entry.sequence, sequence).unwrap_or(0);
+            if skipped > 0 || missing > 0 {
+                records.push(self.lose(missing, skipped));
+                skipped = 0;
+            }
+
+            let mut data = vec![0u8; entry.data_len as usize];
+            // SAFETY: as above; the payload lies within `total_size`.
+            unsafe { self.copy_out((read_pos + ENTRY_HEADER_SIZE) % capacity, &mut data) };
+            records.push(match entry.kind {
+                // `is_valid` checked the payload size.
+                ENTRY_KIND_GAP => Record::Gap {
+                    timestamp: entry.timestamp,
+                    drops: Drops::from_bytes(&data).unwrap_or_default(),
+                },
+                _ => Record::Packet(RawPacket {
+                    timestamp: entry.timestamp,
+                    direction: entry.direction,
+                    opcode: entry.opcode,
+                    data,
+                }),
+            });
+            let total = entry.total_size as usize;
+            read_pos = (read_pos + total) % capacity;
+            available -= total;
+            sequence = entry.sequence.wrapping_add(1);
+        }
+        if skipped > 0 {
+            // How many entries were in there shows once the next one
+            // arrives.
+            records.push(self.lose(0, skipped));
+        }
+
+        header.read_sequence.store(sequence, Ordering::Relaxed);
+        header.read_pos.store(read_pos as u32, Ordering::Release);
+        records
+    }
+
+    /// Count a loss in the header and describe it.
+    fn lose(&self, packets: u32, bytes: usize) -> Record {
+        let header = self.header();
+        header.lost_packets.fetch_add(packets, Ordering::Relaxed);
+        header.lost_bytes.fetch_add(bytes as u32, Ordering::Relaxed);
+        Record::Lost(Lost {
+            packets,
+            bytes: bytes as u32,
+        })
+    }
+
+    // -- Helpers ------------------------------------------------------------
+
+    /// Copy `src` into the data area at `offset`, wrapping at the end.
+    unsafe fn copy_in(&self, offset: usize, src: &[u8]) {
+        let first = src.len().min(self.capacity as usize - offset);
+        ptr::copy_nonoverlapping(src.as_ptr(), self.data().add(offset), first);
+        ptr::copy_nonoverlapping(src.as_ptr().add(first), self.data(), src.len() - first);
+    }
+
+    /// Fill `dst` from the data area at `offset`, wrapping at the end.
+    unsafe fn copy_out(&self, offset: usize, dst: &mut [u8]) {
+        let first = dst.len().min(self.capacity as usize - offset);
+        ptr::copy_nonoverlapping(self.data().add(offset), dst.as_mut_ptr(), first);
+        ptr::copy_nonoverlapping(self.data(), dst.as_mut_ptr().add(first), dst.len() - first);
+    }
+}
+
+fn check_region(base: *mut u8, len: usize) -> Result<NonNull<u8>, RingError> {
+    let base = NonNull::new(base).ok_or(RingError::TooSmall {
+        len: 0,
+        needed: HEADER_SIZE,
+    })?;
+    if !(base.as_ptr() as usize).is_multiple_of(ENTRY_ALIGN) {
+        return Err(RingError::Misaligned);
+    }
+    if len < HEADER_SIZE {
+        return Err(RingError::TooSmall {
+            len,
+            needed: HEADER_SIZE,
+        });
+    }
+    Ok(base)
+}
+
+fn check_capacity(capacity: u32) -> Result<(), RingError> {
+    if capacity == 0 || !(capacity as usize).is_multiple_of(ENTRY_ALIGN) {
+        return Err(RingError::BadCapacity(capacity));
+    }
+    Ok(())
+}
+
+/// Bytes between the cursors.
+fn used(write_pos: u32, read_pos: u32, capacity: u32) -> u32 {
+    if write_pos >= read_pos {
+        write_pos - read_pos
+    } else {
+        capacity - read_pos + write_pos
+    }
+}
@@ -0,0 +1,151 @@
+use ring_protocol::command::{
+    CaptureStatus, Command, FilterMode, OpcodeFilter, Reply, COMMAND_SLOTS, MAX_FILTER_OPCODES,
+};
+use ring_protocol::{region_size, CaptureOptions, Ring, WriteError};
+
+fn region(capacity: u32) -> Vec<u32> {
+    vec![0xAAAA_AAAA; region_size(capacity).div_ceil(4)]
+}
+
+fn filter(mode: FilterMode, opcodes: Vec<u32>) -> OpcodeFilter {
+    OpcodeFilter { mode, opcodes }
+}
+
+#[test]
+fn commands_and_replies_round_trip() {
+    let mut memory = region(64);
+    let (base, len) = (memory.as_mut_ptr() as *mut u8, memory.len() * 4);
+    let dll = unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(64), 0) }.unwrap();
+    let host = unsafe { Ring::attach_raw(base, len) }.unwrap();
+    let (dll, host) = (dll.commands(), host.commands());
+
+    let commands = [
+        Command::Pause,
+        Command::Resume,
+        Command::SetFilter(filter(FilterMode::Only, vec![0x1DC, 0x1DD])),
+        Command::SetFilter(filter(
+            FilterMode::Except,
+            (0..MAX_FILTER_OPCODES as u32).collect(),
+        )),
+        Command::QueryStatus,
+        Command::Unload,
+    ];
+    for (id, command) in commands.iter().enumerate() {
+        host.send_command(id as u32, command).unwrap();
+    }
+    assert_eq!(dll.next_command(), Some((0, Some(Command::Pause))));
+    for (id, command) in commands.iter().enumerate().skip(1) {
+        assert_eq!(dll.next_command(), Some((id as u32, Some(command.clone()))));
+    }
+    assert_eq!(dll.next_command(), None);
+
+    let status = CaptureStatus {
+        paused: true,
+        hooks_active: true,
+        filter_mode: FilterMode::Except,
+        filter_len: 3,
+        captured: 1234,
+        filtered: 56,
+    };
+    for (id, reply) in [Reply::Done, Reply::Status(status), Reply::Rejected]
+        .iter()
+        .enumerate()
+    {
+        dll.send_reply(id as u32 + 7, reply).unwrap();
+    }
+    assert_eq!(host.next_reply(), Some((7, Reply::Done)));
+    assert_eq!(host.next_reply(), Some((8, Reply::Status(status))));
+    assert_eq!(host.next_reply(), Some((9, Reply::Rejected)));
+    assert_eq!(host.next_reply(), None);
+}
+
+#[test]
+fn queues_are_bounded() {
+    let mut memory = region(64);
+    let (base, len) = (memory.as_mut_ptr() as *mut u8, memory.len() * 4);
+    let ring =
+        unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(64), 0) }.unwrap();
+    let channel = ring.commands();
+
+    let too_many = filter(FilterMode::Only, (0..=MAX_FILTER_OPCODES as u32).collect());
+    assert_eq!(
+        channel.send_command(0, &Command::SetFilter(too_many)),
+        Err(WriteError::TooLarge)
+    );
+    // Fill and drain a few times over so the counters wrap the slots.
+    for round in 0..3u32 {
+        for id in 0..COMMAND_SLOTS as u32 {
+            channel
+                .send_command(round * 100 + id, &Command::Pause)
+                .unwrap();
+        }
+        assert_eq!(
+            channel.send_command(0, &Command::Pause),
+            Err(WriteError::Full)
+        );
+        for id in 0..COMMAND_SLOTS as u32 {
+            assert_eq!(
+                channel.next_command(),
+                Some((round * 100 + id, Some(Command::Pause)))
+            );
+        }
+        assert_eq!(channel.next_command(), None);
+    }
+}
+
+#[test]
+fn host_and_dll_talk_across_threads() {
+    let mut memory = region(64);
+    let (base, len) = (memory.as_mut_ptr() as *mut u8, memory.len() * 4);
+    let dll = unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(64), 0) }.unwrap();
+    let host = unsafe { Ring::attach_raw(base, len) }.unwrap();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let channel = dll.commands();
+            let mut served = 0;
+            while served < 200 {
+                match channel.next_command() {
+                    Some((id, Some(Command::QueryStatus))) => {
+                        let status = CaptureStatus {
+                            captured: id,
+                            ..Default::default()
+                        };
+                        while channel.send_reply(id, &Reply::Status(status)).is_err() {
+                            std::thread::yield_now();
+                        }
+                        served += 1;
+                    }
+                    Some(other) => panic!("unexpected command {:?}", other),
+                    None => std::thread::yield_now(),
+                }
+            }
+        });
+
+        let channel = host.commands();
+        for id in 0..200 {
+            while channel.send_command(id, &Command::QueryStatus).is_err() {
+                std::thread::yield_now();
+            }
+            let reply = loop {
+                match channel.next_reply() {
+                    Some(reply) => break reply,
+                    None => std::thread::yield_now(),
+                }
+            };
+            let Reply::Status(status) = reply.1 else {
+                panic!("unexpected reply {:?}", reply)
+            };
+            assert_eq!((reply.0, status.captured), (id, id));
+        }
+    });
+}
+
+#[test]
+fn filters_opcodes() {
+    let only = filter(FilterMode::Only, vec![1, 2]);
+    let except = filter(FilterMode::Except, vec![1, 2]);
+    assert!(OpcodeFilter::default().allows(1));
+    assert!(only.allows(2) && !only.allows(3));
+    assert!(!except.allows(2) && except.allows(3));
+}
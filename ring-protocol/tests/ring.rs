@@ -0,0 +1,427 @@
+use proptest::prelude::*;
+use ring_protocol::{
+    entry_size, region_size, CaptureOptions, Drops, Lost, RawPacket, Record, Ring, RingError,
+    WriteError, ALL_DIRECTIONS, DIRECTION_SERVER, ENTRY_ALIGN, HEADER_SIZE, LEGACY_RING_MAGIC,
+    MAX_RING_CAPACITY, MIN_RING_CAPACITY, PROTOCOL_VERSION, SUPPORTED_FEATURES,
+};
+use std::collections::VecDeque;
+
+/// A 4-byte aligned region, as a mapping would be.
+fn region(capacity: u32) -> Vec<u32> {
+    vec![0xAAAA_AAAA; region_size(capacity).div_ceil(4)]
+}
+
+fn bytes(region: &mut [u32]) -> (*mut u8, usize) {
+    (region.as_mut_ptr() as *mut u8, region.len() * 4)
+}
+
+fn packet(seq: u32, len: usize) -> RawPacket {
+    RawPacket {
+        timestamp: seq.wrapping_mul(7),
+        direction: (seq % 2) as u8,
+        opcode: seq,
+        data: (0..len).map(|i| (seq as usize + i) as u8).collect(),
+    }
+}
+
+fn count_drop(drops: &mut Drops, p: &RawPacket) {
+    let direction = p.direction as usize;
+    drops.packets[direction] += 1;
+    drops.bytes[direction] += p.data.len() as u32;
+}
+
+/// The packets among `records`, in order.
+fn packets(records: Vec<Record>) -> Vec<RawPacket> {
+    records
+        .into_iter()
+        .filter_map(|record| match record {
+            Record::Packet(p) => Some(p),
+            Record::Gap { .. } | Record::Lost(_) => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Write(usize),
+    Read,
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(
+        prop_oneof![3 => (0usize..120).prop_map(Op::Write), 1 => Just(Op::Read)],
+        1..200,
+    )
+}
+
+proptest! {
+    /// Against a queue model: writes fit exactly when the model says they
+    /// should, reads return everything written, in order, across
+    /// wrap-arounds and full rings, and every drop is counted and reported
+    /// by a gap right before the next packet that fits.
+    #[test]
+    fn matches_queue_model(capacity in (16u32..128).prop_map(|c| c * 4), ops in ops()) {
+        let mut memory = region(capacity);
+        let (base, len) = bytes(&mut memory);
+        let producer = unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(capacity), 12340) }.unwrap();
+        let consumer = unsafe { Ring::attach_raw(base, len) }.unwrap();
+        prop_assert_eq!(consumer.build_number(), 12340);
+
+        let mut model: VecDeque<Record> = VecDeque::new();
+        let mut model_used = 0usize;
+        let mut dropped = Drops::default();
+        let mut unreported = Drops::default();
+        for (seq, op) in ops.into_iter().enumerate() {
+            match op {
+                Op::Write(len) => {
+                    let p = packet(seq as u32, len);
+                    let size = entry_size(len);
+                    let gap_size = if unreported.is_empty() { 0 } else { entry_size(16) };
+                    let result = producer.write_packet(p.timestamp, p.direction, p.opcode, &p.data);
+                    let expected = if size >= capacity as usize {
+                        Err(WriteError::TooLarge)
+                    } else if gap_size + size > capacity as usize - 1 - model_used {
+                        Err(WriteError::Full)
+                    } else {
+                        Ok(())
+                    };
+                    prop_assert_eq!(result, expected);
+                    if result.is_ok() {
+                        model_used += gap_size + size;
+                        if gap_size > 0 {
+                            let drops = std::mem::take(&mut unreported);
+                            model.push_back(Record::Gap { timestamp: p.timestamp, drops });
+                        }
+                        model.push_back(Record::Packet(p));
+                    } else {
+                        count_drop(&mut dropped, &p);
+                        count_drop(&mut unreported, &p);
+                    }
+                    prop_assert_eq!(consumer.drops(), dropped);
+                }
+                Op::Read => {
+                    let got = consumer.read_records();
+                    prop_assert_eq!(got, model.drain(..).collect::<Vec<_>>());
+                    model_used = 0;
+                }
+            }
+            prop_assert_eq!(consumer.used() as usize, model_used);
+            prop_assert_eq!(consumer.used() as usize % ENTRY_ALIGN, 0);
+        }
+        prop_assert_eq!(consumer.read_records(), model.into_iter().collect::<Vec<_>>());
+        prop_assert_eq!(consumer.drops(), dropped);
+        prop_assert_eq!(consumer.used(), 0);
+    }
+
+    /// A producer and a consumer on separate threads lose and reorder
+    /// nothing while the ring wraps many times over. Retried writes count
+    /// as drops, so the ring leaves room for a gap entry next to the
+    /// largest packet.
+    #[test]
+    fn concurrent_producer_consumer(
+        capacity in (32u32..64).prop_map(|c| c * 4),
+        lens in prop::collection::vec(0usize..40, 1..300),
+    ) {
+        let mut memory = region(capacity);
+        let (base, len) = bytes(&mut memory);
+        let producer = unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(capacity), 5875) }.unwrap();
+        let consumer = unsafe { Ring::attach_raw(base, len) }.unwrap();
+        let expected: Vec<RawPacket> =
+            lens.iter().enumerate().map(|(seq, &len)| packet(seq as u32, len)).collect();
+
+        let received = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for p in &expected {
+                    while producer.write_packet(p.timestamp, p.direction, p.opcode, &p.data)
+                        == Err(WriteError::Full)
+                    {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            let mut received = Vec::new();
+            while received.len() < expected.len() {
+                received.extend(packets(consumer.read_records()));
+                std::thread::yield_now();
+            }
+            received
+        });
+        prop_assert_eq!(received, expected);
+    }
+
+    /// Whatever bytes of the data area get scribbled over, one read skips
+    /// the damage and the next packet comes through intact.
+    #[test]
+    fn recovers_from_corruption(
+        lens in prop::collection::vec(0usize..40, 1..12),
+        damage in prop::collection::vec((0usize..1024, any::<u8>()), 1..16),
+    ) {
+        let capacity = 1024;
+        let mut memory = region(capacity);
+        let (base, len) = bytes(&mut memory);
+        let ring = unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(capacity), 0) }.unwrap();
+        for (seq, &len) in lens.iter().enumerate() {
+            let p = packet(seq as u32, len);
+            ring.write_packet(p.timestamp, p.direction, p.opcode, &p.data).unwrap();
+        }
+        for (offset, value) in damage {
+            unsafe { *base.add(HEADER_SIZE + offset) = value };
+        }
+
+        ring.read_records();
+        prop_assert_eq!(ring.used(), 0);
+        let p = packet(99, 10);
+        ring.write_packet(p.timestamp, p.direction, p.opcode, &p.data).unwrap();
+        let records = ring.read_records();
+        prop_assert_eq!(records.last(), Some(&Record::Packet(p)));
+    }
+}
+
+#[test]
+fn wraps_entries_across_the_end() {
+    let capacity = 64;
+    let mut memory = region(capacity);
+    let (base, len) = bytes(&mut memory);
+    let ring =
+        unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(capacity), 0) }.unwrap();
+    // 40 bytes each: the second entry starts at 40 and wraps after 24.
+    for seq in 0..10 {
+        let p = packet(seq, 20);
+        ring.write_packet(p.timestamp, p.direction, p.opcode, &p.data)
+            .unwrap();
+        assert_eq!(ring.read_records(), vec![Record::Packet(p)]);
+    }
+}
+
+#[test]
+fn rejects_bad_regions() {
+    let mut memory = region(64);
+    let (base, len) = bytes(&mut memory);
+    unsafe {
+        assert!(Ring::attach_raw(base, len).is_err());
+        assert!(Ring::create_raw(base, len, CaptureOptions::with_capacity(62), 0).is_err());
+        assert!(Ring::create_raw(base, len - 4, CaptureOptions::with_capacity(64), 0).is_err());
+        assert!(
+            Ring::create_raw(base.add(1), len - 4, CaptureOptions::with_capacity(32), 0).is_err()
+        );
+    }
+    let mut bytes = vec![0u8; 0];
+    assert!(Ring::create(&mut bytes, CaptureOptions::with_capacity(4), 0).is_err());
+}
+
+#[test]
+fn reports_drops_before_the_next_packet() {
+    let capacity = 128;
+    let mut memory = region(capacity);
+    let (base, len) = bytes(&mut memory);
+    let ring =
+        unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(capacity), 0) }.unwrap();
+    let big = packet(1, 100);
+    let first = packet(2, 60);
+    let full = packet(4, 4);
+    assert_eq!(
+        ring.write_packet(big.timestamp, big.direction, big.opcode, &big.data),
+        Err(WriteError::TooLarge)
+    );
+    // 88 bytes of packet plus 44 of gap don't fit in 127 free bytes.
+    assert_eq!(
+        ring.write_packet(first.timestamp, first.direction, first.opcode, &first.data),
+        Err(WriteError::Full)
+    );
+    ring.write_packet(full.timestamp, full.direction, full.opcode, &full.data)
+        .unwrap();
+
+    let drops = Drops {
+        packets: [1, 1],
+        bytes: [60, 100],
+    };
+    assert_eq!(ring.drops(), drops);
+    assert_eq!(
+        ring.read_records(),
+        vec![
+            Record::Gap {
+                timestamp: full.timestamp,
+                drops
+            },
+            Record::Packet(full)
+        ]
+    );
+    assert_eq!(ring.drops(), drops);
+}
+
+#[test]
+fn skips_corrupt_entries_and_counts_them() {
+    let capacity = 256;
+    let mut memory = region(capacity);
+    let (base, len) = bytes(&mut memory);
+    let ring =
+        unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(capacity), 0) }.unwrap();
+    let write = |p: &RawPacket| {
+        ring.write_packet(p.timestamp, p.direction, p.opcode, &p.data)
+            .unwrap()
+    };
+    // 48 bytes each.
+    let p: Vec<RawPacket> = (0..6).map(|seq| packet(seq, 20)).collect();
+    let corrupt_entry = |index: usize| unsafe { *base.add(HEADER_SIZE + index * 48) ^= 0xFF };
+
+    write(&p[0]);
+    write(&p[1]);
+    write(&p[2]);
+    corrupt_entry(1);
+    assert_eq!(
+        ring.read_records(),
+        vec![
+            Record::Packet(p[0].clone()),
+            Record::Lost(Lost {
+                packets: 1,
+                bytes: 48
+            }),
+            Record::Packet(p[2].clone()),
+        ]
+    );
+
+    // Damage at the end is skipped now and counted once the sequence
+    // numbers show what it held.
+    write(&p[3]);
+    corrupt_entry(3);
+    assert_eq!(
+        ring.read_records(),
+        vec![Record::Lost(Lost {
+            packets: 0,
+            bytes: 48
+        })]
+    );
+    write(&p[4]);
+    assert_eq!(
+        ring.read_records(),
+        vec![
+            Record::Lost(Lost {
+                packets: 1,
+                bytes: 0
+            }),
+            Record::Packet(p[4].clone()),
+        ]
+    );
+    assert_eq!(
+        ring.lost(),
+        Lost {
+            packets: 2,
+            bytes: 96
+        }
+    );
+}
+
+#[test]
+fn refuses_other_protocol_versions() {
+    let mut memory = region(64);
+    let (base, len) = bytes(&mut memory);
+    unsafe { Ring::create_raw(base, len, CaptureOptions::with_capacity(64), 0) }.unwrap();
+    let attach = |memory: &mut Vec<u32>| {
+        let (base, len) = bytes(memory);
+        unsafe { Ring::attach_raw(base, len) }.map(|ring| ring.features())
+    };
+    assert_eq!(attach(&mut memory), Ok(SUPPORTED_FEATURES));
+
+    // The header opens with magic, version, header size and features.
+    let mut other = memory.clone();
+    other[1] = PROTOCOL_VERSION + 1;
+    assert_eq!(
+        attach(&mut other),
+        Err(RingError::Incompatible {
+            ours: PROTOCOL_VERSION,
+            theirs: PROTOCOL_VERSION + 1
+        })
+    );
+    let message = attach(&mut other).unwrap_err().to_string();
+    assert!(message.contains(&PROTOCOL_VERSION.to_string()));
+    assert!(message.contains(&(PROTOCOL_VERSION + 1).to_string()));
+
+    let mut legacy = memory.clone();
+    legacy[0] = LEGACY_RING_MAGIC;
+    assert_eq!(
+        attach(&mut legacy),
+        Err(RingError::Incompatible {
+            ours: PROTOCOL_VERSION,
+            theirs: 1
+        })
+    );
+
+    let mut resized = memory.clone();
+    resized[2] += 4;
+    assert_eq!(
+        attach(&mut resized),
+        Err(RingError::BadHeaderSize {
+            expected: HEADER_SIZE,
+            found: HEADER_SIZE as u32 + 4
+        })
+    );
+
+    let mut featured = memory.clone();
+    featured[3] |= 1 << 31;
+    assert_eq!(
+        attach(&mut featured),
+        Err(RingError::UnknownFeatures(1 << 31))
+    );
+}
+
+#[test]
+fn applies_capture_options() {
+    let options = CaptureOptions {
+        ring_capacity: 256,
+        max_packet_size: 16,
+        directions: DIRECTION_SERVER,
+    };
+    assert_eq!(
+        CaptureOptions::from_bytes(&options.to_bytes()),
+        Some(options)
+    );
+    assert_eq!(CaptureOptions::from_bytes(&[0; 20]), None);
+
+    let mut memory = region(256);
+    let (base, len) = bytes(&mut memory);
+    let ring = unsafe { Ring::create_raw(base, len, options, 0) }.unwrap();
+    assert_eq!(ring.options(), options);
+
+    // Server packets are even, client packets odd.
+    let client = packet(1, 4);
+    let large = packet(2, 17);
+    let small = packet(4, 16);
+    for p in [&client, &large, &small] {
+        let _ = ring.write_packet(p.timestamp, p.direction, p.opcode, &p.data);
+    }
+    assert_eq!(
+        ring.read_records(),
+        vec![
+            Record::Gap {
+                timestamp: small.timestamp,
+                drops: Drops {
+                    packets: [1, 0],
+                    bytes: [17, 0]
+                }
+            },
+            Record::Packet(small),
+        ]
+    );
+    assert_eq!(
+        ring.write_packet(0, client.direction, 0, &[]),
+        Err(WriteError::Skipped)
+    );
+
+    let effective = CaptureOptions {
+        ring_capacity: 1,
+        max_packet_size: u32::MAX,
+        directions: u32::MAX,
+    }
+    .effective();
+    assert_eq!(effective.ring_capacity, MIN_RING_CAPACITY);
+    assert_eq!(effective.directions, ALL_DIRECTIONS);
+    let p = packet(0, effective.max_packet_size as usize);
+    assert!(entry_size(p.data.len()) < MIN_RING_CAPACITY as usize);
+    assert!(entry_size(p.data.len() + 1) >= MIN_RING_CAPACITY as usize);
+    assert_eq!(
+        CaptureOptions::with_capacity(u32::MAX)
+            .effective()
+            .ring_capacity,
+        MAX_RING_CAPACITY
+    );
+}
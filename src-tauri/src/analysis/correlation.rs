@@ -0,0 +1,292 @@
+use crate::extract::session_build;
+use crate::parser;
+use crate::state::{Direction, Packet, Session};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// How a request or response names what it is about. Every key this table
+/// needs sits at the start of the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    /// Only one can be outstanding at a time, so replies pair in order.
+    None,
+    U32,
+    /// A u32 entry whose top bit marks "not found" in the reply.
+    Entry,
+    Guid,
+    PackedGuid,
+}
+
+impl Key {
+    fn read(self, data: &[u8]) -> Option<u64> {
+        let u32_at_start = || {
+            data.get(..4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        };
+        match self {
+            Key::None => Some(0),
+            Key::U32 => u32_at_start().map(u64::from),
+            Key::Entry => u32_at_start().map(|v| u64::from(v & 0x7FFF_FFFF)),
+            Key::Guid => data
+                .get(..8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap())),
+            Key::PackedGuid => {
+                let (&mask, rest) = data.split_first()?;
+                let mut guid = 0u64;
+                let mut bytes = rest.iter();
+                for i in 0..8 {
+                    if mask & (1 << i) != 0 {
+                        guid |= u64::from(*bytes.next()?) << (i * 8);
+                    }
+                }
+                Some(guid)
+            }
+        }
+    }
+}
+
+/// A request and the reply it gets.
+#[derive(Debug, Clone, Copy)]
+struct Pairing {
+    request: &'static str,
+    request_key: Key,
+    response: &'static str,
+    response_key: Key,
+}
+
+const fn pairing(request: &'static str, response: &'static str, key: Key) -> Pairing {
+    Pairing {
+        request,
+        request_key: key,
+        response,
+        response_key: key,
+    }
+}
+
+/// The pairs a build has. Opcodes a build lacks never match anything, so
+/// only layouts that differ need per-build entries.
+fn pairings(build: u32) -> Vec<Pairing> {
+    vec![
+        pairing("CMSG_PING", "SMSG_PONG", Key::U32),
+        pairing("SMSG_TIME_SYNC_REQ", "CMSG_TIME_SYNC_RESP", Key::U32),
+        pairing("CMSG_QUERY_TIME", "SMSG_QUERY_TIME_RESPONSE", Key::None),
+        Pairing {
+            request: "CMSG_NAME_QUERY",
+            request_key: Key::Guid,
+            response: "SMSG_NAME_QUERY_RESPONSE",
+            response_key: if build >= 12340 {
+                Key::PackedGuid
+            } else {
+                Key::Guid
+            },
+        },
+        pairing(
+            "CMSG_CREATURE_QUERY",
+            "SMSG_CREATURE_QUERY_RESPONSE",
+            Key::Entry,
+        ),
+        pairing(
+            "CMSG_GAMEOBJECT_QUERY",
+            "SMSG_GAMEOBJECT_QUERY_RESPONSE",
+            Key::Entry,
+        ),
+        pairing(
+            "CMSG_ITEM_QUERY_SINGLE",
+            "SMSG_ITEM_QUERY_SINGLE_RESPONSE",
+            Key::Entry,
+        ),
+        pairing(
+            "CMSG_ITEM_NAME_QUERY",
+            "SMSG_ITEM_NAME_QUERY_RESPONSE",
+            Key::U32,
+        ),
+        pairing("CMSG_QUEST_QUERY", "SMSG_QUEST_QUERY_RESPONSE", Key::U32),
+        pairing("CMSG_NPC_TEXT_QUERY", "SMSG_NPC_TEXT_UPDATE", Key::U32),
+        pairing(
+            "CMSG_PAGE_TEXT_QUERY",
+            "SMSG_PAGE_TEXT_QUERY_RESPONSE",
+            Key::U32,
+        ),
+        pairing("CMSG_GUILD_QUERY", "SMSG_GUILD_QUERY_RESPONSE", Key::U32),
+        pairing("CMSG_GOSSIP_HELLO", "SMSG_GOSSIP_MESSAGE", Key::Guid),
+        pairing("CMSG_LIST_INVENTORY", "SMSG_LIST_INVENTORY", Key::Guid),
+        pairing("CMSG_TRAINER_LIST", "SMSG_TRAINER_LIST", Key::Guid),
+        pairing("CMSG_LOOT", "SMSG_LOOT_RESPONSE", Key::Guid),
+        pairing("CMSG_WHO", "SMSG_WHO", Key::None),
+    ]
+}
+
+/// The direction an opcode name's prefix says it travels.
+fn sent_by(name: &str) -> Direction {
+    if name.starts_with("CMSG_") {
+        Direction::ClientToServer
+    } else {
+        Direction::ServerToClient
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Link {
+    /// Index into `CorrelationReport::pairings`.
+    pub pairing: usize,
+    pub request_id: usize,
+    pub response_id: usize,
+    pub latency_ms: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyStats {
+    pub min_ms: u32,
+    pub max_ms: u32,
+    pub mean_ms: f64,
+    pub median_ms: u32,
+    pub p95_ms: u32,
+}
+
+impl LatencyStats {
+    fn of(mut latencies: Vec<u32>) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort_unstable();
+        let rank = |q: f64| latencies[((latencies.len() - 1) as f64 * q).round() as usize];
+        Some(LatencyStats {
+            min_ms: latencies[0],
+            max_ms: latencies[latencies.len() - 1],
+            mean_ms: latencies.iter().map(|&l| f64::from(l)).sum::<f64>() / latencies.len() as f64,
+            median_ms: rank(0.5),
+            p95_ms: rank(0.95),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingStats {
+    pub request: String,
+    pub response: String,
+    pub matched: usize,
+    /// Requests that never got a reply.
+    pub orphaned: usize,
+    /// Replies with no outstanding request.
+    pub unsolicited: usize,
+    /// Packets too short to hold their key.
+    pub unreadable: usize,
+    pub latency: Option<LatencyStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorrelationReport {
+    pub build: u32,
+    /// Only pairings seen in the session.
+    pub pairings: Vec<PairingStats>,
+    pub links: Vec<Link>,
+    pub orphaned_requests: Vec<usize>,
+    pub unsolicited_responses: Vec<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Role {
+    Request,
+    Response,
+}
+
+/// Pair each request with the oldest outstanding request of the same kind
+/// and key, and measure the time to its reply.
+pub fn correlation_report(session: &Session) -> Result<CorrelationReport, String> {
+    let build = session_build(session)?;
+    let table = pairings(build);
+    let mut roles: HashMap<&str, (usize, Role)> = HashMap::new();
+    for (index, pairing) in table.iter().enumerate() {
+        roles.insert(pairing.request, (index, Role::Request));
+        roles.insert(pairing.response, (index, Role::Response));
+    }
+
+    let mut stats: Vec<PairingStats> = table
+        .iter()
+        .map(|p| PairingStats {
+            request: p.request.to_string(),
+            response: p.response.to_string(),
+            matched: 0,
+            orphaned: 0,
+            unsolicited: 0,
+            unreadable: 0,
+            latency: None,
+        })
+        .collect();
+    let mut latencies: Vec<Vec<u32>> = vec![Vec::new(); table.len()];
+    let mut pending: HashMap<(usize, u64), VecDeque<&Packet>> = HashMap::new();
+    let mut links = Vec::new();
+    let mut unsolicited_responses = Vec::new();
+    let mut seen = vec![false; table.len()];
+
+    for packet in &session.packets {
+        let name = parser::opcode_name(Some(build), packet);
+        let Some(&(index, role)) = roles.get(name) else {
+            continue;
+        };
+        if packet.direction != sent_by(name) {
+            continue;
+        }
+        seen[index] = true;
+        let pairing = &table[index];
+        let key = match role {
+            Role::Request => pairing.request_key,
+            Role::Response => pairing.response_key,
+        };
+        let Some(key) = key.read(&packet.data) else {
+            stats[index].unreadable += 1;
+            continue;
+        };
+        match role {
+            Role::Request => pending.entry((index, key)).or_default().push_back(packet),
+            Role::Response => match pending.get_mut(&(index, key)).and_then(VecDeque::pop_front) {
+                Some(request) => {
+                    let latency_ms = packet.timestamp.saturating_sub(request.timestamp);
+                    latencies[index].push(latency_ms);
+                    stats[index].matched += 1;
+                    links.push(Link {
+                        pairing: index,
+                        request_id: request.id,
+                        response_id: packet.id,
+                        latency_ms,
+                    });
+                }
+                None => {
+                    stats[index].unsolicited += 1;
+                    unsolicited_responses.push(packet.id);
+                }
+            },
+        }
+    }
+
+    let mut orphaned_requests = Vec::new();
+    for ((index, _), requests) in pending {
+        stats[index].orphaned += requests.len();
+        orphaned_requests.extend(requests.iter().map(|p| p.id));
+    }
+    orphaned_requests.sort_unstable();
+    for (row, latencies) in stats.iter_mut().zip(latencies) {
+        row.latency = LatencyStats::of(latencies);
+    }
+
+    // Renumber links to the pairings that survive the filter.
+    let mut renumber = vec![usize::MAX; table.len()];
+    let mut pairings = Vec::new();
+    for (index, row) in stats.into_iter().enumerate() {
+        if seen[index] {
+            renumber[index] = pairings.len();
+            pairings.push(row);
+        }
+    }
+    for link in &mut links {
+        link.pairing = renumber[link.pairing];
+    }
+
+    Ok(CorrelationReport {
+        build,
+        pairings,
+        links,
+        orphaned_requests,
+        unsolicited_responses,
+    })
+}
@@ -0,0 +1,92 @@
+use crate::parser::{self, ParseStatus};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Coverage numbers for a single opcode in one direction.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpcodeCoverage {
+    pub opcode: u32,
+    pub direction: Direction,
+    pub opcode_name: String,
+    pub count: usize,
+    pub name_known: bool,
+    pub has_definition: bool,
+    pub clean: usize,
+    pub trailing_bytes: usize,
+    pub failed: usize,
+}
+
+/// How much of a session the Rust decoders understand.
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    pub build: Option<u32>,
+    pub total_packets: usize,
+    /// Packets whose opcode has a known name.
+    pub named_packets: usize,
+    /// Packets whose opcode has a structure definition.
+    pub defined_packets: usize,
+    pub clean: usize,
+    pub trailing_bytes: usize,
+    pub failed: usize,
+    /// One row per (opcode, direction), most frequent first.
+    pub opcodes: Vec<OpcodeCoverage>,
+}
+
+pub fn coverage_report(session: &Session) -> CoverageReport {
+    let mut rows: HashMap<(u32, Direction), OpcodeCoverage> = HashMap::new();
+
+    for packet in &session.packets {
+        let outcome = parser::parse_packet(session.build, packet);
+        let row = rows
+            .entry((packet.opcode, packet.direction))
+            .or_insert_with(|| {
+                let name = parser::opcode_name(session.build, packet);
+                OpcodeCoverage {
+                    opcode: packet.opcode,
+                    direction: packet.direction,
+                    opcode_name: name.to_string(),
+                    count: 0,
+                    name_known: name != "UNKNOWN",
+                    has_definition: outcome.status != ParseStatus::NoDefinition,
+                    clean: 0,
+                    trailing_bytes: 0,
+                    failed: 0,
+                }
+            });
+        row.count += 1;
+        match outcome.status {
+            ParseStatus::Clean => row.clean += 1,
+            ParseStatus::TrailingBytes => row.trailing_bytes += 1,
+            ParseStatus::Failed => row.failed += 1,
+            ParseStatus::NoDefinition => {}
+        }
+    }
+
+    let mut opcodes: Vec<OpcodeCoverage> = rows.into_values().collect();
+    opcodes.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(a.opcode.cmp(&b.opcode))
+            .then((a.direction as u8).cmp(&(b.direction as u8)))
+    });
+
+    CoverageReport {
+        build: session.build,
+        total_packets: session.packets.len(),
+        named_packets: opcodes
+            .iter()
+            .filter(|o| o.name_known)
+            .map(|o| o.count)
+            .sum(),
+        defined_packets: opcodes
+            .iter()
+            .filter(|o| o.has_definition)
+            .map(|o| o.count)
+            .sum(),
+        clean: opcodes.iter().map(|o| o.clean).sum(),
+        trailing_bytes: opcodes.iter().map(|o| o.trailing_bytes).sum(),
+        failed: opcodes.iter().map(|o| o.failed).sum(),
+        opcodes,
+    }
+}
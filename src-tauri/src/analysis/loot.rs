@@ -0,0 +1,454 @@
+//! Drop-rate estimates from loot windows, aggregated over any number of
+//! sessions.
+//!
+//! Each corpse, object or item counts once until it is destroyed or created
+//! anew: only the first window opened on it is sampled, since reopening
+//! shows what is left rather than what dropped. Respawns keep their spawn's
+//! GUID, so a new create block or `SMSG_DESTROY_OBJECT` for it starts a new
+//! sample. Quest items only appear for players on the quest, so
+//! their rates are rates among those windows, not the table's chance.
+
+use crate::extract::{decode, decode_update, session_build, ObjectEntries};
+use crate::parser;
+use crate::parser::guid::{high_guid, HighGuid};
+use crate::parser::loot;
+use crate::parser::update_object::{self, UpdateBlock};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// z for the 95% confidence interval.
+const Z: f64 = 1.96;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LootSource {
+    Creature,
+    GameObject,
+    Item,
+    Other,
+}
+
+impl LootSource {
+    fn of(guid: u64) -> Self {
+        match high_guid(guid) {
+            HighGuid::Creature | HighGuid::Vehicle => LootSource::Creature,
+            HighGuid::GameObject => LootSource::GameObject,
+            HighGuid::Item => LootSource::Item,
+            _ => LootSource::Other,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LootSource::Creature => "creature",
+            LootSource::GameObject => "gameobject",
+            LootSource::Item => "item",
+            LootSource::Other => "other",
+        }
+    }
+}
+
+/// The emulator loot table a source and loot type most likely map to.
+fn loot_table(source: LootSource, loot_type: u8) -> Option<&'static str> {
+    match (source, loot_type) {
+        (LootSource::Creature, 1) => Some("creature_loot_template"),
+        (LootSource::Creature, 2) => Some("pickpocketing_loot_template"),
+        (LootSource::Creature, 6) => Some("skinning_loot_template"),
+        (LootSource::GameObject, 1) => Some("gameobject_loot_template"),
+        (LootSource::GameObject, 3) => Some("fishing_loot_template"),
+        (LootSource::Item, 1) => Some("item_loot_template"),
+        (LootSource::Item, 4) => Some("disenchant_loot_template"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LootDrop {
+    pub item: u32,
+    /// Windows the item appeared in.
+    pub drops: usize,
+    pub rate: f64,
+    /// 95% Wilson score interval for `rate`.
+    pub rate_low: f64,
+    pub rate_high: f64,
+    pub min_count: u32,
+    pub max_count: u32,
+    pub total_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LootTable {
+    pub source: LootSource,
+    pub entry: u32,
+    pub loot_type: u8,
+    pub table: Option<&'static str>,
+    /// Windows sampled; the sample size behind every rate below.
+    pub samples: usize,
+    /// Sessions contributing at least one window.
+    pub sessions: usize,
+    pub gold_min: u32,
+    pub gold_max: u32,
+    pub gold_avg: f64,
+    /// Most frequent first.
+    pub drops: Vec<LootDrop>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LootReport {
+    pub tables: Vec<LootTable>,
+    /// Sessions left out, with the reason.
+    pub skipped: Vec<String>,
+    pub csv: String,
+}
+
+#[derive(Default)]
+struct Tally {
+    samples: usize,
+    sessions: HashSet<usize>,
+    gold: Vec<u32>,
+    /// item -> (windows, min, max, total)
+    items: BTreeMap<u32, (usize, u32, u32, u64)>,
+}
+
+fn wilson(k: usize, n: usize) -> (f64, f64) {
+    if n == 0 {
+        return (0.0, 0.0);
+    }
+    let (k, n) = (k as f64, n as f64);
+    let p = k / n;
+    let denom = 1.0 + Z * Z / n;
+    let center = (p + Z * Z / (2.0 * n)) / denom;
+    let half = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denom;
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+pub fn loot_report(sessions: &[&Session]) -> LootReport {
+    let mut tallies: BTreeMap<(LootSource, u32, u8), Tally> = BTreeMap::new();
+    let mut skipped = Vec::new();
+
+    for (index, session) in sessions.iter().enumerate() {
+        let build = match session_build(session) {
+            Ok(build) => build,
+            Err(e) => {
+                skipped.push(e);
+                continue;
+            }
+        };
+        let entries = ObjectEntries::collect(session, build);
+        // GUID -> loot types already sampled since the object was created.
+        let mut opened: HashMap<u64, HashSet<u8>> = HashMap::new();
+        for packet in &session.packets {
+            if packet.direction != Direction::ServerToClient {
+                continue;
+            }
+            let name = parser::opcode_name(Some(build), packet);
+            if name == "SMSG_DESTROY_OBJECT" {
+                let read = |r: &mut _| update_object::read_destroy_object(r, build);
+                if let Some(destroy) = decode(packet, read) {
+                    opened.remove(&destroy.guid);
+                }
+                continue;
+            }
+            if let Some(update) = decode_update(packet, name, build) {
+                for block in &update.blocks {
+                    if let UpdateBlock::Create { guid, .. } = block {
+                        opened.remove(guid);
+                    }
+                }
+                continue;
+            }
+            if name != "SMSG_LOOT_RESPONSE" {
+                continue;
+            }
+            let Some(response) = decode(packet, loot::read_loot_response) else {
+                continue;
+            };
+            if response.error.is_some()
+                || !opened
+                    .entry(response.guid)
+                    .or_default()
+                    .insert(response.loot_type)
+            {
+                continue;
+            }
+            let source = LootSource::of(response.guid);
+            let entry = entries.get(response.guid).unwrap_or(0);
+            let tally = tallies
+                .entry((source, entry, response.loot_type))
+                .or_default();
+            tally.samples += 1;
+            tally.sessions.insert(index);
+            tally.gold.push(response.gold);
+            // A window can list the same item in several slots.
+            let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+            for item in &response.items {
+                *counts.entry(item.item).or_default() += item.count;
+            }
+            for (item, count) in counts {
+                let seen = tally.items.entry(item).or_insert((0, count, count, 0));
+                seen.0 += 1;
+                seen.1 = seen.1.min(count);
+                seen.2 = seen.2.max(count);
+                seen.3 += u64::from(count);
+            }
+        }
+    }
+
+    let tables: Vec<LootTable> = tallies
+        .into_iter()
+        .map(|((source, entry, loot_type), tally)| {
+            let n = tally.samples;
+            let mut drops: Vec<LootDrop> = tally
+                .items
+                .into_iter()
+                .map(|(item, (k, min_count, max_count, total_count))| {
+                    let (rate_low, rate_high) = wilson(k, n);
+                    LootDrop {
+                        item,
+                        drops: k,
+                        rate: k as f64 / n as f64,
+                        rate_low,
+                        rate_high,
+                        min_count,
+                        max_count,
+                        total_count,
+                    }
+                })
+                .collect();
+            drops.sort_by(|a, b| b.drops.cmp(&a.drops).then(a.item.cmp(&b.item)));
+            LootTable {
+                source,
+                entry,
+                loot_type,
+                table: loot_table(source, loot_type),
+                samples: n,
+                sessions: tally.sessions.len(),
+                gold_min: tally.gold.iter().copied().min().unwrap_or(0),
+                gold_max: tally.gold.iter().copied().max().unwrap_or(0),
+                gold_avg: tally.gold.iter().map(|&g| f64::from(g)).sum::<f64>() / n as f64,
+                drops,
+            }
+        })
+        .collect();
+
+    let csv = loot_csv(&tables);
+    LootReport {
+        tables,
+        skipped,
+        csv,
+    }
+}
+
+/// One line per table and item.
+fn loot_csv(tables: &[LootTable]) -> String {
+    let mut out = String::from(
+        "source,entry,loot_type,table,samples,sessions,gold_avg,item,drops,rate,rate_low,rate_high,min_count,max_count,total_count\n",
+    );
+    for table in tables {
+        for drop in &table.drops {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{:.1},{},{},{:.4},{:.4},{:.4},{},{},{}\n",
+                table.source.as_str(),
+                table.entry,
+                table.loot_type,
+                table.table.unwrap_or(""),
+                table.samples,
+                table.sessions,
+                table.gold_avg,
+                drop.item,
+                drop.drops,
+                drop.rate,
+                drop.rate_low,
+                drop.rate_high,
+                drop.min_count,
+                drop.max_count,
+                drop.total_count
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{session, vector, Bytes};
+    use crate::parser::loot::tests::{loot_error, loot_response};
+    use crate::parser::update_fields::OBJECT_FIELD_ENTRY;
+    use crate::parser::update_object::TYPEID_UNIT;
+
+    const BUILDS: [u32; 3] = [5875, 8606, 12340];
+    /// Creature 1234.
+    const CORPSE: u64 = 0xF130_0004_D200_0101;
+    /// Gameobject 1617.
+    const VEIN: u64 = 0xF110_0006_5100_0201;
+    /// An item with no entry bits; its entry comes from an update.
+    const BOX: u64 = 0x4000_0000_0000_0301;
+
+    fn respawn(build: u32) -> Vec<u8> {
+        Bytes::new()
+            .update_header(build, 1)
+            .create_header(CORPSE, TYPEID_UNIT)
+            .living(build, vector(1.0, 2.0, 3.0), 0.0)
+            .update_mask(&[(OBJECT_FIELD_ENTRY, 1234)])
+            .build()
+    }
+
+    fn item_entry(build: u32) -> Vec<u8> {
+        Bytes::new()
+            .update_header(build, 1)
+            .u8(0)
+            .packed_guid(BOX)
+            .update_mask(&[(OBJECT_FIELD_ENTRY, 6827)])
+            .build()
+    }
+
+    fn destroy(build: u32) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.guid(CORPSE);
+        if build >= 12340 {
+            b.u8(1);
+        }
+        b.build()
+    }
+
+    /// The corpse is sampled three times: once before it is destroyed, once
+    /// after, and once more after it respawns. Reopened and refused windows
+    /// don't count.
+    fn capture(build: u32) -> Session {
+        session(
+            build,
+            vec![
+                ("SMSG_UPDATE_OBJECT", item_entry(build)),
+                (
+                    "SMSG_LOOT_RESPONSE",
+                    loot_response(CORPSE, 1, 10, &[(2589, 2), (2589, 1)]),
+                ),
+                (
+                    "SMSG_LOOT_RESPONSE",
+                    loot_response(CORPSE, 1, 0, &[(2589, 1)]),
+                ),
+                (
+                    "SMSG_LOOT_RESPONSE",
+                    loot_response(CORPSE, 2, 5, &[(5368, 1)]),
+                ),
+                ("SMSG_DESTROY_OBJECT", destroy(build)),
+                ("SMSG_LOOT_RESPONSE", loot_error(CORPSE)),
+                (
+                    "SMSG_LOOT_RESPONSE",
+                    loot_response(CORPSE, 1, 30, &[(2589, 1), (6070, 1)]),
+                ),
+                ("SMSG_UPDATE_OBJECT", respawn(build)),
+                ("SMSG_LOOT_RESPONSE", loot_response(CORPSE, 1, 20, &[])),
+                (
+                    "SMSG_LOOT_RESPONSE",
+                    loot_response(VEIN, 1, 0, &[(2770, 3)]),
+                ),
+                ("SMSG_LOOT_RESPONSE", loot_response(BOX, 1, 0, &[(858, 1)])),
+            ],
+        )
+    }
+
+    fn table(report: &LootReport, source: LootSource, loot_type: u8) -> &LootTable {
+        report
+            .tables
+            .iter()
+            .find(|t| t.source == source && t.loot_type == loot_type)
+            .unwrap()
+    }
+
+    #[test]
+    fn ties_windows_to_their_source() {
+        for build in BUILDS {
+            let report = loot_report(&[&capture(build)]);
+            let keys: Vec<_> = report
+                .tables
+                .iter()
+                .map(|t| (t.source, t.entry, t.loot_type, t.table, t.samples))
+                .collect();
+            assert_eq!(
+                keys,
+                [
+                    (
+                        LootSource::Creature,
+                        1234,
+                        1,
+                        Some("creature_loot_template"),
+                        3
+                    ),
+                    (
+                        LootSource::Creature,
+                        1234,
+                        2,
+                        Some("pickpocketing_loot_template"),
+                        1
+                    ),
+                    (
+                        LootSource::GameObject,
+                        1617,
+                        1,
+                        Some("gameobject_loot_template"),
+                        1
+                    ),
+                    (LootSource::Item, 6827, 1, Some("item_loot_template"), 1),
+                ],
+                "build {}",
+                build
+            );
+        }
+    }
+
+    #[test]
+    fn aggregates_drop_rates() {
+        let report = loot_report(&[&capture(12340)]);
+        let corpse = table(&report, LootSource::Creature, 1);
+        assert_eq!(
+            (corpse.gold_min, corpse.gold_max, corpse.gold_avg),
+            (10, 30, 20.0)
+        );
+        let drops: Vec<_> = corpse
+            .drops
+            .iter()
+            .map(|d| (d.item, d.drops, d.min_count, d.max_count, d.total_count))
+            .collect();
+        // Stacks in two slots of one window are one drop of three.
+        assert_eq!(drops, [(2589, 2, 1, 3, 4), (6070, 1, 1, 1, 1)]);
+        let common = &corpse.drops[0];
+        assert_eq!(common.rate, 2.0 / 3.0);
+        assert!(common.rate_low < common.rate && common.rate < common.rate_high);
+        assert!(report
+            .csv
+            .contains("creature,1234,1,creature_loot_template,3,1,20.0,2589,2,0.6667,"));
+    }
+
+    #[test]
+    fn wilson_interval() {
+        let (low, high) = wilson(5, 10);
+        assert!((low - 0.2366).abs() < 1e-4 && (high - 0.7634).abs() < 1e-4);
+        assert_eq!(wilson(0, 10).0, 0.0);
+        assert_eq!(wilson(10, 10).1, 1.0);
+        assert_eq!(wilson(0, 0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn sessions_are_sampled_separately() {
+        let first = capture(5875);
+        let second = session(
+            8606,
+            vec![(
+                "SMSG_LOOT_RESPONSE",
+                loot_response(CORPSE, 1, 40, &[(2589, 1)]),
+            )],
+        );
+        let unknown = Session::new("no build");
+        let report = loot_report(&[&first, &second, &unknown]);
+        let corpse = table(&report, LootSource::Creature, 1);
+        assert_eq!((corpse.samples, corpse.sessions), (4, 2));
+        assert_eq!(corpse.drops[0].drops, 3);
+        assert_eq!(
+            report.skipped,
+            ["Session no build has no client build recorded"]
+        );
+    }
+}
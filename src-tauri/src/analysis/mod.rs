@@ -0,0 +1,9 @@
+//! Session-level reports computed in the backend so the frontend never has to
+//! pull raw payloads to answer questions about a capture.
+
+pub mod correlation;
+pub mod coverage;
+pub mod loot;
+pub mod stats;
+pub mod timeline;
+pub mod validation;
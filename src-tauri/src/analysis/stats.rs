@@ -0,0 +1,149 @@
+use crate::parser;
+use crate::state::{Direction, Packet, Session};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Width of the sliding window behind `peak_per_second`.
+const PEAK_WINDOW_MS: u32 = 1000;
+
+/// Counts and rates for one slice of the traffic.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrafficStats {
+    pub count: usize,
+    pub bytes: u64,
+    pub average_bytes: f64,
+    pub first_seen: u32,
+    pub last_seen: u32,
+    /// Most packets seen within any one-second window.
+    pub peak_per_second: usize,
+    /// Averaged over the whole session, so slices compare directly.
+    pub packets_per_second: f64,
+    pub bytes_per_second: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpcodeStats {
+    pub opcode: u32,
+    pub direction: Direction,
+    pub opcode_name: String,
+    #[serde(flatten)]
+    pub traffic: TrafficStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectionStats {
+    pub direction: Direction,
+    #[serde(flatten)]
+    pub traffic: TrafficStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStats {
+    pub build: Option<u32>,
+    /// From the first packet to the last.
+    pub duration_ms: u32,
+    pub overall: TrafficStats,
+    pub directions: Vec<DirectionStats>,
+    /// One row per (opcode, direction), most bytes first.
+    pub opcodes: Vec<OpcodeStats>,
+}
+
+/// Packet timestamps and sizes, folded into `TrafficStats` at the end.
+#[derive(Default)]
+struct Tally {
+    timestamps: Vec<u32>,
+    bytes: u64,
+}
+
+impl Tally {
+    fn add(&mut self, packet: &Packet) {
+        self.timestamps.push(packet.timestamp);
+        self.bytes += packet.size as u64;
+    }
+
+    fn finish(mut self, duration_ms: u32) -> TrafficStats {
+        let count = self.timestamps.len();
+        if count == 0 {
+            return TrafficStats::default();
+        }
+        // Capture order is nearly but not strictly time order.
+        self.timestamps.sort_unstable();
+        let mut peak = 0;
+        let mut start = 0;
+        for (end, &time) in self.timestamps.iter().enumerate() {
+            while time - self.timestamps[start] >= PEAK_WINDOW_MS {
+                start += 1;
+            }
+            peak = peak.max(end - start + 1);
+        }
+        let seconds = f64::from(duration_ms.max(1)) / 1000.0;
+        TrafficStats {
+            count,
+            bytes: self.bytes,
+            average_bytes: self.bytes as f64 / count as f64,
+            first_seen: self.timestamps[0],
+            last_seen: self.timestamps[count - 1],
+            peak_per_second: peak,
+            packets_per_second: count as f64 / seconds,
+            bytes_per_second: self.bytes as f64 / seconds,
+        }
+    }
+}
+
+/// Traffic by opcode and direction, from packet headers only.
+pub fn session_stats(session: &Session) -> SessionStats {
+    let mut overall = Tally::default();
+    let mut directions: HashMap<Direction, Tally> = HashMap::new();
+    let mut opcodes: HashMap<(u32, Direction), Tally> = HashMap::new();
+    let mut names: HashMap<u32, &str> = HashMap::new();
+    for packet in &session.packets {
+        names
+            .entry(packet.opcode)
+            .or_insert_with(|| parser::opcode_name(session.build, packet));
+        overall.add(packet);
+        directions.entry(packet.direction).or_default().add(packet);
+        opcodes
+            .entry((packet.opcode, packet.direction))
+            .or_default()
+            .add(packet);
+    }
+
+    let first = session.packets.iter().map(|p| p.timestamp).min();
+    let last = session.packets.iter().map(|p| p.timestamp).max();
+    let duration_ms = first.zip(last).map_or(0, |(first, last)| last - first);
+
+    let mut directions: Vec<DirectionStats> = directions
+        .into_iter()
+        .map(|(direction, tally)| DirectionStats {
+            direction,
+            traffic: tally.finish(duration_ms),
+        })
+        .collect();
+    directions.sort_by_key(|d| d.direction as u8);
+
+    let mut opcodes: Vec<OpcodeStats> = opcodes
+        .into_iter()
+        .map(|((opcode, direction), tally)| OpcodeStats {
+            opcode,
+            direction,
+            opcode_name: names[&opcode].to_string(),
+            traffic: tally.finish(duration_ms),
+        })
+        .collect();
+    opcodes.sort_by(|a, b| {
+        b.traffic
+            .bytes
+            .cmp(&a.traffic.bytes)
+            .then(b.traffic.count.cmp(&a.traffic.count))
+            .then(a.opcode.cmp(&b.opcode))
+            .then((a.direction as u8).cmp(&(b.direction as u8)))
+    });
+
+    SessionStats {
+        build: session.build,
+        duration_ms,
+        overall: overall.finish(duration_ms),
+        directions,
+        opcodes,
+    }
+}
@@ -0,0 +1,239 @@
+use crate::parser;
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Finer buckets than this make multi-hour captures unchartable.
+pub const MIN_INTERVAL_MS: u32 = 10;
+
+/// Rough grouping of opcodes by what they are about, from their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Movement,
+    Objects,
+    Queries,
+    Npc,
+    Chat,
+    Spells,
+    Combat,
+    Items,
+    Social,
+    Other,
+}
+
+impl Category {
+    pub const ALL: [Category; 10] = [
+        Category::Movement,
+        Category::Objects,
+        Category::Queries,
+        Category::Npc,
+        Category::Chat,
+        Category::Spells,
+        Category::Combat,
+        Category::Items,
+        Category::Social,
+        Category::Other,
+    ];
+
+    /// First match wins, so `CMSG_ITEM_QUERY_SINGLE` is a query and
+    /// `SMSG_LIST_INVENTORY` is an NPC interaction.
+    const RULES: &'static [(Category, &'static [&'static str])] = &[
+        (Category::Queries, &["QUERY"]),
+        (
+            Category::Movement,
+            &["MOVE", "SPLINE", "TELEPORT", "TRANSPORT"],
+        ),
+        (Category::Objects, &["UPDATE_OBJECT", "DESTROY_OBJECT"]),
+        (
+            Category::Npc,
+            &["QUEST", "GOSSIP", "TRAINER", "LIST_INVENTORY", "NPC_TEXT"],
+        ),
+        (Category::Chat, &["CHAT", "EMOTE", "CHANNEL", "WHO"]),
+        (Category::Spells, &["SPELL", "AURA", "CAST", "COOLDOWN"]),
+        (
+            Category::Combat,
+            &["ATTACK", "DAMAGE", "COMBAT", "ENVIRONMENTAL", "THREAT"],
+        ),
+        (
+            Category::Items,
+            &[
+                "ITEM",
+                "LOOT",
+                "INVENTORY",
+                "VENDOR",
+                "TRADE",
+                "BANK",
+                "MAIL",
+                "AUCTION",
+            ],
+        ),
+        (
+            Category::Social,
+            &["GROUP", "PARTY", "RAID", "GUILD", "FRIEND", "IGNORE"],
+        ),
+    ];
+
+    pub fn of(opcode_name: &str) -> Self {
+        Self::RULES
+            .iter()
+            .find(|(_, needles)| needles.iter().any(|n| opcode_name.contains(n)))
+            .map_or(Category::Other, |&(category, _)| category)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Category::Movement => "movement",
+            Category::Objects => "objects",
+            Category::Queries => "queries",
+            Category::Npc => "npc",
+            Category::Chat => "chat",
+            Category::Spells => "spells",
+            Category::Combat => "combat",
+            Category::Items => "items",
+            Category::Social => "social",
+            Category::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Traffic {
+    pub packets: usize,
+    pub bytes: u64,
+}
+
+impl Traffic {
+    fn add(&mut self, size: usize) {
+        self.packets += 1;
+        self.bytes += size as u64;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bucket {
+    /// Since the session's first packet.
+    pub start_ms: u64,
+    pub total: Traffic,
+    pub server: Traffic,
+    pub client: Traffic,
+    /// Only categories seen in this bucket.
+    pub categories: BTreeMap<Category, Traffic>,
+}
+
+/// Traffic over time in fixed-width buckets, kept per session and folded
+/// forward as packets arrive instead of recomputed on every poll.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    interval_ms: u32,
+    start: Option<u32>,
+    folded: usize,
+    buckets: Vec<Bucket>,
+    categories: HashMap<u32, Category>,
+}
+
+/// Part of a timeline, ready to chart.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineView {
+    pub interval_ms: u32,
+    /// Session timestamp of the first bucket's start.
+    pub start_timestamp: Option<u32>,
+    /// Index of the first bucket below in the whole timeline.
+    pub first_bucket: usize,
+    pub bucket_count: usize,
+    pub buckets: Vec<Bucket>,
+}
+
+impl Timeline {
+    pub fn new(interval_ms: u32) -> Self {
+        Timeline {
+            interval_ms,
+            start: None,
+            folded: 0,
+            buckets: Vec::new(),
+            categories: HashMap::new(),
+        }
+    }
+
+    pub fn interval_ms(&self) -> u32 {
+        self.interval_ms
+    }
+
+    /// Fold in the packets added since the last update. Starts over if the
+    /// session has fewer packets than already folded.
+    pub fn update(&mut self, session: &Session) {
+        if session.packets.len() < self.folded {
+            *self = Timeline::new(self.interval_ms);
+        }
+        for packet in &session.packets[self.folded..] {
+            let start = *self.start.get_or_insert(packet.timestamp);
+            // Late packets stamped before the first one land in bucket zero.
+            let index = (packet.timestamp.saturating_sub(start) / self.interval_ms) as usize;
+            while self.buckets.len() <= index {
+                self.buckets.push(Bucket {
+                    start_ms: self.buckets.len() as u64 * u64::from(self.interval_ms),
+                    total: Traffic::default(),
+                    server: Traffic::default(),
+                    client: Traffic::default(),
+                    categories: BTreeMap::new(),
+                });
+            }
+            let category = *self
+                .categories
+                .entry(packet.opcode)
+                .or_insert_with(|| Category::of(parser::opcode_name(session.build, packet)));
+            let bucket = &mut self.buckets[index];
+            bucket.total.add(packet.size);
+            match packet.direction {
+                Direction::ServerToClient => bucket.server.add(packet.size),
+                Direction::ClientToServer => bucket.client.add(packet.size),
+            }
+            bucket
+                .categories
+                .entry(category)
+                .or_default()
+                .add(packet.size);
+        }
+        self.folded = session.packets.len();
+    }
+
+    /// Buckets from `since` on. While capturing, the last bucket is still
+    /// filling, so pollers should ask again from it.
+    pub fn view(&self, since: usize) -> TimelineView {
+        let first_bucket = since.min(self.buckets.len());
+        TimelineView {
+            interval_ms: self.interval_ms,
+            start_timestamp: self.start,
+            first_bucket,
+            bucket_count: self.buckets.len(),
+            buckets: self.buckets[first_bucket..].to_vec(),
+        }
+    }
+
+    /// One row per bucket, with a packets and bytes column for each
+    /// direction and category.
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("start_ms,packets,bytes,smsg_packets,smsg_bytes,cmsg_packets,cmsg_bytes");
+        for category in Category::ALL {
+            csv.push_str(&format!(",{0}_packets,{0}_bytes", category.label()));
+        }
+        csv.push('\n');
+        for bucket in &self.buckets {
+            csv.push_str(&bucket.start_ms.to_string());
+            for traffic in [bucket.total, bucket.server, bucket.client] {
+                csv.push_str(&format!(",{},{}", traffic.packets, traffic.bytes));
+            }
+            for category in Category::ALL {
+                let traffic = bucket
+                    .categories
+                    .get(&category)
+                    .copied()
+                    .unwrap_or_default();
+                csv.push_str(&format!(",{},{}", traffic.packets, traffic.bytes));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
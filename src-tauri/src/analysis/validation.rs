@@ -0,0 +1,118 @@
+use crate::parser::reader::{Finding, FindingKind};
+use crate::parser::{self, ParseError, ParseStatus};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+
+/// A packet the Rust decoders flagged as malformed or suspicious.
+#[derive(Debug, Clone, Serialize)]
+pub struct PacketFindings {
+    pub packet_id: usize,
+    pub opcode: u32,
+    pub direction: Direction,
+    pub opcode_name: String,
+    pub size: usize,
+    pub status: ParseStatus,
+    /// Offset where decoding stopped.
+    pub stopped_at: usize,
+    pub error: Option<ParseError>,
+    pub findings: Vec<Finding>,
+}
+
+/// Decode every packet in a session and return those with findings.
+///
+/// When `kinds` is non-empty only packets with at least one finding of those
+/// kinds are returned, and their finding lists are narrowed to match.
+pub fn find_malformed(session: &Session, kinds: &[FindingKind]) -> Vec<PacketFindings> {
+    session
+        .packets
+        .iter()
+        .filter_map(|packet| {
+            let outcome = parser::parse_packet(session.build, packet);
+            let findings: Vec<Finding> = outcome
+                .findings
+                .into_iter()
+                .filter(|f| kinds.is_empty() || kinds.contains(&f.kind))
+                .collect();
+            if findings.is_empty() {
+                return None;
+            }
+            Some(PacketFindings {
+                packet_id: packet.id,
+                opcode: packet.opcode,
+                direction: packet.direction,
+                opcode_name: parser::opcode_name(session.build, packet).to_string(),
+                size: packet.size,
+                status: outcome.status,
+                stopped_at: outcome.consumed,
+                error: outcome.error,
+                findings,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{compressed, session, vector, Bytes};
+    use crate::parser::update_object::TYPEID_UNIT;
+
+    fn update() -> Vec<u8> {
+        Bytes::new()
+            .update_header(5875, 1)
+            .create_header(0xF130_0004_D200_0101, TYPEID_UNIT)
+            .living(5875, vector(1.0, 2.0, 3.0), 0.0)
+            .update_mask(&[(3, 1234)])
+            .build()
+    }
+
+    /// One packet of each kind of trouble, plus a clean one that must not
+    /// show up.
+    fn capture() -> Session {
+        let ping = Bytes::new().u32(1).u32(50).build();
+        let mut trailing = ping.clone();
+        trailing.push(0);
+        let mut resized = compressed(&update());
+        resized[0] += 1;
+        session(
+            5875,
+            vec![
+                ("CMSG_PING", ping.clone()),
+                ("CMSG_PING", ping[..6].to_vec()),
+                ("CMSG_PING", trailing),
+                ("SMSG_COMPRESSED_UPDATE_OBJECT", resized),
+            ],
+        )
+    }
+
+    fn flagged(kinds: &[FindingKind]) -> Vec<(usize, ParseStatus, FindingKind)> {
+        find_malformed(&capture(), kinds)
+            .into_iter()
+            .map(|p| (p.packet_id, p.status, p.findings[0].kind))
+            .collect()
+    }
+
+    #[test]
+    fn flags_truncated_trailing_and_corrupt_packets() {
+        assert_eq!(
+            flagged(&[]),
+            [
+                (1, ParseStatus::Failed, FindingKind::Overrun),
+                (2, ParseStatus::TrailingBytes, FindingKind::TrailingBytes),
+                (3, ParseStatus::Failed, FindingKind::BadCompression),
+            ]
+        );
+    }
+
+    #[test]
+    fn narrows_to_the_requested_kinds() {
+        assert_eq!(
+            flagged(&[FindingKind::BadCompression]),
+            [(3, ParseStatus::Failed, FindingKind::BadCompression)]
+        );
+        let truncated = find_malformed(&capture(), &[FindingKind::Overrun]);
+        assert_eq!(truncated.len(), 1);
+        assert_eq!(truncated[0].stopped_at, 4);
+        assert_eq!(truncated[0].error.as_ref().unwrap().field, "latency");
+    }
+}
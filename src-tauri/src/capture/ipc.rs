@@ -1,64 +1,149 @@
-use serde::Serialize;
+use crate::state::{CaptureOptions, CaptureStatus, Direction, FilterMode};
+use ring_protocol::command::{self, Command, OpcodeFilter, Reply, MAX_FILTER_OPCODES};
+use ring_protocol::{
+    options_mapping_name, ring_mapping_name, Ring, RingError, ALL_DIRECTIONS,
+    DEFAULT_RING_CAPACITY, DIRECTION_CLIENT, DIRECTION_SERVER, OPTIONS_SIZE,
+};
 use std::ffi::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use windows::core::PCWSTR;
-use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
 use windows::Win32::System::Memory::{
-    MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, FILE_MAP_ALL_ACCESS, MEMORY_MAPPED_VIEW_ADDRESS,
+    CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery,
+    FILE_MAP_ALL_ACCESS, MEMORY_BASIC_INFORMATION, MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
 };
 
-/// Magic value the DLL writes into the shared memory header to signal
-/// that the ring buffer is initialised.
-const RING_MAGIC: u32 = 0x57535059;
-
-#[repr(C)]
-pub struct PacketRingHeader {
-    pub magic: u32,        // 0x57535059
-    pub write_pos: u32,    // written by DLL
-    pub read_pos: u32,     // written by host
-    pub capacity: u32,     // data area size
-    pub dll_ready: u32,    // 1 when DLL hooks installed
-    pub build_number: u32, // detected build
+/// Packet data read from shared memory and ready for the application layer,
+/// the gap markers the DLL leaves where packets were dropped, and what the
+/// reader lost to corruption.
+pub use ring_protocol::{Drops, Lost, RawPacket, Record};
+
+/// Capture options left in shared memory for the DLL to pick up when it
+/// creates its ring. Must outlive the DLL's `init_shared_memory`.
+pub struct OptionsMapping {
+    mapping: HANDLE,
 }
 
-/// A single entry in the ring buffer, immediately followed by
-/// `data_len` bytes of packet payload.
-#[repr(C)]
-pub struct PacketEntry {
-    pub total_size: u32,
-    pub timestamp: u32,
-    pub direction: u8,
-    pub _pad: [u8; 3],
-    pub opcode: u32,
-    pub data_len: u32,
+// The handle is only closed, never used concurrently.
+unsafe impl Send for OptionsMapping {}
+
+impl OptionsMapping {
+    /// Publish `options` as `Local\WowCaptureOptions_{pid}`.
+    pub fn publish(pid: u32, options: &CaptureOptions) -> Result<Self, String> {
+        let bytes = to_ring_options(options).to_bytes();
+        let wide_name: Vec<u16> = options_mapping_name(pid)
+            .encode_utf16()
+            .chain(Some(0))
+            .collect();
+
+        unsafe {
+            let mapping = CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                None,
+                PAGE_READWRITE,
+                0,
+                OPTIONS_SIZE as u32,
+                PCWSTR(wide_name.as_ptr()),
+            )
+            .map_err(|e| format!("CreateFileMappingW failed: {}", e))?;
+
+            let view = MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, OPTIONS_SIZE);
+            if view.Value.is_null() {
+                let _ = CloseHandle(mapping);
+                return Err("MapViewOfFile returned NULL".to_string());
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), view.Value as *mut u8, bytes.len());
+            let _ = UnmapViewOfFile(view);
+            Ok(Self { mapping })
+        }
+    }
 }
 
-const ENTRY_HEADER_SIZE: usize = std::mem::size_of::<PacketEntry>(); // 20 bytes
+impl Drop for OptionsMapping {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.mapping);
+        }
+    }
+}
 
-/// Packet data read from shared memory and ready for the application layer.
-#[derive(Debug, Clone, Serialize)]
-pub struct RawPacket {
-    pub timestamp: u32,
-    pub direction: u8, // 0=SMSG, 1=CMSG
-    pub opcode: u32,
-    pub data: Vec<u8>,
+fn to_ring_options(options: &CaptureOptions) -> ring_protocol::CaptureOptions {
+    let directions = options.directions.as_ref().map_or(ALL_DIRECTIONS, |directions| {
+        directions
+            .iter()
+            .map(|direction| match direction {
+                Direction::ServerToClient => DIRECTION_SERVER,
+                Direction::ClientToServer => DIRECTION_CLIENT,
+            })
+            .fold(0, |bits, bit| bits | bit)
+    });
+    ring_protocol::CaptureOptions {
+        ring_capacity: options.ring_size.unwrap_or(DEFAULT_RING_CAPACITY),
+        max_packet_size: options.max_packet_size.unwrap_or(u32::MAX),
+        directions,
+    }
 }
 
-/// Reads packets from the DLL-side shared-memory ring buffer.
+fn from_ring_options(options: &ring_protocol::CaptureOptions) -> CaptureOptions {
+    let directions = [
+        (DIRECTION_SERVER, Direction::ServerToClient),
+        (DIRECTION_CLIENT, Direction::ClientToServer),
+    ]
+    .into_iter()
+    .filter(|(bit, _)| options.directions & bit != 0)
+    .map(|(_, direction)| direction)
+    .collect();
+    CaptureOptions {
+        ring_size: Some(options.ring_capacity),
+        max_packet_size: Some(options.max_packet_size),
+        directions: Some(directions),
+    }
+}
+
+fn from_ring_status(status: &command::CaptureStatus) -> CaptureStatus {
+    CaptureStatus {
+        paused: status.paused,
+        hooks_active: status.hooks_active,
+        filter_mode: match status.filter_mode {
+            command::FilterMode::Off => FilterMode::Off,
+            command::FilterMode::Only => FilterMode::Only,
+            command::FilterMode::Except => FilterMode::Except,
+        },
+        filter_len: status.filter_len,
+        captured: status.captured,
+        filtered: status.filtered,
+    }
+}
+
+/// How long to wait for the DLL to answer a command. It looks for commands
+/// every 20 ms.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reads packets from the DLL-side shared-memory ring buffer, and sends the
+/// DLL commands over the same mapping.
 pub struct SharedMemoryReader {
     mapping: HANDLE,
     view: *mut u8,
-    capacity: u32,
+    /// `None` once the view is unmapped.
+    ring: Option<Ring<'static>>,
+    /// Held while a command waits for its reply; the channel has one sender.
+    commands: Mutex<()>,
+    next_command_id: AtomicU32,
 }
 
-// SharedMemoryReader is only accessed from one async task at a time.
+// The packet reader task is the ring's only consumer, and commands are
+// serialized by the `commands` lock.
 unsafe impl Send for SharedMemoryReader {}
 unsafe impl Sync for SharedMemoryReader {}
 
 impl SharedMemoryReader {
     /// Open an existing shared-memory region created by the capture DLL in the
     /// target process.  The name follows the pattern `Local\WowCapture_{pid}`.
+    /// Fails if the DLL speaks another ring protocol version.
     pub fn open(pid: u32) -> Result<Self, String> {
-        let name = format!("Local\\WowCapture_{}", pid);
+        let name = ring_mapping_name(pid);
         let wide_name: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
 
         unsafe {
@@ -77,104 +162,147 @@ impl SharedMemoryReader {
 
             let base = view.Value as *mut u8;
 
-            // Validate magic
-            let header = &*(base as *const PacketRingHeader);
-            if header.magic != RING_MAGIC {
-                let _ = UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS { Value: base as *mut c_void });
-                let _ = CloseHandle(mapping);
-                return Err(format!(
-                    "Bad magic in shared memory: 0x{:08X} (expected 0x{:08X})",
-                    header.magic, RING_MAGIC
-                ));
-            }
-
-            let capacity = header.capacity;
+            // The view covers the whole mapping; ask how big that is rather
+            // than trusting the capacity in the header.
+            let mut info = MEMORY_BASIC_INFORMATION::default();
+            let len = match VirtualQuery(
+                Some(base as *const c_void),
+                &mut info,
+                std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            ) {
+                0 => 0,
+                _ => info.RegionSize,
+            };
 
-            Ok(Self {
-                mapping,
-                view: base,
-                capacity,
-            })
+            match Ring::attach_raw(base, len) {
+                Ok(ring) => Ok(Self {
+                    mapping,
+                    view: base,
+                    ring: Some(ring),
+                    commands: Mutex::new(()),
+                    next_command_id: AtomicU32::new(0),
+                }),
+                Err(e) => {
+                    let _ = UnmapViewOfFile(view);
+                    let _ = CloseHandle(mapping);
+                    Err(match e {
+                        RingError::Incompatible { .. }
+                        | RingError::BadHeaderSize { .. }
+                        | RingError::UnknownFeatures(_) => {
+                            format!("Capture DLL is incompatible with this app: {}", e)
+                        }
+                        _ => e.to_string(),
+                    })
+                }
+            }
         }
     }
 
     /// Returns `true` once the DLL signals that its hooks are installed.
     pub fn is_ready(&self) -> bool {
-        unsafe {
-            let header = &*(self.view as *const PacketRingHeader);
-            std::ptr::read_volatile(&header.dll_ready) == 1
-        }
+        self.ring.as_ref().is_some_and(Ring::is_ready)
     }
 
-    /// Read all available packets from the ring buffer and advance `read_pos`.
-    pub fn read_packets(&self) -> Vec<RawPacket> {
-        let mut packets = Vec::new();
-
-        unsafe {
-            let header = self.view as *mut PacketRingHeader;
-            let data_base = self.view.add(std::mem::size_of::<PacketRingHeader>());
+    /// The options the DLL created the ring with.
+    pub fn options(&self) -> CaptureOptions {
+        self.ring
+            .as_ref()
+            .map(|ring| from_ring_options(&ring.options()))
+            .unwrap_or_default()
+    }
 
-            let write_pos = std::ptr::read_volatile(&(*header).write_pos);
-            let mut read_pos = std::ptr::read_volatile(&(*header).read_pos);
-            let cap = self.capacity;
+    /// Read all available records from the ring buffer and advance `read_pos`.
+    pub fn read_records(&self) -> Vec<Record> {
+        self.ring
+            .as_ref()
+            .map(Ring::read_records)
+            .unwrap_or_default()
+    }
 
-            // Nothing to read
-            if read_pos == write_pos {
-                return packets;
-            }
+    /// Everything the DLL has dropped since it created the ring.
+    pub fn drops(&self) -> Drops {
+        self.ring.as_ref().map(Ring::drops).unwrap_or_default()
+    }
 
-            // Calculate how many bytes are available
-            let available = if write_pos >= read_pos {
-                write_pos - read_pos
-            } else {
-                cap - read_pos + write_pos
-            };
+    /// Everything skipped over corrupt stretches of the ring so far.
+    pub fn lost(&self) -> Lost {
+        self.ring.as_ref().map(Ring::lost).unwrap_or_default()
+    }
 
-            let mut consumed: u32 = 0;
+    /// Stop the DLL writing packets until [`resume`](Self::resume).
+    pub fn pause(&self) -> Result<(), String> {
+        self.command(&Command::Pause).map(drop)
+    }
 
-            while consumed < available {
-                // Read the entry header, handling wrap-around
-                let mut entry_bytes = [0u8; ENTRY_HEADER_SIZE];
-                ring_read(data_base, cap, read_pos, &mut entry_bytes);
+    pub fn resume(&self) -> Result<(), String> {
+        self.command(&Command::Resume).map(drop)
+    }
 
-                let entry = &*(entry_bytes.as_ptr() as *const PacketEntry);
-                let total_size = entry.total_size;
+    /// Have the DLL capture only the opcodes `mode` lets through.
+    pub fn set_filter(&self, mode: FilterMode, opcodes: Vec<u32>) -> Result<(), String> {
+        if opcodes.len() > MAX_FILTER_OPCODES {
+            return Err(format!(
+                "Opcode filter has {} opcodes, at most {} are supported",
+                opcodes.len(),
+                MAX_FILTER_OPCODES
+            ));
+        }
+        let mode = match mode {
+            FilterMode::Off => command::FilterMode::Off,
+            FilterMode::Only => command::FilterMode::Only,
+            FilterMode::Except => command::FilterMode::Except,
+        };
+        self.command(&Command::SetFilter(OpcodeFilter { mode, opcodes }))
+            .map(drop)
+    }
 
-                // Sanity check
-                if total_size < ENTRY_HEADER_SIZE as u32
-                    || total_size > cap
-                    || entry.data_len > (total_size - ENTRY_HEADER_SIZE as u32)
-                {
-                    // Corrupted entry; reset read_pos to write_pos to skip
-                    break;
-                }
+    /// The DLL's state and counters.
+    pub fn status(&self) -> Result<CaptureStatus, String> {
+        match self.command(&Command::QueryStatus)? {
+            Reply::Status(status) => Ok(from_ring_status(&status)),
+            reply => Err(format!("Unexpected reply from capture DLL: {:?}", reply)),
+        }
+    }
 
-                // Read payload
-                let data_len = entry.data_len as usize;
-                let mut payload = vec![0u8; data_len];
-                let payload_offset = (read_pos + ENTRY_HEADER_SIZE as u32) % cap;
-                ring_read(data_base, cap, payload_offset, &mut payload);
-
-                packets.push(RawPacket {
-                    timestamp: entry.timestamp,
-                    direction: entry.direction,
-                    opcode: entry.opcode,
-                    data: payload,
-                });
+    /// Have the DLL remove its hooks and unload itself. The ring stays
+    /// readable until this reader is closed.
+    pub fn unload(&self) -> Result<(), String> {
+        self.command(&Command::Unload).map(drop)
+    }
 
-                read_pos = (read_pos + total_size) % cap;
-                consumed += total_size;
+    /// Send `command` and wait for the DLL's reply to it. Replies to
+    /// commands that timed out earlier are skipped. Blocks for up to
+    /// [`COMMAND_TIMEOUT`], so keep it off the main thread.
+    fn command(&self, command: &Command) -> Result<Reply, String> {
+        let ring = self.ring.as_ref().ok_or("Shared memory is closed")?;
+        let _sending = self.commands.lock().unwrap();
+        let channel = ring.commands();
+        let id = self.next_command_id.fetch_add(1, Ordering::Relaxed);
+        channel
+            .send_command(id, command)
+            .map_err(|e| format!("Failed to send command to capture DLL: {:?}", e))?;
+
+        let deadline = Instant::now() + COMMAND_TIMEOUT;
+        loop {
+            while let Some((reply_id, reply)) = channel.next_reply() {
+                if reply_id != id {
+                    continue;
+                }
+                return match reply {
+                    Reply::Rejected => Err("Capture DLL rejected the command".to_string()),
+                    reply => Ok(reply),
+                };
             }
-
-            // Commit our new read position
-            std::ptr::write_volatile(&mut (*header).read_pos, read_pos);
+            if Instant::now() >= deadline {
+                return Err("Capture DLL did not answer in time".to_string());
+            }
+            std::thread::sleep(Duration::from_millis(5));
         }
-
-        packets
     }
 
     /// Explicitly close the shared memory mapping.
     pub fn close(&mut self) {
+        self.ring = None;
         unsafe {
             if !self.view.is_null() {
                 let _ = UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
@@ -195,21 +323,3 @@ impl Drop for SharedMemoryReader {
         self.close();
     }
 }
-
-/// Copy `dst.len()` bytes from a ring buffer starting at `offset`,
-/// handling wrap-around at `capacity`.
-unsafe fn ring_read(base: *const u8, capacity: u32, offset: u32, dst: &mut [u8]) {
-    let cap = capacity as usize;
-    let off = offset as usize;
-    let len = dst.len();
-
-    if off + len <= cap {
-        // No wrap
-        std::ptr::copy_nonoverlapping(base.add(off), dst.as_mut_ptr(), len);
-    } else {
-        // Wraps around
-        let first_chunk = cap - off;
-        std::ptr::copy_nonoverlapping(base.add(off), dst.as_mut_ptr(), first_chunk);
-        std::ptr::copy_nonoverlapping(base, dst.as_mut_ptr().add(first_chunk), len - first_chunk);
-    }
-}
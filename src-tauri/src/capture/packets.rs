@@ -48,10 +48,14 @@ fn wotlk_opcode_name(opcode: u32) -> &'static str {
         m.insert(0x00EE, "MSG_MOVE_HEARTBEAT");
         m.insert(0x00E1, "MSG_MOVE_SET_RUN_MODE");
         m.insert(0x00E2, "MSG_MOVE_SET_WALK_MODE");
+        m.insert(0x00DD, "SMSG_MONSTER_MOVE");
+        m.insert(0x02AE, "SMSG_MONSTER_MOVE_TRANSPORT");
 
         // Chat
         m.insert(0x0095, "CMSG_MESSAGECHAT");
         m.insert(0x0096, "SMSG_MESSAGECHAT");
+        m.insert(0x0103, "SMSG_EMOTE");
+        m.insert(0x0105, "SMSG_TEXT_EMOTE");
 
         // Spell
         m.insert(0x012E, "CMSG_CAST_SPELL");
@@ -66,22 +70,73 @@ fn wotlk_opcode_name(opcode: u32) -> &'static str {
 
         // Update
         m.insert(0x00A9, "SMSG_UPDATE_OBJECT");
-        m.insert(0x00A8, "SMSG_COMPRESSED_UPDATE_OBJECT");
+        m.insert(0x01F6, "SMSG_COMPRESSED_UPDATE_OBJECT");
 
         // Query
         m.insert(0x0050, "CMSG_NAME_QUERY");
         m.insert(0x0051, "SMSG_NAME_QUERY_RESPONSE");
         m.insert(0x0060, "CMSG_CREATURE_QUERY");
         m.insert(0x0061, "SMSG_CREATURE_QUERY_RESPONSE");
+        m.insert(0x0056, "CMSG_ITEM_QUERY_SINGLE");
+        m.insert(0x0058, "SMSG_ITEM_QUERY_SINGLE_RESPONSE");
+        m.insert(0x005E, "CMSG_GAMEOBJECT_QUERY");
+        m.insert(0x005F, "SMSG_GAMEOBJECT_QUERY_RESPONSE");
+
+        // Quest
+        m.insert(0x005C, "CMSG_QUEST_QUERY");
+        m.insert(0x005D, "SMSG_QUEST_QUERY_RESPONSE");
+        m.insert(0x0182, "CMSG_QUESTGIVER_STATUS_QUERY");
+        m.insert(0x0183, "SMSG_QUESTGIVER_STATUS");
+        m.insert(0x0184, "CMSG_QUESTGIVER_HELLO");
+        m.insert(0x0185, "SMSG_QUESTGIVER_QUEST_LIST");
+        m.insert(0x0186, "CMSG_QUESTGIVER_QUERY_QUEST");
+        m.insert(0x0188, "SMSG_QUESTGIVER_QUEST_DETAILS");
+        m.insert(0x0189, "CMSG_QUESTGIVER_ACCEPT_QUEST");
+        m.insert(0x018A, "CMSG_QUESTGIVER_COMPLETE_QUEST");
+        m.insert(0x018B, "SMSG_QUESTGIVER_REQUEST_ITEMS");
+        m.insert(0x018C, "CMSG_QUESTGIVER_REQUEST_REWARD");
+        m.insert(0x018D, "SMSG_QUESTGIVER_OFFER_REWARD");
+        m.insert(0x018E, "CMSG_QUESTGIVER_CHOOSE_REWARD");
+        m.insert(0x0191, "SMSG_QUESTGIVER_QUEST_COMPLETE");
+
+        // Gossip
+        m.insert(0x017B, "CMSG_GOSSIP_HELLO");
+        m.insert(0x017C, "CMSG_GOSSIP_SELECT_OPTION");
+        m.insert(0x017D, "SMSG_GOSSIP_MESSAGE");
+        m.insert(0x017E, "SMSG_GOSSIP_COMPLETE");
+        m.insert(0x017F, "CMSG_NPC_TEXT_QUERY");
+        m.insert(0x0180, "SMSG_NPC_TEXT_UPDATE");
+        m.insert(0x0224, "SMSG_GOSSIP_POI");
+
+        // NPC services
+        m.insert(0x019E, "CMSG_LIST_INVENTORY");
+        m.insert(0x019F, "SMSG_LIST_INVENTORY");
+        m.insert(0x01A9, "SMSG_SHOWTAXINODES");
+        m.insert(0x01B0, "CMSG_TRAINER_LIST");
+        m.insert(0x01B1, "SMSG_TRAINER_LIST");
+        m.insert(0x01B8, "SMSG_SHOW_BANK");
+        m.insert(0x01BC, "SMSG_PETITION_SHOWLIST");
+        m.insert(0x01F2, "MSG_TABARDVENDOR_ACTIVATE");
+        m.insert(0x0222, "SMSG_SPIRIT_HEALER_CONFIRM");
+        m.insert(0x0255, "MSG_AUCTION_HELLO");
 
         // Misc
         m.insert(0x0001, "CMSG_BOOTME");
-        m.insert(0x001C, "SMSG_PONG");
         m.insert(0x01DC, "CMSG_PING");
+        m.insert(0x01DD, "SMSG_PONG");
+        m.insert(0x01CE, "CMSG_QUERY_TIME");
+        m.insert(0x01CF, "SMSG_QUERY_TIME_RESPONSE");
+        m.insert(0x0390, "SMSG_TIME_SYNC_REQ");
+        m.insert(0x0391, "CMSG_TIME_SYNC_RESP");
+        m.insert(0x005A, "CMSG_PAGE_TEXT_QUERY");
+        m.insert(0x005B, "SMSG_PAGE_TEXT_QUERY_RESPONSE");
+        m.insert(0x02C4, "CMSG_ITEM_NAME_QUERY");
+        m.insert(0x02C5, "SMSG_ITEM_NAME_QUERY_RESPONSE");
         m.insert(0x03FD, "CMSG_WORLD_TELEPORT");
         m.insert(0x0046, "SMSG_TRANSFER_PENDING");
         m.insert(0x003E, "SMSG_NEW_WORLD");
-        m.insert(0x006C, "SMSG_DESTROY_OBJECT");
+        m.insert(0x0236, "SMSG_LOGIN_VERIFY_WORLD");
+        m.insert(0x00AA, "SMSG_DESTROY_OBJECT");
 
         // Guild
         m.insert(0x008B, "CMSG_GUILD_QUERY");
@@ -92,10 +147,15 @@ fn wotlk_opcode_name(opcode: u32) -> &'static str {
         m.insert(0x0063, "SMSG_WHO");
 
         // Loot
+        m.insert(0x0108, "CMSG_AUTOSTORE_LOOT_ITEM");
         m.insert(0x015D, "CMSG_LOOT");
-        m.insert(0x0160, "SMSG_LOOT_RESPONSE");
         m.insert(0x015E, "CMSG_LOOT_MONEY");
-        m.insert(0x015F, "CMSG_AUTOSTORE_LOOT_ITEM");
+        m.insert(0x015F, "CMSG_LOOT_RELEASE");
+        m.insert(0x0160, "SMSG_LOOT_RESPONSE");
+        m.insert(0x0161, "SMSG_LOOT_RELEASE_RESPONSE");
+        m.insert(0x0162, "SMSG_LOOT_REMOVED");
+        m.insert(0x0163, "SMSG_LOOT_MONEY_NOTIFY");
+        m.insert(0x0165, "SMSG_LOOT_CLEAR_MONEY");
 
         // Logout
         m.insert(0x004B, "CMSG_PLAYER_LOGOUT");
@@ -118,7 +178,7 @@ fn vanilla_opcode_name(opcode: u32) -> &'static str {
         m.insert(0x01ED, "CMSG_AUTH_SESSION");
         m.insert(0x01EC, "SMSG_AUTH_CHALLENGE");
         m.insert(0x01EE, "SMSG_AUTH_RESPONSE");
-        m.insert(0x001D, "SMSG_PONG");
+        m.insert(0x01DD, "SMSG_PONG");
 
         // Character Management
         m.insert(0x0036, "CMSG_CHAR_CREATE");
@@ -341,7 +401,7 @@ fn vanilla_opcode_name(opcode: u32) -> &'static str {
         m.insert(0x017C, "CMSG_GOSSIP_SELECT_OPTION");
         m.insert(0x017D, "SMSG_GOSSIP_MESSAGE");
         m.insert(0x017E, "SMSG_GOSSIP_COMPLETE");
-        m.insert(0x0223, "SMSG_GOSSIP_POI");
+        m.insert(0x0224, "SMSG_GOSSIP_POI");
         m.insert(0x0180, "SMSG_NPC_TEXT_UPDATE");
         m.insert(0x017F, "CMSG_NPC_TEXT_QUERY");
 
@@ -801,10 +861,14 @@ fn tbc_opcode_name(opcode: u32) -> &'static str {
         m.insert(0x00B7, "MSG_MOVE_STOP");
         m.insert(0x00BB, "MSG_MOVE_JUMP");
         m.insert(0x00EE, "MSG_MOVE_HEARTBEAT");
+        m.insert(0x00DD, "SMSG_MONSTER_MOVE");
+        m.insert(0x02AE, "SMSG_MONSTER_MOVE_TRANSPORT");
 
         // Chat
         m.insert(0x0095, "CMSG_MESSAGECHAT");
         m.insert(0x0096, "SMSG_MESSAGECHAT");
+        m.insert(0x0103, "SMSG_EMOTE");
+        m.insert(0x0105, "SMSG_TEXT_EMOTE");
 
         // Spell
         m.insert(0x012E, "CMSG_CAST_SPELL");
@@ -818,16 +882,81 @@ fn tbc_opcode_name(opcode: u32) -> &'static str {
 
         // Update
         m.insert(0x00A9, "SMSG_UPDATE_OBJECT");
-        m.insert(0x00A8, "SMSG_COMPRESSED_UPDATE_OBJECT");
+        m.insert(0x01F6, "SMSG_COMPRESSED_UPDATE_OBJECT");
 
         // Query
         m.insert(0x0050, "CMSG_NAME_QUERY");
         m.insert(0x0051, "SMSG_NAME_QUERY_RESPONSE");
+        m.insert(0x0060, "CMSG_CREATURE_QUERY");
+        m.insert(0x0061, "SMSG_CREATURE_QUERY_RESPONSE");
+        m.insert(0x0056, "CMSG_ITEM_QUERY_SINGLE");
+        m.insert(0x0058, "SMSG_ITEM_QUERY_SINGLE_RESPONSE");
+        m.insert(0x005E, "CMSG_GAMEOBJECT_QUERY");
+        m.insert(0x005F, "SMSG_GAMEOBJECT_QUERY_RESPONSE");
+
+        // Quest
+        m.insert(0x005C, "CMSG_QUEST_QUERY");
+        m.insert(0x005D, "SMSG_QUEST_QUERY_RESPONSE");
+        m.insert(0x0182, "CMSG_QUESTGIVER_STATUS_QUERY");
+        m.insert(0x0183, "SMSG_QUESTGIVER_STATUS");
+        m.insert(0x0184, "CMSG_QUESTGIVER_HELLO");
+        m.insert(0x0185, "SMSG_QUESTGIVER_QUEST_LIST");
+        m.insert(0x0186, "CMSG_QUESTGIVER_QUERY_QUEST");
+        m.insert(0x0188, "SMSG_QUESTGIVER_QUEST_DETAILS");
+        m.insert(0x0189, "CMSG_QUESTGIVER_ACCEPT_QUEST");
+        m.insert(0x018A, "CMSG_QUESTGIVER_COMPLETE_QUEST");
+        m.insert(0x018B, "SMSG_QUESTGIVER_REQUEST_ITEMS");
+        m.insert(0x018C, "CMSG_QUESTGIVER_REQUEST_REWARD");
+        m.insert(0x018D, "SMSG_QUESTGIVER_OFFER_REWARD");
+        m.insert(0x018E, "CMSG_QUESTGIVER_CHOOSE_REWARD");
+        m.insert(0x0191, "SMSG_QUESTGIVER_QUEST_COMPLETE");
+
+        // Gossip
+        m.insert(0x017B, "CMSG_GOSSIP_HELLO");
+        m.insert(0x017C, "CMSG_GOSSIP_SELECT_OPTION");
+        m.insert(0x017D, "SMSG_GOSSIP_MESSAGE");
+        m.insert(0x017E, "SMSG_GOSSIP_COMPLETE");
+        m.insert(0x017F, "CMSG_NPC_TEXT_QUERY");
+        m.insert(0x0180, "SMSG_NPC_TEXT_UPDATE");
+        m.insert(0x0224, "SMSG_GOSSIP_POI");
+
+        // NPC services
+        m.insert(0x019E, "CMSG_LIST_INVENTORY");
+        m.insert(0x019F, "SMSG_LIST_INVENTORY");
+        m.insert(0x01A9, "SMSG_SHOWTAXINODES");
+        m.insert(0x01B0, "CMSG_TRAINER_LIST");
+        m.insert(0x01B1, "SMSG_TRAINER_LIST");
+        m.insert(0x01B8, "SMSG_SHOW_BANK");
+        m.insert(0x01BC, "SMSG_PETITION_SHOWLIST");
+        m.insert(0x01F2, "MSG_TABARDVENDOR_ACTIVATE");
+        m.insert(0x0222, "SMSG_SPIRIT_HEALER_CONFIRM");
+        m.insert(0x0255, "MSG_AUCTION_HELLO");
+
+        // Loot
+        m.insert(0x0108, "CMSG_AUTOSTORE_LOOT_ITEM");
+        m.insert(0x015D, "CMSG_LOOT");
+        m.insert(0x015E, "CMSG_LOOT_MONEY");
+        m.insert(0x015F, "CMSG_LOOT_RELEASE");
+        m.insert(0x0160, "SMSG_LOOT_RESPONSE");
+        m.insert(0x0161, "SMSG_LOOT_RELEASE_RESPONSE");
+        m.insert(0x0162, "SMSG_LOOT_REMOVED");
+        m.insert(0x0163, "SMSG_LOOT_MONEY_NOTIFY");
+        m.insert(0x0165, "SMSG_LOOT_CLEAR_MONEY");
 
         // Misc
-        m.insert(0x001C, "SMSG_PONG");
         m.insert(0x01DC, "CMSG_PING");
-        m.insert(0x006C, "SMSG_DESTROY_OBJECT");
+        m.insert(0x01DD, "SMSG_PONG");
+        m.insert(0x01CE, "CMSG_QUERY_TIME");
+        m.insert(0x01CF, "SMSG_QUERY_TIME_RESPONSE");
+        m.insert(0x0390, "SMSG_TIME_SYNC_REQ");
+        m.insert(0x0391, "CMSG_TIME_SYNC_RESP");
+        m.insert(0x005A, "CMSG_PAGE_TEXT_QUERY");
+        m.insert(0x005B, "SMSG_PAGE_TEXT_QUERY_RESPONSE");
+        m.insert(0x02C4, "CMSG_ITEM_NAME_QUERY");
+        m.insert(0x02C5, "SMSG_ITEM_NAME_QUERY_RESPONSE");
+        m.insert(0x003E, "SMSG_NEW_WORLD");
+        m.insert(0x0236, "SMSG_LOGIN_VERIFY_WORLD");
+        m.insert(0x00AA, "SMSG_DESTROY_OBJECT");
 
         // Logout
         m.insert(0x004B, "CMSG_PLAYER_LOGOUT");
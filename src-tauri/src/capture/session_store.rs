@@ -1,4 +1,4 @@
-use crate::state::{Packet, Session};
+use crate::state::{CaptureGap, Packet, Session};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
@@ -13,6 +13,8 @@ pub struct SessionFile {
     #[serde(default)]
     pub build: Option<u32>,
     pub packets: Vec<Packet>,
+    #[serde(default)]
+    pub gaps: Vec<CaptureGap>,
 }
 
 #[derive(Serialize)]
@@ -46,6 +48,7 @@ pub fn save_session(app: &AppHandle, session: &Session) -> Result<PathBuf, Strin
         saved_at: chrono::Utc::now().to_rfc3339(),
         build: session.build,
         packets: session.packets.clone(),
+        gaps: session.gaps.clone(),
     };
     let json =
         serde_json::to_string_pretty(&file).map_err(|e| format!("Serialization failed: {e}"))?;
@@ -58,27 +61,36 @@ pub fn load_session_file(path: &PathBuf) -> Result<SessionFile, String> {
     serde_json::from_str(&json).map_err(|e| format!("Parse failed: {e}"))
 }
 
-pub fn list_saved_sessions(app: &AppHandle) -> Result<Vec<SavedSessionMeta>, String> {
+/// Every session file in the library, unread.
+pub fn saved_session_paths(app: &AppHandle) -> Result<Vec<PathBuf>, String> {
     let dir = sessions_dir(app)?;
-    let mut results = Vec::new();
+    let mut paths = Vec::new();
     if let Ok(entries) = std::fs::read_dir(&dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.extension().and_then(|e| e.to_str()) == Some("json") {
-                if let Ok(sf) = load_session_file(&path) {
-                    results.push(SavedSessionMeta {
-                        id: sf.id,
-                        name: sf.name,
-                        created_at: sf.created_at,
-                        saved_at: sf.saved_at,
-                        packet_count: sf.packets.len(),
-                        file_path: path.to_string_lossy().into_owned(),
-                        build: sf.build,
-                    });
-                }
+                paths.push(path);
             }
         }
     }
+    Ok(paths)
+}
+
+pub fn list_saved_sessions(app: &AppHandle) -> Result<Vec<SavedSessionMeta>, String> {
+    let mut results = Vec::new();
+    for path in saved_session_paths(app)? {
+        if let Ok(sf) = load_session_file(&path) {
+            results.push(SavedSessionMeta {
+                id: sf.id,
+                name: sf.name,
+                created_at: sf.created_at,
+                saved_at: sf.saved_at,
+                packet_count: sf.packets.len(),
+                file_path: path.to_string_lossy().into_owned(),
+                build: sf.build,
+            });
+        }
+    }
     results.sort_by(|a, b| b.saved_at.cmp(&a.saved_at));
     Ok(results)
 }
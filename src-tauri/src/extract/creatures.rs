@@ -0,0 +1,318 @@
+//! Creature spawns rebuilt from update object create blocks.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{SqlExport, SPAWN_TIME_SECS};
+use crate::parser::guid::{guid_entry, high_guid, HighGuid};
+use crate::parser::reader::Vector3;
+use crate::parser::update_fields::{unit_fields, OBJECT_FIELD_ENTRY};
+use crate::parser::update_object::TYPEID_UNIT;
+use crate::state::Session;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatureSpawn {
+    pub guid: u64,
+    pub entry: u32,
+    /// `None` if the creature appeared before any map packet was captured.
+    pub map: Option<u32>,
+    pub position: Vector3,
+    pub orientation: f32,
+    pub display_id: u32,
+    pub faction: u32,
+    pub unit_flags: u32,
+    pub npc_flags: u32,
+    pub dynamic_flags: u32,
+    pub level: u32,
+    pub health: u32,
+    pub max_health: u32,
+    /// Id of the packet holding the first create block; its position wins.
+    pub first_seen_packet: usize,
+    /// How many create blocks were seen for this GUID.
+    pub sightings: usize,
+}
+
+/// Collect one spawn per creature GUID, keeping the first-seen values.
+pub fn creature_spawns(session: &Session, build: u32) -> Vec<CreatureSpawn> {
+    let fields = unit_fields(build);
+    let spawns = super::first_sightings(
+        session,
+        build,
+        TYPEID_UNIT,
+        |packet, map, guid, movement, values| {
+            if !matches!(high_guid(guid), HighGuid::Creature | HighGuid::Vehicle) {
+                return None;
+            }
+            let position = movement.position?;
+            let field = |index: u32| values.get(&index).copied().unwrap_or(0);
+            let entry = values
+                .get(&OBJECT_FIELD_ENTRY)
+                .copied()
+                .or_else(|| guid_entry(guid))
+                .unwrap_or(0);
+            Some(CreatureSpawn {
+                guid,
+                entry,
+                map,
+                position,
+                orientation: movement.orientation,
+                display_id: field(fields.display_id),
+                faction: field(fields.faction_template),
+                unit_flags: field(fields.flags),
+                npc_flags: field(fields.npc_flags),
+                dynamic_flags: field(fields.dynamic_flags),
+                level: field(fields.level),
+                health: field(fields.health),
+                max_health: field(fields.max_health),
+                first_seen_packet: packet.id,
+                sightings: 1,
+            })
+        },
+    );
+    spawns
+        .into_iter()
+        .map(|(spawn, sightings)| CreatureSpawn { sightings, ..spawn })
+        .collect()
+}
+
+/// `creature` rows keyed off `@CGUID`. Values the target schema keeps in
+/// `creature_template` (faction, level) go in the row comment instead.
+pub fn creature_sql(spawns: &[CreatureSpawn], flavor: SqlFlavor, build: u32) -> String {
+    let rows: Vec<SqlRow> = spawns
+        .iter()
+        .enumerate()
+        .map(|(i, spawn)| {
+            let mut comment = format!(
+                "entry {}, display {}, faction {}, level {}, guid 0x{:016X}",
+                spawn.entry, spawn.display_id, spawn.faction, spawn.level, spawn.guid
+            );
+            if spawn.map.is_none() {
+                comment.push_str(", map unknown");
+            }
+            let mut values = vec![
+                format!("@CGUID+{}", i),
+                spawn.entry.to_string(),
+                spawn.map.unwrap_or(0).to_string(),
+                "1".to_string(),
+            ];
+            if flavor == SqlFlavor::TrinityCore {
+                values.push("1".to_string());
+                values.push(spawn.display_id.to_string());
+            }
+            values.extend([
+                sql::float(spawn.position.x),
+                sql::float(spawn.position.y),
+                sql::float(spawn.position.z),
+                sql::float(spawn.orientation),
+            ]);
+            match flavor {
+                // Spawn at full health even if the creature was seen hurt.
+                SqlFlavor::TrinityCore => values.extend([
+                    SPAWN_TIME_SECS.to_string(),
+                    "0".to_string(),
+                    "0".to_string(),
+                    spawn.max_health.max(spawn.health).to_string(),
+                    spawn.npc_flags.to_string(),
+                    spawn.unit_flags.to_string(),
+                    spawn.dynamic_flags.to_string(),
+                    build.to_string(),
+                ]),
+                SqlFlavor::CMaNGOS => values.extend([
+                    SPAWN_TIME_SECS.to_string(),
+                    SPAWN_TIME_SECS.to_string(),
+                    "0".to_string(),
+                    "0".to_string(),
+                ]),
+            }
+            SqlRow {
+                values,
+                comment: Some(comment),
+            }
+        })
+        .collect();
+
+    let columns: &[&str] = match flavor {
+        SqlFlavor::TrinityCore => &[
+            "guid",
+            "id",
+            "map",
+            "spawnMask",
+            "phaseMask",
+            "modelid",
+            "position_x",
+            "position_y",
+            "position_z",
+            "orientation",
+            "spawntimesecs",
+            "wander_distance",
+            "MovementType",
+            "curhealth",
+            "npcflag",
+            "unit_flags",
+            "dynamicflags",
+            "VerifiedBuild",
+        ],
+        SqlFlavor::CMaNGOS => &[
+            "guid",
+            "id",
+            "map",
+            "spawnMask",
+            "position_x",
+            "position_y",
+            "position_z",
+            "orientation",
+            "spawntimesecsmin",
+            "spawntimesecsmax",
+            "spawndist",
+            "MovementType",
+        ],
+    };
+
+    let mut out = String::from("SET @CGUID := 0;\n");
+    out.push_str(&sql::delete_range("creature", "guid", "CGUID", rows.len()));
+    out.push_str(&sql::insert("creature", columns, &rows));
+    out
+}
+
+pub fn extract_creature_spawns(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<CreatureSpawn>, String> {
+    let build = super::session_build(session)?;
+    let rows = creature_spawns(session, build);
+    let sql = creature_sql(&rows, flavor, build);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{compressed, session, vector, Bytes};
+
+    const BUILDS: [u32; 3] = [5875, 8606, 12340];
+    /// Creature 1234 (`0x4D2` in the GUID's entry bits).
+    const GUID: u64 = 0xF130_0004_D200_0101;
+    const OTHER: u64 = 0xF130_0004_D200_0102;
+
+    fn world(map: u32) -> Vec<u8> {
+        Bytes::new()
+            .u32(map)
+            .vector3(vector(0.0, 0.0, 0.0))
+            .f32(0.0)
+            .build()
+    }
+
+    fn creature(build: u32, guid: u64, x: f32, health: u32) -> Vec<u8> {
+        let f = unit_fields(build);
+        Bytes::new()
+            .update_header(build, 1)
+            .create_header(guid, TYPEID_UNIT)
+            .living(build, vector(x, 20.5, 30.0), 3.0)
+            .update_mask(&[
+                (OBJECT_FIELD_ENTRY, 1234),
+                (f.health, health),
+                (f.max_health, 100),
+                (f.level, 12),
+                (f.faction_template, 14),
+                (f.display_id, 999),
+                (f.npc_flags, 1),
+            ])
+            .build()
+    }
+
+    /// `GUID` shows up before any map packet and again, compressed and
+    /// elsewhere, once the map is known; `OTHER` only after it.
+    fn capture(build: u32) -> Session {
+        session(
+            build,
+            vec![
+                ("SMSG_UPDATE_OBJECT", creature(build, GUID, 10.0, 60)),
+                ("SMSG_LOGIN_VERIFY_WORLD", world(1)),
+                (
+                    "SMSG_COMPRESSED_UPDATE_OBJECT",
+                    compressed(&creature(build, GUID, 99.0, 100)),
+                ),
+                ("SMSG_UPDATE_OBJECT", creature(build, OTHER, 5.0, 100)),
+            ],
+        )
+    }
+
+    #[test]
+    fn first_sighting_wins() {
+        for build in BUILDS {
+            let spawns = creature_spawns(&capture(build), build);
+            assert_eq!(spawns.len(), 2, "build {}", build);
+            let first = &spawns[0];
+            assert_eq!((first.guid, first.entry, first.map), (GUID, 1234, None));
+            assert_eq!(first.position, vector(10.0, 20.5, 30.0));
+            assert_eq!((first.health, first.max_health), (60, 100));
+            assert_eq!(
+                (first.level, first.faction, first.display_id),
+                (12, 14, 999)
+            );
+            assert_eq!((first.first_seen_packet, first.sightings), (0, 2));
+            assert_eq!((spawns[1].guid, spawns[1].map), (OTHER, Some(1)));
+        }
+    }
+
+    #[test]
+    fn skips_players_and_falls_back_to_guid_entry() {
+        let build = 12340;
+        let player = Bytes::new()
+            .update_header(build, 1)
+            .create_header(0x0000_0000_0000_0042, TYPEID_UNIT)
+            .living(build, vector(1.0, 2.0, 3.0), 0.0)
+            .update_mask(&[(OBJECT_FIELD_ENTRY, 1)])
+            .build();
+        let unnamed = Bytes::new()
+            .update_header(build, 1)
+            .create_header(GUID, TYPEID_UNIT)
+            .living(build, vector(1.0, 2.0, 3.0), 0.0)
+            .update_mask(&[])
+            .build();
+        let capture = session(
+            build,
+            vec![
+                ("SMSG_UPDATE_OBJECT", player),
+                ("SMSG_UPDATE_OBJECT", unnamed),
+            ],
+        );
+        let spawns = creature_spawns(&capture, build);
+        assert_eq!(spawns.len(), 1);
+        assert_eq!((spawns[0].guid, spawns[0].entry), (GUID, 1234));
+    }
+
+    #[test]
+    fn trinitycore_rows() {
+        let export = extract_creature_spawns(&capture(12340), SqlFlavor::TrinityCore).unwrap();
+        let sql = export.sql;
+        assert!(sql.starts_with("SET @CGUID := 0;\nDELETE FROM `creature` WHERE `guid` BETWEEN @CGUID+0 AND @CGUID+1;\n"));
+        assert!(sql.contains("(`guid`, `id`, `map`, `spawnMask`, `phaseMask`, `modelid`, "));
+        // Hurt creatures still spawn at full health.
+        assert!(sql.contains(
+            "(@CGUID+0, 1234, 0, 1, 1, 999, 10, 20.5, 30, 3, 300, 0, 0, 100, 1, 0, 0, 12340), \
+             -- entry 1234, display 999, faction 14, level 12, guid 0xF1300004D2000101, map unknown\n"
+        ));
+        assert!(sql.contains(
+            "(@CGUID+1, 1234, 1, 1, 1, 999, 5, 20.5, 30, 3, 300, 0, 0, 100, 1, 0, 0, 12340);"
+        ));
+    }
+
+    #[test]
+    fn cmangos_rows() {
+        let export = extract_creature_spawns(&capture(5875), SqlFlavor::CMaNGOS).unwrap();
+        let sql = export.sql;
+        assert!(
+            sql.contains("`spawntimesecsmin`, `spawntimesecsmax`, `spawndist`, `MovementType`)")
+        );
+        assert!(sql.contains("(@CGUID+0, 1234, 0, 1, 10, 20.5, 30, 3, 300, 300, 0, 0),"));
+        assert!(sql.contains("(@CGUID+1, 1234, 1, 1, 5, 20.5, 30, 3, 300, 300, 0, 0);"));
+        assert!(!sql.contains("VerifiedBuild"));
+    }
+
+    #[test]
+    fn needs_a_supported_build() {
+        let mut capture = capture(5875);
+        capture.build = Some(4044);
+        assert!(extract_creature_spawns(&capture, SqlFlavor::CMaNGOS).is_err());
+    }
+}
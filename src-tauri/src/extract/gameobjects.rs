@@ -0,0 +1,327 @@
+//! Gameobject spawns rebuilt from update object create blocks.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{SqlExport, SPAWN_TIME_SECS};
+use crate::parser::guid::{guid_entry, high_guid, HighGuid};
+use crate::parser::reader::Vector3;
+use crate::parser::update_fields::{gameobject_fields, OBJECT_FIELD_ENTRY};
+use crate::parser::update_object::{unpack_rotation, Quaternion, TYPEID_GAMEOBJECT};
+use crate::state::Session;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GameObjectSpawn {
+    pub guid: u64,
+    pub entry: u32,
+    /// `None` if the object appeared before any map packet was captured.
+    pub map: Option<u32>,
+    pub position: Vector3,
+    pub orientation: f32,
+    pub rotation: Quaternion,
+    pub state: u32,
+    pub anim_progress: u32,
+    pub display_id: u32,
+    pub go_type: u32,
+    pub faction: u32,
+    pub flags: u32,
+    /// Id of the packet holding the first create block; its position wins.
+    pub first_seen_packet: usize,
+    /// How many create blocks were seen for this GUID.
+    pub sightings: usize,
+}
+
+/// Collect one spawn per gameobject GUID, keeping the first-seen values.
+/// Transports are skipped; they are not spawned from the `gameobject` table.
+pub fn gameobject_spawns(session: &Session, build: u32) -> Vec<GameObjectSpawn> {
+    let fields = gameobject_fields(build);
+    let spawns = super::first_sightings(
+        session,
+        build,
+        TYPEID_GAMEOBJECT,
+        |packet, map, guid, movement, values| {
+            if high_guid(guid) != HighGuid::GameObject {
+                return None;
+            }
+            let position = movement.position?;
+            let field = |index: u32| values.get(&index).copied().unwrap_or(0);
+            let entry = values
+                .get(&OBJECT_FIELD_ENTRY)
+                .copied()
+                .or_else(|| guid_entry(guid))
+                .unwrap_or(0);
+            // Vanilla and TBC send the quaternion as four float fields; WotLK
+            // packs it into the movement block instead.
+            let rotation = match fields.rotation {
+                Some(first) => Quaternion {
+                    x: f32::from_bits(field(first)),
+                    y: f32::from_bits(field(first + 1)),
+                    z: f32::from_bits(field(first + 2)),
+                    w: f32::from_bits(field(first + 3)),
+                },
+                None => movement
+                    .packed_rotation
+                    .map(unpack_rotation)
+                    .unwrap_or_default(),
+            };
+            Some(GameObjectSpawn {
+                guid,
+                entry,
+                map,
+                position,
+                orientation: movement.orientation,
+                rotation,
+                state: fields.state.get(values),
+                anim_progress: fields.anim_progress.get(values),
+                display_id: field(fields.display_id),
+                go_type: fields.type_id.get(values),
+                faction: field(fields.faction),
+                flags: field(fields.flags),
+                first_seen_packet: packet.id,
+                sightings: 1,
+            })
+        },
+    );
+    spawns
+        .into_iter()
+        .map(|(spawn, sightings)| GameObjectSpawn { sightings, ..spawn })
+        .collect()
+}
+
+/// `gameobject` rows keyed off `@OGUID`.
+pub fn gameobject_sql(spawns: &[GameObjectSpawn], flavor: SqlFlavor, build: u32) -> String {
+    let rows: Vec<SqlRow> = spawns
+        .iter()
+        .enumerate()
+        .map(|(i, spawn)| {
+            let mut comment = format!(
+                "entry {}, type {}, display {}, guid 0x{:016X}",
+                spawn.entry, spawn.go_type, spawn.display_id, spawn.guid
+            );
+            if spawn.map.is_none() {
+                comment.push_str(", map unknown");
+            }
+            let mut values = vec![
+                format!("@OGUID+{}", i),
+                spawn.entry.to_string(),
+                spawn.map.unwrap_or(0).to_string(),
+                "1".to_string(),
+            ];
+            if flavor == SqlFlavor::TrinityCore {
+                values.push("1".to_string());
+            }
+            values.extend([
+                sql::float(spawn.position.x),
+                sql::float(spawn.position.y),
+                sql::float(spawn.position.z),
+                sql::float(spawn.orientation),
+                sql::float(spawn.rotation.x),
+                sql::float(spawn.rotation.y),
+                sql::float(spawn.rotation.z),
+                sql::float(spawn.rotation.w),
+                SPAWN_TIME_SECS.to_string(),
+            ]);
+            if flavor == SqlFlavor::CMaNGOS {
+                values.push(SPAWN_TIME_SECS.to_string());
+            }
+            values.extend([spawn.anim_progress.to_string(), spawn.state.to_string()]);
+            if flavor == SqlFlavor::TrinityCore {
+                values.push(build.to_string());
+            }
+            SqlRow {
+                values,
+                comment: Some(comment),
+            }
+        })
+        .collect();
+
+    let columns: &[&str] = match flavor {
+        SqlFlavor::TrinityCore => &[
+            "guid",
+            "id",
+            "map",
+            "spawnMask",
+            "phaseMask",
+            "position_x",
+            "position_y",
+            "position_z",
+            "orientation",
+            "rotation0",
+            "rotation1",
+            "rotation2",
+            "rotation3",
+            "spawntimesecs",
+            "animprogress",
+            "state",
+            "VerifiedBuild",
+        ],
+        SqlFlavor::CMaNGOS => &[
+            "guid",
+            "id",
+            "map",
+            "spawnMask",
+            "position_x",
+            "position_y",
+            "position_z",
+            "orientation",
+            "rotation0",
+            "rotation1",
+            "rotation2",
+            "rotation3",
+            "spawntimesecsmin",
+            "spawntimesecsmax",
+            "animprogress",
+            "state",
+        ],
+    };
+
+    let mut out = String::from("SET @OGUID := 0;\n");
+    out.push_str(&sql::delete_range(
+        "gameobject",
+        "guid",
+        "OGUID",
+        rows.len(),
+    ));
+    out.push_str(&sql::insert("gameobject", columns, &rows));
+    out
+}
+
+pub fn extract_gameobject_spawns(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<GameObjectSpawn>, String> {
+    let build = super::session_build(session)?;
+    let rows = gameobject_spawns(session, build);
+    let sql = gameobject_sql(&rows, flavor, build);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{session, vector, Bytes};
+
+    /// Gameobject 181 (`0xB5` in the GUID's entry bits).
+    const GUID: u64 = 0xF110_0000_B500_0007;
+    const TRANSPORT: u64 = 0xF120_0000_B600_0001;
+    /// A quarter turn about Z.
+    const QUARTER_TURN: Quaternion = Quaternion {
+        x: 0.0,
+        y: 0.0,
+        z: std::f32::consts::FRAC_1_SQRT_2,
+        w: std::f32::consts::FRAC_1_SQRT_2,
+    };
+
+    fn pack(q: Quaternion) -> i64 {
+        let x = (q.x * 2_097_152.0) as i64;
+        let y = (q.y * 1_048_576.0) as i64;
+        let z = (q.z * 1_048_576.0) as i64;
+        (x << 42) | ((y & 0x1F_FFFF) << 21) | (z & 0x1F_FFFF)
+    }
+
+    fn assert_near(a: Quaternion, b: Quaternion) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(
+            close(a.x, b.x) && close(a.y, b.y) && close(a.z, b.z) && close(a.w, b.w),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    fn create(build: u32, guid: u64, rotation: Quaternion) -> Vec<u8> {
+        let mut fields = vec![(OBJECT_FIELD_ENTRY, 181), (8, 3211), (9, 32)];
+        let packed = if build < 12340 {
+            fields.extend([
+                (10, rotation.x.to_bits()),
+                (11, rotation.y.to_bits()),
+                (12, rotation.z.to_bits()),
+                (13, rotation.w.to_bits()),
+                (14, 1),
+                (20, 94),
+                (21, 19),
+                (24, 255),
+            ]);
+            None
+        } else {
+            fields.extend([(15, 94), (17, 1 | 19 << 8 | 255 << 24)]);
+            Some(pack(rotation))
+        };
+        Bytes::new()
+            .update_header(build, 1)
+            .create_header(guid, TYPEID_GAMEOBJECT)
+            .stationary(build, vector(-8829.0, 627.5, 94.25), 1.25, packed)
+            .update_mask(&fields)
+            .build()
+    }
+
+    #[test]
+    fn unpacks_known_rotations() {
+        assert_near(unpack_rotation(741_455), QUARTER_TURN);
+        let tilted = Quaternion {
+            x: 0.5,
+            y: -0.5,
+            z: 0.5,
+            w: 0.5,
+        };
+        assert_near(unpack_rotation(pack(tilted)), tilted);
+        let identity = Quaternion {
+            w: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(unpack_rotation(0), identity);
+    }
+
+    #[test]
+    fn reads_rotation_per_build() {
+        for build in [5875, 8606, 12340] {
+            let capture = session(
+                build,
+                vec![
+                    ("SMSG_UPDATE_OBJECT", create(build, GUID, QUARTER_TURN)),
+                    ("SMSG_UPDATE_OBJECT", create(build, TRANSPORT, QUARTER_TURN)),
+                    (
+                        "SMSG_UPDATE_OBJECT",
+                        create(build, GUID, Quaternion::default()),
+                    ),
+                ],
+            );
+            let spawns = gameobject_spawns(&capture, build);
+            assert_eq!(spawns.len(), 1, "build {}", build);
+            let spawn = &spawns[0];
+            if build < 12340 {
+                assert_eq!(spawn.rotation, QUARTER_TURN);
+            } else {
+                assert_near(spawn.rotation, QUARTER_TURN);
+            }
+            assert_eq!(
+                (spawn.entry, spawn.display_id, spawn.flags),
+                (181, 3211, 32)
+            );
+            assert_eq!(
+                (spawn.state, spawn.go_type, spawn.anim_progress),
+                (1, 19, 255)
+            );
+            assert_eq!((spawn.faction, spawn.sightings), (94, 2));
+        }
+    }
+
+    #[test]
+    fn writes_rotation_columns() {
+        let capture = session(
+            5875,
+            vec![("SMSG_UPDATE_OBJECT", create(5875, GUID, QUARTER_TURN))],
+        );
+        let sql = extract_gameobject_spawns(&capture, SqlFlavor::CMaNGOS)
+            .unwrap()
+            .sql;
+        assert!(sql.contains(
+            "(@OGUID+0, 181, 0, 1, -8829, 627.5, 94.25, 1.25, 0, 0, 0.70710677, 0.70710677, 300, 300, 255, 1);"
+        ));
+        let sql = extract_gameobject_spawns(&capture, SqlFlavor::TrinityCore)
+            .unwrap()
+            .sql;
+        assert!(sql.contains(
+            "(@OGUID+0, 181, 0, 1, 1, -8829, 627.5, 94.25, 1.25, 0, 0, 0.70710677, 0.70710677, 300, 255, 1, 5875);"
+        ));
+    }
+}
@@ -0,0 +1,693 @@
+//! Gossip dialog trees rebuilt from menus, option selections and the
+//! server's reaction to each selection.
+//!
+//! Menus are identified by menu id and text id. Vanilla servers do not send
+//! menu ids, so there a menu is identified by the NPC entry and text id, and
+//! the SQL numbers those menus off `@MENU`.
+
+use super::decode;
+use super::sql::{self, SqlFlavor, SqlRow};
+use crate::parser;
+use crate::parser::gossip::{self, GossipOption, GossipPoi, GossipQuest, NpcText};
+use crate::parser::guid::{guid_entry, high_guid, HighGuid};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// TrinityCore's `GOSSIP_OPTION_GOSSIP`, used for options that open a menu
+/// or do nothing the capture can see.
+const OPTION_TYPE_GOSSIP: u32 = 1;
+const NPC_FLAG_GOSSIP: u32 = 0x1;
+
+/// Server packets that show an option opened a service window: opcode,
+/// option type, NPC flag in vanilla, NPC flag from TBC on.
+const SERVICE_OPCODES: &[(&str, u32, u32, u32)] = &[
+    ("SMSG_LIST_INVENTORY", 3, 0x4, 0x80),
+    ("SMSG_SHOWTAXINODES", 4, 0x8, 0x2000),
+    ("SMSG_TRAINER_LIST", 5, 0x10, 0x10),
+    ("SMSG_SPIRIT_HEALER_CONFIRM", 6, 0x20, 0x4000),
+    ("SMSG_SHOW_BANK", 9, 0x100, 0x20000),
+    ("SMSG_PETITION_SHOWLIST", 10, 0x200, 0x40000),
+    ("MSG_TABARDVENDOR_ACTIVATE", 11, 0x400, 0x80000),
+    ("MSG_AUCTION_HELLO", 13, 0x1000, 0x200000),
+];
+
+/// What the server did after an option was selected.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GossipAction {
+    /// Another menu was shown; `node` indexes `GossipTree::menus`.
+    Menu { node: usize },
+    /// A point of interest was marked; `poi` indexes `GossipTree::pois`.
+    Poi { poi: usize },
+    /// A service window opened (vendor, trainer, ...).
+    Service { opcode: String },
+    /// The gossip window closed without anything else visible.
+    Close,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GossipOptionNode {
+    #[serde(flatten)]
+    pub option: GossipOption,
+    /// How many times the option was picked in the capture.
+    pub selections: usize,
+    /// Every distinct outcome seen; conditions can make one option lead to
+    /// different places.
+    pub actions: Vec<GossipAction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GossipMenuNode {
+    pub node: usize,
+    pub menu_id: Option<u32>,
+    pub text_id: u32,
+    /// Entries of every NPC that showed this menu.
+    pub npc_entries: Vec<u32>,
+    /// Entries whose gossip hello opened this menu directly.
+    pub root_of: Vec<u32>,
+    pub options: Vec<GossipOptionNode>,
+    pub quests: Vec<GossipQuest>,
+    pub first_seen_packet: usize,
+    pub sightings: usize,
+}
+
+/// The dialog graph: menus are nodes, option actions are edges.
+#[derive(Debug, Clone, Serialize)]
+pub struct GossipTree {
+    pub menus: Vec<GossipMenuNode>,
+    pub texts: Vec<NpcText>,
+    pub pois: Vec<GossipPoi>,
+    pub sql: String,
+}
+
+/// An option selection still waiting for the server's reaction.
+struct Pending {
+    guid: u64,
+    node: Option<usize>,
+    option: u32,
+}
+
+impl GossipMenuNode {
+    fn option_mut(&mut self, id: u32) -> Option<&mut GossipOptionNode> {
+        self.options.iter_mut().find(|o| o.option.id == id)
+    }
+}
+
+fn resolve(menus: &mut [GossipMenuNode], pending: Option<Pending>, action: GossipAction) {
+    let Some(pending) = pending else {
+        return;
+    };
+    let Some(option) = pending
+        .node
+        .and_then(|node| menus[node].option_mut(pending.option))
+    else {
+        return;
+    };
+    if !option.actions.contains(&action) {
+        option.actions.push(action);
+    }
+}
+
+pub fn gossip_tree(
+    session: &Session,
+    build: u32,
+) -> (Vec<GossipMenuNode>, Vec<NpcText>, Vec<GossipPoi>) {
+    let mut menus: Vec<GossipMenuNode> = Vec::new();
+    let mut by_key: HashMap<(Option<u32>, u32, u32), usize> = HashMap::new();
+    let mut texts: BTreeMap<u32, NpcText> = BTreeMap::new();
+    let mut pois: Vec<GossipPoi> = Vec::new();
+    let mut last_menu: HashMap<u64, usize> = HashMap::new();
+    let mut hellos: HashSet<u64> = HashSet::new();
+    let mut pending: Option<Pending> = None;
+
+    for packet in &session.packets {
+        let name = parser::opcode_name(Some(build), packet);
+        match (packet.direction, name) {
+            (Direction::ClientToServer, "CMSG_GOSSIP_HELLO") => {
+                if let Some(guid) = decode(packet, |r| r.guid("guid")) {
+                    hellos.insert(guid);
+                }
+                pending = None;
+            }
+            (Direction::ClientToServer, "CMSG_GOSSIP_SELECT_OPTION") => {
+                let Some(select) = decode(packet, |r| gossip::read_gossip_select_option(r, build))
+                else {
+                    continue;
+                };
+                // Prefer the menu last shown by this NPC; fall back to any
+                // menu with the echoed id.
+                let node = last_menu
+                    .get(&select.guid)
+                    .copied()
+                    .filter(|&n| select.menu_id.is_none() || menus[n].menu_id == select.menu_id)
+                    .or_else(|| {
+                        select
+                            .menu_id
+                            .and_then(|id| menus.iter().position(|m| m.menu_id == Some(id)))
+                    });
+                if let Some(option) = node.and_then(|n| menus[n].option_mut(select.option)) {
+                    option.selections += 1;
+                }
+                pending = Some(Pending {
+                    guid: select.guid,
+                    node,
+                    option: select.option,
+                });
+            }
+            (Direction::ServerToClient, "SMSG_GOSSIP_MESSAGE") => {
+                let Some(message) = decode(packet, |r| gossip::read_gossip_message(r, build))
+                else {
+                    continue;
+                };
+                let entry = guid_entry(message.guid).unwrap_or(0);
+                let key = match message.menu_id {
+                    Some(id) => (Some(id), 0, message.text_id),
+                    None => (None, entry, message.text_id),
+                };
+                let node = *by_key.entry(key).or_insert_with(|| {
+                    menus.push(GossipMenuNode {
+                        node: menus.len(),
+                        menu_id: message.menu_id,
+                        text_id: message.text_id,
+                        npc_entries: Vec::new(),
+                        root_of: Vec::new(),
+                        options: Vec::new(),
+                        quests: Vec::new(),
+                        first_seen_packet: packet.id,
+                        sightings: 0,
+                    });
+                    menus.len() - 1
+                });
+                let menu = &mut menus[node];
+                menu.sightings += 1;
+                if entry != 0 && !menu.npc_entries.contains(&entry) {
+                    menu.npc_entries.push(entry);
+                }
+                if hellos.remove(&message.guid)
+                    && high_guid(message.guid) == HighGuid::Creature
+                    && !menu.root_of.contains(&entry)
+                {
+                    menu.root_of.push(entry);
+                }
+                for option in message.options {
+                    if menu.option_mut(option.id).is_none() {
+                        menu.options.push(GossipOptionNode {
+                            option,
+                            selections: 0,
+                            actions: Vec::new(),
+                        });
+                    }
+                }
+                for quest in message.quests {
+                    if !menu.quests.iter().any(|q| q.quest_id == quest.quest_id) {
+                        menu.quests.push(quest);
+                    }
+                }
+                last_menu.insert(message.guid, node);
+                if pending.as_ref().is_some_and(|p| p.guid == message.guid) {
+                    resolve(&mut menus, pending.take(), GossipAction::Menu { node });
+                }
+            }
+            (Direction::ServerToClient, "SMSG_NPC_TEXT_UPDATE") => {
+                if let Some(text) = decode(packet, gossip::read_npc_text_update) {
+                    texts.entry(text.text_id).or_insert(text);
+                }
+            }
+            (Direction::ServerToClient, "SMSG_GOSSIP_POI") => {
+                let Some(poi) = decode(packet, gossip::read_gossip_poi) else {
+                    continue;
+                };
+                let index = match pois.iter().position(|p| *p == poi) {
+                    Some(index) => index,
+                    None => {
+                        pois.push(poi);
+                        pois.len() - 1
+                    }
+                };
+                resolve(&mut menus, pending.take(), GossipAction::Poi { poi: index });
+            }
+            (Direction::ServerToClient, "SMSG_GOSSIP_COMPLETE") => {
+                resolve(&mut menus, pending.take(), GossipAction::Close);
+            }
+            (Direction::ServerToClient, name)
+                if SERVICE_OPCODES.iter().any(|(op, ..)| *op == name) =>
+            {
+                let action = GossipAction::Service {
+                    opcode: name.to_string(),
+                };
+                resolve(&mut menus, pending.take(), action);
+            }
+            _ => {}
+        }
+    }
+    (menus, texts.into_values().collect(), pois)
+}
+
+/// SQL reference to a menu: its id, or an `@MENU` offset for vanilla menus.
+fn menu_ref(menus: &[GossipMenuNode], node: usize, vars: &HashMap<usize, usize>) -> String {
+    match menus[node].menu_id {
+        Some(id) => id.to_string(),
+        None => format!("@MENU+{}", vars[&node]),
+    }
+}
+
+/// Option type and NPC flag for the first outcome seen.
+fn option_type(option: &GossipOptionNode, build: u32) -> (u32, u32) {
+    let service = option.actions.iter().find_map(|action| match action {
+        GossipAction::Service { opcode } => SERVICE_OPCODES.iter().find(|(op, ..)| op == opcode),
+        _ => None,
+    });
+    match service {
+        Some(&(_, option_type, vanilla_flag, flag)) => {
+            (option_type, if build >= 8606 { flag } else { vanilla_flag })
+        }
+        None => (OPTION_TYPE_GOSSIP, NPC_FLAG_GOSSIP),
+    }
+}
+
+pub fn gossip_sql(
+    menus: &[GossipMenuNode],
+    texts: &[NpcText],
+    pois: &[GossipPoi],
+    flavor: SqlFlavor,
+    build: u32,
+) -> String {
+    let tc = flavor == SqlFlavor::TrinityCore;
+    let vars: HashMap<usize, usize> = menus
+        .iter()
+        .filter(|m| m.menu_id.is_none())
+        .enumerate()
+        .map(|(i, m)| (m.node, i))
+        .collect();
+    let mut out = String::new();
+    if !vars.is_empty() {
+        out.push_str("SET @MENU := 0;\n");
+    }
+    if !pois.is_empty() {
+        out.push_str("SET @POI := 0;\n");
+    }
+
+    // gossip_menu: one row per menu/text pair.
+    let menu_ids: Vec<u32> = {
+        let mut ids: Vec<u32> = menus.iter().filter_map(|m| m.menu_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    };
+    let (menu_table_key, menu_columns): (&str, &[&str]) = if tc {
+        ("MenuID", &["MenuID", "TextID", "VerifiedBuild"])
+    } else {
+        ("entry", &["entry", "text_id"])
+    };
+    let rows: Vec<SqlRow> = menus
+        .iter()
+        .map(|menu| {
+            let mut values = vec![menu_ref(menus, menu.node, &vars), menu.text_id.to_string()];
+            if tc {
+                values.push(build.to_string());
+            }
+            SqlRow {
+                values,
+                comment: (!menu.npc_entries.is_empty()).then(|| {
+                    let entries: Vec<String> =
+                        menu.npc_entries.iter().map(|e| e.to_string()).collect();
+                    format!("shown by {}", entries.join(", "))
+                }),
+            }
+        })
+        .collect();
+    out.push_str(&sql::delete_in("gossip_menu", menu_table_key, &menu_ids));
+    out.push_str(&sql::delete_range(
+        "gossip_menu",
+        menu_table_key,
+        "MENU",
+        vars.len(),
+    ));
+    out.push_str(&sql::insert("gossip_menu", menu_columns, &rows));
+
+    // gossip_menu_option: options are per menu id, so menus sharing an id
+    // with different texts contribute one set of rows.
+    let option_columns: &[&str] = if tc {
+        &[
+            "MenuID",
+            "OptionID",
+            "OptionIcon",
+            "OptionText",
+            "OptionType",
+            "OptionNpcFlag",
+            "ActionMenuID",
+            "ActionPoiID",
+            "BoxCoded",
+            "BoxMoney",
+            "BoxText",
+            "VerifiedBuild",
+        ]
+    } else {
+        &[
+            "menu_id",
+            "id",
+            "option_icon",
+            "option_text",
+            "option_id",
+            "npc_option_npcflag",
+            "action_menu_id",
+            "action_poi_id",
+            "box_coded",
+            "box_money",
+            "box_text",
+        ]
+    };
+    let mut written: HashSet<(String, u32)> = HashSet::new();
+    let mut rows = Vec::new();
+    for menu in menus {
+        let menu_key = menu_ref(menus, menu.node, &vars);
+        for option in &menu.options {
+            if !written.insert((menu_key.clone(), option.option.id)) {
+                continue;
+            }
+            let (option_type, npc_flag) = option_type(option, build);
+            let action_menu = option.actions.iter().find_map(|a| match a {
+                GossipAction::Menu { node } => Some(menu_ref(menus, *node, &vars)),
+                _ => None,
+            });
+            let action_poi = option.actions.iter().find_map(|a| match a {
+                GossipAction::Poi { poi } => Some(format!("@POI+{}", poi)),
+                _ => None,
+            });
+            let mut values = vec![
+                menu_key.clone(),
+                option.option.id.to_string(),
+                option.option.icon.to_string(),
+                sql::quote(&option.option.text),
+                option_type.to_string(),
+                npc_flag.to_string(),
+                action_menu.unwrap_or_else(|| "0".to_string()),
+                action_poi.unwrap_or_else(|| "0".to_string()),
+                u8::from(option.option.coded).to_string(),
+                option.option.box_money.to_string(),
+                sql::quote(&option.option.box_text),
+            ];
+            if tc {
+                values.push(build.to_string());
+            }
+            rows.push(SqlRow {
+                values,
+                comment: (option.selections == 0).then(|| "never selected".to_string()),
+            });
+        }
+    }
+    let option_key = option_columns[0];
+    out.push_str(&sql::delete_in("gossip_menu_option", option_key, &menu_ids));
+    out.push_str(&sql::delete_range(
+        "gossip_menu_option",
+        option_key,
+        "MENU",
+        vars.len(),
+    ));
+    out.push_str(&sql::insert("gossip_menu_option", option_columns, &rows));
+
+    // npc_text: the packet carries every column, so rows are replaced.
+    let mut text_columns = vec!["ID".to_string()];
+    for i in 0..8 {
+        text_columns.push(format!("text{}_0", i));
+        text_columns.push(format!("text{}_1", i));
+        if tc {
+            text_columns.push(format!("BroadcastTextID{}", i));
+            text_columns.push(format!("lang{}", i));
+            text_columns.push(format!("Probability{}", i));
+            for j in 0..6 {
+                text_columns.push(format!("em{}_{}", i, j));
+            }
+        } else {
+            text_columns.push(format!("lang{}", i));
+            text_columns.push(format!("prob{}", i));
+            for j in 0..3 {
+                text_columns.push(format!("em{}_{}_delay", i, j));
+                text_columns.push(format!("em{}_{}", i, j));
+            }
+        }
+    }
+    if tc {
+        text_columns.push("VerifiedBuild".to_string());
+    }
+    let rows: Vec<SqlRow> = texts
+        .iter()
+        .map(|text| {
+            let mut values = vec![text.text_id.to_string()];
+            for entry in &text.entries {
+                values.push(sql::quote(&entry.texts[0]));
+                values.push(sql::quote(&entry.texts[1]));
+                if tc {
+                    values.push("0".to_string());
+                    values.push(entry.language.to_string());
+                    values.push(sql::float(entry.probability));
+                } else {
+                    values.push(entry.language.to_string());
+                    values.push(sql::float(entry.probability));
+                }
+                for emote in &entry.emotes {
+                    values.push(emote.delay.to_string());
+                    values.push(emote.emote.to_string());
+                }
+            }
+            if tc {
+                values.push(build.to_string());
+            }
+            SqlRow {
+                values,
+                comment: None,
+            }
+        })
+        .collect();
+    let text_ids: Vec<u32> = texts.iter().map(|t| t.text_id).collect();
+    out.push_str(&sql::delete_in("npc_text", "ID", &text_ids));
+    out.push_str(&sql::insert("npc_text", &text_columns, &rows));
+
+    // points_of_interest: ids are not sent, so they are numbered off @POI.
+    let poi_columns: &[&str] = if tc {
+        &[
+            "ID",
+            "PositionX",
+            "PositionY",
+            "Icon",
+            "Flags",
+            "Importance",
+            "Name",
+            "VerifiedBuild",
+        ]
+    } else {
+        &["entry", "x", "y", "icon", "flags", "data", "icon_name"]
+    };
+    let rows: Vec<SqlRow> = pois
+        .iter()
+        .enumerate()
+        .map(|(i, poi)| {
+            let mut values = vec![
+                format!("@POI+{}", i),
+                sql::float(poi.x),
+                sql::float(poi.y),
+                poi.icon.to_string(),
+                poi.flags.to_string(),
+                poi.importance.to_string(),
+                sql::quote(&poi.name),
+            ];
+            if tc {
+                values.push(build.to_string());
+            }
+            SqlRow {
+                values,
+                comment: None,
+            }
+        })
+        .collect();
+    out.push_str(&sql::delete_range(
+        "points_of_interest",
+        poi_columns[0],
+        "POI",
+        pois.len(),
+    ));
+    out.push_str(&sql::insert("points_of_interest", poi_columns, &rows));
+
+    // Point each creature at the first menu its gossip hello opened.
+    let (table_column, entry_column) = if tc {
+        ("gossip_menu_id", "entry")
+    } else {
+        ("GossipMenuId", "Entry")
+    };
+    let mut pointed = HashSet::new();
+    for menu in menus {
+        for entry in menu.root_of.iter().filter(|&&e| pointed.insert(e)) {
+            out.push_str(&format!(
+                "UPDATE `creature_template` SET `{}`={} WHERE `{}`={};\n",
+                table_column,
+                menu_ref(menus, menu.node, &vars),
+                entry_column,
+                entry
+            ));
+        }
+    }
+    out
+}
+
+pub fn extract_gossip(session: &Session, flavor: SqlFlavor) -> Result<GossipTree, String> {
+    let build = super::session_build(session)?;
+    let (menus, texts, pois) = gossip_tree(session, build);
+    let sql = gossip_sql(&menus, &texts, &pois, flavor, build);
+    Ok(GossipTree {
+        menus,
+        texts,
+        pois,
+        sql,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{session, Bytes};
+    use crate::parser::gossip::tests::{message, npc_text, poi, select, BUILDS, NPC};
+
+    const ROOT: u32 = 1291;
+    const DIRECTIONS: u32 = 1292;
+
+    /// Farley is greeted twice; the first menu's options open a vendor, mark
+    /// the bank and lead to a second menu whose option closes the window.
+    fn capture(build: u32) -> Session {
+        let root = || {
+            message(
+                build,
+                NPC,
+                ROOT,
+                2593,
+                &[
+                    (0, "Let me browse your goods."),
+                    (1, "Where is the bank?"),
+                    (2, "More."),
+                ],
+                &[],
+            )
+        };
+        let hello = || Bytes::new().guid(NPC).build();
+        session(
+            build,
+            vec![
+                ("CMSG_GOSSIP_HELLO", hello()),
+                ("SMSG_GOSSIP_MESSAGE", root()),
+                (
+                    "SMSG_NPC_TEXT_UPDATE",
+                    npc_text(2593, "Welcome to the inn."),
+                ),
+                ("CMSG_GOSSIP_SELECT_OPTION", select(build, NPC, ROOT, 0)),
+                ("SMSG_LIST_INVENTORY", Vec::new()),
+                ("CMSG_GOSSIP_HELLO", hello()),
+                ("SMSG_GOSSIP_MESSAGE", root()),
+                ("CMSG_GOSSIP_SELECT_OPTION", select(build, NPC, ROOT, 1)),
+                ("SMSG_GOSSIP_POI", poi("Stormwind Bank")),
+                ("CMSG_GOSSIP_SELECT_OPTION", select(build, NPC, ROOT, 2)),
+                (
+                    "SMSG_GOSSIP_MESSAGE",
+                    message(build, NPC, DIRECTIONS, 2594, &[(0, "Thanks.")], &[]),
+                ),
+                (
+                    "CMSG_GOSSIP_SELECT_OPTION",
+                    select(build, NPC, DIRECTIONS, 0),
+                ),
+                ("SMSG_GOSSIP_COMPLETE", Vec::new()),
+            ],
+        )
+    }
+
+    #[test]
+    fn rebuilds_the_menu_tree() {
+        for build in BUILDS {
+            let (menus, texts, pois) = gossip_tree(&capture(build), build);
+            assert_eq!(menus.len(), 2, "build {}", build);
+            let (root, next) = (&menus[0], &menus[1]);
+            assert_eq!(root.menu_id, (build >= 8606).then_some(ROOT));
+            assert_eq!((root.text_id, root.sightings), (2593, 2));
+            assert_eq!(root.npc_entries, [295]);
+            assert_eq!(root.root_of, [295]);
+            assert!(next.root_of.is_empty());
+
+            let actions: Vec<_> = root.options.iter().map(|o| o.actions.clone()).collect();
+            assert_eq!(
+                actions,
+                [
+                    vec![GossipAction::Service {
+                        opcode: "SMSG_LIST_INVENTORY".to_string()
+                    }],
+                    vec![GossipAction::Poi { poi: 0 }],
+                    vec![GossipAction::Menu { node: 1 }],
+                ]
+            );
+            assert!(root.options.iter().all(|o| o.selections == 1));
+            assert_eq!(next.options[0].actions, [GossipAction::Close]);
+
+            assert_eq!(texts.len(), 1);
+            assert_eq!(texts[0].text_id, 2593);
+            assert_eq!(pois.len(), 1);
+            assert_eq!(pois[0].name, "Stormwind Bank");
+        }
+    }
+
+    #[test]
+    fn replies_to_another_npc_do_not_resolve_a_selection() {
+        let build = 12340;
+        let other = 0xF130_0001_2800_0012;
+        let packets = vec![
+            (
+                "SMSG_GOSSIP_MESSAGE",
+                message(build, NPC, ROOT, 2593, &[(0, "More.")], &[]),
+            ),
+            ("CMSG_GOSSIP_SELECT_OPTION", select(build, NPC, ROOT, 0)),
+            (
+                "SMSG_GOSSIP_MESSAGE",
+                message(build, other, DIRECTIONS, 2594, &[], &[]),
+            ),
+        ];
+        let (menus, ..) = gossip_tree(&session(build, packets), build);
+        assert_eq!(menus[0].options[0].selections, 1);
+        assert!(menus[0].options[0].actions.is_empty());
+        // No hello was seen, so neither menu is a creature's root.
+        assert!(menus.iter().all(|m| m.root_of.is_empty()));
+    }
+
+    #[test]
+    fn trinitycore_rows() {
+        let tree = extract_gossip(&capture(12340), SqlFlavor::TrinityCore).unwrap();
+        let sql = tree.sql;
+        assert!(sql.contains("DELETE FROM `gossip_menu` WHERE `MenuID` IN (1291, 1292);\n"));
+        assert!(sql.contains("(1291, 2593, 12340), -- shown by 295"));
+        assert!(sql
+            .contains("(1291, 0, 0, 'Let me browse your goods.', 3, 128, 0, 0, 0, 0, '', 12340),"));
+        assert!(sql.contains(
+            "(1291, 1, 1, 'Where is the bank?', 1, 1, 0, @POI+0, 0, 10, \
+            'Pay up?', 12340),"
+        ));
+        assert!(sql.contains("(1291, 2, 2, 'More.', 1, 1, 1292, 0, 0, 0, '', 12340),"));
+        assert!(sql.contains("(@POI+0, -8811.5, 626.25, 7, 99, 0, 'Stormwind Bank', 12340);"));
+        assert!(sql
+            .contains("UPDATE `creature_template` SET `gossip_menu_id`=1291 WHERE `entry`=295;\n"));
+        assert!(!sql.contains("@MENU"));
+    }
+
+    #[test]
+    fn cmangos_numbers_vanilla_menus() {
+        let tree = extract_gossip(&capture(5875), SqlFlavor::CMaNGOS).unwrap();
+        let sql = tree.sql;
+        assert!(sql.starts_with("SET @MENU := 0;\nSET @POI := 0;\n"));
+        assert!(
+            sql.contains("DELETE FROM `gossip_menu` WHERE `entry` BETWEEN @MENU+0 AND @MENU+1;")
+        );
+        assert!(sql.contains("(@MENU+0, 2593), -- shown by 295"));
+        // Vanilla has its own NPC flags.
+        let vendor = "(@MENU+0, 0, 0, 'Let me browse your goods.', 3, 4, 0, 0, 0, 0, ''),";
+        assert!(sql.contains(vendor));
+        assert!(sql.contains("(@MENU+0, 2, 2, 'More.', 1, 1, @MENU+1, 0, 0, 0, ''),"));
+        assert!(sql.contains("(@MENU+1, 0, 0, 'Thanks.', 1, 1, 0, 0, 0, 0, '');"));
+        assert!(sql.contains(
+            "UPDATE `creature_template` SET `GossipMenuId`=@MENU+0 WHERE `Entry`=295;\n"
+        ));
+    }
+}
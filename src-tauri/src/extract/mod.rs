@@ -0,0 +1,226 @@
+//! Extractors that rebuild server database rows from a captured session.
+//!
+//! Each extractor returns the typed rows (which the frontend can dump as
+//! JSON) together with ready-to-run SQL for the requested emulator flavor.
+
+pub mod creatures;
+pub mod gameobjects;
+pub mod gossip;
+pub mod quests;
+pub mod sql;
+pub mod templates;
+pub mod texts;
+pub mod trainers;
+pub mod vendors;
+pub mod waypoints;
+
+use crate::parser::guid::guid_entry;
+use crate::parser::reader::{PacketReader, ParseError};
+use crate::parser::update_fields::OBJECT_FIELD_ENTRY;
+use crate::parser::update_object::{self, MovementBlock, UpdateBlock, UpdateFields, UpdateObject};
+use crate::parser::{self, world, SUPPORTED_BUILDS};
+use crate::state::{Direction, Packet, Session};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SqlExport<T> {
+    pub rows: Vec<T>,
+    pub sql: String,
+}
+
+/// A field whose value changed between two sightings of the same record.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldConflict {
+    /// Packet type the differing value came from.
+    pub source: String,
+    /// Dotted path of the field, e.g. `rewards.money`.
+    pub field: String,
+    pub first: Value,
+    pub other: Value,
+    /// Packet carrying the differing value.
+    pub packet_id: usize,
+}
+
+/// Record every field of `next` that differs from `first`, recursing into
+/// nested objects. Fields in `ignore` legitimately vary between sightings
+/// (the NPC offering a shared quest, for instance).
+pub(crate) fn diff_sightings<T: Serialize>(
+    source: &str,
+    first: &T,
+    next: &T,
+    ignore: &[&str],
+    packet_id: usize,
+    conflicts: &mut Vec<FieldConflict>,
+) {
+    let (Ok(first), Ok(next)) = (serde_json::to_value(first), serde_json::to_value(next)) else {
+        return;
+    };
+    diff_values(source, "", &first, &next, ignore, packet_id, conflicts);
+}
+
+fn diff_values(
+    source: &str,
+    path: &str,
+    first: &Value,
+    next: &Value,
+    ignore: &[&str],
+    packet_id: usize,
+    conflicts: &mut Vec<FieldConflict>,
+) {
+    if ignore.contains(&path) || first == next {
+        return;
+    }
+    if let (Value::Object(a), Value::Object(b)) = (first, next) {
+        for (key, value) in a {
+            let child = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            let other = b.get(key).unwrap_or(&Value::Null);
+            diff_values(source, &child, value, other, ignore, packet_id, conflicts);
+        }
+        return;
+    }
+    // Report each distinct value once rather than once per repeat.
+    let seen = conflicts
+        .iter()
+        .any(|c| c.source == source && c.field == path && &c.other == next);
+    if !seen {
+        conflicts.push(FieldConflict {
+            source: source.to_string(),
+            field: path.to_string(),
+            first: first.clone(),
+            other: next.clone(),
+            packet_id,
+        });
+    }
+}
+
+/// The session's build, if the decoders can read it.
+pub(crate) fn session_build(session: &Session) -> Result<u32, String> {
+    match session.build {
+        Some(build) if SUPPORTED_BUILDS.contains(&build) => Ok(build),
+        Some(build) => Err(format!("Build {} is not supported for extraction", build)),
+        None => Err(format!(
+            "Session {} has no client build recorded",
+            session.name
+        )),
+    }
+}
+
+/// Decode a packet with a typed reader, ignoring packets that fail to parse.
+pub(crate) fn decode<'a, T>(
+    packet: &'a Packet,
+    read: impl FnOnce(&mut PacketReader<'a>) -> Result<T, ParseError>,
+) -> Option<T> {
+    read(&mut PacketReader::new(&packet.data)).ok()
+}
+
+/// The update data in a packet, if it is a regular or compressed update
+/// packet that decodes.
+pub(crate) fn decode_update(packet: &Packet, name: &str, build: u32) -> Option<UpdateObject> {
+    match name {
+        "SMSG_UPDATE_OBJECT" => decode(packet, |r| update_object::read_update_object(r, build)),
+        "SMSG_COMPRESSED_UPDATE_OBJECT" => decode(packet, |r| {
+            update_object::read_compressed_update_object(r, build)
+        }),
+        _ => None,
+    }
+}
+
+/// Walk every update block in capture order, together with the map the
+/// client was on when the packet arrived.
+pub(crate) fn for_each_update_block(
+    session: &Session,
+    build: u32,
+    mut visit: impl FnMut(&Packet, Option<u32>, &UpdateBlock),
+) {
+    let mut map = None;
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient {
+            continue;
+        }
+        let update = match parser::opcode_name(Some(build), packet) {
+            "SMSG_LOGIN_VERIFY_WORLD" | "SMSG_NEW_WORLD" => {
+                if let Some(world) = decode(packet, world::read_world_position) {
+                    map = Some(world.map);
+                }
+                continue;
+            }
+            name => decode_update(packet, name, build),
+        };
+        for block in update.iter().flat_map(|u| &u.blocks) {
+            visit(packet, map, block);
+        }
+    }
+}
+
+/// Respawn time written for every spawn row; captures cannot observe the
+/// real one.
+pub(crate) const SPAWN_TIME_SECS: u32 = 300;
+
+/// One row per GUID of `object_type` from its first create block, paired
+/// with how many create blocks were seen for it. `first` builds the row and
+/// returns `None` to skip a block; later blocks for the GUID are only
+/// counted.
+pub(crate) fn first_sightings<T>(
+    session: &Session,
+    build: u32,
+    object_type: u8,
+    mut first: impl FnMut(&Packet, Option<u32>, u64, &MovementBlock, &UpdateFields) -> Option<T>,
+) -> Vec<(T, usize)> {
+    let mut rows: Vec<(T, usize)> = Vec::new();
+    let mut by_guid: HashMap<u64, usize> = HashMap::new();
+    for_each_update_block(session, build, |packet, map, block| {
+        let UpdateBlock::Create {
+            guid,
+            object_type: block_type,
+            movement,
+            fields,
+            ..
+        } = block
+        else {
+            return;
+        };
+        if *block_type != object_type {
+            return;
+        }
+        if let Some(&index) = by_guid.get(guid) {
+            rows[index].1 += 1;
+            return;
+        }
+        if let Some(row) = first(packet, map, *guid, movement, fields) {
+            by_guid.insert(*guid, rows.len());
+            rows.push((row, 1));
+        }
+    });
+    rows
+}
+
+/// Entry of every object the update data named, keyed by GUID. Objects
+/// never seen in an update fall back to the entry packed into the GUID.
+pub(crate) struct ObjectEntries(HashMap<u64, u32>);
+
+impl ObjectEntries {
+    pub(crate) fn collect(session: &Session, build: u32) -> Self {
+        let mut entries = HashMap::new();
+        for_each_update_block(session, build, |_, _, block| {
+            let (UpdateBlock::Create { guid, fields, .. } | UpdateBlock::Values { guid, fields }) =
+                block
+            else {
+                return;
+            };
+            if let Some(&entry) = fields.get(&OBJECT_FIELD_ENTRY) {
+                entries.entry(*guid).or_insert(entry);
+            }
+        });
+        ObjectEntries(entries)
+    }
+
+    pub(crate) fn get(&self, guid: u64) -> Option<u32> {
+        self.0.get(&guid).copied().or_else(|| guid_entry(guid))
+    }
+}
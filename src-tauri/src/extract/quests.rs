@@ -0,0 +1,870 @@
+//! Quest templates and dialog texts merged from every sighting of a quest.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{decode, diff_sightings, FieldConflict, SqlExport};
+use crate::parser;
+use crate::parser::quests::{
+    self, QuestDetails, QuestEmote, QuestOfferReward, QuestQueryResponse, QuestRequestItems,
+};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Everything seen about one quest. The first sighting of each packet type
+/// is kept; later sightings only contribute conflicts.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuestRecord {
+    pub quest_id: u32,
+    pub query: Option<QuestQueryResponse>,
+    pub details: Option<QuestDetails>,
+    pub request_items: Option<QuestRequestItems>,
+    /// Request-items emotes are sent for whichever state the player was in,
+    /// so both are collected separately.
+    pub complete_emote: Option<u32>,
+    pub incomplete_emote: Option<u32>,
+    pub offer_reward: Option<QuestOfferReward>,
+    pub sightings: usize,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+impl QuestRecord {
+    fn new(quest_id: u32) -> Self {
+        QuestRecord {
+            quest_id,
+            query: None,
+            details: None,
+            request_items: None,
+            complete_emote: None,
+            incomplete_emote: None,
+            offer_reward: None,
+            sightings: 0,
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// Keep the first sighting of a packet type, diffing later ones against it.
+    fn merge<T: Serialize>(
+        slot: &mut Option<T>,
+        conflicts: &mut Vec<FieldConflict>,
+        source: &str,
+        ignore: &[&str],
+        value: T,
+        packet_id: usize,
+    ) {
+        match slot {
+            Some(first) => diff_sightings(source, first, &value, ignore, packet_id, conflicts),
+            None => *slot = Some(value),
+        }
+    }
+
+    /// Dialog packets repeat texts the query response also carries; flag any
+    /// that disagree with what is already known.
+    fn check_text(&mut self, source: &str, field: &str, text: &str, packet_id: usize) {
+        let known = match (field, &self.query, &self.details) {
+            ("title", Some(q), _) => &q.title,
+            ("title", None, Some(d)) => &d.title,
+            ("details", Some(q), _) => &q.details,
+            ("objectives", Some(q), _) => &q.objectives,
+            _ => return,
+        };
+        if known != text {
+            let (first, other) = (known.clone().into(), text.into());
+            if !self
+                .conflicts
+                .iter()
+                .any(|c| c.source == source && c.field == field && c.other == other)
+            {
+                self.conflicts.push(FieldConflict {
+                    source: source.to_string(),
+                    field: field.to_string(),
+                    first,
+                    other,
+                    packet_id,
+                });
+            }
+        }
+    }
+}
+
+/// Collect one record per quest id, in id order.
+pub fn quest_records(session: &Session, build: u32) -> Vec<QuestRecord> {
+    let mut records: BTreeMap<u32, QuestRecord> = BTreeMap::new();
+
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient {
+            continue;
+        }
+        match parser::opcode_name(Some(build), packet) {
+            "SMSG_QUEST_QUERY_RESPONSE" => {
+                let Some(query) = decode(packet, |r| quests::read_quest_query_response(r, build))
+                else {
+                    continue;
+                };
+                let record = records
+                    .entry(query.quest_id)
+                    .or_insert_with(|| QuestRecord::new(query.quest_id));
+                record.sightings += 1;
+                QuestRecord::merge(
+                    &mut record.query,
+                    &mut record.conflicts,
+                    "quest_query_response",
+                    &[],
+                    query,
+                    packet.id,
+                );
+            }
+            "SMSG_QUESTGIVER_QUEST_DETAILS" => {
+                let Some(details) = decode(packet, |r| quests::read_quest_details(r, build)) else {
+                    continue;
+                };
+                let record = records
+                    .entry(details.quest_id)
+                    .or_insert_with(|| QuestRecord::new(details.quest_id));
+                record.sightings += 1;
+                record.check_text("quest_details", "title", &details.title, packet.id);
+                record.check_text("quest_details", "details", &details.details, packet.id);
+                record.check_text(
+                    "quest_details",
+                    "objectives",
+                    &details.objectives,
+                    packet.id,
+                );
+                QuestRecord::merge(
+                    &mut record.details,
+                    &mut record.conflicts,
+                    "quest_details",
+                    &["npc_guid"],
+                    details,
+                    packet.id,
+                );
+            }
+            "SMSG_QUESTGIVER_REQUEST_ITEMS" => {
+                let Some(request) = decode(packet, |r| quests::read_quest_request_items(r, build))
+                else {
+                    continue;
+                };
+                let record = records
+                    .entry(request.quest_id)
+                    .or_insert_with(|| QuestRecord::new(request.quest_id));
+                record.sightings += 1;
+                record.check_text("quest_request_items", "title", &request.title, packet.id);
+                let emote = if request.completable {
+                    &mut record.complete_emote
+                } else {
+                    &mut record.incomplete_emote
+                };
+                emote.get_or_insert(request.emote);
+                QuestRecord::merge(
+                    &mut record.request_items,
+                    &mut record.conflicts,
+                    "quest_request_items",
+                    &["npc_guid", "emote", "completable"],
+                    request,
+                    packet.id,
+                );
+            }
+            "SMSG_QUESTGIVER_OFFER_REWARD" => {
+                let Some(offer) = decode(packet, |r| quests::read_quest_offer_reward(r, build))
+                else {
+                    continue;
+                };
+                let record = records
+                    .entry(offer.quest_id)
+                    .or_insert_with(|| QuestRecord::new(offer.quest_id));
+                record.sightings += 1;
+                record.check_text("quest_offer_reward", "title", &offer.title, packet.id);
+                QuestRecord::merge(
+                    &mut record.offer_reward,
+                    &mut record.conflicts,
+                    "quest_offer_reward",
+                    &["npc_guid"],
+                    offer,
+                    packet.id,
+                );
+            }
+            _ => {}
+        }
+    }
+    records.into_values().collect()
+}
+
+/// Values padded with zeros to the number of numbered columns.
+fn push_padded<T: ToString>(values: &mut Vec<String>, list: &[T], slots: usize) {
+    values.extend(list.iter().take(slots).map(|v| v.to_string()));
+    values.extend((list.len()..slots).map(|_| "0".to_string()));
+}
+
+/// Item ids then counts, padded to `slots` columns each.
+fn push_items(values: &mut Vec<String>, items: &[quests::QuestItem], slots: usize) {
+    let ids: Vec<u32> = items.iter().map(|i| i.item).collect();
+    let counts: Vec<u32> = items.iter().map(|i| i.count).collect();
+    push_padded(values, &ids, slots);
+    push_padded(values, &counts, slots);
+}
+
+/// Emote ids then delays, padded to the four slots the schemas have.
+fn push_emotes(values: &mut Vec<String>, emotes: &[QuestEmote]) {
+    let padded = |f: fn(&QuestEmote) -> u32| {
+        (0..4).map(move |i| emotes.get(i).map(f).unwrap_or(0).to_string())
+    };
+    values.extend(padded(|e| e.emote));
+    values.extend(padded(|e| e.delay));
+}
+
+fn trinity_quest_template(records: &[&QuestRecord], build: u32) -> String {
+    let mut columns: Vec<String> = [
+        "ID",
+        "QuestType",
+        "QuestLevel",
+        "MinLevel",
+        "QuestSortID",
+        "QuestInfoID",
+        "SuggestedGroupNum",
+        "RequiredFactionId1",
+        "RequiredFactionValue1",
+        "RequiredFactionId2",
+        "RequiredFactionValue2",
+        "RewardNextQuest",
+        "RewardXPDifficulty",
+        "RewardMoney",
+        "RewardBonusMoney",
+        "RewardDisplaySpell",
+        "RewardSpell",
+        "RewardHonor",
+        "RewardKillHonor",
+        "StartItem",
+        "Flags",
+        "RequiredPlayerKills",
+        "RewardTitle",
+        "RewardTalents",
+        "RewardArenaPoints",
+    ]
+    .iter()
+    .map(|c| c.to_string())
+    .collect();
+    columns.extend(sql::numbered("RewardItem", 4));
+    columns.extend(sql::numbered("RewardAmount", 4));
+    columns.extend(sql::numbered("RewardChoiceItemID", 6));
+    columns.extend(sql::numbered("RewardChoiceItemQuantity", 6));
+    columns.extend(sql::numbered("RewardFactionID", 5));
+    columns.extend(sql::numbered("RewardFactionValue", 5));
+    columns.extend(sql::numbered("RewardFactionOverride", 5));
+    columns.extend(
+        [
+            "POIContinent",
+            "POIx",
+            "POIy",
+            "POIPriority",
+            "LogTitle",
+            "LogDescription",
+            "QuestDescription",
+            "AreaDescription",
+            "QuestCompletionLog",
+        ]
+        .iter()
+        .map(|c| c.to_string()),
+    );
+    columns.extend(sql::numbered("RequiredNpcOrGo", 4));
+    columns.extend(sql::numbered("RequiredNpcOrGoCount", 4));
+    columns.extend(sql::numbered("ItemDrop", 4));
+    columns.extend(sql::numbered("ItemDropQuantity", 4));
+    columns.extend(sql::numbered("RequiredItemId", 6));
+    columns.extend(sql::numbered("RequiredItemCount", 6));
+    columns.extend(sql::numbered("ObjectiveText", 4));
+    columns.push("VerifiedBuild".to_string());
+
+    let rows: Vec<SqlRow> = records
+        .iter()
+        .filter_map(|record| record.query.as_ref())
+        .map(|q| {
+            let mut values: Vec<String> = [
+                q.quest_id.to_string(),
+                q.method.to_string(),
+                q.level.to_string(),
+                q.min_level.to_string(),
+                q.zone_or_sort.to_string(),
+                q.quest_type.to_string(),
+                q.suggested_players.to_string(),
+                q.rep_objective_faction.to_string(),
+                q.rep_objective_value.to_string(),
+                q.required_opposite_faction.to_string(),
+                q.required_opposite_value.to_string(),
+                q.next_quest_in_chain.to_string(),
+                q.xp_id.to_string(),
+                q.reward_money.to_string(),
+                q.reward_money_max_level.to_string(),
+                q.reward_spell.to_string(),
+                q.reward_spell_cast.to_string(),
+                q.reward_honor.to_string(),
+                sql::float(q.reward_honor_multiplier),
+                q.source_item.to_string(),
+                q.flags.to_string(),
+                q.players_slain.to_string(),
+                q.reward_title.to_string(),
+                q.bonus_talents.to_string(),
+                q.reward_arena_points.to_string(),
+            ]
+            .into();
+            push_items(&mut values, &q.reward_items, 4);
+            push_items(&mut values, &q.choice_items, 6);
+            push_padded(&mut values, &q.reward_factions, 5);
+            push_padded(&mut values, &q.reward_faction_values, 5);
+            push_padded(&mut values, &q.reward_faction_overrides, 5);
+            values.extend([
+                q.point_map.to_string(),
+                sql::float(q.point_x),
+                sql::float(q.point_y),
+                q.point_opt.to_string(),
+                sql::quote(&q.title),
+                sql::quote(&q.objectives),
+                sql::quote(&q.details),
+                sql::quote(&q.end_text),
+                sql::quote(&q.completed_text),
+            ]);
+            values.extend(
+                q.objectives_npc_or_go
+                    .iter()
+                    .map(|o| o.npc_or_go.to_string()),
+            );
+            values.extend(q.objectives_npc_or_go.iter().map(|o| o.count.to_string()));
+            push_items(&mut values, &q.source_items, 4);
+            push_items(&mut values, &q.required_items, 6);
+            values.extend(q.objective_texts.iter().map(|t| sql::quote(t)));
+            values.push(build.to_string());
+            SqlRow {
+                values,
+                comment: None,
+            }
+        })
+        .collect();
+
+    let ids: Vec<u32> = records
+        .iter()
+        .filter(|r| r.query.is_some())
+        .map(|r| r.quest_id)
+        .collect();
+    let mut out = sql::delete_in("quest_template", "ID", &ids);
+    out.push_str(&sql::insert("quest_template", &columns, &rows));
+    out
+}
+
+fn trinity_dialog_tables(records: &[&QuestRecord], build: u32) -> String {
+    let mut out = String::new();
+
+    let details: Vec<(u32, &QuestDetails)> = records
+        .iter()
+        .filter_map(|r| r.details.as_ref().map(|d| (r.quest_id, d)))
+        .collect();
+    let rows: Vec<SqlRow> = details
+        .iter()
+        .map(|(id, d)| {
+            let mut values = vec![id.to_string()];
+            push_emotes(&mut values, &d.emotes);
+            values.push(build.to_string());
+            SqlRow {
+                values,
+                comment: None,
+            }
+        })
+        .collect();
+    let ids: Vec<u32> = details.iter().map(|(id, _)| *id).collect();
+    let mut columns = vec!["ID".to_string()];
+    columns.extend(sql::numbered("Emote", 4));
+    columns.extend(sql::numbered("EmoteDelay", 4));
+    columns.push("VerifiedBuild".to_string());
+    out.push_str(&sql::delete_in("quest_details", "ID", &ids));
+    out.push_str(&sql::insert("quest_details", &columns, &rows));
+
+    let requests: Vec<&&QuestRecord> = records
+        .iter()
+        .filter(|r| r.request_items.is_some())
+        .collect();
+    let rows: Vec<SqlRow> = requests
+        .iter()
+        .map(|r| SqlRow {
+            values: vec![
+                r.quest_id.to_string(),
+                r.complete_emote.unwrap_or(0).to_string(),
+                r.incomplete_emote.unwrap_or(0).to_string(),
+                sql::quote(r.request_items.as_ref().map_or("", |q| &q.text)),
+                build.to_string(),
+            ],
+            comment: None,
+        })
+        .collect();
+    let ids: Vec<u32> = requests.iter().map(|r| r.quest_id).collect();
+    out.push_str(&sql::delete_in("quest_request_items", "ID", &ids));
+    out.push_str(&sql::insert(
+        "quest_request_items",
+        &[
+            "ID",
+            "EmoteOnComplete",
+            "EmoteOnIncomplete",
+            "CompletionText",
+            "VerifiedBuild",
+        ],
+        &rows,
+    ));
+
+    let offers: Vec<(u32, &QuestOfferReward)> = records
+        .iter()
+        .filter_map(|r| r.offer_reward.as_ref().map(|o| (r.quest_id, o)))
+        .collect();
+    let rows: Vec<SqlRow> = offers
+        .iter()
+        .map(|(id, o)| {
+            let mut values = vec![id.to_string()];
+            push_emotes(&mut values, &o.emotes);
+            values.push(sql::quote(&o.text));
+            values.push(build.to_string());
+            SqlRow {
+                values,
+                comment: None,
+            }
+        })
+        .collect();
+    let ids: Vec<u32> = offers.iter().map(|(id, _)| *id).collect();
+    let mut columns = vec!["ID".to_string()];
+    columns.extend(sql::numbered("Emote", 4));
+    columns.extend(sql::numbered("EmoteDelay", 4));
+    columns.push("RewardText".to_string());
+    columns.push("VerifiedBuild".to_string());
+    out.push_str(&sql::delete_in("quest_offer_reward", "ID", &ids));
+    out.push_str(&sql::insert("quest_offer_reward", &columns, &rows));
+    out
+}
+
+fn cmangos_quest_template(records: &[&QuestRecord], build: u32) -> String {
+    let tbc = build >= 8606;
+    let wotlk = build >= 12340;
+
+    let mut columns: Vec<String> = vec!["entry".into(), "Method".into(), "ZoneOrSort".into()];
+    if wotlk {
+        columns.push("MinLevel".into());
+    }
+    columns.extend(["QuestLevel".into(), "Type".into()]);
+    if tbc {
+        columns.push("SuggestedPlayers".into());
+    }
+    columns.extend(
+        [
+            "RepObjectiveFaction",
+            "RepObjectiveValue",
+            "NextQuestInChain",
+            "RewOrReqMoney",
+            "RewMoneyMaxLevel",
+            "RewSpell",
+            "RewSpellCast",
+            "SrcItemId",
+            "QuestFlags",
+        ]
+        .iter()
+        .map(|c| c.to_string()),
+    );
+    if tbc {
+        columns.push("CharTitleId".into());
+    }
+    columns.extend(
+        ["Title", "Details", "Objectives", "EndText"]
+            .iter()
+            .map(|c| c.to_string()),
+    );
+    columns.extend(sql::numbered("ObjectiveText", 4));
+    let required_slots = if wotlk { 6 } else { 4 };
+    columns.extend(sql::numbered("ReqItemId", required_slots));
+    columns.extend(sql::numbered("ReqItemCount", required_slots));
+    if wotlk {
+        columns.extend(sql::numbered("ReqSourceId", 4));
+        columns.extend(sql::numbered("ReqSourceCount", 4));
+    }
+    columns.extend(sql::numbered("ReqCreatureOrGOId", 4));
+    columns.extend(sql::numbered("ReqCreatureOrGOCount", 4));
+    columns.extend(sql::numbered("RewChoiceItemId", 6));
+    columns.extend(sql::numbered("RewChoiceItemCount", 6));
+    columns.extend(sql::numbered("RewItemId", 4));
+    columns.extend(sql::numbered("RewItemCount", 4));
+    columns.extend(
+        ["PointMapId", "PointX", "PointY", "PointOpt"]
+            .iter()
+            .map(|c| c.to_string()),
+    );
+
+    let rows: Vec<SqlRow> = records
+        .iter()
+        .filter_map(|record| record.query.as_ref())
+        .map(|q| {
+            let mut values = vec![
+                q.quest_id.to_string(),
+                q.method.to_string(),
+                q.zone_or_sort.to_string(),
+            ];
+            if wotlk {
+                values.push(q.min_level.to_string());
+            }
+            values.extend([q.level.to_string(), q.quest_type.to_string()]);
+            if tbc {
+                values.push(q.suggested_players.to_string());
+            }
+            values.extend([
+                q.rep_objective_faction.to_string(),
+                q.rep_objective_value.to_string(),
+                q.next_quest_in_chain.to_string(),
+                q.reward_money.to_string(),
+                q.reward_money_max_level.to_string(),
+                q.reward_spell.to_string(),
+                q.reward_spell_cast.to_string(),
+                q.source_item.to_string(),
+                q.flags.to_string(),
+            ]);
+            if tbc {
+                values.push(q.reward_title.to_string());
+            }
+            values.extend([
+                sql::quote(&q.title),
+                sql::quote(&q.details),
+                sql::quote(&q.objectives),
+                sql::quote(&q.end_text),
+            ]);
+            values.extend(q.objective_texts.iter().map(|t| sql::quote(t)));
+            push_items(&mut values, &q.required_items, required_slots);
+            if wotlk {
+                push_items(&mut values, &q.source_items, 4);
+            }
+            values.extend(
+                q.objectives_npc_or_go
+                    .iter()
+                    .map(|o| o.npc_or_go.to_string()),
+            );
+            values.extend(q.objectives_npc_or_go.iter().map(|o| o.count.to_string()));
+            push_items(&mut values, &q.choice_items, 6);
+            push_items(&mut values, &q.reward_items, 4);
+            values.extend([
+                q.point_map.to_string(),
+                sql::float(q.point_x),
+                sql::float(q.point_y),
+                q.point_opt.to_string(),
+            ]);
+            SqlRow {
+                values,
+                comment: None,
+            }
+        })
+        .collect();
+
+    let ids: Vec<u32> = records
+        .iter()
+        .filter(|r| r.query.is_some())
+        .map(|r| r.quest_id)
+        .collect();
+    let mut out = sql::delete_in("quest_template", "entry", &ids);
+    out.push_str(&sql::insert("quest_template", &columns, &rows));
+    out
+}
+
+/// CMaNGOS keeps dialog texts and emotes on `quest_template`, so they are
+/// written as updates that apply whether or not the row was inserted above.
+fn cmangos_dialog_updates(records: &[&QuestRecord]) -> String {
+    let mut out = String::new();
+    for record in records {
+        let mut sets: Vec<String> = Vec::new();
+        let mut emote_sets = |prefix: &str, emotes: &[QuestEmote]| {
+            for i in 0..4 {
+                let emote = emotes.get(i);
+                sets.push(format!(
+                    "`{}Emote{}` = {}",
+                    prefix,
+                    i + 1,
+                    emote.map_or(0, |e| e.emote)
+                ));
+                sets.push(format!(
+                    "`{}EmoteDelay{}` = {}",
+                    prefix,
+                    i + 1,
+                    emote.map_or(0, |e| e.delay)
+                ));
+            }
+        };
+        if let Some(details) = &record.details {
+            emote_sets("Details", &details.emotes);
+        }
+        if let Some(offer) = &record.offer_reward {
+            emote_sets("OfferReward", &offer.emotes);
+            sets.push(format!("`OfferRewardText` = {}", sql::quote(&offer.text)));
+        }
+        if let Some(request) = &record.request_items {
+            sets.push(format!(
+                "`RequestItemsText` = {}",
+                sql::quote(&request.text)
+            ));
+        }
+        if let Some(emote) = record.complete_emote {
+            sets.push(format!("`CompleteEmote` = {}", emote));
+        }
+        if let Some(emote) = record.incomplete_emote {
+            sets.push(format!("`IncompleteEmote` = {}", emote));
+        }
+        if !sets.is_empty() {
+            out.push_str(&format!(
+                "UPDATE `quest_template` SET {} WHERE `entry` = {};\n",
+                sets.join(", "),
+                record.quest_id
+            ));
+        }
+    }
+    out
+}
+
+pub fn quest_sql(records: &[QuestRecord], flavor: SqlFlavor, build: u32) -> String {
+    let records: Vec<&QuestRecord> = records.iter().collect();
+    match flavor {
+        SqlFlavor::TrinityCore => {
+            let mut out = trinity_quest_template(&records, build);
+            out.push_str(&trinity_dialog_tables(&records, build));
+            out
+        }
+        SqlFlavor::CMaNGOS => {
+            let mut out = cmangos_quest_template(&records, build);
+            out.push_str(&cmangos_dialog_updates(&records));
+            out
+        }
+    }
+}
+
+pub fn extract_quests(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<QuestRecord>, String> {
+    let build = super::session_build(session)?;
+    let rows = quest_records(session, build);
+    let sql = quest_sql(&rows, flavor, build);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::session;
+    use crate::parser::quests::tests::{
+        details, offer_reward, query_response, request_items, BUILDS, DETAILS, NPC, QUEST,
+    };
+
+    const OTHER_NPC: u64 = 0xF130_0000_C600_0009;
+
+    fn record(build: u32, packets: Vec<(&str, Vec<u8>)>) -> QuestRecord {
+        let mut records = quest_records(&session(build, packets), build);
+        assert_eq!(records.len(), 1);
+        records.remove(0)
+    }
+
+    fn conflicts(record: &QuestRecord) -> Vec<(&str, &str)> {
+        record
+            .conflicts
+            .iter()
+            .map(|c| (c.source.as_str(), c.field.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn repeats_that_agree_are_not_conflicts() {
+        for build in BUILDS {
+            let record = record(
+                build,
+                vec![
+                    (
+                        "SMSG_QUEST_QUERY_RESPONSE",
+                        query_response(build, QUEST, DETAILS),
+                    ),
+                    (
+                        "SMSG_QUESTGIVER_QUEST_DETAILS",
+                        details(build, NPC, QUEST, "Kobold Camp Cleanup"),
+                    ),
+                    // Shared quests arrive from whoever shares them.
+                    (
+                        "SMSG_QUESTGIVER_QUEST_DETAILS",
+                        details(build, OTHER_NPC, QUEST, "Kobold Camp Cleanup"),
+                    ),
+                    (
+                        "SMSG_QUESTGIVER_REQUEST_ITEMS",
+                        request_items(build, 6, false),
+                    ),
+                    (
+                        "SMSG_QUESTGIVER_REQUEST_ITEMS",
+                        request_items(build, 1, true),
+                    ),
+                    (
+                        "SMSG_QUESTGIVER_OFFER_REWARD",
+                        offer_reward(build, "Thanks"),
+                    ),
+                    (
+                        "SMSG_QUEST_QUERY_RESPONSE",
+                        query_response(build, QUEST, DETAILS),
+                    ),
+                ],
+            );
+            assert_eq!(record.sightings, 7, "build {}", build);
+            assert_eq!(conflicts(&record), [], "build {}", build);
+            assert_eq!(
+                (record.incomplete_emote, record.complete_emote),
+                (Some(6), Some(1))
+            );
+            assert_eq!(record.details.unwrap().npc_guid, NPC);
+        }
+    }
+
+    #[test]
+    fn flags_disagreeing_sightings_once() {
+        let build = 8606;
+        let record = record(
+            build,
+            vec![
+                (
+                    "SMSG_QUEST_QUERY_RESPONSE",
+                    query_response(build, QUEST, "Details"),
+                ),
+                (
+                    "SMSG_QUEST_QUERY_RESPONSE",
+                    query_response(build, QUEST, "Reworded"),
+                ),
+                (
+                    "SMSG_QUEST_QUERY_RESPONSE",
+                    query_response(build, QUEST, "Reworded"),
+                ),
+                (
+                    "SMSG_QUESTGIVER_QUEST_DETAILS",
+                    details(build, NPC, QUEST, "Renamed"),
+                ),
+                (
+                    "SMSG_QUESTGIVER_OFFER_REWARD",
+                    offer_reward(build, "Thanks"),
+                ),
+                (
+                    "SMSG_QUESTGIVER_OFFER_REWARD",
+                    offer_reward(build, "Thank you"),
+                ),
+            ],
+        );
+        assert_eq!(
+            conflicts(&record),
+            [
+                ("quest_query_response", "details"),
+                ("quest_details", "title"),
+                ("quest_details", "details"),
+                ("quest_offer_reward", "text"),
+            ]
+        );
+        let title = &record.conflicts[1];
+        assert_eq!(
+            (title.first.as_str(), title.other.as_str()),
+            (Some("Kobold Camp Cleanup"), Some("Renamed"))
+        );
+        assert_eq!((title.packet_id, record.conflicts[3].packet_id), (3, 5));
+        // The first query response is the one kept.
+        assert_eq!(record.query.unwrap().details, "Details");
+    }
+
+    #[test]
+    fn dialog_titles_are_checked_without_a_query() {
+        let build = 5875;
+        let record = record(
+            build,
+            vec![
+                (
+                    "SMSG_QUESTGIVER_QUEST_DETAILS",
+                    details(build, NPC, QUEST, "Kobold Camp Cleanup"),
+                ),
+                (
+                    "SMSG_QUESTGIVER_OFFER_REWARD",
+                    offer_reward(build, "Thanks"),
+                ),
+            ],
+        );
+        assert_eq!(conflicts(&record), []);
+        let record = quest_records(
+            &session(
+                build,
+                vec![
+                    (
+                        "SMSG_QUESTGIVER_QUEST_DETAILS",
+                        details(build, NPC, QUEST, "Old Title"),
+                    ),
+                    (
+                        "SMSG_QUESTGIVER_OFFER_REWARD",
+                        offer_reward(build, "Thanks"),
+                    ),
+                ],
+            ),
+            build,
+        );
+        assert_eq!(conflicts(&record[0]), [("quest_offer_reward", "title")]);
+    }
+
+    fn full_capture(build: u32) -> Session {
+        session(
+            build,
+            vec![
+                (
+                    "SMSG_QUEST_QUERY_RESPONSE",
+                    query_response(build, QUEST, DETAILS),
+                ),
+                (
+                    "SMSG_QUESTGIVER_QUEST_DETAILS",
+                    details(build, NPC, QUEST, "Kobold Camp Cleanup"),
+                ),
+                (
+                    "SMSG_QUESTGIVER_REQUEST_ITEMS",
+                    request_items(build, 6, false),
+                ),
+                (
+                    "SMSG_QUESTGIVER_OFFER_REWARD",
+                    offer_reward(build, "Thanks"),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn trinitycore_tables() {
+        let sql = extract_quests(&full_capture(12340), SqlFlavor::TrinityCore)
+            .unwrap()
+            .sql;
+        assert!(sql.starts_with("DELETE FROM `quest_template` WHERE `ID` IN (7);\n"));
+        assert!(sql.contains(
+            "(7, 2, 5, 4, 12, 0, 0, 0, 0, 0, 0, 15, 5, 250, 60, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, \
+             2589, 0, 0, 0, 1, 0, 0, 0, "
+        ));
+        assert!(sql.contains(
+            ", 'Kobold Camp Cleanup', 'Kill 10 Kobold Vermin.', \
+             'Kobolds have been seen near the mine.', '', 'Return to Marshal McBride.', \
+             -1617, 6, 0, 0, 1, 10, 0, 0, 750, 0, 0, 0, 8, 0, 0, 0, 1307, "
+        ));
+        assert!(sql.contains(
+            "INSERT INTO `quest_details` \
+             (`ID`, `Emote1`, `Emote2`, `Emote3`, `Emote4`, `EmoteDelay1`, "
+        ));
+        assert!(sql.contains("(7, 1, 6, 0, 0, 0, 500, 0, 0, 12340);"));
+        assert!(sql.contains("(7, 0, 6, 'Have you dealt with the kobolds?', 12340);"));
+        assert!(sql.contains("(7, 4, 0, 0, 0, 100, 0, 0, 0, 'Thanks', 12340);"));
+    }
+
+    #[test]
+    fn cmangos_template_and_updates() {
+        let sql = extract_quests(&full_capture(5875), SqlFlavor::CMaNGOS)
+            .unwrap()
+            .sql;
+        assert!(sql.starts_with("DELETE FROM `quest_template` WHERE `entry` IN (7);\n"));
+        assert!(sql.contains(
+            "(7, 2, 12, 5, 0, 0, 0, 15, 250, 60, 0, 0, 0, 8, 'Kobold Camp Cleanup', \
+             'Kobolds have been seen near the mine.', "
+        ));
+        assert!(!sql.contains("SuggestedPlayers") && !sql.contains("ReqSourceId"));
+        assert!(sql.contains(
+            "UPDATE `quest_template` SET `DetailsEmote1` = 1, `DetailsEmoteDelay1` = 0, \
+             `DetailsEmote2` = 6, `DetailsEmoteDelay2` = 500, "
+        ));
+        assert!(sql.contains(
+            "`OfferRewardText` = 'Thanks', \
+             `RequestItemsText` = 'Have you dealt with the kobolds?', \
+             `IncompleteEmote` = 6 WHERE `entry` = 7;\n"
+        ));
+    }
+}
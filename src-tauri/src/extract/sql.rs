@@ -0,0 +1,138 @@
+//! Helpers for writing emulator SQL.
+
+use serde::{Deserialize, Serialize};
+
+/// Target emulator schema. Table names mostly match; column sets differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SqlFlavor {
+    #[serde(rename = "trinitycore")]
+    TrinityCore,
+    #[serde(rename = "cmangos")]
+    CMaNGOS,
+}
+
+/// One `VALUES` tuple plus an optional trailing comment.
+pub struct SqlRow {
+    pub values: Vec<String>,
+    pub comment: Option<String>,
+}
+
+/// Quote a string literal for MySQL.
+pub fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('\'');
+    for c in text.chars() {
+        match c {
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// Format a float so that MySQL reads back the same value. Non-finite
+/// values, which only show up in corrupt packets, become 0.
+pub fn float(value: f32) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "0".to_string()
+    }
+}
+
+/// A multi-row `INSERT` with one tuple per line.
+pub fn insert<S: AsRef<str>>(table: &str, columns: &[S], rows: &[SqlRow]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let mut out = insert_header(table, columns);
+    push_tuples(&mut out, rows, ";");
+    out
+}
+
+/// Like `insert`, but rows whose key (the first column) already exists only
+/// have the given columns overwritten. Used for templates, where a capture
+/// fills some columns and the rest of an existing row must survive.
+pub fn upsert<S: AsRef<str>>(table: &str, columns: &[S], rows: &[SqlRow]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let mut out = insert_header(table, columns);
+    push_tuples(&mut out, rows, "");
+    let updates: Vec<String> = columns
+        .iter()
+        .skip(1)
+        .map(|c| format!("`{0}`=VALUES(`{0}`)", c.as_ref()))
+        .collect();
+    out.push_str(&format!(
+        "ON DUPLICATE KEY UPDATE {};\n",
+        updates.join(", ")
+    ));
+    out
+}
+
+fn insert_header<S: AsRef<str>>(table: &str, columns: &[S]) -> String {
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|c| format!("`{}`", c.as_ref()))
+        .collect();
+    format!("INSERT INTO `{}` ({}) VALUES\n", table, columns.join(", "))
+}
+
+fn push_tuples(out: &mut String, rows: &[SqlRow], last: &str) {
+    for (i, row) in rows.iter().enumerate() {
+        let end = if i + 1 == rows.len() { last } else { "," };
+        out.push_str(&format!("({}){}", row.values.join(", "), end));
+        if let Some(comment) = &row.comment {
+            // Comments hold server-sent names; a line break would end the
+            // comment and run the rest as SQL.
+            let comment: String = comment
+                .chars()
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .collect();
+            out.push_str(&format!(" -- {}", comment));
+        }
+        out.push('\n');
+    }
+}
+
+/// `DELETE` matching the `@VAR+0 .. @VAR+n-1` range used by `insert` rows
+/// keyed off a user-set starting GUID.
+pub fn delete_range(table: &str, column: &str, var: &str, count: usize) -> String {
+    if count == 0 {
+        return String::new();
+    }
+    format!(
+        "DELETE FROM `{}` WHERE `{}` BETWEEN @{}+0 AND @{}+{};\n",
+        table,
+        column,
+        var,
+        var,
+        count - 1
+    )
+}
+
+/// `DELETE` for a set of keys, e.g. template entries.
+pub fn delete_in(table: &str, column: &str, keys: &[u32]) -> String {
+    if keys.is_empty() {
+        return String::new();
+    }
+    let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+    format!(
+        "DELETE FROM `{}` WHERE `{}` IN ({});\n",
+        table,
+        column,
+        keys.join(", ")
+    )
+}
+
+/// `name1`, `name2`, ... `nameN`, for the numbered columns emulator
+/// schemas use instead of child tables.
+pub fn numbered(name: &str, count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("{}{}", name, i)).collect()
+}
@@ -0,0 +1,947 @@
+//! Creature, item and gameobject templates from query responses, plus a diff
+//! against rows the user already has in their database.
+//!
+//! Query responses only carry part of each template (no levels, factions or
+//! loot), so the SQL updates the captured columns of existing rows instead of
+//! replacing them.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{decode, diff_sightings, FieldConflict, SqlExport};
+use crate::parser;
+use crate::parser::templates::{self, CreatureTemplate, GameObjectTemplate, ItemTemplate};
+use crate::state::{Direction, Packet, Session};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemplateKind {
+    #[serde(rename = "creature")]
+    Creature,
+    #[serde(rename = "item")]
+    Item,
+    #[serde(rename = "gameobject")]
+    GameObject,
+}
+
+impl TemplateKind {
+    fn opcode(self) -> &'static str {
+        match self {
+            TemplateKind::Creature => "SMSG_CREATURE_QUERY_RESPONSE",
+            TemplateKind::Item => "SMSG_ITEM_QUERY_SINGLE_RESPONSE",
+            TemplateKind::GameObject => "SMSG_GAMEOBJECT_QUERY_RESPONSE",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            TemplateKind::Creature => "creature_template",
+            TemplateKind::Item => "item_template",
+            TemplateKind::GameObject => "gameobject_template",
+        }
+    }
+
+    fn read(self, packet: &Packet, build: u32) -> Option<Template> {
+        match self {
+            TemplateKind::Creature => decode(packet, |r| {
+                templates::read_creature_query_response(r, build)
+            })
+            .flatten()
+            .map(Template::Creature),
+            TemplateKind::Item => decode(packet, |r| templates::read_item_query_response(r, build))
+                .flatten()
+                .map(|t| Template::Item(Box::new(t))),
+            TemplateKind::GameObject => decode(packet, |r| {
+                templates::read_gameobject_query_response(r, build)
+            })
+            .flatten()
+            .map(Template::GameObject),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Template {
+    Creature(CreatureTemplate),
+    /// Boxed; item templates are several times larger than the others.
+    Item(Box<ItemTemplate>),
+    GameObject(GameObjectTemplate),
+}
+
+impl Template {
+    fn entry(&self) -> u32 {
+        match self {
+            Template::Creature(t) => t.entry,
+            Template::Item(t) => t.entry,
+            Template::GameObject(t) => t.entry,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Template::Creature(t) => &t.name,
+            Template::Item(t) => &t.name,
+            Template::GameObject(t) => &t.name,
+        }
+    }
+
+    fn quest_items(&self) -> &[u32] {
+        match self {
+            Template::Creature(t) => &t.quest_items,
+            Template::Item(_) => &[],
+            Template::GameObject(t) => &t.quest_items,
+        }
+    }
+}
+
+/// One template per entry. The first response wins; later responses for the
+/// same entry only contribute conflicts.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateRecord {
+    pub entry: u32,
+    pub template: Template,
+    pub first_seen_packet: usize,
+    pub sightings: usize,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+/// Collect one record per entry, in entry order.
+pub fn template_records(session: &Session, kind: TemplateKind, build: u32) -> Vec<TemplateRecord> {
+    let mut records: BTreeMap<u32, TemplateRecord> = BTreeMap::new();
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient
+            || parser::opcode_name(Some(build), packet) != kind.opcode()
+        {
+            continue;
+        }
+        let Some(template) = kind.read(packet, build) else {
+            continue;
+        };
+        match records.get_mut(&template.entry()) {
+            Some(record) => {
+                record.sightings += 1;
+                diff_sightings(
+                    kind.table(),
+                    &record.template,
+                    &template,
+                    &[],
+                    packet.id,
+                    &mut record.conflicts,
+                );
+            }
+            None => {
+                records.insert(
+                    template.entry(),
+                    TemplateRecord {
+                        entry: template.entry(),
+                        template,
+                        first_seen_packet: packet.id,
+                        sightings: 1,
+                        conflicts: Vec::new(),
+                    },
+                );
+            }
+        }
+    }
+    records.into_values().collect()
+}
+
+/// A single column value, kept typed so it can be compared against rows
+/// loaded from CSV or JSON as well as written as SQL.
+#[derive(Debug, Clone)]
+enum Column {
+    Int(i64),
+    Float(f32),
+    Text(String),
+}
+
+impl From<u32> for Column {
+    fn from(value: u32) -> Self {
+        Column::Int(value.into())
+    }
+}
+
+impl From<i32> for Column {
+    fn from(value: i32) -> Self {
+        Column::Int(value.into())
+    }
+}
+
+impl From<bool> for Column {
+    fn from(value: bool) -> Self {
+        Column::Int(value.into())
+    }
+}
+
+impl From<f32> for Column {
+    fn from(value: f32) -> Self {
+        Column::Float(value)
+    }
+}
+
+impl From<&String> for Column {
+    fn from(value: &String) -> Self {
+        Column::Text(value.clone())
+    }
+}
+
+impl Column {
+    fn sql(&self) -> String {
+        match self {
+            Column::Text(text) => sql::quote(text),
+            other => other.display(),
+        }
+    }
+
+    fn display(&self) -> String {
+        match self {
+            Column::Int(value) => value.to_string(),
+            Column::Float(value) => sql::float(*value),
+            Column::Text(text) => text.clone(),
+        }
+    }
+
+    /// Numbers compare by value so `1`, `1.0` and `"1"` all match; floats
+    /// allow for the rounding of a decimal round-trip.
+    fn matches(&self, existing: &str) -> bool {
+        match self {
+            Column::Text(text) => text == existing,
+            Column::Int(value) => existing
+                .trim()
+                .parse::<f64>()
+                .is_ok_and(|e| e == *value as f64),
+            Column::Float(value) => existing.trim().parse::<f64>().is_ok_and(|e| {
+                let value = f64::from(*value);
+                (e - value).abs() <= 1e-4 * value.abs().max(1.0)
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Columns(Vec<(String, Column)>);
+
+impl Columns {
+    fn push(&mut self, name: impl Into<String>, value: impl Into<Column>) {
+        self.0.push((name.into(), value.into()));
+    }
+}
+
+fn creature_columns(t: &CreatureTemplate, flavor: SqlFlavor, build: u32) -> Columns {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+    let tc = flavor == SqlFlavor::TrinityCore;
+    let mut c = Columns::default();
+
+    c.push(if tc { "entry" } else { "Entry" }, t.entry);
+    for (i, credit) in t.kill_credits.iter().enumerate() {
+        c.push(format!("KillCredit{}", i + 1), *credit);
+    }
+    for (i, display_id) in t.display_ids.iter().enumerate() {
+        let name = if tc { "modelid" } else { "ModelId" };
+        c.push(format!("{}{}", name, i + 1), *display_id);
+    }
+    c.push(if tc { "name" } else { "Name" }, &t.name);
+    c.push(if tc { "subname" } else { "SubName" }, &t.subname);
+    if tbc {
+        c.push("IconName", &t.icon_name);
+    }
+    if tc {
+        c.push("type_flags", t.type_flags);
+        c.push("type", t.creature_type);
+        c.push("family", t.family);
+        c.push("rank", t.rank);
+    } else {
+        c.push("CreatureTypeFlags", t.type_flags);
+        c.push("CreatureType", t.creature_type);
+        c.push("Family", t.family);
+        c.push("Rank", t.rank);
+    }
+    if !wotlk {
+        c.push("PetSpellDataId", t.pet_spell_data_id);
+    }
+    if tbc {
+        if tc {
+            c.push("HealthModifier", t.health_multiplier);
+            c.push("ManaModifier", t.power_multiplier);
+        } else {
+            c.push("HealthMultiplier", t.health_multiplier);
+            c.push("PowerMultiplier", t.power_multiplier);
+        }
+    }
+    c.push("RacialLeader", t.racial_leader);
+    if wotlk && tc {
+        c.push("movementId", t.movement_id);
+    }
+    c
+}
+
+fn item_columns(t: &ItemTemplate, flavor: SqlFlavor, build: u32) -> Columns {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+    let tc = flavor == SqlFlavor::TrinityCore;
+    let mut c = Columns::default();
+
+    c.push("entry", t.entry);
+    c.push("class", t.class);
+    c.push("subclass", t.subclass);
+    if tbc && tc {
+        c.push("SoundOverrideSubclass", t.sound_override_subclass);
+    }
+    c.push("name", &t.name);
+    c.push("displayid", t.display_id);
+    c.push("Quality", t.quality);
+    c.push("Flags", t.flags);
+    if wotlk {
+        c.push(if tc { "FlagsExtra" } else { "Flags2" }, t.flags2);
+    }
+    c.push("BuyPrice", t.buy_price);
+    c.push("SellPrice", t.sell_price);
+    c.push("InventoryType", t.inventory_type);
+    c.push("AllowableClass", t.allowable_class);
+    c.push("AllowableRace", t.allowable_race);
+    c.push("ItemLevel", t.item_level);
+    c.push("RequiredLevel", t.required_level);
+    c.push("RequiredSkill", t.required_skill);
+    c.push("RequiredSkillRank", t.required_skill_rank);
+    c.push("requiredspell", t.required_spell);
+    c.push("requiredhonorrank", t.required_honor_rank);
+    c.push("RequiredCityRank", t.required_city_rank);
+    c.push("RequiredReputationFaction", t.required_reputation_faction);
+    c.push("RequiredReputationRank", t.required_reputation_rank);
+    c.push("maxcount", t.max_count);
+    c.push("stackable", t.stackable);
+    c.push("ContainerSlots", t.container_slots);
+    if wotlk {
+        c.push("StatsCount", t.stats.len() as u32);
+    }
+    // The schema always has ten stat slots; WotLK only sends the used ones.
+    for i in 0..10 {
+        let (stat_type, value) = t.stats.get(i).map_or((0, 0), |s| (s.stat_type, s.value));
+        c.push(format!("stat_type{}", i + 1), stat_type);
+        c.push(format!("stat_value{}", i + 1), value);
+    }
+    if wotlk {
+        c.push("ScalingStatDistribution", t.scaling_stat_distribution);
+        c.push("ScalingStatValue", t.scaling_stat_value);
+    }
+    for (i, damage) in t.damages.iter().enumerate() {
+        c.push(format!("dmg_min{}", i + 1), damage.min);
+        c.push(format!("dmg_max{}", i + 1), damage.max);
+        c.push(format!("dmg_type{}", i + 1), damage.school);
+    }
+    c.push("armor", t.armor);
+    let schools = ["holy", "fire", "nature", "frost", "shadow", "arcane"];
+    for (school, value) in schools.iter().zip(&t.resistances) {
+        c.push(format!("{}_res", school), *value);
+    }
+    c.push("delay", t.delay);
+    c.push("ammo_type", t.ammo_type);
+    c.push("RangedModRange", t.ranged_mod_range);
+    for (i, spell) in t.spells.iter().enumerate() {
+        let n = i + 1;
+        c.push(format!("spellid_{}", n), spell.spell);
+        c.push(format!("spelltrigger_{}", n), spell.trigger);
+        c.push(format!("spellcharges_{}", n), spell.charges);
+        c.push(format!("spellcooldown_{}", n), spell.cooldown);
+        c.push(format!("spellcategory_{}", n), spell.category);
+        c.push(
+            format!("spellcategorycooldown_{}", n),
+            spell.category_cooldown,
+        );
+    }
+    c.push("bonding", t.bonding);
+    c.push("description", &t.description);
+    c.push("PageText", t.page_text);
+    c.push("LanguageID", t.language);
+    c.push("PageMaterial", t.page_material);
+    c.push("startquest", t.start_quest);
+    c.push("lockid", t.lock_id);
+    c.push("Material", t.material);
+    c.push("sheath", t.sheath);
+    c.push("RandomProperty", t.random_property);
+    if tbc {
+        c.push("RandomSuffix", t.random_suffix);
+    }
+    c.push("block", t.block);
+    c.push("itemset", t.item_set);
+    c.push("MaxDurability", t.max_durability);
+    c.push("area", t.area);
+    c.push("Map", t.map);
+    c.push("BagFamily", t.bag_family);
+    if tbc {
+        c.push("TotemCategory", t.totem_category);
+        for (i, socket) in t.sockets.iter().enumerate() {
+            c.push(format!("socketColor_{}", i + 1), socket.color);
+            c.push(format!("socketContent_{}", i + 1), socket.content);
+        }
+        c.push("socketBonus", t.socket_bonus);
+        c.push("GemProperties", t.gem_properties);
+        c.push("RequiredDisenchantSkill", t.required_disenchant_skill);
+        c.push("ArmorDamageModifier", t.armor_damage_modifier);
+        c.push(if tc { "duration" } else { "Duration" }, t.duration);
+    }
+    if wotlk {
+        c.push("ItemLimitCategory", t.item_limit_category);
+        c.push("HolidayId", t.holiday_id);
+    }
+    c
+}
+
+fn gameobject_columns(t: &GameObjectTemplate, flavor: SqlFlavor, build: u32) -> Columns {
+    let tbc = build >= 8606;
+    let tc = flavor == SqlFlavor::TrinityCore;
+    let mut c = Columns::default();
+
+    c.push("entry", t.entry);
+    c.push("type", t.go_type);
+    c.push("displayId", t.display_id);
+    c.push("name", &t.name);
+    if tbc {
+        c.push("IconName", &t.icon_name);
+        c.push("castBarCaption", &t.cast_bar_caption);
+        c.push("unk1", &t.unk1);
+        c.push("size", t.size);
+    }
+    for (i, value) in t.data.iter().enumerate() {
+        let name = if tc { "Data" } else { "data" };
+        c.push(format!("{}{}", name, i), *value);
+    }
+    c
+}
+
+fn columns(template: &Template, flavor: SqlFlavor, build: u32) -> Columns {
+    let mut c = match template {
+        Template::Creature(t) => creature_columns(t, flavor, build),
+        Template::Item(t) => item_columns(t, flavor, build),
+        Template::GameObject(t) => gameobject_columns(t, flavor, build),
+    };
+    if flavor == SqlFlavor::TrinityCore {
+        c.push("VerifiedBuild", build);
+    }
+    c
+}
+
+/// TrinityCore keeps WotLK quest drop items in a child table.
+fn trinity_quest_items(records: &[&TemplateRecord], kind: TemplateKind, build: u32) -> String {
+    let (table, key) = match kind {
+        TemplateKind::Creature => ("creature_questitem", "CreatureEntry"),
+        TemplateKind::GameObject => ("gameobject_questitem", "GameObjectEntry"),
+        TemplateKind::Item => return String::new(),
+    };
+    let mut entries = Vec::new();
+    let mut rows = Vec::new();
+    for record in records {
+        let items = record.template.quest_items();
+        if items.iter().all(|&item| item == 0) {
+            continue;
+        }
+        entries.push(record.entry);
+        for (idx, item) in items.iter().filter(|&&item| item != 0).enumerate() {
+            rows.push(SqlRow {
+                values: vec![
+                    record.entry.to_string(),
+                    idx.to_string(),
+                    item.to_string(),
+                    build.to_string(),
+                ],
+                comment: None,
+            });
+        }
+    }
+    let mut out = sql::delete_in(table, key, &entries);
+    out.push_str(&sql::insert(
+        table,
+        &[key, "Idx", "ItemId", "VerifiedBuild"],
+        &rows,
+    ));
+    out
+}
+
+fn records_sql(
+    records: &[&TemplateRecord],
+    kind: TemplateKind,
+    flavor: SqlFlavor,
+    build: u32,
+) -> String {
+    let mut names: Vec<String> = Vec::new();
+    let rows: Vec<SqlRow> = records
+        .iter()
+        .map(|record| {
+            let Columns(columns) = columns(&record.template, flavor, build);
+            if names.is_empty() {
+                names = columns.iter().map(|(name, _)| name.clone()).collect();
+            }
+            SqlRow {
+                values: columns.iter().map(|(_, value)| value.sql()).collect(),
+                comment: Some(record.template.name().to_string()),
+            }
+        })
+        .collect();
+    let mut out = sql::upsert(kind.table(), &names, &rows);
+    if flavor == SqlFlavor::TrinityCore {
+        out.push_str(&trinity_quest_items(records, kind, build));
+    }
+    out
+}
+
+pub fn template_sql(
+    records: &[TemplateRecord],
+    kind: TemplateKind,
+    flavor: SqlFlavor,
+    build: u32,
+) -> String {
+    let records: Vec<&TemplateRecord> = records.iter().collect();
+    records_sql(&records, kind, flavor, build)
+}
+
+pub fn extract_templates(
+    session: &Session,
+    kind: TemplateKind,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<TemplateRecord>, String> {
+    let build = super::session_build(session)?;
+    let rows = template_records(session, kind, build);
+    let sql = template_sql(&rows, kind, flavor, build);
+    Ok(SqlExport { rows, sql })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnDiff {
+    pub column: String,
+    pub existing: String,
+    pub captured: String,
+}
+
+/// A captured template that is absent from, or disagrees with, the
+/// existing rows.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateDiff {
+    pub entry: u32,
+    pub name: String,
+    pub missing: bool,
+    pub columns: Vec<ColumnDiff>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateDiffReport {
+    pub diffs: Vec<TemplateDiff>,
+    /// Captured entries whose existing row matched in every shared column.
+    pub unchanged: usize,
+    /// SQL for the missing and differing entries only.
+    pub sql: String,
+}
+
+/// Existing rows keyed by entry; column names are lowercased and NULL
+/// values left out, so they never count as a difference.
+type ExistingRows = HashMap<u32, HashMap<String, String>>;
+
+/// Compare the session's templates against an export of the user's table.
+/// Only columns present in the export are compared, so a partial export
+/// (just `entry` and `name`, say) works too.
+pub fn diff_templates(
+    session: &Session,
+    kind: TemplateKind,
+    flavor: SqlFlavor,
+    existing: &Path,
+) -> Result<TemplateDiffReport, String> {
+    let build = super::session_build(session)?;
+    let existing = load_existing(existing)?;
+    let records = template_records(session, kind, build);
+
+    let mut diffs = Vec::new();
+    let mut changed = Vec::new();
+    for record in &records {
+        let name = record.template.name().to_string();
+        let Some(row) = existing.get(&record.entry) else {
+            diffs.push(TemplateDiff {
+                entry: record.entry,
+                name,
+                missing: true,
+                columns: Vec::new(),
+            });
+            changed.push(record);
+            continue;
+        };
+        let Columns(captured) = columns(&record.template, flavor, build);
+        let columns: Vec<ColumnDiff> = captured
+            .iter()
+            .skip(1)
+            .filter(|(column, _)| column != "VerifiedBuild")
+            .filter_map(|(column, value)| {
+                let existing = row.get(&column.to_lowercase())?;
+                (!value.matches(existing)).then(|| ColumnDiff {
+                    column: column.clone(),
+                    existing: existing.clone(),
+                    captured: value.display(),
+                })
+            })
+            .collect();
+        if !columns.is_empty() {
+            diffs.push(TemplateDiff {
+                entry: record.entry,
+                name,
+                missing: false,
+                columns,
+            });
+            changed.push(record);
+        }
+    }
+
+    Ok(TemplateDiffReport {
+        unchanged: records.len() - changed.len(),
+        sql: records_sql(&changed, kind, flavor, build),
+        diffs,
+    })
+}
+
+/// Load a CSV (header row first) or JSON (an array of row objects, or an
+/// object holding one) export of an existing template table.
+fn load_existing(path: &Path) -> Result<ExistingRows, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let text = text.trim_start_matches('\u{feff}');
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let rows = match extension.as_deref() {
+        Some("csv") => csv_rows(text)?,
+        Some("json") => json_rows(text)?,
+        _ => {
+            return Err(format!(
+                "{} is neither a .csv nor a .json file",
+                path.display()
+            ))
+        }
+    };
+
+    let mut existing = ExistingRows::new();
+    for (i, row) in rows.into_iter().enumerate() {
+        let entry = row
+            .get("entry")
+            .and_then(|e| e.trim().parse::<u32>().ok())
+            .ok_or_else(|| format!("Row {} has no numeric entry column", i + 1))?;
+        existing.insert(entry, row);
+    }
+    Ok(existing)
+}
+
+fn csv_rows(text: &str) -> Result<Vec<HashMap<String, String>>, String> {
+    let mut records = parse_csv(text)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+    Ok(records
+        .map(|record| {
+            header
+                .iter()
+                .zip(record)
+                .filter(|(_, value)| value != "NULL" && value != "\\N")
+                .map(|(column, value)| (column.clone(), value))
+                .collect()
+        })
+        .collect())
+}
+
+/// RFC 4180 CSV: comma separated, `"` quoting with `""` escapes, quoted
+/// fields may span lines. Blank lines are skipped.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field in CSV".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn json_rows(text: &str) -> Result<Vec<HashMap<String, String>>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    // MySQL Workbench wraps the rows in an object keyed by table name.
+    let rows = match value {
+        Value::Array(rows) => rows,
+        Value::Object(map) => match map.into_iter().find_map(|(_, v)| match v {
+            Value::Array(rows) => Some(rows),
+            _ => None,
+        }) {
+            Some(rows) => rows,
+            None => return Err("JSON object holds no array of rows".to_string()),
+        },
+        _ => return Err("JSON must be an array of rows".to_string()),
+    };
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let Value::Object(row) = row else {
+                return Err(format!("Row {} is not an object", i + 1));
+            };
+            Ok(row
+                .into_iter()
+                .filter_map(|(column, value)| {
+                    let value = match value {
+                        Value::Null => return None,
+                        Value::String(s) => s,
+                        Value::Bool(b) => u8::from(b).to_string(),
+                        other => other.to_string(),
+                    };
+                    Some((column.to_lowercase(), value))
+                })
+                .collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::session;
+    use crate::parser::templates::tests::{
+        creature_response, gameobject_response, item_response, not_found, BUILDS,
+    };
+    use std::path::PathBuf;
+
+    /// Write an export of the user's table for `diff_templates` to load.
+    fn export(name: &str, text: &str) -> PathBuf {
+        let file = format!("template-diff-{}-{}", std::process::id(), name);
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn creatures(build: u32, second_name: &str) -> Session {
+        session(
+            build,
+            vec![
+                (
+                    "SMSG_CREATURE_QUERY_RESPONSE",
+                    creature_response(build, 2044, "Forlorn Spirit"),
+                ),
+                ("SMSG_CREATURE_QUERY_RESPONSE", not_found(9999)),
+                (
+                    "SMSG_CREATURE_QUERY_RESPONSE",
+                    creature_response(build, 1, "Waypoint"),
+                ),
+                (
+                    "SMSG_CREATURE_QUERY_RESPONSE",
+                    creature_response(build, 2044, second_name),
+                ),
+            ],
+        )
+    }
+
+    fn diff(build: u32, name: &str, text: &str) -> Result<TemplateDiffReport, String> {
+        let path = export(name, text);
+        let report = diff_templates(
+            &creatures(build, "Forlorn Spirit"),
+            TemplateKind::Creature,
+            SqlFlavor::TrinityCore,
+            &path,
+        );
+        std::fs::remove_file(path).unwrap();
+        report
+    }
+
+    fn rows(rows: &[(&str, &str)]) -> HashMap<String, String> {
+        rows.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn csv_handles_quotes_and_line_breaks() {
+        let text = "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",x,\r\n\r\n3";
+        assert_eq!(
+            parse_csv(text).unwrap(),
+            [
+                vec!["a", "b,c", "say \"hi\""],
+                vec!["two\nlines", "x", ""],
+                vec!["3"],
+            ]
+        );
+        assert_eq!(
+            parse_csv("a,\"b\n").unwrap_err(),
+            "Unterminated quoted field in CSV"
+        );
+    }
+
+    #[test]
+    fn csv_rows_leave_out_nulls() {
+        let text = "Entry , Name,SubName\n2044,NULL,\\N\n1,\"Waypoint\",\n";
+        assert_eq!(
+            csv_rows(text).unwrap(),
+            [
+                rows(&[("entry", "2044")]),
+                rows(&[("entry", "1"), ("name", "Waypoint"), ("subname", "")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_rows_accept_workbench_exports() {
+        let row = rows(&[
+            ("entry", "2044"),
+            ("name", "Forlorn Spirit"),
+            ("racialleader", "0"),
+        ]);
+        let array = r#"[
+            {"entry": 2044, "Name": "Forlorn Spirit", "RacialLeader": false, "subname": null}
+        ]"#;
+        assert_eq!(json_rows(array).unwrap(), std::slice::from_ref(&row));
+        let wrapped = format!(r#"{{"creature_template": {}}}"#, array);
+        assert_eq!(json_rows(&wrapped).unwrap(), [row]);
+        assert!(json_rows(r#"{"entry": 1}"#).is_err());
+        assert!(json_rows("[1]").is_err());
+        assert!(json_rows("[").is_err());
+    }
+
+    #[test]
+    fn keeps_first_response_per_entry() {
+        for build in BUILDS {
+            let records = template_records(
+                &creatures(build, "Forlorn Spirit"),
+                TemplateKind::Creature,
+                build,
+            );
+            let entries: Vec<(u32, usize)> =
+                records.iter().map(|r| (r.entry, r.sightings)).collect();
+            assert_eq!(entries, [(1, 1), (2044, 2)], "build {}", build);
+            assert!(records.iter().all(|r| r.conflicts.is_empty()));
+        }
+        let records = template_records(
+            &creatures(5875, "Forlorn Ghost"),
+            TemplateKind::Creature,
+            5875,
+        );
+        let conflict = &records[1].conflicts[0];
+        assert_eq!(
+            (conflict.source.as_str(), conflict.field.as_str()),
+            ("creature_template", "name")
+        );
+        assert_eq!(
+            (conflict.other.as_str(), conflict.packet_id),
+            (Some("Forlorn Ghost"), 3)
+        );
+    }
+
+    #[test]
+    fn partial_exports_only_compare_their_columns() {
+        let report = diff(
+            5875,
+            "partial.csv",
+            "\u{feff}entry,name\r\n2044,Forlorn Spirit\r\n",
+        )
+        .unwrap();
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.diffs.len(), 1);
+        assert_eq!((report.diffs[0].entry, report.diffs[0].missing), (1, true));
+        assert!(report.sql.contains("(1, 11412, 'Waypoint', "));
+        assert!(!report.sql.contains("2044"));
+    }
+
+    #[test]
+    fn reports_differing_columns() {
+        let text = r#"[
+            {"entry": 2044, "name": "Forlorn Spirit", "rank": "1", "subname": null,
+             "HealthModifier": 1.50001},
+            {"entry": 1, "name": "Waypoint", "type_flags": 1.0}
+        ]"#;
+        let report = diff(8606, "differs.json", text).unwrap();
+        assert_eq!(report.unchanged, 1);
+        let diff = &report.diffs[0];
+        assert_eq!((diff.entry, diff.missing), (2044, false));
+        let columns: Vec<(&str, &str, &str)> = diff
+            .columns
+            .iter()
+            .map(|c| (c.column.as_str(), c.existing.as_str(), c.captured.as_str()))
+            .collect();
+        assert_eq!(columns, [("rank", "1", "2")]);
+        assert!(report
+            .sql
+            .starts_with("INSERT INTO `creature_template` (`entry`, `modelid1`, "));
+        assert!(report
+            .sql
+            .contains("ON DUPLICATE KEY UPDATE `modelid1`=VALUES(`modelid1`), "));
+    }
+
+    #[test]
+    fn rejects_unusable_exports() {
+        assert!(diff(5875, "rows.txt", "entry\n1\n")
+            .unwrap_err()
+            .contains("neither a .csv nor a .json"));
+        assert_eq!(
+            diff(5875, "noentry.csv", "name\nWaypoint\n").unwrap_err(),
+            "Row 1 has no numeric entry column"
+        );
+    }
+
+    #[test]
+    fn writes_quest_items_for_trinitycore() {
+        for build in BUILDS {
+            let capture = session(
+                build,
+                vec![
+                    (
+                        "SMSG_GAMEOBJECT_QUERY_RESPONSE",
+                        gameobject_response(build, 2843, "Battered Chest"),
+                    ),
+                    (
+                        "SMSG_ITEM_QUERY_SINGLE_RESPONSE",
+                        item_response(build, 2489, "Two-Handed Sword"),
+                    ),
+                ],
+            );
+            let gameobjects =
+                extract_templates(&capture, TemplateKind::GameObject, SqlFlavor::TrinityCore)
+                    .unwrap();
+            let quest_items = "INSERT INTO `gameobject_questitem` \
+                (`GameObjectEntry`, `Idx`, `ItemId`, `VerifiedBuild`) VALUES\n\
+                (2843, 0, 3898, 12340);\n";
+            assert_eq!(gameobjects.sql.contains(quest_items), build >= 12340);
+            let items =
+                extract_templates(&capture, TemplateKind::Item, SqlFlavor::CMaNGOS).unwrap();
+            assert_eq!(items.rows[0].entry, 2489);
+            assert!(items.sql.contains(
+                ") -- Two-Handed Sword\nON DUPLICATE KEY UPDATE `class`=VALUES(`class`), "
+            ));
+            assert!(!items.sql.contains("VerifiedBuild"));
+        }
+    }
+}
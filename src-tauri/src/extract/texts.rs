@@ -0,0 +1,549 @@
+//! Creature yells, says and emotes from `SMSG_MESSAGECHAT`, `SMSG_EMOTE`
+//! and `SMSG_TEXT_EMOTE`, timed from the speaker's entering combat.
+//!
+//! Combat start is when the update data first sets `UNIT_FLAG_IN_COMBAT` on
+//! the speaker. Lines said out of combat, or by a creature that was already
+//! fighting when it came into view, have no offset.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{decode, for_each_update_block, ObjectEntries, SqlExport};
+use crate::parser;
+use crate::parser::chat::{self, MonsterChat};
+use crate::parser::guid::{high_guid, HighGuid};
+use crate::parser::templates;
+use crate::parser::update_fields::unit_fields;
+use crate::parser::update_object::UpdateBlock;
+use crate::state::{Direction, Packet, Session};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+const UNIT_FLAG_IN_COMBAT: u32 = 0x0008_0000;
+
+/// An `SMSG_EMOTE` this soon after a line from the same creature is the
+/// line's emote rather than one of its own.
+const EMOTE_WINDOW_MS: u32 = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatureTextLine {
+    pub kind: MonsterChat,
+    pub text: String,
+    pub language: u32,
+    /// Emote played with the line, 0 if none.
+    pub emote: u32,
+    pub sightings: usize,
+    /// Milliseconds into combat, one per sighting said in combat.
+    pub combat_offsets_ms: Vec<u32>,
+    pub first_seen_packet: usize,
+}
+
+/// An emote played without a line.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatureEmote {
+    pub emote: u32,
+    /// Came as `SMSG_TEXT_EMOTE`; `emote` is then a text emote id.
+    pub text_emote: bool,
+    pub sightings: usize,
+    pub combat_offsets_ms: Vec<u32>,
+    pub first_seen_packet: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatureTextRecord {
+    pub entry: u32,
+    pub name: String,
+    /// In order of first sighting.
+    pub lines: Vec<CreatureTextLine>,
+    pub emotes: Vec<CreatureEmote>,
+}
+
+/// A stretch of packets during which a unit was flagged in combat.
+struct CombatSpan {
+    /// `None` if the unit was already fighting when it came into view.
+    start: Option<u32>,
+    first_packet: usize,
+    /// Packet that cleared the flag.
+    end_packet: Option<usize>,
+}
+
+#[derive(Default)]
+struct CombatLog(HashMap<u64, Vec<CombatSpan>>);
+
+impl CombatLog {
+    fn collect(session: &Session, build: u32) -> Self {
+        let flags_field = unit_fields(build).flags;
+        let mut log = CombatLog::default();
+        for_each_update_block(session, build, |packet, _, block| {
+            let (guid, fields, created) = match block {
+                UpdateBlock::Create { guid, fields, .. } => (guid, fields, true),
+                UpdateBlock::Values { guid, fields } => (guid, fields, false),
+                _ => return,
+            };
+            let Some(&flags) = fields.get(&flags_field) else {
+                return;
+            };
+            let spans = log.0.entry(*guid).or_default();
+            let open = spans.last().is_some_and(|span| span.end_packet.is_none());
+            if flags & UNIT_FLAG_IN_COMBAT != 0 && !open {
+                spans.push(CombatSpan {
+                    start: (!created).then_some(packet.timestamp),
+                    first_packet: packet.id,
+                    end_packet: None,
+                });
+            } else if flags & UNIT_FLAG_IN_COMBAT == 0 && open {
+                if let Some(span) = spans.last_mut() {
+                    span.end_packet = Some(packet.id);
+                }
+            }
+        });
+        log
+    }
+
+    /// Milliseconds since `guid` entered the combat it is in at `packet`.
+    fn offset(&self, guid: u64, packet: &Packet) -> Option<u32> {
+        let span = self.0.get(&guid)?.iter().rev().find(|span| {
+            span.first_packet <= packet.id && span.end_packet.is_none_or(|end| packet.id < end)
+        })?;
+        span.start.map(|start| packet.timestamp.wrapping_sub(start))
+    }
+}
+
+/// Creature names from query responses, for vanilla messages that name
+/// the speaker without its GUID.
+fn creature_names(session: &Session, build: u32) -> HashMap<String, u32> {
+    let mut names = HashMap::new();
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient
+            || parser::opcode_name(Some(build), packet) != "SMSG_CREATURE_QUERY_RESPONSE"
+        {
+            continue;
+        }
+        if let Some(Some(template)) = decode(packet, |r| {
+            templates::read_creature_query_response(r, build)
+        }) {
+            names.entry(template.name).or_insert(template.entry);
+        }
+    }
+    names
+}
+
+fn is_creature(guid: u64) -> bool {
+    matches!(high_guid(guid), HighGuid::Creature | HighGuid::Vehicle)
+}
+
+/// Group every creature line and emote by entry, in entry order.
+pub fn creature_text_records(session: &Session, build: u32) -> Vec<CreatureTextRecord> {
+    let entries = ObjectEntries::collect(session, build);
+    let combat = CombatLog::collect(session, build);
+    let names = creature_names(session, build);
+    let mut records: BTreeMap<u32, CreatureTextRecord> = BTreeMap::new();
+    // Last line per speaker: (timestamp, entry, line index).
+    let mut last_line: HashMap<u64, (u32, u32, usize)> = HashMap::new();
+
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient {
+            continue;
+        }
+        match parser::opcode_name(Some(build), packet) {
+            "SMSG_MESSAGECHAT" => {
+                let Some(message) = decode(packet, |r| chat::read_message_chat(r, build)) else {
+                    continue;
+                };
+                let Some(kind) = MonsterChat::from_chat_type(build, message.chat_type) else {
+                    continue;
+                };
+                let name = message.sender_name.clone().unwrap_or_default();
+                let entry = if message.sender != 0 {
+                    if !is_creature(message.sender) {
+                        continue;
+                    }
+                    entries.get(message.sender)
+                } else {
+                    names.get(&name).copied()
+                };
+                let Some(entry) = entry else {
+                    continue;
+                };
+                let offset = combat.offset(message.sender, packet);
+                let record = records.entry(entry).or_insert_with(|| CreatureTextRecord {
+                    entry,
+                    name: String::new(),
+                    lines: Vec::new(),
+                    emotes: Vec::new(),
+                });
+                if record.name.is_empty() {
+                    record.name = name;
+                }
+                let index = match record
+                    .lines
+                    .iter()
+                    .position(|l| l.kind == kind && l.text == message.text)
+                {
+                    Some(index) => index,
+                    None => {
+                        record.lines.push(CreatureTextLine {
+                            kind,
+                            text: message.text,
+                            language: message.language,
+                            emote: 0,
+                            sightings: 0,
+                            combat_offsets_ms: Vec::new(),
+                            first_seen_packet: packet.id,
+                        });
+                        record.lines.len() - 1
+                    }
+                };
+                let line = &mut record.lines[index];
+                line.sightings += 1;
+                line.combat_offsets_ms.extend(offset);
+                if message.sender != 0 {
+                    last_line.insert(message.sender, (packet.timestamp, entry, index));
+                }
+            }
+            opcode @ ("SMSG_EMOTE" | "SMSG_TEXT_EMOTE") => {
+                let text_emote = opcode == "SMSG_TEXT_EMOTE";
+                let emote = if text_emote {
+                    decode(packet, chat::read_text_emote).map(|e| (e.guid, e.text_emote))
+                } else {
+                    decode(packet, chat::read_emote).map(|e| (e.guid, e.emote))
+                };
+                let Some((guid, emote)) = emote else {
+                    continue;
+                };
+                if !is_creature(guid) {
+                    continue;
+                }
+                if let Some(&(time, entry, index)) = last_line.get(&guid) {
+                    let line = &mut records.get_mut(&entry).unwrap().lines[index];
+                    if !text_emote
+                        && packet.timestamp.wrapping_sub(time) <= EMOTE_WINDOW_MS
+                        && (line.emote == 0 || line.emote == emote)
+                    {
+                        line.emote = emote;
+                        last_line.remove(&guid);
+                        continue;
+                    }
+                }
+                let Some(entry) = entries.get(guid) else {
+                    continue;
+                };
+                let offset = combat.offset(guid, packet);
+                let record = records.entry(entry).or_insert_with(|| CreatureTextRecord {
+                    entry,
+                    name: String::new(),
+                    lines: Vec::new(),
+                    emotes: Vec::new(),
+                });
+                let seen = record
+                    .emotes
+                    .iter_mut()
+                    .find(|e| e.emote == emote && e.text_emote == text_emote);
+                match seen {
+                    Some(seen) => {
+                        seen.sightings += 1;
+                        seen.combat_offsets_ms.extend(offset);
+                    }
+                    None => record.emotes.push(CreatureEmote {
+                        emote,
+                        text_emote,
+                        sightings: 1,
+                        combat_offsets_ms: offset.into_iter().collect(),
+                        first_seen_packet: packet.id,
+                    }),
+                }
+            }
+            _ => {}
+        }
+    }
+    records.into_values().collect()
+}
+
+/// Chat type column value for the target schema.
+fn text_type(kind: MonsterChat, flavor: SqlFlavor) -> u8 {
+    match flavor {
+        // 3.3.5 ChatMsg values, whatever build the capture came from.
+        SqlFlavor::TrinityCore => match kind {
+            MonsterChat::Say => 12,
+            MonsterChat::Party => 13,
+            MonsterChat::Yell => 14,
+            MonsterChat::Whisper => 15,
+            MonsterChat::Emote => 16,
+            MonsterChat::BossEmote => 41,
+            MonsterChat::BossWhisper => 42,
+        },
+        SqlFlavor::CMaNGOS => match kind {
+            MonsterChat::Say | MonsterChat::Party => 0,
+            MonsterChat::Yell => 1,
+            MonsterChat::Emote => 2,
+            MonsterChat::BossEmote => 3,
+            MonsterChat::Whisper => 4,
+            MonsterChat::BossWhisper => 5,
+        },
+    }
+}
+
+fn line_comment(record: &CreatureTextRecord, line: &CreatureTextLine) -> String {
+    let mut comment = format!("{} - seen {}x", record.name, line.sightings);
+    let offsets = &line.combat_offsets_ms;
+    if let (Some(min), Some(max)) = (offsets.iter().min(), offsets.iter().max()) {
+        comment.push_str(&format!(
+            ", {:.1}-{:.1}s into combat",
+            f64::from(*min) / 1000.0,
+            f64::from(*max) / 1000.0
+        ));
+    }
+    comment
+}
+
+/// TrinityCore gets `creature_text` with one group per line; CMaNGOS gets
+/// `script_texts` numbered down from `@TEXT`, for scripts to reference.
+pub fn creature_text_sql(records: &[CreatureTextRecord], flavor: SqlFlavor) -> String {
+    let mut rows = Vec::new();
+    for record in records {
+        for (group, line) in record.lines.iter().enumerate() {
+            let kind = text_type(line.kind, flavor).to_string();
+            let values = match flavor {
+                SqlFlavor::TrinityCore => vec![
+                    record.entry.to_string(),
+                    group.to_string(),
+                    "0".to_string(),
+                    sql::quote(&line.text),
+                    kind,
+                    line.language.to_string(),
+                    "100".to_string(),
+                    line.emote.to_string(),
+                    "0".to_string(),
+                    "0".to_string(),
+                    "0".to_string(),
+                    "0".to_string(),
+                    sql::quote(&line_comment(record, line)),
+                ],
+                SqlFlavor::CMaNGOS => vec![
+                    format!("@TEXT-{}", rows.len()),
+                    sql::quote(&line.text),
+                    "0".to_string(),
+                    kind,
+                    line.language.to_string(),
+                    line.emote.to_string(),
+                    sql::quote(&line_comment(record, line)),
+                ],
+            };
+            rows.push(SqlRow {
+                values,
+                comment: None,
+            });
+        }
+    }
+
+    match flavor {
+        SqlFlavor::TrinityCore => {
+            let entries: Vec<u32> = records
+                .iter()
+                .filter(|r| !r.lines.is_empty())
+                .map(|r| r.entry)
+                .collect();
+            let mut out = sql::delete_in("creature_text", "CreatureID", &entries);
+            out.push_str(&sql::insert(
+                "creature_text",
+                &[
+                    "CreatureID",
+                    "GroupID",
+                    "ID",
+                    "Text",
+                    "Type",
+                    "Language",
+                    "Probability",
+                    "Emote",
+                    "Duration",
+                    "Sound",
+                    "BroadcastTextId",
+                    "TextRange",
+                    "comment",
+                ],
+                &rows,
+            ));
+            out
+        }
+        SqlFlavor::CMaNGOS => {
+            let mut out = String::from("SET @TEXT := -1000000;\n");
+            if !rows.is_empty() {
+                out.push_str(&format!(
+                    "DELETE FROM `script_texts` WHERE `entry` BETWEEN @TEXT-{} AND @TEXT;\n",
+                    rows.len() - 1
+                ));
+            }
+            out.push_str(&sql::insert(
+                "script_texts",
+                &[
+                    "entry",
+                    "content_default",
+                    "sound",
+                    "type",
+                    "language",
+                    "emote",
+                    "comment",
+                ],
+                &rows,
+            ));
+            out
+        }
+    }
+}
+
+pub fn extract_creature_texts(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<CreatureTextRecord>, String> {
+    let build = super::session_build(session)?;
+    let rows = creature_text_records(session, build);
+    let sql = creature_text_sql(&rows, flavor);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::chat::tests::{emote, monster_chat, text_emote, BUILDS};
+    use crate::parser::fixture::{session, vector, Bytes};
+    use crate::parser::templates::tests::creature_response;
+    use crate::parser::update_object::TYPEID_UNIT;
+
+    /// Creature 1234.
+    const GUID: u64 = 0xF130_0004_D200_0101;
+
+    fn spawn(build: u32) -> Vec<u8> {
+        Bytes::new()
+            .update_header(build, 1)
+            .create_header(GUID, TYPEID_UNIT)
+            .living(build, vector(1.0, 2.0, 3.0), 0.0)
+            .update_mask(&[(unit_fields(build).flags, 0)])
+            .build()
+    }
+
+    fn flags(build: u32, flags: u32) -> Vec<u8> {
+        Bytes::new()
+            .update_header(build, 1)
+            .u8(0)
+            .packed_guid(GUID)
+            .update_mask(&[(unit_fields(build).flags, flags)])
+            .build()
+    }
+
+    /// A greeting with a bow, then combat: a yell a second in and a text
+    /// emote two seconds in. The yell comes again, with an emote, after the
+    /// fight.
+    fn capture(build: u32) -> Session {
+        let say = |kind, text| monster_chat(build, kind, GUID, "Defias Thug", text);
+        session(
+            build,
+            vec![
+                ("SMSG_UPDATE_OBJECT", spawn(build)),
+                ("SMSG_MESSAGECHAT", say(MonsterChat::Say, "Who goes there?")),
+                ("SMSG_EMOTE", emote(GUID, 2)),
+                ("SMSG_UPDATE_OBJECT", flags(build, UNIT_FLAG_IN_COMBAT)),
+                ("SMSG_MESSAGECHAT", say(MonsterChat::Yell, "You'll pay!")),
+                ("SMSG_TEXT_EMOTE", text_emote(GUID, 101)),
+                ("SMSG_UPDATE_OBJECT", flags(build, 0)),
+                ("SMSG_MESSAGECHAT", say(MonsterChat::Yell, "You'll pay!")),
+                ("SMSG_EMOTE", emote(GUID, 5)),
+            ],
+        )
+    }
+
+    #[test]
+    fn groups_lines_and_times_them_from_combat() {
+        for build in BUILDS {
+            let records = creature_text_records(&capture(build), build);
+            assert_eq!(records.len(), 1, "build {}", build);
+            let record = &records[0];
+            assert_eq!((record.entry, record.name.as_str()), (1234, "Defias Thug"));
+
+            let lines: Vec<_> = record
+                .lines
+                .iter()
+                .map(|l| (l.kind, l.text.as_str(), l.emote, l.sightings))
+                .collect();
+            assert_eq!(
+                lines,
+                [
+                    (MonsterChat::Say, "Who goes there?", 2, 1),
+                    (MonsterChat::Yell, "You'll pay!", 5, 2),
+                ]
+            );
+            assert!(record.lines[0].combat_offsets_ms.is_empty());
+            assert_eq!(record.lines[1].combat_offsets_ms, [1000]);
+
+            let emote = &record.emotes[0];
+            assert_eq!((emote.emote, emote.text_emote), (101, true));
+            assert_eq!(emote.combat_offsets_ms, [2000]);
+        }
+    }
+
+    #[test]
+    fn late_emotes_stand_alone() {
+        let build = 12340;
+        let mut session = capture(build);
+        // Two seconds after the greeting.
+        session.packets[2].timestamp = 3000;
+        let records = creature_text_records(&session, build);
+        let record = &records[0];
+        assert_eq!(record.lines[0].emote, 0);
+        let emotes: Vec<_> = record
+            .emotes
+            .iter()
+            .map(|e| (e.emote, e.text_emote))
+            .collect();
+        assert_eq!(emotes, [(2, false), (101, true)]);
+    }
+
+    #[test]
+    fn vanilla_speakers_are_found_by_name() {
+        let build = 5875;
+        let packets = vec![
+            (
+                "SMSG_CREATURE_QUERY_RESPONSE",
+                creature_response(build, 448, "Hogger"),
+            ),
+            (
+                "SMSG_MESSAGECHAT",
+                monster_chat(build, MonsterChat::Emote, GUID, "Hogger", "%s growls."),
+            ),
+            (
+                "SMSG_MESSAGECHAT",
+                monster_chat(build, MonsterChat::Emote, GUID, "Stranger", "%s waves."),
+            ),
+        ];
+        let records = creature_text_records(&session(build, packets), build);
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].entry, records[0].lines.len()), (448, 1));
+        assert_eq!(records[0].lines[0].text, "%s growls.");
+    }
+
+    #[test]
+    fn trinitycore_rows() {
+        let export = extract_creature_texts(&capture(8606), SqlFlavor::TrinityCore).unwrap();
+        let sql = export.sql;
+        assert!(sql.starts_with("DELETE FROM `creature_text` WHERE `CreatureID` IN (1234);\n"));
+        assert!(sql.contains(
+            "(1234, 0, 0, 'Who goes there?', 12, 0, 100, 2, 0, 0, 0, 0, \
+             'Defias Thug - seen 1x'),\n"
+        ));
+        assert!(sql.contains(
+            "(1234, 1, 0, 'You\\'ll pay!', 14, 0, 100, 5, 0, 0, 0, 0, \
+             'Defias Thug - seen 2x, 1.0-1.0s into combat');\n"
+        ));
+    }
+
+    #[test]
+    fn cmangos_rows() {
+        let export = extract_creature_texts(&capture(5875), SqlFlavor::CMaNGOS).unwrap();
+        let sql = export.sql;
+        assert!(sql.starts_with(
+            "SET @TEXT := -1000000;\n\
+             DELETE FROM `script_texts` WHERE `entry` BETWEEN @TEXT-1 AND @TEXT;\n"
+        ));
+        assert!(
+            sql.contains("(@TEXT-0, 'Who goes there?', 0, 0, 0, 2, 'Defias Thug - seen 1x'),\n")
+        );
+        assert!(sql.contains("(@TEXT-1, 'You\\'ll pay!', 0, 1, 0, 5, "));
+    }
+}
@@ -0,0 +1,217 @@
+//! Trainer spell lists from `SMSG_TRAINER_LIST`.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{decode, diff_sightings, FieldConflict, ObjectEntries, SqlExport};
+use crate::parser;
+use crate::parser::npc::{self, TrainerSpell};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrainerSpellRecord {
+    /// The first sighting of the spell.
+    #[serde(flatten)]
+    pub spell: TrainerSpell,
+    /// Highest cost seen. Costs are sent after reputation discounts, so the
+    /// highest one is the closest to the undiscounted database value.
+    pub base_cost: u32,
+    pub sightings: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrainerRecord {
+    pub entry: u32,
+    pub guids: Vec<u64>,
+    pub trainer_type: u32,
+    pub greeting: String,
+    pub spells: Vec<TrainerSpellRecord>,
+    pub conflicts: Vec<FieldConflict>,
+    pub first_seen_packet: usize,
+    pub sightings: usize,
+}
+
+/// Merge every trainer list by creature entry, in entry order.
+pub fn trainer_records(session: &Session, build: u32) -> Vec<TrainerRecord> {
+    let entries = ObjectEntries::collect(session, build);
+    let mut trainers: BTreeMap<u32, TrainerRecord> = BTreeMap::new();
+
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient
+            || parser::opcode_name(Some(build), packet) != "SMSG_TRAINER_LIST"
+        {
+            continue;
+        }
+        let Some(list) = decode(packet, npc::read_trainer_list) else {
+            continue;
+        };
+        let Some(entry) = entries.get(list.guid) else {
+            continue;
+        };
+        let trainer = trainers.entry(entry).or_insert_with(|| TrainerRecord {
+            entry,
+            guids: Vec::new(),
+            trainer_type: list.trainer_type,
+            greeting: list.greeting.clone(),
+            spells: Vec::new(),
+            conflicts: Vec::new(),
+            first_seen_packet: packet.id,
+            sightings: 0,
+        });
+        trainer.sightings += 1;
+        if !trainer.guids.contains(&list.guid) {
+            trainer.guids.push(list.guid);
+        }
+        for spell in list.spells {
+            match trainer
+                .spells
+                .iter_mut()
+                .find(|s| s.spell.spell == spell.spell)
+            {
+                Some(record) => {
+                    record.sightings += 1;
+                    record.base_cost = record.base_cost.max(spell.cost);
+                    // State and profession slots depend on the player, and
+                    // cost on their reputation.
+                    diff_sightings(
+                        "trainer_list",
+                        &record.spell,
+                        &spell,
+                        &["state", "cost", "can_learn_primary_profession"],
+                        packet.id,
+                        &mut trainer.conflicts,
+                    );
+                }
+                None => trainer.spells.push(TrainerSpellRecord {
+                    base_cost: spell.cost,
+                    spell,
+                    sightings: 1,
+                }),
+            }
+        }
+    }
+    trainers.into_values().collect()
+}
+
+/// `npc_trainer` rows, replacing each captured trainer's spell list.
+pub fn trainer_sql(trainers: &[TrainerRecord], flavor: SqlFlavor) -> String {
+    let columns: &[&str] = match flavor {
+        SqlFlavor::TrinityCore => &[
+            "ID",
+            "SpellID",
+            "MoneyCost",
+            "ReqSkillLine",
+            "ReqSkillRank",
+            "ReqLevel",
+        ],
+        SqlFlavor::CMaNGOS => &[
+            "entry",
+            "spell",
+            "spellcost",
+            "reqskill",
+            "reqskillvalue",
+            "reqlevel",
+        ],
+    };
+    let rows: Vec<SqlRow> = trainers
+        .iter()
+        .flat_map(|trainer| {
+            trainer.spells.iter().map(|record| SqlRow {
+                values: vec![
+                    trainer.entry.to_string(),
+                    record.spell.spell.to_string(),
+                    record.base_cost.to_string(),
+                    record.spell.required_skill.to_string(),
+                    record.spell.required_skill_value.to_string(),
+                    record.spell.required_level.to_string(),
+                ],
+                comment: None,
+            })
+        })
+        .collect();
+    let entries: Vec<u32> = trainers.iter().map(|t| t.entry).collect();
+    let mut out = sql::delete_in("npc_trainer", columns[0], &entries);
+    out.push_str(&sql::insert("npc_trainer", columns, &rows));
+    out
+}
+
+pub fn extract_trainers(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<TrainerRecord>, String> {
+    let build = super::session_build(session)?;
+    let rows = trainer_records(session, build);
+    let sql = trainer_sql(&rows, flavor);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::session;
+    use crate::parser::npc::tests::{trainer_list, BUILDS};
+
+    const GUID: u64 = 0xF130_0004_D200_0101;
+    const OTHER: u64 = 0xF130_0004_D200_0102;
+
+    /// Two spawns of trainer 1234; the second quotes a discounted cost and
+    /// then a different level for the same spell.
+    fn capture(build: u32) -> Session {
+        session(
+            build,
+            vec![
+                ("SMSG_TRAINER_LIST", trainer_list(GUID, &[(3274, 100, 10)])),
+                (
+                    "SMSG_TRAINER_LIST",
+                    trainer_list(OTHER, &[(3274, 90, 10), (7934, 450, 20)]),
+                ),
+                ("SMSG_TRAINER_LIST", trainer_list(OTHER, &[(3274, 100, 12)])),
+            ],
+        )
+    }
+
+    #[test]
+    fn merges_spawns_and_keeps_the_highest_cost() {
+        for build in BUILDS {
+            let trainers = trainer_records(&capture(build), build);
+            assert_eq!(trainers.len(), 1, "build {}", build);
+            let trainer = &trainers[0];
+            assert_eq!((trainer.entry, trainer.trainer_type), (1234, 2));
+            assert_eq!(trainer.guids, [GUID, OTHER]);
+            assert_eq!(trainer.greeting, "Hello, $c.");
+            assert_eq!(trainer.sightings, 3);
+
+            let spells: Vec<_> = trainer
+                .spells
+                .iter()
+                .map(|s| (s.spell.spell, s.base_cost, s.sightings))
+                .collect();
+            assert_eq!(spells, [(3274, 100, 3), (7934, 450, 1)]);
+        }
+    }
+
+    #[test]
+    fn only_player_independent_fields_conflict() {
+        let trainers = trainer_records(&capture(12340), 12340);
+        let conflicts: Vec<_> = trainers[0]
+            .conflicts
+            .iter()
+            .map(|c| (c.source.as_str(), c.field.as_str(), c.packet_id))
+            .collect();
+        assert_eq!(conflicts, [("trainer_list", "required_level", 2)]);
+    }
+
+    #[test]
+    fn writes_both_flavors() {
+        let session = capture(12340);
+        let tc = extract_trainers(&session, SqlFlavor::TrinityCore)
+            .unwrap()
+            .sql;
+        assert!(tc.starts_with("DELETE FROM `npc_trainer` WHERE `ID` IN (1234);\n"));
+        assert!(tc.contains("(1234, 3274, 100, 164, 75, 10),\n(1234, 7934, 450, 164, 75, 20);\n"));
+        let cmangos = extract_trainers(&session, SqlFlavor::CMaNGOS).unwrap().sql;
+        assert!(cmangos.starts_with("DELETE FROM `npc_trainer` WHERE `entry` IN (1234);\n"));
+        assert!(cmangos
+            .contains("(`entry`, `spell`, `spellcost`, `reqskill`, `reqskillvalue`, `reqlevel`)"));
+    }
+}
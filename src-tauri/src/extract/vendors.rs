@@ -0,0 +1,254 @@
+//! Vendor inventories from `SMSG_LIST_INVENTORY`.
+
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{decode, ObjectEntries, SqlExport};
+use crate::parser;
+use crate::parser::npc::{self, VendorItem};
+use crate::state::{Direction, Session};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Restock time written for limited items; captures only show the stock
+/// going down, never how fast it comes back.
+const RESTOCK_SECS: u32 = 3600;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VendorItemRecord {
+    /// The first sighting of the item.
+    #[serde(flatten)]
+    pub item: VendorItem,
+    /// The vendor only holds a limited number of these.
+    pub limited: bool,
+    /// Highest stock seen, the best available guess for `maxcount`.
+    pub max_stock_seen: Option<u32>,
+    pub sightings: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VendorRecord {
+    pub entry: u32,
+    /// Every spawn of this entry whose inventory was opened.
+    pub guids: Vec<u64>,
+    pub items: Vec<VendorItemRecord>,
+    pub first_seen_packet: usize,
+    pub sightings: usize,
+}
+
+/// Merge every inventory list by creature entry, in entry order.
+pub fn vendor_records(session: &Session, build: u32) -> Vec<VendorRecord> {
+    let entries = ObjectEntries::collect(session, build);
+    let mut vendors: BTreeMap<u32, VendorRecord> = BTreeMap::new();
+
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient
+            || parser::opcode_name(Some(build), packet) != "SMSG_LIST_INVENTORY"
+        {
+            continue;
+        }
+        let Some(list) = decode(packet, |r| npc::read_list_inventory(r, build)) else {
+            continue;
+        };
+        let Some(entry) = entries.get(list.guid) else {
+            continue;
+        };
+        let vendor = vendors.entry(entry).or_insert_with(|| VendorRecord {
+            entry,
+            guids: Vec::new(),
+            items: Vec::new(),
+            first_seen_packet: packet.id,
+            sightings: 0,
+        });
+        vendor.sightings += 1;
+        if !vendor.guids.contains(&list.guid) {
+            vendor.guids.push(list.guid);
+        }
+        for item in list.items {
+            let existing = vendor
+                .items
+                .iter_mut()
+                .find(|i| i.item.item == item.item && i.item.extended_cost == item.extended_cost);
+            match existing {
+                Some(record) => {
+                    record.sightings += 1;
+                    if let Some(stock) = item.stock {
+                        record.limited = true;
+                        record.max_stock_seen = record.max_stock_seen.max(Some(stock));
+                    }
+                }
+                None => vendor.items.push(VendorItemRecord {
+                    limited: item.stock.is_some(),
+                    max_stock_seen: item.stock,
+                    item,
+                    sightings: 1,
+                }),
+            }
+        }
+    }
+    vendors.into_values().collect()
+}
+
+/// `npc_vendor` rows, replacing each captured vendor's inventory.
+pub fn vendor_sql(vendors: &[VendorRecord], flavor: SqlFlavor, build: u32) -> String {
+    let tc = flavor == SqlFlavor::TrinityCore;
+    let tbc = build >= 8606;
+    let mut rows = Vec::new();
+    for vendor in vendors {
+        for record in &vendor.items {
+            let (max_count, restock) = match record.max_stock_seen {
+                Some(stock) if record.limited => (stock, RESTOCK_SECS),
+                _ => (0, 0),
+            };
+            let mut values = vec![vendor.entry.to_string()];
+            if tc {
+                values.push(record.item.slot.to_string());
+            }
+            values.extend([
+                record.item.item.to_string(),
+                max_count.to_string(),
+                restock.to_string(),
+            ]);
+            if tc || tbc {
+                values.push(record.item.extended_cost.to_string());
+            }
+            if tc {
+                values.push(build.to_string());
+            }
+            rows.push(SqlRow {
+                values,
+                comment: record.limited.then(|| {
+                    format!(
+                        "limited stock, at most {} seen; restock time guessed",
+                        max_count
+                    )
+                }),
+            });
+        }
+    }
+
+    let mut columns = vec!["entry"];
+    if tc {
+        columns.push("slot");
+    }
+    columns.extend(["item", "maxcount", "incrtime"]);
+    if tc || tbc {
+        columns.push("ExtendedCost");
+    }
+    if tc {
+        columns.push("VerifiedBuild");
+    }
+    let entries: Vec<u32> = vendors.iter().map(|v| v.entry).collect();
+    let mut out = sql::delete_in("npc_vendor", "entry", &entries);
+    out.push_str(&sql::insert("npc_vendor", &columns, &rows));
+    out
+}
+
+pub fn extract_vendors(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<VendorRecord>, String> {
+    let build = super::session_build(session)?;
+    let rows = vendor_records(session, build);
+    let sql = vendor_sql(&rows, flavor, build);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{session, vector, Bytes};
+    use crate::parser::npc::tests::{list_inventory, BUILDS};
+    use crate::parser::update_fields::OBJECT_FIELD_ENTRY;
+    use crate::parser::update_object::TYPEID_UNIT;
+
+    /// Entry 1234 in its GUID bits, but 5678 in its update fields.
+    const GUID: u64 = 0xF130_0004_D200_0101;
+    /// Entry 5678, only ever seen through its GUID.
+    const OTHER: u64 = 0xF130_0016_2E00_0102;
+    const PLAYER: u64 = 0x42;
+
+    fn capture(build: u32) -> Session {
+        let create = Bytes::new()
+            .update_header(build, 1)
+            .create_header(GUID, TYPEID_UNIT)
+            .living(build, vector(1.0, 2.0, 3.0), 0.0)
+            .update_mask(&[(OBJECT_FIELD_ENTRY, 5678)])
+            .build();
+        session(
+            build,
+            vec![
+                ("SMSG_UPDATE_OBJECT", create),
+                (
+                    "SMSG_LIST_INVENTORY",
+                    list_inventory(build, GUID, &[(159, None, 5, 0), (4540, Some(3), 25, 0)]),
+                ),
+                (
+                    "SMSG_LIST_INVENTORY",
+                    list_inventory(build, OTHER, &[(159, None, 5, 0), (4540, Some(5), 25, 0)]),
+                ),
+                (
+                    "SMSG_LIST_INVENTORY",
+                    list_inventory(build, PLAYER, &[(2287, None, 1, 0)]),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn merges_spawns_by_resolved_entry() {
+        for build in BUILDS {
+            let vendors = vendor_records(&capture(build), build);
+            assert_eq!(vendors.len(), 1, "build {}", build);
+            let vendor = &vendors[0];
+            assert_eq!(vendor.entry, 5678);
+            assert_eq!(vendor.guids, [GUID, OTHER]);
+            assert_eq!((vendor.first_seen_packet, vendor.sightings), (1, 2));
+
+            let items: Vec<_> = vendor
+                .items
+                .iter()
+                .map(|i| (i.item.item, i.limited, i.max_stock_seen, i.sightings))
+                .collect();
+            assert_eq!(items, [(159, false, None, 2), (4540, true, Some(5), 2)]);
+        }
+    }
+
+    #[test]
+    fn extended_costs_are_separate_items() {
+        let build = 8606;
+        let packets = vec![(
+            "SMSG_LIST_INVENTORY",
+            list_inventory(build, OTHER, &[(29434, None, 0, 0), (29434, None, 0, 2425)]),
+        )];
+        let vendors = vendor_records(&session(build, packets), build);
+        assert_eq!(vendors[0].items.len(), 2);
+    }
+
+    #[test]
+    fn trinitycore_rows() {
+        let export = extract_vendors(&capture(12340), SqlFlavor::TrinityCore).unwrap();
+        let sql = export.sql;
+        assert!(sql.starts_with("DELETE FROM `npc_vendor` WHERE `entry` IN (5678);\n"));
+        assert!(sql.contains(
+            "(`entry`, `slot`, `item`, `maxcount`, `incrtime`, `ExtendedCost`, `VerifiedBuild`)"
+        ));
+        assert!(sql.contains("(5678, 1, 159, 0, 0, 0, 12340),\n"));
+        assert!(sql.contains(
+            "(5678, 2, 4540, 5, 3600, 0, 12340); \
+             -- limited stock, at most 5 seen; restock time guessed\n"
+        ));
+    }
+
+    #[test]
+    fn cmangos_rows_follow_the_build() {
+        let vanilla = extract_vendors(&capture(5875), SqlFlavor::CMaNGOS)
+            .unwrap()
+            .sql;
+        assert!(vanilla.contains("(`entry`, `item`, `maxcount`, `incrtime`) VALUES\n"));
+        assert!(vanilla.contains("(5678, 159, 0, 0),\n"));
+        let tbc = extract_vendors(&capture(8606), SqlFlavor::CMaNGOS)
+            .unwrap()
+            .sql;
+        assert!(tbc.contains("(`entry`, `item`, `maxcount`, `incrtime`, `ExtendedCost`) VALUES\n"));
+        assert!(tbc.contains("(5678, 4540, 5, 3600, 0);"));
+    }
+}
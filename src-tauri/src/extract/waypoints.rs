@@ -0,0 +1,516 @@
+//! Creature patrol paths rebuilt from `SMSG_MONSTER_MOVE`.
+//!
+//! Each move's destination becomes a waypoint. Linear moves carry the
+//! server's pathfinding points too; those stay with the waypoint they lead
+//! to, since the target server will path between waypoints itself. Smooth
+//! (Catmull-Rom) moves are flight paths whose points are the waypoints, so
+//! every point becomes one.
+//!
+//! Moves on transports are relative to the transport and are left out.
+
+use super::creatures::creature_spawns;
+use super::sql::{self, SqlFlavor, SqlRow};
+use super::{decode, ObjectEntries, SqlExport};
+use crate::parser;
+use crate::parser::guid::{high_guid, HighGuid};
+use crate::parser::movement::{self, MonsterMove, MoveFacing};
+use crate::parser::reader::Vector3;
+use crate::state::{Direction, Packet, Session};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Points closer than this, in yards, are the same waypoint.
+const SAME_POINT: f32 = 0.5;
+
+/// A gap between moves longer than this is the creature out of sight or
+/// fighting, not a pause on its path.
+const MAX_DELAY_MS: u32 = 300_000;
+
+/// CMaNGOS `creature_movement` orientation meaning "keep facing".
+const CMANGOS_NO_ORIENTATION: f32 = 100.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WaypointNode {
+    pub position: Vector3,
+    /// Facing set on arrival, when the move asked for an angle.
+    pub orientation: Option<f32>,
+    /// Time taken to get here from the previous waypoint.
+    pub travel_ms: u32,
+    /// Time spent here before the next move; `None` if no next move was
+    /// seen in time.
+    pub delay_ms: Option<u32>,
+    pub walk: bool,
+    /// Pathfinding points between the previous waypoint and this one.
+    pub path: Vec<Vector3>,
+    pub packet_id: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WaypointPath {
+    pub guid: u64,
+    pub entry: u32,
+    /// Offset from `@CGUID` of the spawn in the creature export, if the
+    /// spawn itself was captured.
+    pub spawn: Option<usize>,
+    /// One lap when `cyclic`, otherwise everything seen in order.
+    pub nodes: Vec<WaypointNode>,
+    /// The path was seen coming back around to its start.
+    pub cyclic: bool,
+    /// Waypoints reached in the capture, over all laps.
+    pub nodes_seen: usize,
+    pub first_seen_packet: usize,
+}
+
+struct Sighting<'a> {
+    packet: &'a Packet,
+    movement: MonsterMove,
+}
+
+fn distance(a: Vector3, b: Vector3) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+fn same_point(a: Vector3, b: Vector3) -> bool {
+    distance(a, b) < SAME_POINT
+}
+
+/// Time between the end of one move and the start of the next.
+fn delay_between(sighting: &Sighting, next: Option<&Sighting>) -> Option<u32> {
+    let elapsed = next?
+        .packet
+        .timestamp
+        .wrapping_sub(sighting.packet.timestamp);
+    let delay = elapsed.saturating_sub(sighting.movement.duration);
+    (elapsed <= i32::MAX as u32 && delay <= MAX_DELAY_MS).then_some(delay)
+}
+
+/// Turn one creature's moves into waypoints, merging repeats of the same
+/// spot.
+fn nodes_from_moves(sightings: &[Sighting]) -> Vec<WaypointNode> {
+    let mut nodes: Vec<WaypointNode> = Vec::new();
+    for (i, sighting) in sightings.iter().enumerate() {
+        let movement = &sighting.movement;
+        let delay_ms = delay_between(sighting, sightings.get(i + 1));
+        if nodes.is_empty() {
+            nodes.push(WaypointNode {
+                position: movement.start,
+                orientation: None,
+                travel_ms: 0,
+                delay_ms: None,
+                walk: movement.walk,
+                path: Vec::new(),
+                packet_id: sighting.packet.id,
+            });
+        }
+        let Some(&destination) = movement.points.last() else {
+            continue;
+        };
+        let orientation = match movement.facing {
+            MoveFacing::Angle { angle } => Some(angle),
+            _ => None,
+        };
+
+        let mut arrivals = Vec::new();
+        if movement.smooth {
+            // Split the duration by distance along the control points.
+            let mut previous = movement.start;
+            let lengths: Vec<f32> = movement
+                .points
+                .iter()
+                .map(|&point| {
+                    let length = distance(previous, point);
+                    previous = point;
+                    length
+                })
+                .collect();
+            let total: f32 = lengths.iter().sum();
+            for (&point, &length) in movement.points.iter().zip(&lengths) {
+                let share = if total > 0.0 { length / total } else { 0.0 };
+                arrivals.push((point, (movement.duration as f32 * share) as u32, Vec::new()));
+            }
+        } else {
+            let path = movement.points[..movement.points.len() - 1].to_vec();
+            arrivals.push((destination, movement.duration, path));
+        }
+
+        let last = arrivals.len() - 1;
+        for (j, (position, travel_ms, path)) in arrivals.into_iter().enumerate() {
+            let node = WaypointNode {
+                position,
+                orientation: if j == last { orientation } else { None },
+                travel_ms,
+                delay_ms: if j == last { delay_ms } else { Some(0) },
+                walk: movement.walk,
+                path,
+                packet_id: sighting.packet.id,
+            };
+            match nodes.last_mut() {
+                // The creature was re-sent to where it already stands.
+                Some(previous) if same_point(previous.position, node.position) => {
+                    previous.delay_ms = match (previous.delay_ms, node.delay_ms) {
+                        (Some(a), Some(b)) => Some(a + node.travel_ms + b),
+                        _ => None,
+                    };
+                    previous.orientation = node.orientation.or(previous.orientation);
+                }
+                _ => nodes.push(node),
+            }
+        }
+    }
+    nodes
+}
+
+/// Smallest lap length `p` such that every waypoint after the first lap
+/// repeats the one `p` earlier.
+fn loop_length(nodes: &[WaypointNode]) -> Option<usize> {
+    (2..nodes.len())
+        .find(|&p| (p..nodes.len()).all(|i| same_point(nodes[i].position, nodes[i - p].position)))
+}
+
+/// Fold repeated laps into one, filling in delays a lap missed from the
+/// others.
+fn fold_laps(mut nodes: Vec<WaypointNode>, lap: usize) -> Vec<WaypointNode> {
+    let rest = nodes.split_off(lap);
+    for (i, node) in rest.into_iter().enumerate() {
+        let first = &mut nodes[i % lap];
+        if first.delay_ms.is_none() {
+            first.delay_ms = node.delay_ms;
+        }
+        if first.orientation.is_none() {
+            first.orientation = node.orientation;
+        }
+        // The first waypoint is where the capture found the creature, so
+        // its arrival is only timed on later laps.
+        if first.travel_ms == 0 {
+            first.travel_ms = node.travel_ms;
+            first.path = node.path;
+        }
+    }
+    nodes
+}
+
+/// One path per creature that moved, in order of its first move.
+pub fn waypoint_paths(session: &Session, build: u32) -> Vec<WaypointPath> {
+    let entries = ObjectEntries::collect(session, build);
+    let mut moves: Vec<(u64, Vec<Sighting>)> = Vec::new();
+    let mut by_guid: HashMap<u64, usize> = HashMap::new();
+
+    for packet in &session.packets {
+        if packet.direction != Direction::ServerToClient
+            || parser::opcode_name(Some(build), packet) != "SMSG_MONSTER_MOVE"
+        {
+            continue;
+        }
+        let Some(movement) = decode(packet, |r| movement::read_monster_move(r, build, false))
+        else {
+            continue;
+        };
+        if movement.stop
+            || !matches!(
+                high_guid(movement.guid),
+                HighGuid::Creature | HighGuid::Vehicle
+            )
+        {
+            continue;
+        }
+        let index = *by_guid.entry(movement.guid).or_insert_with(|| {
+            moves.push((movement.guid, Vec::new()));
+            moves.len() - 1
+        });
+        moves[index].1.push(Sighting { packet, movement });
+    }
+
+    let spawns: HashMap<u64, usize> = creature_spawns(session, build)
+        .iter()
+        .enumerate()
+        .map(|(i, spawn)| (spawn.guid, i))
+        .collect();
+    moves
+        .into_iter()
+        .map(|(guid, sightings)| {
+            let nodes = nodes_from_moves(&sightings);
+            let nodes_seen = nodes.len();
+            let lap = loop_length(&nodes);
+            WaypointPath {
+                guid,
+                entry: entries.get(guid).unwrap_or(0),
+                spawn: spawns.get(&guid).copied(),
+                nodes: match lap {
+                    Some(lap) => fold_laps(nodes, lap),
+                    None => nodes,
+                },
+                cyclic: lap.is_some(),
+                nodes_seen,
+                first_seen_packet: sightings[0].packet.id,
+            }
+        })
+        .collect()
+}
+
+/// Waypoint rows for every path whose spawn is in the creature export,
+/// keyed off the same `@CGUID`. Each spawn is switched to waypoint
+/// movement.
+pub fn waypoint_sql(paths: &[WaypointPath], flavor: SqlFlavor) -> String {
+    let mut out = String::from("SET @CGUID := 0;\n");
+    for path in paths.iter().filter(|p| p.nodes.len() > 1) {
+        let header = format!(
+            "\n-- entry {}, guid 0x{:016X}, {} waypoints{}\n",
+            path.entry,
+            path.guid,
+            path.nodes.len(),
+            if path.cyclic { ", loops" } else { "" }
+        );
+        out.push_str(&header);
+        let Some(spawn) = path.spawn else {
+            out.push_str("-- spawn not captured, path skipped\n");
+            continue;
+        };
+        out.push_str(&format!("SET @NPC := @CGUID+{};\n", spawn));
+
+        let rows: Vec<SqlRow> = path
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let orientation = match (node.orientation, flavor) {
+                    (Some(o), _) => o,
+                    (None, SqlFlavor::TrinityCore) => 0.0,
+                    (None, SqlFlavor::CMaNGOS) => CMANGOS_NO_ORIENTATION,
+                };
+                let key = match flavor {
+                    SqlFlavor::TrinityCore => "@PATH",
+                    SqlFlavor::CMaNGOS => "@NPC",
+                };
+                let mut values = vec![
+                    key.to_string(),
+                    (i + 1).to_string(),
+                    sql::float(node.position.x),
+                    sql::float(node.position.y),
+                    sql::float(node.position.z),
+                    sql::float(orientation),
+                    node.delay_ms.unwrap_or(0).to_string(),
+                ];
+                if flavor == SqlFlavor::TrinityCore {
+                    values.push(if node.walk { "0" } else { "1" }.to_string());
+                }
+                SqlRow {
+                    values,
+                    comment: Some(format!("{} ms travel", node.travel_ms)),
+                }
+            })
+            .collect();
+
+        match flavor {
+            SqlFlavor::TrinityCore => {
+                out.push_str("SET @PATH := @NPC*10;\n");
+                out.push_str(
+                    "UPDATE `creature` SET `MovementType`=2, `wander_distance`=0 WHERE `guid`=@NPC;\n",
+                );
+                out.push_str(&sql::upsert(
+                    "creature_addon",
+                    &["guid", "path_id"],
+                    &[SqlRow {
+                        values: vec!["@NPC".to_string(), "@PATH".to_string()],
+                        comment: None,
+                    }],
+                ));
+                out.push_str("DELETE FROM `waypoint_data` WHERE `id`=@PATH;\n");
+                out.push_str(&sql::insert(
+                    "waypoint_data",
+                    &[
+                        "id",
+                        "point",
+                        "position_x",
+                        "position_y",
+                        "position_z",
+                        "orientation",
+                        "delay",
+                        "move_type",
+                    ],
+                    &rows,
+                ));
+            }
+            SqlFlavor::CMaNGOS => {
+                out.push_str(
+                    "UPDATE `creature` SET `MovementType`=2, `spawndist`=0 WHERE `guid`=@NPC;\n",
+                );
+                out.push_str("DELETE FROM `creature_movement` WHERE `id`=@NPC;\n");
+                out.push_str(&sql::insert(
+                    "creature_movement",
+                    &[
+                        "id",
+                        "point",
+                        "position_x",
+                        "position_y",
+                        "position_z",
+                        "orientation",
+                        "waittime",
+                    ],
+                    &rows,
+                ));
+            }
+        }
+    }
+    out
+}
+
+pub fn extract_waypoints(
+    session: &Session,
+    flavor: SqlFlavor,
+) -> Result<SqlExport<WaypointPath>, String> {
+    let build = super::session_build(session)?;
+    let rows = waypoint_paths(session, build);
+    let sql = waypoint_sql(&rows, flavor);
+    Ok(SqlExport { rows, sql })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{session, vector, Bytes};
+    use crate::parser::movement::tests::{monster_move, Spline, BUILDS};
+    use crate::parser::update_object::TYPEID_UNIT;
+
+    /// Creature 1234.
+    const GUID: u64 = 0xF130_0004_D200_0101;
+    const A: Vector3 = Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    const B: Vector3 = Vector3 {
+        x: 20.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    const C: Vector3 = Vector3 {
+        x: 20.0,
+        y: 20.0,
+        z: 0.0,
+    };
+
+    /// `GUID`'s walks between consecutive stops, one move every ten
+    /// seconds, each taking four.
+    fn patrol(build: u32, stops: &[Vector3], spawned: bool) -> Session {
+        let mut packets = Vec::new();
+        if spawned {
+            let create = Bytes::new()
+                .update_header(build, 1)
+                .create_header(GUID, TYPEID_UNIT)
+                .living(build, stops[0], 0.0)
+                .update_mask(&[])
+                .build();
+            packets.push(("SMSG_UPDATE_OBJECT", create));
+        }
+        for pair in stops.windows(2) {
+            let data = monster_move(build, GUID, pair[0], None, Spline::Walk, 4000, &[pair[1]]);
+            packets.push(("SMSG_MONSTER_MOVE", data));
+        }
+        let mut session = session(build, packets);
+        for packet in &mut session.packets {
+            packet.timestamp = packet.id as u32 * 10_000;
+        }
+        session
+    }
+
+    fn positions(path: &WaypointPath) -> Vec<Vector3> {
+        path.nodes.iter().map(|n| n.position).collect()
+    }
+
+    #[test]
+    fn folds_repeated_laps() {
+        for build in BUILDS {
+            let session = patrol(build, &[A, B, C, A, B, C, A], false);
+            let paths = waypoint_paths(&session, build);
+            assert_eq!(paths.len(), 1, "build {}", build);
+            let path = &paths[0];
+            assert_eq!((path.guid, path.entry, path.spawn), (GUID, 1234, None));
+            assert!(path.cyclic);
+            assert_eq!(path.nodes_seen, 7);
+            assert_eq!(positions(path), [A, B, C]);
+            // The start's arrival and pause only show up on the second lap.
+            for node in &path.nodes {
+                assert_eq!((node.travel_ms, node.delay_ms), (4000, Some(6000)));
+                assert!(node.walk);
+            }
+        }
+    }
+
+    #[test]
+    fn open_paths_keep_every_waypoint() {
+        let paths = waypoint_paths(&patrol(12340, &[A, B, C], false), 12340);
+        let path = &paths[0];
+        assert!(!path.cyclic);
+        assert_eq!(positions(path), [A, B, C]);
+        // Nothing was seen after the last move.
+        assert_eq!(path.nodes[2].delay_ms, None);
+    }
+
+    #[test]
+    fn resends_to_the_same_spot_extend_the_pause() {
+        let build = 12340;
+        let mut session = patrol(build, &[A, B, C], false);
+        let resend = monster_move(build, GUID, B, Some(3.0), Spline::Walk, 500, &[B]);
+        let mut packet = session.packets[1].clone();
+        packet.size = resend.len();
+        packet.data = resend;
+        session.packets.insert(1, packet);
+        for (id, packet) in session.packets.iter_mut().enumerate() {
+            packet.id = id;
+            packet.timestamp = id as u32 * 5000;
+        }
+
+        let paths = waypoint_paths(&session, build);
+        let path = &paths[0];
+        assert_eq!(positions(path), [A, B, C]);
+        let b = &path.nodes[1];
+        // 1s after arriving, a 0.5s turn, then 4.5s until the move to C.
+        assert_eq!(b.delay_ms, Some(1000 + 500 + 4500));
+        assert_eq!(b.orientation, Some(3.0));
+    }
+
+    #[test]
+    fn linear_paths_stay_with_their_waypoint() {
+        let build = 8606;
+        let corner = vector(10.0, 5.0, 0.0);
+        let data = monster_move(build, GUID, A, None, Spline::Run, 4000, &[corner, B]);
+        let paths = waypoint_paths(&session(build, vec![("SMSG_MONSTER_MOVE", data)]), build);
+        let path = &paths[0];
+        assert_eq!(positions(path), [A, B]);
+        assert_eq!(path.nodes[1].path, [corner]);
+        assert!(!path.nodes[1].walk);
+    }
+
+    #[test]
+    fn smooth_points_are_waypoints() {
+        let build = 12340;
+        let data = monster_move(build, GUID, A, None, Spline::Fly, 6000, &[B, C]);
+        let paths = waypoint_paths(&session(build, vec![("SMSG_MONSTER_MOVE", data)]), build);
+        let travel: Vec<_> = paths[0].nodes.iter().map(|n| n.travel_ms).collect();
+        assert_eq!(positions(&paths[0]), [A, B, C]);
+        // Both legs are 20 yards.
+        assert_eq!(travel, [0, 3000, 3000]);
+    }
+
+    #[test]
+    fn writes_paths_of_captured_spawns() {
+        let session = patrol(5875, &[A, B, C, A, B, C, A], true);
+        let tc = extract_waypoints(&session, SqlFlavor::TrinityCore)
+            .unwrap()
+            .sql;
+        assert!(tc.contains("-- entry 1234, guid 0xF1300004D2000101, 3 waypoints, loops\n"));
+        assert!(tc.contains("SET @NPC := @CGUID+0;\nSET @PATH := @NPC*10;\n"));
+        assert!(tc.contains("(@PATH, 1, 0, 0, 0, 0, 6000, 0), -- 4000 ms travel\n"));
+        assert!(tc.contains("(@PATH, 3, 20, 20, 0, 0, 6000, 0); -- 4000 ms travel\n"));
+
+        let cmangos = extract_waypoints(&session, SqlFlavor::CMaNGOS).unwrap().sql;
+        assert!(cmangos.contains("DELETE FROM `creature_movement` WHERE `id`=@NPC;\n"));
+        assert!(cmangos.contains("(@NPC, 2, 20, 0, 0, 100, 6000), -- 4000 ms travel\n"));
+
+        let unspawned = patrol(5875, &[A, B], false);
+        let sql = extract_waypoints(&unspawned, SqlFlavor::TrinityCore)
+            .unwrap()
+            .sql;
+        assert!(sql.ends_with("-- spawn not captured, path skipped\n"));
+    }
+}
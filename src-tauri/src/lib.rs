@@ -1,13 +1,45 @@
+mod analysis;
 mod capture;
+mod extract;
+mod parser;
+mod query;
 mod state;
 
 use capture::process::WowProcess;
 use capture::session_store;
-use state::{AppState, Direction, Packet, PacketSummary, Session, SessionInfo};
+use state::{
+    AppState, CaptureDrops, CaptureGap, CaptureOptions, CaptureStatus, Direction, FilterMode,
+    Packet, PacketSummary, Session, SessionInfo,
+};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter, Manager};
 
+/// The shared memory of the current attach, held alongside the packet
+/// reader so commands can reach the DLL.
+#[derive(Default)]
+struct CaptureLink(Mutex<Option<Arc<capture::ipc::SharedMemoryReader>>>);
+
+/// Run `f` on a blocking thread, for commands that would otherwise stall the
+/// main thread: whole-session scans and waits on the DLL.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Background task failed: {}", e))?
+}
+
+/// The session as it is now, to scan without holding the sessions lock.
+fn session_snapshot(app: &AppHandle, session_id: &str) -> Result<Arc<Session>, String> {
+    let state = app.state::<Arc<AppState>>();
+    let sessions = state.sessions.lock().unwrap();
+    sessions
+        .get(session_id)
+        .cloned()
+        .ok_or_else(|| format!("Session {} not found", session_id))
+}
+
 #[tauri::command]
 fn discover_processes() -> Result<Vec<WowProcess>, String> {
     Ok(capture::process::discover_processes())
@@ -29,7 +61,14 @@ fn locate_dll_path() -> Result<PathBuf, String> {
     Ok(dll_path)
 }
 
-fn initialize_capture(pid: u32, dll_path: &PathBuf) -> Result<capture::ipc::SharedMemoryReader, String> {
+fn initialize_capture(
+    pid: u32,
+    dll_path: &PathBuf,
+    options: &CaptureOptions,
+) -> Result<capture::ipc::SharedMemoryReader, String> {
+    // The DLL reads its options while creating the ring, so they must be in
+    // place before injection and stay there until the ring is open.
+    let _options = capture::ipc::OptionsMapping::publish(pid, options)?;
     capture::injector::inject_dll(pid, dll_path)?;
 
     // Open shared memory — retry because the DLL needs a moment after LoadLibrary
@@ -52,6 +91,7 @@ fn update_attached_state(
     pid: u32,
     build: u32,
     version_name: String,
+    capture: CaptureOptions,
 ) {
     {
         let mut attached = state.attached.lock().unwrap();
@@ -59,16 +99,20 @@ fn update_attached_state(
             pid,
             build,
             version_name,
+            capture,
         });
     }
 
+    // A fresh DLL ring starts counting drops from zero
+    *state.capture_drops.lock().unwrap() = CaptureDrops::default();
+
     // Stamp the active session with this build number
     {
         let active_sid = state.active_session_id.lock().unwrap().clone();
         if let Some(sid) = active_sid {
             let mut sessions = state.sessions.lock().unwrap();
             if let Some(session) = sessions.get_mut(&sid) {
-                session.build = Some(build);
+                Arc::make_mut(session).build = Some(build);
             }
         }
     }
@@ -76,10 +120,51 @@ fn update_attached_state(
     *state.capturing.lock().unwrap() = true;
 }
 
+fn capture_drops(drops: &capture::ipc::Drops, lost: &capture::ipc::Lost) -> CaptureDrops {
+    CaptureDrops {
+        server_packets: drops.packets[0],
+        server_bytes: drops.bytes[0],
+        client_packets: drops.packets[1],
+        client_bytes: drops.bytes[1],
+        lost_packets: lost.packets,
+        lost_bytes: lost.bytes,
+    }
+}
+
+/// Mark where packets went missing in the active session, between the last
+/// packet received and the next. Losses to corruption have no timestamp of
+/// their own and take the last packet's.
+fn record_gap(state: &AppState, app: &AppHandle, timestamp: Option<u32>, drops: CaptureDrops) {
+    let active_sid = state.active_session_id.lock().unwrap().clone();
+    let Some(sid) = active_sid else { return };
+
+    let gap = {
+        let mut sessions = state.sessions.lock().unwrap();
+        let Some(session) = sessions.get_mut(&sid).map(Arc::make_mut) else { return };
+        let timestamp = timestamp
+            .or_else(|| session.packets.last().map(|p| p.timestamp))
+            .unwrap_or(0);
+        let gap = CaptureGap {
+            before_packet_id: session.next_packet_id,
+            timestamp,
+            drops,
+        };
+        session.gaps.push(gap.clone());
+        gap
+    };
+
+    if let Err(e) = app.emit(
+        "capture_gap",
+        serde_json::json!({ "session_id": &sid, "gap": gap }),
+    ) {
+        log::warn!("Failed to emit capture gap event: {}", e);
+    }
+}
+
 fn spawn_packet_reader(
     state: Arc<AppState>,
     app: AppHandle,
-    reader: capture::ipc::SharedMemoryReader,
+    reader: Arc<capture::ipc::SharedMemoryReader>,
     build: u32,
 ) {
     tauri::async_runtime::spawn(async move {
@@ -89,8 +174,21 @@ fn spawn_packet_reader(
                 break;
             }
 
-            let raw_packets = reader.read_packets();
-            for raw in raw_packets {
+            let records = reader.read_records();
+            for record in records {
+                let raw = match record {
+                    capture::ipc::Record::Packet(raw) => raw,
+                    capture::ipc::Record::Gap { timestamp, drops } => {
+                        let drops = capture_drops(&drops, &Default::default());
+                        record_gap(&state, &app, Some(timestamp), drops);
+                        continue;
+                    }
+                    capture::ipc::Record::Lost(lost) => {
+                        let drops = capture_drops(&Default::default(), &lost);
+                        record_gap(&state, &app, None, drops);
+                        continue;
+                    }
+                };
                 let opcode_name =
                     capture::packets::get_opcode_name(build, raw.opcode).to_string();
                 let direction = Direction::from(raw.direction);
@@ -106,7 +204,7 @@ fn spawn_packet_reader(
 
                 let packet_id = {
                     let mut sessions = state.sessions.lock().unwrap();
-                    if let Some(session) = sessions.get_mut(&sid) {
+                    if let Some(session) = sessions.get_mut(&sid).map(Arc::make_mut) {
                         let id = session.next_packet_id;
                         session.next_packet_id += 1;
                         session.packets.push(Packet {
@@ -142,6 +240,9 @@ fn spawn_packet_reader(
                 }
             }
 
+            *state.capture_drops.lock().unwrap() =
+                capture_drops(&reader.drops(), &reader.lost());
+
             tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
         }
         drop(reader);
@@ -153,13 +254,22 @@ fn attach_process(
     pid: u32,
     build: u32,
     version_name: String,
+    options: Option<CaptureOptions>,
     app: AppHandle,
 ) -> Result<(), String> {
     let state = app.state::<Arc<AppState>>();
 
+    let options = options.unwrap_or_default();
+    if options.directions.as_ref().is_some_and(Vec::is_empty) {
+        return Err("No packet direction selected to capture".to_string());
+    }
+
     let dll_path = locate_dll_path()?;
-    let reader = initialize_capture(pid, &dll_path)?;
-    update_attached_state(&state, pid, build, version_name);
+    let reader = initialize_capture(pid, &dll_path, &options)?;
+    update_attached_state(&state, pid, build, version_name, reader.options());
+
+    let reader = Arc::new(reader);
+    *app.state::<CaptureLink>().0.lock().unwrap() = Some(Arc::clone(&reader));
     spawn_packet_reader(Arc::clone(state.inner()), app, reader, build);
 
     Ok(())
@@ -170,24 +280,80 @@ fn detach_process(app: AppHandle) -> Result<(), String> {
     let state = app.state::<Arc<AppState>>();
     *state.capturing.lock().unwrap() = false;
     let _attached = state.attached.lock().unwrap().take();
+    let _reader = app.state::<CaptureLink>().0.lock().unwrap().take();
     Ok(())
 }
 
+// --- Capture control commands ---
+
+/// Run `f` against the shared memory of the current attach, on a blocking
+/// thread since it waits for the DLL to answer.
+async fn with_capture<T: Send + 'static>(
+    app: &AppHandle,
+    f: impl FnOnce(&capture::ipc::SharedMemoryReader) -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let reader = app
+        .state::<CaptureLink>()
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or("Not attached to a process")?;
+    blocking(move || f(&reader)).await
+}
+
+#[tauri::command]
+async fn pause_capture(app: AppHandle) -> Result<(), String> {
+    with_capture(&app, |reader| reader.pause()).await
+}
+
+#[tauri::command]
+async fn resume_capture(app: AppHandle) -> Result<(), String> {
+    with_capture(&app, |reader| reader.resume()).await
+}
+
+#[tauri::command]
+async fn set_opcode_filter(
+    mode: FilterMode,
+    opcodes: Vec<u32>,
+    app: AppHandle,
+) -> Result<(), String> {
+    with_capture(&app, move |reader| reader.set_filter(mode, opcodes)).await
+}
+
+#[tauri::command]
+async fn get_capture_status(app: AppHandle) -> Result<CaptureStatus, String> {
+    with_capture(&app, |reader| reader.status()).await
+}
+
+/// Have the DLL unhook and unload itself, then detach.
+#[tauri::command]
+async fn unload_capture(app: AppHandle) -> Result<(), String> {
+    with_capture(&app, |reader| reader.unload()).await?;
+    detach_process(app)
+}
+
 #[tauri::command]
 fn get_status(app: AppHandle) -> serde_json::Value {
     let state = app.state::<Arc<AppState>>();
     let active_sid = state.active_session_id.lock().unwrap().clone();
-    let packet_count = if let Some(ref sid) = active_sid {
+    let (packet_count, gap_count) = if let Some(ref sid) = active_sid {
         let sessions = state.sessions.lock().unwrap();
-        sessions.get(sid).map(|s| s.packets.len()).unwrap_or(0)
+        sessions
+            .get(sid)
+            .map(|s| (s.packets.len(), s.gaps.len()))
+            .unwrap_or((0, 0))
     } else {
-        0
+        (0, 0)
     };
+    let dropped = *state.capture_drops.lock().unwrap();
     let attached = state.attached.lock().unwrap();
     serde_json::json!({
         "attached": attached.is_some(),
         "process": *attached,
         "packet_count": packet_count,
+        "gap_count": gap_count,
+        "dropped": dropped,
     })
 }
 
@@ -212,7 +378,11 @@ fn create_session(name: String, app: AppHandle) -> SessionInfo {
     let state = app.state::<Arc<AppState>>();
     let session = Session::new(name);
     let info = SessionInfo::from(&session);
-    state.sessions.lock().unwrap().insert(session.id.clone(), session);
+    state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(session.id.clone(), Arc::new(session));
     info
 }
 
@@ -237,7 +407,7 @@ fn rename_session(session_id: String, new_name: String, app: AppHandle) -> Resul
         .get_mut(&session_id)
         .ok_or_else(|| format!("Session {} not found", session_id))
         .map(|s| {
-            s.name = new_name;
+            Arc::make_mut(s).name = new_name;
         })
 }
 
@@ -252,6 +422,8 @@ fn close_session(session_id: String, app: AppHandle) -> Result<(), String> {
             return Err("Cannot close the last session".to_string());
         }
         sessions.remove(&session_id);
+        state.timelines.lock().unwrap().remove(&session_id);
+        state.packet_windows.lock().unwrap().remove(&session_id);
         sessions.keys().next().cloned()
     };
 
@@ -268,10 +440,13 @@ fn close_session(session_id: String, app: AppHandle) -> Result<(), String> {
 fn clear_packets(session_id: String, app: AppHandle) {
     let state = app.state::<Arc<AppState>>();
     let mut sessions = state.sessions.lock().unwrap();
-    if let Some(session) = sessions.get_mut(&session_id) {
+    if let Some(session) = sessions.get_mut(&session_id).map(Arc::make_mut) {
         session.packets.clear();
+        session.gaps.clear();
         session.next_packet_id = 0;
     }
+    state.timelines.lock().unwrap().remove(&session_id);
+    state.packet_windows.lock().unwrap().remove(&session_id);
 }
 
 #[tauri::command]
@@ -300,7 +475,145 @@ fn get_packet_detail(session_id: String, id: usize, app: AppHandle) -> Option<Pa
     let sessions = state.sessions.lock().unwrap();
     sessions
         .get(&session_id)
-        .and_then(|s| s.packets.iter().find(|p| p.id == id).cloned())
+        .and_then(|s| s.packet(id).cloned())
+}
+
+/// One page of the packet list, filtered with the query language, sorted
+/// and optionally with repeats collapsed, with the total number of rows.
+#[tauri::command]
+async fn get_packet_window(
+    session_id: String,
+    filter: String,
+    sort: Option<query::window::PacketSort>,
+    collapse: Option<query::collapse::Collapse>,
+    offset: usize,
+    limit: usize,
+    app: AppHandle,
+) -> Result<query::window::PacketWindow, String> {
+    let query = query::Query::parse(&filter)?;
+    let session = session_snapshot(&app, &session_id)?;
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    blocking(move || {
+        let sort = sort.unwrap_or_default();
+        let key = query::window::WindowKey::new(&session, &filter, sort, collapse);
+        let cached = state
+            .packet_windows
+            .lock()
+            .unwrap()
+            .get(&session_id)
+            .filter(|rows| *rows.key() == key)
+            .cloned();
+        // Scrolling asks for the same rows again; only a new filter, sort
+        // or packet count rebuilds them.
+        let rows = match cached {
+            Some(rows) => rows,
+            None => {
+                let rows = Arc::new(query::window::WindowRows::new(&session, &query, key));
+                state
+                    .packet_windows
+                    .lock()
+                    .unwrap()
+                    .insert(session_id, Arc::clone(&rows));
+                rows
+            }
+        };
+        Ok(rows.window(&session, offset, limit))
+    })
+    .await
+}
+
+/// The packets of a collapsed row, given the filter the window used.
+#[tauri::command]
+async fn expand_packet_run(
+    session_id: String,
+    filter: String,
+    first_id: usize,
+    last_id: usize,
+    app: AppHandle,
+) -> Result<Vec<PacketSummary>, String> {
+    let filter = query::Query::parse(&filter)?;
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || {
+        Ok(query::window::expand_run(
+            &session, &filter, first_id, last_id,
+        ))
+    })
+    .await
+}
+
+/// Ids of the packets matching a query; see the `query` module for the
+/// syntax.
+#[tauri::command]
+async fn query_packets(
+    session_id: String,
+    query: String,
+    app: AppHandle,
+) -> Result<Vec<usize>, String> {
+    let query = query::Query::parse(&query)?;
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || Ok(query.matching_ids(&session))).await
+}
+
+/// Packets whose payload contains `pattern`, in one open session or, with no
+/// session id, in every session file in the library.
+#[tauri::command]
+async fn search_bytes(
+    pattern: query::search::SearchPattern,
+    session_id: Option<String>,
+    app: AppHandle,
+) -> Result<Vec<query::search::SessionHits>, String> {
+    let pattern = pattern.compile()?;
+    if let Some(session_id) = session_id {
+        let session = session_snapshot(&app, &session_id)?;
+        return blocking(move || {
+            Ok(vec![query::search::SessionHits {
+                session_id: session.id.clone(),
+                session_name: session.name.clone(),
+                file_path: None,
+                hits: query::search::search_session(&pattern, &session),
+            }])
+        })
+        .await;
+    }
+
+    let paths = session_store::saved_session_paths(&app)?;
+    blocking(move || {
+        let mut results = Vec::new();
+        for path in paths {
+            // Unreadable files are left out, as in the library listing.
+            let Ok(file) = session_store::load_session_file(&path) else {
+                continue;
+            };
+            let session = session_from_file(file);
+            let hits = query::search::search_session(&pattern, &session);
+            if !hits.is_empty() {
+                results.push(query::search::SessionHits {
+                    session_id: session.id,
+                    session_name: session.name,
+                    file_path: Some(path.to_string_lossy().into_owned()),
+                    hits,
+                });
+            }
+        }
+        Ok(results)
+    })
+    .await
+}
+
+/// Every packet involving a GUID, raw or packed, grouped by opcode. The GUID
+/// is decimal or `0x` hex.
+#[tauri::command]
+async fn find_guid(
+    session_id: String,
+    guid: String,
+    app: AppHandle,
+) -> Result<query::guid::GuidReport, String> {
+    let guid = query::search::parse_guid(&guid)?;
+    if guid == 0 {
+        return Err("GUID 0 matches every run of zero bytes".to_string());
+    }
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || Ok(query::guid::find_guid(&session, guid))).await
 }
 
 #[tauri::command]
@@ -323,25 +636,271 @@ fn list_saved_sessions(app: AppHandle) -> Result<Vec<session_store::SavedSession
     session_store::list_saved_sessions(&app)
 }
 
-#[tauri::command]
-fn load_session_cmd(file_path: String, app: AppHandle) -> Result<SessionInfo, String> {
-    let path = std::path::PathBuf::from(&file_path);
-    let sf = session_store::load_session_file(&path)?;
+fn session_from_file(sf: session_store::SessionFile) -> Session {
     let max_id = sf.packets.iter().map(|p| p.id).max().map(|m| m + 1).unwrap_or(0);
-    let session = Session {
+    Session {
         id: sf.id,
         name: sf.name,
         created_at: sf.created_at,
         build: sf.build,
         packets: sf.packets,
         next_packet_id: max_id,
-    };
+        gaps: sf.gaps,
+    }
+}
+
+#[tauri::command]
+fn load_session_cmd(file_path: String, app: AppHandle) -> Result<SessionInfo, String> {
+    let path = std::path::PathBuf::from(&file_path);
+    let session = session_from_file(session_store::load_session_file(&path)?);
     let info = SessionInfo::from(&session);
     let state = app.state::<Arc<AppState>>();
-    state.sessions.lock().unwrap().insert(session.id.clone(), session);
+    state.timelines.lock().unwrap().remove(&session.id);
+    state.packet_windows.lock().unwrap().remove(&session.id);
+    state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(session.id.clone(), Arc::new(session));
     Ok(info)
 }
 
+#[tauri::command]
+async fn get_parse_coverage(
+    session_id: String,
+    app: AppHandle,
+) -> Result<analysis::coverage::CoverageReport, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || Ok(analysis::coverage::coverage_report(&session))).await
+}
+
+#[tauri::command]
+async fn get_session_stats(
+    session_id: String,
+    app: AppHandle,
+) -> Result<analysis::stats::SessionStats, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || Ok(analysis::stats::session_stats(&session))).await
+}
+
+/// Bring the session's timeline up to date and hand it to `f`. A different
+/// interval from last time starts a new timeline.
+async fn with_timeline<T: Send + 'static>(
+    session_id: String,
+    interval_ms: u32,
+    app: &AppHandle,
+    f: impl FnOnce(&analysis::timeline::Timeline) -> T + Send + 'static,
+) -> Result<T, String> {
+    if interval_ms < analysis::timeline::MIN_INTERVAL_MS {
+        return Err(format!(
+            "Interval must be at least {} ms",
+            analysis::timeline::MIN_INTERVAL_MS
+        ));
+    }
+    let session = session_snapshot(app, &session_id)?;
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    blocking(move || {
+        let mut timelines = state.timelines.lock().unwrap();
+        let timeline = timelines
+            .entry(session_id)
+            .or_insert_with(|| analysis::timeline::Timeline::new(interval_ms));
+        if timeline.interval_ms() != interval_ms {
+            *timeline = analysis::timeline::Timeline::new(interval_ms);
+        }
+        timeline.update(&session);
+        Ok(f(timeline))
+    })
+    .await
+}
+
+/// Packet and byte counts over time. Live views pass the index of the last
+/// bucket they have as `since` to fetch only what changed.
+#[tauri::command]
+async fn get_timeline(
+    session_id: String,
+    interval_ms: u32,
+    since: Option<usize>,
+    app: AppHandle,
+) -> Result<analysis::timeline::TimelineView, String> {
+    with_timeline(session_id, interval_ms, &app, move |timeline| {
+        timeline.view(since.unwrap_or(0))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn export_timeline_csv(
+    session_id: String,
+    interval_ms: u32,
+    app: AppHandle,
+) -> Result<String, String> {
+    with_timeline(session_id, interval_ms, &app, |timeline| timeline.to_csv()).await
+}
+
+/// Requests paired with their replies, with latencies and anything left
+/// unmatched.
+#[tauri::command]
+async fn correlate_requests(
+    session_id: String,
+    app: AppHandle,
+) -> Result<analysis::correlation::CorrelationReport, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || analysis::correlation::correlation_report(&session)).await
+}
+
+#[tauri::command]
+async fn get_parse_findings(
+    session_id: String,
+    kinds: Option<Vec<parser::reader::FindingKind>>,
+    app: AppHandle,
+) -> Result<Vec<analysis::validation::PacketFindings>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || {
+        Ok(analysis::validation::find_malformed(
+            &session,
+            kinds.as_deref().unwrap_or_default(),
+        ))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn extract_creature_spawns(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::creatures::CreatureSpawn>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::creatures::extract_creature_spawns(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_gameobject_spawns(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::gameobjects::GameObjectSpawn>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::gameobjects::extract_gameobject_spawns(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_quests(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::quests::QuestRecord>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::quests::extract_quests(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_templates(
+    session_id: String,
+    kind: extract::templates::TemplateKind,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::templates::TemplateRecord>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::templates::extract_templates(&session, kind, flavor)).await
+}
+
+#[tauri::command]
+async fn diff_templates(
+    session_id: String,
+    kind: extract::templates::TemplateKind,
+    flavor: extract::sql::SqlFlavor,
+    existing_path: String,
+    app: AppHandle,
+) -> Result<extract::templates::TemplateDiffReport, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || {
+        extract::templates::diff_templates(
+            &session,
+            kind,
+            flavor,
+            std::path::Path::new(&existing_path),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+async fn extract_gossip(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::gossip::GossipTree, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::gossip::extract_gossip(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_vendors(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::vendors::VendorRecord>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::vendors::extract_vendors(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_trainers(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::trainers::TrainerRecord>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::trainers::extract_trainers(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_waypoints(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::waypoints::WaypointPath>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::waypoints::extract_waypoints(&session, flavor)).await
+}
+
+#[tauri::command]
+async fn extract_creature_texts(
+    session_id: String,
+    flavor: extract::sql::SqlFlavor,
+    app: AppHandle,
+) -> Result<extract::SqlExport<extract::texts::CreatureTextRecord>, String> {
+    let session = session_snapshot(&app, &session_id)?;
+    blocking(move || extract::texts::extract_creature_texts(&session, flavor)).await
+}
+
+/// Loot rates over open sessions and saved session files; the files are
+/// read for the report only and not opened as sessions.
+#[tauri::command]
+async fn analyze_loot(
+    session_ids: Vec<String>,
+    file_paths: Vec<String>,
+    app: AppHandle,
+) -> Result<analysis::loot::LootReport, String> {
+    let open = session_ids
+        .iter()
+        .map(|id| session_snapshot(&app, id))
+        .collect::<Result<Vec<Arc<Session>>, String>>()?;
+    blocking(move || {
+        let loaded = file_paths
+            .iter()
+            .map(|path| {
+                session_store::load_session_file(&PathBuf::from(path)).map(session_from_file)
+            })
+            .collect::<Result<Vec<Session>, String>>()?;
+        let mut selected: Vec<&Session> = open.iter().map(|session| &**session).collect();
+        selected.extend(loaded.iter());
+        Ok(analysis::loot::loot_report(&selected))
+    })
+    .await
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let app_state = Arc::new(AppState::new());
@@ -349,10 +908,16 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(app_state)
+        .manage(CaptureLink::default())
         .invoke_handler(tauri::generate_handler![
             discover_processes,
             attach_process,
             detach_process,
+            pause_capture,
+            resume_capture,
+            set_opcode_filter,
+            get_capture_status,
+            unload_capture,
             get_status,
             get_sessions,
             get_active_session_id,
@@ -364,9 +929,31 @@ pub fn run() {
             get_packet_summaries,
             get_packets,
             get_packet_detail,
+            get_packet_window,
+            expand_packet_run,
+            query_packets,
+            search_bytes,
+            find_guid,
             save_session_cmd,
             list_saved_sessions,
             load_session_cmd,
+            get_parse_coverage,
+            get_parse_findings,
+            get_session_stats,
+            get_timeline,
+            export_timeline_csv,
+            correlate_requests,
+            extract_creature_spawns,
+            extract_gameobject_spawns,
+            extract_quests,
+            extract_templates,
+            diff_templates,
+            extract_gossip,
+            extract_vendors,
+            extract_trainers,
+            extract_waypoints,
+            extract_creature_texts,
+            analyze_loot,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
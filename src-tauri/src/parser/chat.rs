@@ -0,0 +1,331 @@
+//! Chat messages and emotes.
+
+use super::guid::{high_guid, HighGuid};
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+/// `CHAT_MSG_CHANNEL`, whose body carries the channel name.
+fn is_channel(build: u32, chat_type: u8) -> bool {
+    chat_type == if build == 5875 { 0x0E } else { 0x11 }
+}
+
+/// Chat types a creature speaks with. The numbering moved between builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonsterChat {
+    Say,
+    Party,
+    Yell,
+    Whisper,
+    Emote,
+    BossEmote,
+    BossWhisper,
+}
+
+impl MonsterChat {
+    pub fn from_chat_type(build: u32, chat_type: u8) -> Option<Self> {
+        use MonsterChat::*;
+        match (build, chat_type) {
+            (5875, 0x0B) => Some(Say),
+            (5875, 0x0C) => Some(Yell),
+            (5875, 0x0D) => Some(Emote),
+            (5875, 0x1A) => Some(Whisper),
+            (5875, 0x59) => Some(BossWhisper),
+            (5875, 0x5A) => Some(BossEmote),
+            (5875, _) => None,
+            (_, 0x0C) => Some(Say),
+            (_, 0x0D) => Some(Party),
+            (_, 0x0E) => Some(Yell),
+            (_, 0x0F) => Some(Whisper),
+            (_, 0x10) => Some(Emote),
+            (8606, 0x29) => Some(BossWhisper),
+            (8606, 0x2A) => Some(BossEmote),
+            (_, 0x29) => Some(BossEmote),
+            (_, 0x2A) => Some(BossWhisper),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    pub chat_type: u8,
+    pub language: u32,
+    /// Zero where the build leaves it out: vanilla monster emotes and
+    /// whispers only name the speaker.
+    pub sender: u64,
+    /// Monster chat only.
+    pub sender_name: Option<String>,
+    pub target: u64,
+    pub channel: Option<String>,
+    pub text: String,
+    pub chat_tag: u8,
+}
+
+/// `SMSG_MESSAGECHAT`.
+pub fn read_message_chat(r: &mut PacketReader, build: u32) -> Result<ChatMessage, ParseError> {
+    let chat_type = r.u8("chat_type")?;
+    let language = r.u32("language")?;
+    let monster = MonsterChat::from_chat_type(build, chat_type);
+    let mut message = ChatMessage {
+        chat_type,
+        language,
+        sender: 0,
+        sender_name: None,
+        target: 0,
+        channel: None,
+        text: String::new(),
+        chat_tag: 0,
+    };
+
+    if build == 5875 {
+        match monster {
+            Some(MonsterChat::Say | MonsterChat::Yell) => {
+                message.sender = r.guid("sender")?;
+                r.u32("sender_name_length")?;
+                message.sender_name = Some(r.cstring("sender_name")?);
+                message.target = r.guid("target")?;
+            }
+            Some(_) => {
+                r.u32("sender_name_length")?;
+                message.sender_name = Some(r.cstring("sender_name")?);
+                message.target = r.guid("target")?;
+            }
+            // Say, party and yell repeat the sender.
+            None if matches!(chat_type, 0x00 | 0x01 | 0x05) => {
+                message.sender = r.guid("sender")?;
+                r.guid("sender")?;
+            }
+            None if is_channel(build, chat_type) => {
+                message.channel = Some(r.cstring("channel")?);
+                r.u32("player_rank")?;
+                message.sender = r.guid("sender")?;
+            }
+            None => message.sender = r.guid("sender")?,
+        }
+    } else {
+        let wotlk = build >= 12340;
+        message.sender = r.guid("sender")?;
+        r.u32("unknown")?;
+        if monster.is_some() {
+            r.u32("sender_name_length")?;
+            message.sender_name = Some(r.cstring("sender_name")?);
+            message.target = r.guid("target")?;
+            let named = match high_guid(message.target) {
+                HighGuid::Player => false,
+                HighGuid::Pet => !wotlk,
+                _ => message.target != 0,
+            };
+            if named {
+                r.u32("target_name_length")?;
+                r.cstring("target_name")?;
+            }
+        } else if wotlk && chat_type == 0x08 {
+            // CHAT_MSG_WHISPER_FOREIGN
+            r.u32("sender_name_length")?;
+            message.sender_name = Some(r.cstring("sender_name")?);
+            message.target = r.guid("target")?;
+        } else if wotlk && matches!(chat_type, 0x24..=0x26) {
+            // CHAT_MSG_BG_SYSTEM_*
+            message.target = r.guid("target")?;
+            if message.target != 0 && high_guid(message.target) != HighGuid::Player {
+                r.u32("target_name_length")?;
+                r.cstring("target_name")?;
+            }
+        } else {
+            if is_channel(build, chat_type) {
+                message.channel = Some(r.cstring("channel")?);
+            }
+            message.target = r.guid("target")?;
+        }
+    }
+
+    r.u32("text_length")?;
+    message.text = r.cstring("text")?;
+    message.chat_tag = r.u8("chat_tag")?;
+    // CHAT_MSG_ACHIEVEMENT and CHAT_MSG_GUILD_ACHIEVEMENT
+    if build >= 12340 && matches!(chat_type, 0x30 | 0x31) {
+        r.u32("achievement")?;
+    }
+    Ok(message)
+}
+
+/// `SMSG_EMOTE`: a unit playing an animation. Same layout in every build.
+#[derive(Debug, Clone, Serialize)]
+pub struct Emote {
+    pub emote: u32,
+    pub guid: u64,
+}
+
+pub fn read_emote(r: &mut PacketReader) -> Result<Emote, ParseError> {
+    Ok(Emote {
+        emote: r.u32("emote")?,
+        guid: r.guid("guid")?,
+    })
+}
+
+/// `SMSG_TEXT_EMOTE`: a `/bow`-style emote, shown as text and animated.
+#[derive(Debug, Clone, Serialize)]
+pub struct TextEmote {
+    pub guid: u64,
+    pub text_emote: u32,
+    pub emote_num: u32,
+    pub target_name: String,
+}
+
+pub fn read_text_emote(r: &mut PacketReader) -> Result<TextEmote, ParseError> {
+    let guid = r.guid("guid")?;
+    let text_emote = r.u32("text_emote")?;
+    let emote_num = r.u32("emote_num")?;
+    r.u32("target_name_length")?;
+    Ok(TextEmote {
+        guid,
+        text_emote,
+        emote_num,
+        target_name: r.cstring("target_name")?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::Bytes;
+
+    pub(crate) const BUILDS: [u32; 3] = [5875, 8606, 12340];
+
+    /// A creature line with no target. Vanilla leaves the sender GUID out
+    /// of everything but says and yells.
+    pub(crate) fn monster_chat(
+        build: u32,
+        kind: MonsterChat,
+        sender: u64,
+        name: &str,
+        text: &str,
+    ) -> Vec<u8> {
+        let chat_type = (0..=u8::MAX)
+            .find(|&t| MonsterChat::from_chat_type(build, t) == Some(kind))
+            .unwrap();
+        let mut b = Bytes::new();
+        b.u8(chat_type).u32(0);
+        if build >= 8606 {
+            b.guid(sender).u32(0);
+        } else if matches!(kind, MonsterChat::Say | MonsterChat::Yell) {
+            b.guid(sender);
+        }
+        b.u32(name.len() as u32 + 1).cstring(name).guid(0);
+        b.u32(text.len() as u32 + 1).cstring(text).u8(0).build()
+    }
+
+    pub(crate) fn emote(guid: u64, emote: u32) -> Vec<u8> {
+        Bytes::new().u32(emote).guid(guid).build()
+    }
+
+    pub(crate) fn text_emote(guid: u64, text_emote: u32) -> Vec<u8> {
+        Bytes::new()
+            .guid(guid)
+            .u32(text_emote)
+            .u32(0)
+            .u32(1)
+            .cstring("")
+            .build()
+    }
+
+    const GUID: u64 = 0xF130_0004_D200_0101;
+    const PLAYER: u64 = 0x42;
+
+    fn read_all(data: &[u8], build: u32) -> ChatMessage {
+        let mut r = PacketReader::new(data);
+        let message = read_message_chat(&mut r, build).unwrap();
+        assert_eq!(r.remaining(), 0);
+        message
+    }
+
+    #[test]
+    fn chat_types_map_per_build() {
+        use MonsterChat::*;
+        assert_eq!(MonsterChat::from_chat_type(5875, 0x0C), Some(Yell));
+        assert_eq!(MonsterChat::from_chat_type(8606, 0x0C), Some(Say));
+        assert_eq!(MonsterChat::from_chat_type(8606, 0x29), Some(BossWhisper));
+        assert_eq!(MonsterChat::from_chat_type(12340, 0x29), Some(BossEmote));
+        assert_eq!(MonsterChat::from_chat_type(12340, 0x00), None);
+    }
+
+    #[test]
+    fn reads_monster_chat() {
+        for build in BUILDS {
+            let data = monster_chat(build, MonsterChat::Yell, GUID, "Hogger", "Grrr...");
+            let message = read_all(&data, build);
+            assert_eq!(message.sender, GUID, "build {}", build);
+            assert_eq!(message.sender_name.as_deref(), Some("Hogger"));
+            assert_eq!((message.text.as_str(), message.target), ("Grrr...", 0));
+
+            let data = monster_chat(build, MonsterChat::Emote, GUID, "Hogger", "roars.");
+            let message = read_all(&data, build);
+            let sender = if build == 5875 { 0 } else { GUID };
+            assert_eq!(message.sender, sender);
+            assert_eq!(message.sender_name.as_deref(), Some("Hogger"));
+        }
+    }
+
+    #[test]
+    fn named_targets_are_skipped() {
+        let mut b = Bytes::new();
+        b.u8(0x0C).u32(0).guid(GUID).u32(0).u32(7).cstring("Hogger");
+        b.guid(GUID)
+            .u32(7)
+            .cstring("Hogger")
+            .u32(3)
+            .cstring("Hi")
+            .u8(0);
+        let message = read_all(&b.build(), 12340);
+        assert_eq!((message.target, message.text.as_str()), (GUID, "Hi"));
+    }
+
+    #[test]
+    fn reads_player_chat() {
+        // Vanilla says repeat the sender.
+        let data = Bytes::new()
+            .u8(0x00)
+            .u32(7)
+            .guid(PLAYER)
+            .guid(PLAYER)
+            .u32(6)
+            .cstring("hello")
+            .u8(0)
+            .build();
+        let message = read_all(&data, 5875);
+        assert_eq!((message.sender, message.language), (PLAYER, 7));
+        assert_eq!(
+            (message.text.as_str(), message.sender_name),
+            ("hello", None)
+        );
+
+        let data = Bytes::new()
+            .u8(0x11)
+            .u32(0)
+            .guid(PLAYER)
+            .u32(0)
+            .cstring("General")
+            .guid(0)
+            .u32(3)
+            .cstring("lf")
+            .u8(0)
+            .build();
+        let message = read_all(&data, 8606);
+        assert_eq!(message.channel.as_deref(), Some("General"));
+    }
+
+    #[test]
+    fn reads_emotes() {
+        let e = read_emote(&mut PacketReader::new(&emote(GUID, 5))).unwrap();
+        assert_eq!((e.guid, e.emote), (GUID, 5));
+        let data = text_emote(GUID, 101);
+        let mut r = PacketReader::new(&data);
+        let e = read_text_emote(&mut r).unwrap();
+        assert_eq!(r.remaining(), 0);
+        assert_eq!(
+            (e.guid, e.text_emote, e.target_name.as_str()),
+            (GUID, 101, "")
+        );
+    }
+}
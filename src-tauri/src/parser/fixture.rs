@@ -0,0 +1,199 @@
+//! Packet payloads built byte by byte, for the decoder and extractor tests.
+
+use super::reader::Vector3;
+use crate::capture::packets::get_opcode_name;
+use crate::state::{Direction, Packet, Session};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// A little-endian packet body under construction.
+#[derive(Default)]
+pub struct Bytes(Vec<u8>);
+
+impl Bytes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn build(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.bytes(&[value])
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i32(&mut self, value: i32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn f32(&mut self, value: f32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn vector3(&mut self, v: Vector3) -> &mut Self {
+        self.f32(v.x).f32(v.y).f32(v.z)
+    }
+
+    pub fn guid(&mut self, guid: u64) -> &mut Self {
+        self.u64(guid)
+    }
+
+    pub fn packed_guid(&mut self, guid: u64) -> &mut Self {
+        let bytes = guid.to_le_bytes();
+        let mask = (0..8)
+            .filter(|&i| bytes[i] != 0)
+            .fold(0u8, |m, i| m | 1 << i);
+        self.u8(mask);
+        for byte in bytes.into_iter().filter(|&b| b != 0) {
+            self.u8(byte);
+        }
+        self
+    }
+
+    pub fn cstring(&mut self, text: &str) -> &mut Self {
+        self.bytes(text.as_bytes()).u8(0)
+    }
+
+    /// The head of an update packet holding `count` blocks.
+    pub fn update_header(&mut self, build: u32, count: u32) -> &mut Self {
+        self.u32(count);
+        if build < 12340 {
+            self.u8(0);
+        }
+        self
+    }
+
+    /// A `CREATE_OBJECT` block up to its movement part.
+    pub fn create_header(&mut self, guid: u64, object_type: u8) -> &mut Self {
+        self.u8(2).packed_guid(guid).u8(object_type)
+    }
+
+    /// A living movement part: a unit standing still at `position`.
+    pub fn living(&mut self, build: u32, position: Vector3, orientation: f32) -> &mut Self {
+        const LIVING: u8 = 0x20;
+        match build {
+            5875 => self.u8(LIVING).u32(0).u32(0),
+            8606 => self.u8(LIVING).u32(0).u8(0).u32(0),
+            _ => self.u16(LIVING as u16).u32(0).u16(0).u32(0),
+        };
+        self.vector3(position).f32(orientation).u32(0);
+        let speeds = match build {
+            5875 => 6,
+            8606 => 8,
+            _ => 9,
+        };
+        for _ in 0..speeds {
+            self.f32(2.5);
+        }
+        self
+    }
+
+    /// A stationary movement part, with the WotLK packed rotation if given.
+    pub fn stationary(
+        &mut self,
+        build: u32,
+        position: Vector3,
+        orientation: f32,
+        rotation: Option<i64>,
+    ) -> &mut Self {
+        const HAS_POSITION: u8 = 0x40;
+        const ROTATION: u16 = 0x0200;
+        if build < 12340 {
+            return self.u8(HAS_POSITION).vector3(position).f32(orientation);
+        }
+        let flags = HAS_POSITION as u16 | rotation.map_or(0, |_| ROTATION);
+        self.u16(flags).vector3(position).f32(orientation);
+        if let Some(packed) = rotation {
+            self.i64(packed);
+        }
+        self
+    }
+
+    /// An update mask setting `fields`, then their values.
+    pub fn update_mask(&mut self, fields: &[(u32, u32)]) -> &mut Self {
+        let mut fields = fields.to_vec();
+        fields.sort_by_key(|&(index, _)| index);
+        let blocks = fields.last().map_or(0, |&(index, _)| index / 32 + 1);
+        let mut mask = vec![0u32; blocks as usize];
+        for &(index, _) in &fields {
+            mask[(index / 32) as usize] |= 1 << (index % 32);
+        }
+        self.u8(blocks as u8);
+        for word in mask {
+            self.u32(word);
+        }
+        for (_, value) in fields {
+            self.u32(value);
+        }
+        self
+    }
+}
+
+pub fn vector(x: f32, y: f32, z: f32) -> Vector3 {
+    Vector3 { x, y, z }
+}
+
+/// An `SMSG_COMPRESSED_UPDATE_OBJECT` body holding `inner`.
+pub fn compressed(inner: &[u8]) -> Vec<u8> {
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(inner).unwrap();
+    let mut body = Bytes::new().u32(inner.len() as u32).build();
+    body.extend(zlib.finish().unwrap());
+    body
+}
+
+/// A packet with the opcode `name` has in `build`. `CMSG_` packets go to the
+/// server and everything else comes from it.
+pub fn packet(build: u32, id: usize, name: &str, data: Vec<u8>) -> Packet {
+    let opcode = (0..0x1000)
+        .find(|&opcode| get_opcode_name(build, opcode) == name)
+        .unwrap_or_else(|| panic!("{} has no opcode in build {}", name, build));
+    let direction = if name.starts_with("CMSG_") {
+        Direction::ClientToServer
+    } else {
+        Direction::ServerToClient
+    };
+    Packet {
+        id,
+        timestamp: id as u32 * 1000,
+        direction,
+        opcode,
+        opcode_name: name.to_string(),
+        size: data.len(),
+        data,
+    }
+}
+
+/// A session on `build` holding `packets` in order, one second apart.
+pub fn session(build: u32, packets: Vec<(&str, Vec<u8>)>) -> Session {
+    let mut session = Session::new("fixture");
+    session.build = Some(build);
+    for (id, (name, data)) in packets.into_iter().enumerate() {
+        session.packets.push(packet(build, id, name, data));
+    }
+    session.next_packet_id = session.packets.len();
+    session
+}
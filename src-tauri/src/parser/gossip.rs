@@ -0,0 +1,385 @@
+//! Gossip dialogs: menus, option selection, NPC texts and points of interest.
+
+use super::quests::QuestEmote;
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GossipOption {
+    /// Index the client echoes back in `CMSG_GOSSIP_SELECT_OPTION`.
+    pub id: u32,
+    pub icon: u8,
+    /// The option asks for a code before it is sent.
+    pub coded: bool,
+    /// TBC and later.
+    pub box_money: u32,
+    pub text: String,
+    /// TBC and later.
+    pub box_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GossipQuest {
+    pub quest_id: u32,
+    pub icon: u32,
+    pub level: i32,
+    /// WotLK only.
+    pub flags: u32,
+    /// WotLK only.
+    pub repeatable: bool,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GossipMessage {
+    pub guid: u64,
+    /// Not sent by vanilla servers.
+    pub menu_id: Option<u32>,
+    pub text_id: u32,
+    pub options: Vec<GossipOption>,
+    pub quests: Vec<GossipQuest>,
+}
+
+pub fn read_gossip_message(r: &mut PacketReader, build: u32) -> Result<GossipMessage, ParseError> {
+    let tbc = build >= 8606;
+    let wotlk = build >= 12340;
+
+    let guid = r.guid("guid")?;
+    let menu_id = if tbc { Some(r.u32("menu_id")?) } else { None };
+    let text_id = r.u32("text_id")?;
+    let option_count = r.u32("option_count")?;
+    let mut options = Vec::new();
+    for _ in 0..option_count {
+        let id = r.u32("option_id")?;
+        let icon = r.u8("option_icon")?;
+        let coded = r.bool("option_coded")?;
+        let box_money = if tbc { r.u32("box_money")? } else { 0 };
+        let text = r.cstring("option_text")?;
+        let box_text = if tbc {
+            r.cstring("box_text")?
+        } else {
+            String::new()
+        };
+        options.push(GossipOption {
+            id,
+            icon,
+            coded,
+            box_money,
+            text,
+            box_text,
+        });
+    }
+    let quest_count = r.u32("quest_count")?;
+    let mut quests = Vec::new();
+    for _ in 0..quest_count {
+        let quest_id = r.u32("quest_id")?;
+        let icon = r.u32("quest_icon")?;
+        let level = r.i32("quest_level")?;
+        let (flags, repeatable) = if wotlk {
+            (r.u32("quest_flags")?, r.bool("quest_repeatable")?)
+        } else {
+            (0, false)
+        };
+        quests.push(GossipQuest {
+            quest_id,
+            icon,
+            level,
+            flags,
+            repeatable,
+            title: r.cstring("quest_title")?,
+        });
+    }
+    Ok(GossipMessage {
+        guid,
+        menu_id,
+        text_id,
+        options,
+        quests,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GossipSelectOption {
+    pub guid: u64,
+    /// Not sent by vanilla clients.
+    pub menu_id: Option<u32>,
+    pub option: u32,
+    /// Only present for coded options.
+    pub code: Option<String>,
+}
+
+pub fn read_gossip_select_option(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<GossipSelectOption, ParseError> {
+    let guid = r.guid("guid")?;
+    let menu_id = if build >= 8606 {
+        Some(r.u32("menu_id")?)
+    } else {
+        None
+    };
+    let option = r.u32("option")?;
+    let code = if r.remaining() > 0 {
+        Some(r.cstring("code")?)
+    } else {
+        None
+    };
+    Ok(GossipSelectOption {
+        guid,
+        menu_id,
+        option,
+        code,
+    })
+}
+
+/// One of the eight weighted variants of an NPC text.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NpcTextEntry {
+    pub probability: f32,
+    /// Male and female versions.
+    pub texts: [String; 2],
+    pub language: u32,
+    pub emotes: Vec<QuestEmote>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NpcText {
+    pub text_id: u32,
+    pub entries: Vec<NpcTextEntry>,
+}
+
+pub fn read_npc_text_update(r: &mut PacketReader) -> Result<NpcText, ParseError> {
+    let text_id = r.u32("text_id")?;
+    let mut entries = Vec::new();
+    for _ in 0..8 {
+        let probability = r.f32("probability")?;
+        let texts = [r.cstring("text_male")?, r.cstring("text_female")?];
+        let language = r.u32("language")?;
+        let mut emotes = Vec::new();
+        for _ in 0..3 {
+            let delay = r.u32("emote_delay")?;
+            let emote = r.u32("emote")?;
+            emotes.push(QuestEmote { emote, delay });
+        }
+        entries.push(NpcTextEntry {
+            probability,
+            texts,
+            language,
+            emotes,
+        });
+    }
+    Ok(NpcText { text_id, entries })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GossipPoi {
+    pub flags: u32,
+    pub x: f32,
+    pub y: f32,
+    pub icon: u32,
+    pub importance: u32,
+    pub name: String,
+}
+
+pub fn read_gossip_poi(r: &mut PacketReader) -> Result<GossipPoi, ParseError> {
+    Ok(GossipPoi {
+        flags: r.u32("flags")?,
+        x: r.f32("x")?,
+        y: r.f32("y")?,
+        icon: r.u32("icon")?,
+        importance: r.u32("importance")?,
+        name: r.cstring("name")?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::Bytes;
+
+    pub(crate) const BUILDS: [u32; 3] = [5875, 8606, 12340];
+    /// Innkeeper Farley.
+    pub(crate) const NPC: u64 = 0xF130_0001_2700_0011;
+
+    /// A menu whose options carry their id as icon and, from TBC on, a
+    /// 10 copper box on option 1.
+    pub(crate) fn message(
+        build: u32,
+        guid: u64,
+        menu_id: u32,
+        text_id: u32,
+        options: &[(u32, &str)],
+        quests: &[(u32, &str)],
+    ) -> Vec<u8> {
+        let (wotlk, tbc) = (build >= 12340, build >= 8606);
+        let mut b = Bytes::new();
+        b.guid(guid);
+        if tbc {
+            b.u32(menu_id);
+        }
+        b.u32(text_id).u32(options.len() as u32);
+        for &(id, text) in options {
+            b.u32(id).u8(id as u8).u8(0);
+            if tbc {
+                b.u32(if id == 1 { 10 } else { 0 });
+            }
+            b.cstring(text);
+            if tbc {
+                b.cstring(if id == 1 { "Pay up?" } else { "" });
+            }
+        }
+        b.u32(quests.len() as u32);
+        for &(quest_id, title) in quests {
+            b.u32(quest_id).u32(2).i32(10);
+            if wotlk {
+                b.u32(0x8).u8(1);
+            }
+            b.cstring(title);
+        }
+        b.build()
+    }
+
+    pub(crate) fn select(build: u32, guid: u64, menu_id: u32, option: u32) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.guid(guid);
+        if build >= 8606 {
+            b.u32(menu_id);
+        }
+        b.u32(option).build()
+    }
+
+    /// A text with one variant said with a bow after a second.
+    pub(crate) fn npc_text(text_id: u32, text: &str) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.u32(text_id);
+        for i in 0..8 {
+            if i == 0 {
+                b.f32(1.0).cstring(text).cstring(text).u32(7);
+                b.u32(1000).u32(2);
+            } else {
+                b.f32(0.0).cstring("").cstring("").u32(0);
+                b.u32(0).u32(0);
+            }
+            b.u32(0).u32(0).u32(0).u32(0);
+        }
+        b.build()
+    }
+
+    pub(crate) fn poi(name: &str) -> Vec<u8> {
+        Bytes::new()
+            .u32(99)
+            .f32(-8811.5)
+            .f32(626.25)
+            .u32(7)
+            .u32(0)
+            .cstring(name)
+            .build()
+    }
+
+    fn read_all<T>(
+        data: &[u8],
+        read: impl FnOnce(&mut PacketReader) -> Result<T, ParseError>,
+    ) -> T {
+        let mut r = PacketReader::new(data);
+        let value = read(&mut r).unwrap();
+        assert_eq!(r.remaining(), 0);
+        value
+    }
+
+    #[test]
+    fn reads_gossip_messages() {
+        for build in BUILDS {
+            let data = message(
+                build,
+                NPC,
+                1291,
+                2593,
+                &[
+                    (0, "I want to browse your goods."),
+                    (1, "Make this inn your home."),
+                ],
+                &[(783, "A Threat Within")],
+            );
+            let m = read_all(&data, |r| read_gossip_message(r, build));
+            assert_eq!(m.guid, NPC, "build {}", build);
+            assert_eq!(m.menu_id, (build >= 8606).then_some(1291));
+            assert_eq!(m.text_id, 2593);
+            assert_eq!(m.options.len(), 2);
+            assert_eq!(m.options[1].icon, 1);
+            assert_eq!(m.options[1].text, "Make this inn your home.");
+            assert!(!m.options[1].coded);
+            if build >= 8606 {
+                assert_eq!(m.options[1].box_money, 10);
+                assert_eq!(m.options[1].box_text, "Pay up?");
+            } else {
+                assert_eq!(m.options[1].box_money, 0);
+                assert_eq!(m.options[1].box_text, "");
+            }
+            let quest = &m.quests[0];
+            assert_eq!((quest.quest_id, quest.icon, quest.level), (783, 2, 10));
+            assert_eq!(quest.title, "A Threat Within");
+            assert_eq!(
+                (quest.flags, quest.repeatable),
+                if build >= 12340 {
+                    (0x8, true)
+                } else {
+                    (0, false)
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_messages_fail() {
+        for build in BUILDS {
+            let mut data = message(build, NPC, 1291, 2593, &[(0, "Hello")], &[]);
+            data.truncate(data.len() - 5);
+            let mut r = PacketReader::new(&data);
+            assert!(
+                read_gossip_message(&mut r, build).is_err(),
+                "build {}",
+                build
+            );
+        }
+    }
+
+    #[test]
+    fn reads_option_selections() {
+        for build in BUILDS {
+            let data = select(build, NPC, 1291, 1);
+            let s = read_all(&data, |r| read_gossip_select_option(r, build));
+            assert_eq!(s.guid, NPC);
+            assert_eq!(s.menu_id, (build >= 8606).then_some(1291));
+            assert_eq!((s.option, s.code), (1, None));
+
+            let mut coded = select(build, NPC, 1291, 2);
+            coded.extend(b"1234\0");
+            let s = read_all(&coded, |r| read_gossip_select_option(r, build));
+            assert_eq!(s.code.as_deref(), Some("1234"));
+        }
+    }
+
+    #[test]
+    fn reads_npc_texts_and_pois() {
+        let text = read_all(&npc_text(2593, "Welcome to the inn."), read_npc_text_update);
+        assert_eq!(text.text_id, 2593);
+        assert_eq!(text.entries.len(), 8);
+        let first = &text.entries[0];
+        assert_eq!(first.probability, 1.0);
+        assert_eq!(first.texts, ["Welcome to the inn.", "Welcome to the inn."]);
+        assert_eq!(first.language, 7);
+        assert_eq!(
+            first.emotes[0],
+            QuestEmote {
+                emote: 2,
+                delay: 1000
+            }
+        );
+        assert!(text.entries[7].texts.iter().all(String::is_empty));
+
+        let p = read_all(&poi("Stormwind Bank"), read_gossip_poi);
+        assert_eq!((p.flags, p.x, p.y), (99, -8811.5, 626.25));
+        assert_eq!((p.icon, p.importance), (7, 0));
+        assert_eq!(p.name, "Stormwind Bank");
+    }
+}
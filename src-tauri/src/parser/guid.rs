@@ -0,0 +1,45 @@
+use serde::Serialize;
+
+/// Object kind encoded in the top 16 bits of a GUID. The layout is shared by
+/// every build the decoders support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HighGuid {
+    Player,
+    Item,
+    Creature,
+    Pet,
+    Vehicle,
+    GameObject,
+    Transport,
+    MoTransport,
+    DynamicObject,
+    Corpse,
+    Unknown,
+}
+
+pub fn high_guid(guid: u64) -> HighGuid {
+    match (guid >> 48) as u16 {
+        0x0000 => HighGuid::Player,
+        0x4000 => HighGuid::Item,
+        0xF130 => HighGuid::Creature,
+        0xF140 => HighGuid::Pet,
+        0xF150 => HighGuid::Vehicle,
+        0xF110 => HighGuid::GameObject,
+        0xF120 => HighGuid::Transport,
+        0x1FC0 => HighGuid::MoTransport,
+        0xF100 => HighGuid::DynamicObject,
+        0xF101 => HighGuid::Corpse,
+        _ => HighGuid::Unknown,
+    }
+}
+
+/// Template entry embedded in creature, vehicle and gameobject GUIDs.
+pub fn guid_entry(guid: u64) -> Option<u32> {
+    match high_guid(guid) {
+        HighGuid::Creature | HighGuid::Vehicle | HighGuid::GameObject => {
+            Some(((guid >> 24) & 0x00FF_FFFF) as u32)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,156 @@
+//! Loot windows and the packets that change them while open.
+
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+/// `loot_type` of a response that only carries an error code.
+pub const LOOT_NONE: u8 = 0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LootItem {
+    pub slot: u8,
+    pub item: u32,
+    pub count: u32,
+    pub display_id: u32,
+    pub random_suffix: u32,
+    pub random_property_id: u32,
+    /// 0 lootable, 1 roll, 2 master loot, 3 locked, 4 owner only.
+    pub slot_type: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LootResponse {
+    /// The corpse, gameobject or item being looted.
+    pub guid: u64,
+    pub loot_type: u8,
+    /// Set when the server refused to open the window.
+    pub error: Option<u8>,
+    pub gold: u32,
+    pub items: Vec<LootItem>,
+}
+
+/// Same layout in every supported build.
+pub fn read_loot_response(r: &mut PacketReader) -> Result<LootResponse, ParseError> {
+    let guid = r.guid("guid")?;
+    let loot_type = r.u8("loot_type")?;
+    if loot_type == LOOT_NONE {
+        return Ok(LootResponse {
+            guid,
+            loot_type,
+            error: Some(r.u8("error")?),
+            gold: 0,
+            items: Vec::new(),
+        });
+    }
+    let gold = r.u32("gold")?;
+    let count = r.u8("item_count")?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        items.push(LootItem {
+            slot: r.u8("slot")?,
+            item: r.u32("item")?,
+            count: r.u32("count")?,
+            display_id: r.u32("display_id")?,
+            random_suffix: r.u32("random_suffix")?,
+            random_property_id: r.u32("random_property_id")?,
+            slot_type: r.u8("slot_type")?,
+        });
+    }
+    Ok(LootResponse {
+        guid,
+        loot_type,
+        error: None,
+        gold,
+        items,
+    })
+}
+
+/// `SMSG_LOOT_MONEY_NOTIFY`: the looter's share of the gold.
+pub fn read_loot_money_notify(r: &mut PacketReader, build: u32) -> Result<u32, ParseError> {
+    let amount = r.u32("amount")?;
+    if build >= 12340 {
+        r.bool("solo")?;
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::Bytes;
+
+    /// A window of `loot_type` holding `gold` and (item, count) stacks.
+    pub(crate) fn loot_response(
+        guid: u64,
+        loot_type: u8,
+        gold: u32,
+        items: &[(u32, u32)],
+    ) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.guid(guid).u8(loot_type).u32(gold).u8(items.len() as u8);
+        for (slot, &(item, count)) in items.iter().enumerate() {
+            b.u8(slot as u8)
+                .u32(item)
+                .u32(count)
+                .u32(item + 1000)
+                .u32(0)
+                .u32(0)
+                .u8(0);
+        }
+        b.build()
+    }
+
+    /// A refused window.
+    pub(crate) fn loot_error(guid: u64) -> Vec<u8> {
+        Bytes::new().guid(guid).u8(LOOT_NONE).u8(4).build()
+    }
+
+    const CORPSE: u64 = 0xF130_0004_D200_0101;
+
+    #[test]
+    fn reads_loot_windows() {
+        let data = loot_response(CORPSE, 1, 57, &[(2589, 2), (6070, 1)]);
+        let mut r = PacketReader::new(&data);
+        let window = read_loot_response(&mut r).unwrap();
+        assert_eq!(r.remaining(), 0);
+        assert_eq!(
+            (window.guid, window.loot_type, window.error),
+            (CORPSE, 1, None)
+        );
+        assert_eq!(window.gold, 57);
+        assert_eq!(
+            window.items[1],
+            LootItem {
+                slot: 1,
+                item: 6070,
+                count: 1,
+                display_id: 7070,
+                random_suffix: 0,
+                random_property_id: 0,
+                slot_type: 0,
+            }
+        );
+
+        let data = loot_error(CORPSE);
+        let mut r = PacketReader::new(&data);
+        let refused = read_loot_response(&mut r).unwrap();
+        assert_eq!(r.remaining(), 0);
+        assert_eq!((refused.loot_type, refused.error), (LOOT_NONE, Some(4)));
+        assert!(refused.items.is_empty());
+    }
+
+    #[test]
+    fn money_notify_has_a_solo_flag_in_wotlk() {
+        for build in [5875, 8606, 12340] {
+            let mut b = Bytes::new();
+            b.u32(125);
+            if build >= 12340 {
+                b.u8(1);
+            }
+            let data = b.build();
+            let mut r = PacketReader::new(&data);
+            assert_eq!(read_loot_money_notify(&mut r, build).unwrap(), 125);
+            assert_eq!(r.remaining(), 0, "build {}", build);
+        }
+    }
+}
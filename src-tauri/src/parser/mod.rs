@@ -0,0 +1,232 @@
+//! Rust-side packet decoders.
+//!
+//! The frontend has its own table-driven parser for display. This module
+//! decodes the packets the backend needs to reason about (coverage reports,
+//! extractors, ...) and reports how cleanly each payload was consumed.
+
+pub mod chat;
+#[cfg(test)]
+pub(crate) mod fixture;
+pub mod gossip;
+pub mod guid;
+pub mod loot;
+pub mod movement;
+pub mod npc;
+pub mod queries;
+pub mod quests;
+pub mod reader;
+pub mod templates;
+pub mod update_fields;
+pub mod update_object;
+pub mod world;
+
+use crate::capture::packets::get_opcode_name;
+use crate::state::{Direction, Packet};
+pub use reader::ParseError;
+use reader::{Finding, FindingKind, PacketReader};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Builds the Rust decoders know how to read.
+pub const SUPPORTED_BUILDS: &[u32] = &[5875, 8606, 12340];
+
+/// A decoded packet body, kept opaque until someone asks for its fields.
+pub trait Decoded {
+    fn to_json(&self) -> Value;
+}
+
+impl<T: Serialize> Decoded for T {
+    fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+/// A decoder reads one packet body for the given build. Callers that need
+/// typed values use the typed readers directly; the boxed result only
+/// serves generic consumers such as the query engine.
+pub type Decoder = fn(&mut PacketReader, u32) -> Result<Box<dyn Decoded>, ParseError>;
+
+fn boxed<T: Serialize + 'static>(value: T) -> Box<dyn Decoded> {
+    Box::new(value)
+}
+
+/// Resolve the opcode name for a packet, preferring the build's table and
+/// falling back to the name stamped at capture time.
+pub fn opcode_name(build: Option<u32>, packet: &Packet) -> &str {
+    match build.map(|b| get_opcode_name(b, packet.opcode)) {
+        Some(name) if name != "UNKNOWN" => name,
+        _ => &packet.opcode_name,
+    }
+}
+
+/// Look up the decoder for an opcode, if one exists for this build.
+pub fn decoder(build: u32, direction: Direction, name: &str) -> Option<Decoder> {
+    use Direction::{ClientToServer as Cmsg, ServerToClient as Smsg};
+
+    if !SUPPORTED_BUILDS.contains(&build) {
+        return None;
+    }
+
+    let decoder: Decoder = match (direction, name) {
+        (Cmsg, "CMSG_PING") => |r, _| queries::read_ping(r).map(boxed),
+        (Smsg, "SMSG_PONG") => |r, _| queries::read_pong(r).map(boxed),
+        (Cmsg, "CMSG_NAME_QUERY") => |r, _| queries::read_guid_request(r).map(boxed),
+        (Smsg, "SMSG_NAME_QUERY_RESPONSE") => {
+            |r, b| queries::read_name_query_response(r, b).map(boxed)
+        }
+        (Cmsg, "CMSG_QUERY_TIME") => |_, _| Ok(boxed(())),
+        (Smsg, "SMSG_QUERY_TIME_RESPONSE") => {
+            |r, b| queries::read_query_time_response(r, b).map(boxed)
+        }
+        (Cmsg, "CMSG_CREATURE_QUERY" | "CMSG_GAMEOBJECT_QUERY" | "CMSG_NPC_TEXT_QUERY") => {
+            |r, _| queries::read_entry_query(r).map(boxed)
+        }
+        (Cmsg, "CMSG_ITEM_QUERY_SINGLE") => |r, b| queries::read_item_query(r, b).map(boxed),
+        (Cmsg, "CMSG_QUEST_QUERY") => |r, _| queries::read_quest_query(r).map(boxed),
+        (
+            Cmsg,
+            "CMSG_GOSSIP_HELLO"
+            | "CMSG_QUESTGIVER_HELLO"
+            | "CMSG_QUESTGIVER_STATUS_QUERY"
+            | "CMSG_LIST_INVENTORY"
+            | "CMSG_TRAINER_LIST"
+            | "CMSG_LOOT"
+            | "CMSG_LOOT_RELEASE",
+        ) => |r, _| queries::read_guid_request(r).map(boxed),
+        (Smsg, "SMSG_QUESTGIVER_STATUS") => |r, b| queries::read_questgiver_status(r, b).map(boxed),
+        (Smsg, "SMSG_QUEST_QUERY_RESPONSE") => {
+            |r, b| quests::read_quest_query_response(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_QUESTGIVER_QUEST_DETAILS") => {
+            |r, b| quests::read_quest_details(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_QUESTGIVER_REQUEST_ITEMS") => {
+            |r, b| quests::read_quest_request_items(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_QUESTGIVER_OFFER_REWARD") => {
+            |r, b| quests::read_quest_offer_reward(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_CREATURE_QUERY_RESPONSE") => {
+            |r, b| templates::read_creature_query_response(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_ITEM_QUERY_SINGLE_RESPONSE") => {
+            |r, b| templates::read_item_query_response(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_GAMEOBJECT_QUERY_RESPONSE") => {
+            |r, b| templates::read_gameobject_query_response(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_GOSSIP_MESSAGE") => |r, b| gossip::read_gossip_message(r, b).map(boxed),
+        (Cmsg, "CMSG_GOSSIP_SELECT_OPTION") => {
+            |r, b| gossip::read_gossip_select_option(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_GOSSIP_COMPLETE") => |_, _| Ok(boxed(())),
+        (Smsg, "SMSG_NPC_TEXT_UPDATE") => |r, _| gossip::read_npc_text_update(r).map(boxed),
+        (Smsg, "SMSG_GOSSIP_POI") => |r, _| gossip::read_gossip_poi(r).map(boxed),
+        (Smsg, "SMSG_LIST_INVENTORY") => |r, b| npc::read_list_inventory(r, b).map(boxed),
+        (Smsg, "SMSG_TRAINER_LIST") => |r, _| npc::read_trainer_list(r).map(boxed),
+        (Smsg, "SMSG_LOOT_RESPONSE") => |r, _| loot::read_loot_response(r).map(boxed),
+        (Smsg, "SMSG_LOOT_MONEY_NOTIFY") => |r, b| loot::read_loot_money_notify(r, b).map(boxed),
+        (Smsg, "SMSG_LOOT_REMOVED") | (Cmsg, "CMSG_AUTOSTORE_LOOT_ITEM") => {
+            |r, _| r.u8("slot").map(boxed)
+        }
+        (Smsg, "SMSG_LOOT_RELEASE_RESPONSE") => |r, _| {
+            r.guid("guid")?;
+            r.u8("unknown").map(boxed)
+        },
+        (Smsg, "SMSG_LOOT_CLEAR_MONEY") | (Cmsg, "CMSG_LOOT_MONEY") => |_, _| Ok(boxed(())),
+        (Smsg, "SMSG_UPDATE_OBJECT") => |r, b| update_object::read_update_object(r, b).map(boxed),
+        (Smsg, "SMSG_COMPRESSED_UPDATE_OBJECT") => {
+            |r, b| update_object::read_compressed_update_object(r, b).map(boxed)
+        }
+        (Smsg, "SMSG_DESTROY_OBJECT") => |r, b| update_object::read_destroy_object(r, b).map(boxed),
+        (Smsg, "SMSG_LOGIN_VERIFY_WORLD" | "SMSG_NEW_WORLD") => {
+            |r, _| world::read_world_position(r).map(boxed)
+        }
+        (Smsg, "SMSG_MESSAGECHAT") => |r, b| chat::read_message_chat(r, b).map(boxed),
+        (Smsg, "SMSG_EMOTE") => |r, _| chat::read_emote(r).map(boxed),
+        (Smsg, "SMSG_TEXT_EMOTE") => |r, _| chat::read_text_emote(r).map(boxed),
+        (Smsg, "SMSG_MONSTER_MOVE") => |r, b| movement::read_monster_move(r, b, false).map(boxed),
+        (Smsg, "SMSG_MONSTER_MOVE_TRANSPORT") => {
+            |r, b| movement::read_monster_move(r, b, true).map(boxed)
+        }
+        (Cmsg, n) if movement::MOVEMENT_INFO_OPCODES.contains(&n) => {
+            |r, b| movement::read_move_message(r, b, false).map(boxed)
+        }
+        (Smsg, n) if movement::MOVEMENT_INFO_OPCODES.contains(&n) => {
+            |r, b| movement::read_move_message(r, b, true).map(boxed)
+        }
+        _ => return None,
+    };
+    Some(decoder)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseStatus {
+    /// The decoder consumed the payload exactly.
+    Clean,
+    /// The decoder finished but left bytes unread.
+    TrailingBytes,
+    /// The decoder ran out of data or rejected a value.
+    Failed,
+    /// No decoder exists for this opcode and build.
+    NoDefinition,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseOutcome {
+    pub status: ParseStatus,
+    /// Offset where decoding stopped, successfully or not.
+    pub consumed: usize,
+    pub remaining: usize,
+    pub error: Option<ParseError>,
+    pub findings: Vec<Finding>,
+}
+
+/// Run the registered decoder for a packet and classify the result.
+pub fn parse_packet(build: Option<u32>, packet: &Packet) -> ParseOutcome {
+    let decode = build
+        .and_then(|b| decoder(b, packet.direction, opcode_name(build, packet)).map(|d| (b, d)));
+    let Some((build, decode)) = decode else {
+        return ParseOutcome {
+            status: ParseStatus::NoDefinition,
+            consumed: 0,
+            remaining: packet.data.len(),
+            error: None,
+            findings: Vec::new(),
+        };
+    };
+
+    let mut r = PacketReader::new(&packet.data);
+    let result = decode(&mut r, build);
+    let (consumed, remaining) = (r.position(), r.remaining());
+    let status = match (&result, remaining) {
+        (Err(_), _) => ParseStatus::Failed,
+        (Ok(_), 0) => ParseStatus::Clean,
+        (Ok(_), _) => ParseStatus::TrailingBytes,
+    };
+    if status == ParseStatus::TrailingBytes {
+        r.add_finding(Finding {
+            kind: FindingKind::TrailingBytes,
+            offset: consumed,
+            field: String::new(),
+            detail: format!("{} bytes left unread after offset {}", remaining, consumed),
+        });
+    }
+    ParseOutcome {
+        status,
+        consumed,
+        remaining,
+        error: result.err(),
+        findings: r.into_findings(),
+    }
+}
+
+/// The fields of a packet as JSON, if a decoder exists and reads it
+/// without error.
+pub fn decode_fields(build: Option<u32>, packet: &Packet) -> Option<Value> {
+    let build = build?;
+    let decode = decoder(build, packet.direction, opcode_name(Some(build), packet))?;
+    let decoded = decode(&mut PacketReader::new(&packet.data), build).ok()?;
+    Some(decoded.to_json())
+}
@@ -0,0 +1,539 @@
+use super::reader::{PacketReader, ParseError, Vector3};
+use serde::Serialize;
+
+/// `MSG_MOVE_*` opcodes whose body is a plain `MovementInfo`.
+pub const MOVEMENT_INFO_OPCODES: &[&str] = &[
+    "MSG_MOVE_START_FORWARD",
+    "MSG_MOVE_START_BACKWARD",
+    "MSG_MOVE_STOP",
+    "MSG_MOVE_START_STRAFE_LEFT",
+    "MSG_MOVE_START_STRAFE_RIGHT",
+    "MSG_MOVE_STOP_STRAFE",
+    "MSG_MOVE_JUMP",
+    "MSG_MOVE_START_TURN_LEFT",
+    "MSG_MOVE_START_TURN_RIGHT",
+    "MSG_MOVE_STOP_TURN",
+    "MSG_MOVE_START_PITCH_UP",
+    "MSG_MOVE_START_PITCH_DOWN",
+    "MSG_MOVE_STOP_PITCH",
+    "MSG_MOVE_SET_RUN_MODE",
+    "MSG_MOVE_SET_WALK_MODE",
+    "MSG_MOVE_FALL_LAND",
+    "MSG_MOVE_START_SWIM",
+    "MSG_MOVE_STOP_SWIM",
+    "MSG_MOVE_SET_FACING",
+    "MSG_MOVE_SET_PITCH",
+    "MSG_MOVE_HEARTBEAT",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransportInfo {
+    pub guid: u64,
+    pub position: Vector3,
+    pub orientation: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MovementInfo {
+    pub flags: u32,
+    pub time: u32,
+    pub position: Vector3,
+    pub orientation: f32,
+    pub transport: Option<TransportInfo>,
+    pub fall_time: u32,
+}
+
+/// A `MSG_MOVE_*` packet. The mover GUID is only present in server relays
+/// and, from WotLK on, in the client's own packets as well.
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveMessage {
+    pub guid: Option<u64>,
+    pub info: MovementInfo,
+}
+
+pub fn read_move_message(
+    r: &mut PacketReader,
+    build: u32,
+    from_server: bool,
+) -> Result<MoveMessage, ParseError> {
+    let guid = if from_server || build >= 12340 {
+        Some(r.packed_guid("guid")?)
+    } else {
+        None
+    };
+    let info = read_movement_info(r, build)?;
+    Ok(MoveMessage { guid, info })
+}
+
+pub fn read_movement_info(r: &mut PacketReader, build: u32) -> Result<MovementInfo, ParseError> {
+    match build {
+        5875 => read_movement_info_vanilla(r),
+        8606 => read_movement_info_tbc(r),
+        _ => read_movement_info_wotlk(r),
+    }
+}
+
+fn read_movement_info_vanilla(r: &mut PacketReader) -> Result<MovementInfo, ParseError> {
+    const ON_TRANSPORT: u32 = 0x0200_0000;
+    const SWIMMING: u32 = 0x0020_0000;
+    const JUMPING: u32 = 0x0000_2000;
+    const SPLINE_ELEVATION: u32 = 0x0400_0000;
+
+    let flags = r.u32("flags")?;
+    let time = r.u32("timestamp")?;
+    let position = r.vector3("position")?;
+    let orientation = r.f32("orientation")?;
+    let transport = if flags & ON_TRANSPORT != 0 {
+        let transport = TransportInfo {
+            guid: r.guid("transport_guid")?,
+            position: r.vector3("transport_position")?,
+            orientation: r.f32("transport_orientation")?,
+        };
+        r.u32("transport_timestamp")?;
+        Some(transport)
+    } else {
+        None
+    };
+    if flags & SWIMMING != 0 {
+        r.f32("pitch")?;
+    }
+    let fall_time = r.u32("fall_time")?;
+    if flags & JUMPING != 0 {
+        r.skip(16, "jump_info")?;
+    }
+    if flags & SPLINE_ELEVATION != 0 {
+        r.f32("spline_elevation")?;
+    }
+    Ok(MovementInfo {
+        flags,
+        time,
+        position,
+        orientation,
+        transport,
+        fall_time,
+    })
+}
+
+fn read_movement_info_tbc(r: &mut PacketReader) -> Result<MovementInfo, ParseError> {
+    const ON_TRANSPORT: u32 = 0x0000_0200;
+    const FALLING: u32 = 0x0000_1000;
+    const SWIMMING: u32 = 0x0020_0000;
+    const FLYING: u32 = 0x0200_0000;
+    const SPLINE_ELEVATION: u32 = 0x0400_0000;
+
+    let flags = r.u32("flags")?;
+    r.u8("extra_flags")?;
+    let time = r.u32("timestamp")?;
+    let position = r.vector3("position")?;
+    let orientation = r.f32("orientation")?;
+    let transport = if flags & ON_TRANSPORT != 0 {
+        let transport = TransportInfo {
+            guid: r.guid("transport_guid")?,
+            position: r.vector3("transport_position")?,
+            orientation: r.f32("transport_orientation")?,
+        };
+        r.u32("transport_timestamp")?;
+        Some(transport)
+    } else {
+        None
+    };
+    if flags & (SWIMMING | FLYING) != 0 {
+        r.f32("pitch")?;
+    }
+    let fall_time = r.u32("fall_time")?;
+    if flags & FALLING != 0 {
+        r.skip(16, "jump_info")?;
+    }
+    if flags & SPLINE_ELEVATION != 0 {
+        r.f32("spline_elevation")?;
+    }
+    Ok(MovementInfo {
+        flags,
+        time,
+        position,
+        orientation,
+        transport,
+        fall_time,
+    })
+}
+
+fn read_movement_info_wotlk(r: &mut PacketReader) -> Result<MovementInfo, ParseError> {
+    const ON_TRANSPORT: u32 = 0x0000_0200;
+    const FALLING: u32 = 0x0000_1000;
+    const SWIMMING: u32 = 0x0020_0000;
+    const FLYING: u32 = 0x0200_0000;
+    const SPLINE_ELEVATION: u32 = 0x0400_0000;
+    const ALWAYS_ALLOW_PITCHING: u16 = 0x0020;
+    const INTERPOLATED_MOVEMENT: u16 = 0x0400;
+
+    let flags = r.u32("flags")?;
+    let flags2 = r.u16("extra_flags")?;
+    let time = r.u32("timestamp")?;
+    let position = r.vector3("position")?;
+    let orientation = r.f32("orientation")?;
+    let transport = if flags & ON_TRANSPORT != 0 {
+        let transport = TransportInfo {
+            guid: r.packed_guid("transport_guid")?,
+            position: r.vector3("transport_position")?,
+            orientation: r.f32("transport_orientation")?,
+        };
+        r.u32("transport_timestamp")?;
+        r.u8("transport_seat")?;
+        if flags2 & INTERPOLATED_MOVEMENT != 0 {
+            r.u32("transport_timestamp2")?;
+        }
+        Some(transport)
+    } else {
+        None
+    };
+    if flags & (SWIMMING | FLYING) != 0 || flags2 & ALWAYS_ALLOW_PITCHING != 0 {
+        r.f32("pitch")?;
+    }
+    let fall_time = r.u32("fall_time")?;
+    if flags & FALLING != 0 {
+        r.skip(16, "jump_info")?;
+    }
+    if flags & SPLINE_ELEVATION != 0 {
+        r.f32("spline_elevation")?;
+    }
+    Ok(MovementInfo {
+        flags,
+        time,
+        position,
+        orientation,
+        transport,
+        fall_time,
+    })
+}
+
+/// Where a unit faces once its spline ends.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MoveFacing {
+    None,
+    Spot { point: Vector3 },
+    Target { guid: u64 },
+    Angle { angle: f32 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveTransport {
+    pub guid: u64,
+    /// WotLK only.
+    pub seat: Option<u8>,
+}
+
+/// `SMSG_MONSTER_MOVE` and `SMSG_MONSTER_MOVE_TRANSPORT`. On a transport,
+/// every position is relative to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MonsterMove {
+    pub guid: u64,
+    pub transport: Option<MoveTransport>,
+    pub start: Vector3,
+    pub spline_id: u32,
+    /// The unit halted at `start`; nothing follows in the packet.
+    pub stop: bool,
+    pub facing: MoveFacing,
+    pub spline_flags: u32,
+    pub walk: bool,
+    /// Catmull-Rom spline: the points are control points sent in full.
+    /// Otherwise the path is linear and all but the last point were packed
+    /// as offsets.
+    pub smooth: bool,
+    pub duration: u32,
+    /// Points after `start`, the destination last.
+    pub points: Vec<Vector3>,
+}
+
+pub fn read_monster_move(
+    r: &mut PacketReader,
+    build: u32,
+    on_transport: bool,
+) -> Result<MonsterMove, ParseError> {
+    // Vanilla and TBC have no walk flag, only one for running.
+    const RUNMODE: u32 = 0x0000_0100;
+    const FLYING_CLASSIC: u32 = 0x0000_0200;
+    const PARABOLIC: u32 = 0x0000_0800;
+    const WALKMODE: u32 = 0x0000_1000;
+    const FLYING: u32 = 0x0000_2000;
+    const CATMULLROM: u32 = 0x0004_0000;
+    const ANIMATION: u32 = 0x0020_0000;
+
+    let wotlk = build >= 12340;
+    let guid = r.packed_guid("guid")?;
+    let transport = if on_transport {
+        Some(MoveTransport {
+            guid: r.packed_guid("transport_guid")?,
+            seat: if wotlk {
+                Some(r.u8("transport_seat")?)
+            } else {
+                None
+            },
+        })
+    } else {
+        None
+    };
+    if wotlk {
+        r.u8("unknown")?;
+    }
+    let start = r.vector3("start")?;
+    let spline_id = r.u32("spline_id")?;
+    let facing = match r.u8_enum("move_type", &[0, 1, 2, 3, 4])? {
+        1 => {
+            return Ok(MonsterMove {
+                guid,
+                transport,
+                start,
+                spline_id,
+                stop: true,
+                facing: MoveFacing::None,
+                spline_flags: 0,
+                walk: false,
+                smooth: false,
+                duration: 0,
+                points: Vec::new(),
+            })
+        }
+        2 => MoveFacing::Spot {
+            point: r.vector3("facing_spot")?,
+        },
+        3 => MoveFacing::Target {
+            guid: r.guid("facing_target")?,
+        },
+        4 => MoveFacing::Angle {
+            angle: r.f32("facing_angle")?,
+        },
+        _ => MoveFacing::None,
+    };
+    let flags = r.u32("spline_flags")?;
+    if wotlk && flags & ANIMATION != 0 {
+        r.u8("animation_id")?;
+        r.u32("animation_start_time")?;
+    }
+    let duration = r.u32("duration")?;
+    if wotlk && flags & PARABOLIC != 0 {
+        r.f32("vertical_acceleration")?;
+        r.u32("effect_start_time")?;
+    }
+    let (walk, smooth) = if wotlk {
+        (flags & WALKMODE != 0, flags & (FLYING | CATMULLROM) != 0)
+    } else {
+        (flags & RUNMODE == 0, flags & FLYING_CLASSIC != 0)
+    };
+
+    let count = r.u32("point_count")?;
+    let mut points = Vec::new();
+    if smooth {
+        for _ in 0..count {
+            points.push(r.vector3("point")?);
+        }
+    } else if count > 0 {
+        let destination = r.vector3("destination")?;
+        let middle = Vector3 {
+            x: (start.x + destination.x) / 2.0,
+            y: (start.y + destination.y) / 2.0,
+            z: (start.z + destination.z) / 2.0,
+        };
+        for _ in 1..count {
+            let offset = unpack_offset(r.u32("packed_offset")?);
+            points.push(Vector3 {
+                x: middle.x - offset.x,
+                y: middle.y - offset.y,
+                z: middle.z - offset.z,
+            });
+        }
+        points.push(destination);
+    }
+    Ok(MonsterMove {
+        guid,
+        transport,
+        start,
+        spline_id,
+        stop: false,
+        facing,
+        spline_flags: flags,
+        walk,
+        smooth,
+        duration,
+        points,
+    })
+}
+
+/// Signed 11/11/10-bit x/y/z in quarter yards.
+fn unpack_offset(packed: u32) -> Vector3 {
+    let field = |shift: u32, bits: u32| {
+        let value = ((packed >> shift) & ((1 << bits) - 1)) as i32;
+        let signed = (value << (32 - bits)) >> (32 - bits);
+        signed as f32 * 0.25
+    };
+    Vector3 {
+        x: field(0, 11),
+        y: field(11, 11),
+        z: field(22, 10),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::{vector, Bytes};
+
+    pub(crate) const BUILDS: [u32; 3] = [5875, 8606, 12340];
+
+    #[derive(Clone, Copy)]
+    pub(crate) enum Spline {
+        Walk,
+        Run,
+        Fly,
+    }
+
+    /// Quarter-yard offsets packed the way the server sends them.
+    fn pack_offset(offset: Vector3) -> u32 {
+        let field = |value: f32, bits: u32| ((value * 4.0) as i32 as u32) & ((1 << bits) - 1);
+        field(offset.x, 11) | field(offset.y, 11) << 11 | field(offset.z, 10) << 22
+    }
+
+    /// A move from `start` through `points`, facing `angle` at the end if
+    /// given. Linear moves pack every point but the last as an offset from
+    /// the middle of the path.
+    pub(crate) fn monster_move(
+        build: u32,
+        guid: u64,
+        start: Vector3,
+        angle: Option<f32>,
+        spline: Spline,
+        duration: u32,
+        points: &[Vector3],
+    ) -> Vec<u8> {
+        let wotlk = build >= 12340;
+        let mut b = Bytes::new();
+        b.packed_guid(guid);
+        if wotlk {
+            b.u8(0);
+        }
+        b.vector3(start).u32(1);
+        match angle {
+            Some(angle) => b.u8(4).f32(angle),
+            None => b.u8(0),
+        };
+        let flags = match (spline, wotlk) {
+            (Spline::Walk, true) => 0x1000,
+            (Spline::Walk, false) => 0,
+            (Spline::Run, true) => 0,
+            (Spline::Run, false) => 0x100,
+            (Spline::Fly, true) => 0x2000,
+            (Spline::Fly, false) => 0x300,
+        };
+        b.u32(flags).u32(duration).u32(points.len() as u32);
+        if let Spline::Fly = spline {
+            for &point in points {
+                b.vector3(point);
+            }
+            return b.build();
+        }
+        let Some((&destination, path)) = points.split_last() else {
+            return b.build();
+        };
+        b.vector3(destination);
+        let middle = vector(
+            (start.x + destination.x) / 2.0,
+            (start.y + destination.y) / 2.0,
+            (start.z + destination.z) / 2.0,
+        );
+        for point in path {
+            b.u32(pack_offset(vector(
+                middle.x - point.x,
+                middle.y - point.y,
+                middle.z - point.z,
+            )));
+        }
+        b.build()
+    }
+
+    const GUID: u64 = 0xF130_0004_D200_0101;
+
+    fn read(data: &[u8], build: u32) -> MonsterMove {
+        let mut r = PacketReader::new(data);
+        let movement = read_monster_move(&mut r, build, false).unwrap();
+        assert_eq!(r.remaining(), 0);
+        movement
+    }
+
+    #[test]
+    fn unpacks_signed_offsets() {
+        let offset = unpack_offset(pack_offset(vector(-1.25, 3.0, -0.5)));
+        assert_eq!(offset, vector(-1.25, 3.0, -0.5));
+        // The extremes of each field.
+        assert_eq!(unpack_offset(0x7FF), vector(-0.25, 0.0, 0.0));
+        assert_eq!(unpack_offset(0x3FF), vector(255.75, 0.0, 0.0));
+        assert_eq!(unpack_offset(0x200 << 22), vector(0.0, 0.0, -128.0));
+    }
+
+    #[test]
+    fn linear_moves_unpack_their_path() {
+        let path = [
+            vector(3.0, 1.0, 0.0),
+            vector(7.5, -2.25, 1.0),
+            vector(10.0, 0.0, 2.0),
+        ];
+        for build in BUILDS {
+            let data = monster_move(
+                build,
+                GUID,
+                vector(0.0, 0.0, 0.0),
+                Some(1.5),
+                Spline::Walk,
+                4000,
+                &path,
+            );
+            let movement = read(&data, build);
+            assert_eq!(movement.guid, GUID, "build {}", build);
+            assert!(movement.walk && !movement.smooth && !movement.stop);
+            assert_eq!(movement.facing, MoveFacing::Angle { angle: 1.5 });
+            assert_eq!(movement.duration, 4000);
+            assert_eq!(movement.points, path);
+        }
+    }
+
+    #[test]
+    fn smooth_moves_send_every_point() {
+        let path = [vector(30.0, 0.0, 50.0), vector(60.0, 40.0, 55.0)];
+        for build in BUILDS {
+            let data = monster_move(
+                build,
+                GUID,
+                vector(0.0, 0.0, 45.0),
+                None,
+                Spline::Fly,
+                9000,
+                &path,
+            );
+            let movement = read(&data, build);
+            assert!(movement.smooth && !movement.walk, "build {}", build);
+            assert_eq!(movement.facing, MoveFacing::None);
+            assert_eq!(movement.points, path);
+
+            let run = monster_move(build, GUID, path[0], None, Spline::Run, 100, &path[1..]);
+            assert!(!read(&run, build).walk);
+        }
+    }
+
+    #[test]
+    fn stops_end_the_packet() {
+        for build in BUILDS {
+            let mut b = Bytes::new();
+            b.packed_guid(GUID);
+            if build >= 12340 {
+                b.u8(0);
+            }
+            let data = b.vector3(vector(1.0, 2.0, 3.0)).u32(7).u8(1).build();
+            let movement = read(&data, build);
+            assert!(movement.stop, "build {}", build);
+            assert_eq!(
+                (movement.start, movement.spline_id),
+                (vector(1.0, 2.0, 3.0), 7)
+            );
+            assert!(movement.points.is_empty());
+        }
+    }
+}
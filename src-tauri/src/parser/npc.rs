@@ -0,0 +1,243 @@
+//! Vendor and trainer windows.
+
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+/// `max_count` value for items the vendor never runs out of.
+const UNLIMITED_STOCK: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VendorItem {
+    /// 1-based position in the vendor window.
+    pub slot: u32,
+    pub item: u32,
+    pub display_id: u32,
+    /// Units left, or `None` for unlimited stock.
+    pub stock: Option<u32>,
+    /// Price after reputation discounts.
+    pub price: u32,
+    pub max_durability: u32,
+    pub buy_count: u32,
+    /// TBC and later.
+    pub extended_cost: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VendorList {
+    pub guid: u64,
+    pub items: Vec<VendorItem>,
+    /// Set instead of items when the vendor has nothing to show.
+    pub error: Option<u8>,
+}
+
+pub fn read_list_inventory(r: &mut PacketReader, build: u32) -> Result<VendorList, ParseError> {
+    let guid = r.guid("guid")?;
+    let count = r.u8("item_count")?;
+    if count == 0 {
+        return Ok(VendorList {
+            guid,
+            items: Vec::new(),
+            error: Some(r.u8("error")?),
+        });
+    }
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let slot = r.u32("slot")?;
+        let item = r.u32("item")?;
+        let display_id = r.u32("display_id")?;
+        let stock = r.u32("stock")?;
+        items.push(VendorItem {
+            slot,
+            item,
+            display_id,
+            stock: (stock != UNLIMITED_STOCK).then_some(stock),
+            price: r.u32("price")?,
+            max_durability: r.u32("max_durability")?,
+            buy_count: r.u32("buy_count")?,
+            extended_cost: if build >= 8606 {
+                r.u32("extended_cost")?
+            } else {
+                0
+            },
+        });
+    }
+    Ok(VendorList {
+        guid,
+        items,
+        error: None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrainerSpell {
+    pub spell: u32,
+    /// 0 can learn, 1 cannot learn yet, 2 already known.
+    pub state: u8,
+    /// Cost after reputation discounts.
+    pub cost: u32,
+    pub can_learn_primary_profession: bool,
+    pub primary_profession_first_rank: bool,
+    pub required_level: u8,
+    pub required_skill: u32,
+    pub required_skill_value: u32,
+    /// Previous rank and other prerequisite spells; zero when unused.
+    pub required_spells: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrainerList {
+    pub guid: u64,
+    pub trainer_type: u32,
+    pub spells: Vec<TrainerSpell>,
+    pub greeting: String,
+}
+
+/// The spell entry layout is the same in every supported build; only the
+/// meaning of the three trailing spell ids shifted (vanilla and TBC send
+/// chain links plus an unused zero, WotLK three required abilities).
+pub fn read_trainer_list(r: &mut PacketReader) -> Result<TrainerList, ParseError> {
+    let guid = r.guid("guid")?;
+    let trainer_type = r.u32("trainer_type")?;
+    let count = r.u32("spell_count")?;
+    let mut spells = Vec::new();
+    for _ in 0..count {
+        spells.push(TrainerSpell {
+            spell: r.u32("spell")?,
+            state: r.u8("state")?,
+            cost: r.u32("cost")?,
+            can_learn_primary_profession: r.u32("can_learn_primary_profession")? != 0,
+            primary_profession_first_rank: r.u32("primary_profession_first_rank")? != 0,
+            required_level: r.u8("required_level")?,
+            required_skill: r.u32("required_skill")?,
+            required_skill_value: r.u32("required_skill_value")?,
+            required_spells: vec![
+                r.u32("required_spell")?,
+                r.u32("required_spell")?,
+                r.u32("required_spell")?,
+            ],
+        });
+    }
+    Ok(TrainerList {
+        guid,
+        trainer_type,
+        spells,
+        greeting: r.cstring("greeting")?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::Bytes;
+
+    pub(crate) const BUILDS: [u32; 3] = [5875, 8606, 12340];
+
+    /// Items as (item, stock, price, extended cost); `None` stock is
+    /// unlimited.
+    pub(crate) fn list_inventory(
+        build: u32,
+        guid: u64,
+        items: &[(u32, Option<u32>, u32, u32)],
+    ) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.guid(guid).u8(items.len() as u8);
+        if items.is_empty() {
+            return b.u8(0).build();
+        }
+        for (slot, &(item, stock, price, extended_cost)) in items.iter().enumerate() {
+            b.u32(slot as u32 + 1)
+                .u32(item)
+                .u32(item + 1000)
+                .u32(stock.unwrap_or(UNLIMITED_STOCK))
+                .u32(price)
+                .u32(0)
+                .u32(1);
+            if build >= 8606 {
+                b.u32(extended_cost);
+            }
+        }
+        b.build()
+    }
+
+    /// Spells as (spell, cost, required level), each the second rank of
+    /// `spell - 1` and needing 75 in skill 164.
+    pub(crate) fn trainer_list(guid: u64, spells: &[(u32, u32, u8)]) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.guid(guid).u32(2).u32(spells.len() as u32);
+        for &(spell, cost, level) in spells {
+            b.u32(spell).u8(0).u32(cost).u32(0).u32(1).u8(level);
+            b.u32(164).u32(75).u32(spell - 1).u32(0).u32(0);
+        }
+        b.cstring("Hello, $c.").build()
+    }
+
+    const GUID: u64 = 0xF130_0004_D200_0101;
+
+    fn read_all<T>(
+        data: &[u8],
+        read: impl FnOnce(&mut PacketReader) -> Result<T, ParseError>,
+    ) -> T {
+        let mut r = PacketReader::new(data);
+        let value = read(&mut r).unwrap();
+        assert_eq!(r.remaining(), 0);
+        value
+    }
+
+    #[test]
+    fn reads_vendor_lists() {
+        for build in BUILDS {
+            let data = list_inventory(build, GUID, &[(159, None, 5, 0), (4540, Some(3), 25, 7)]);
+            let list = read_all(&data, |r| read_list_inventory(r, build));
+            assert_eq!((list.guid, list.error), (GUID, None));
+            assert_eq!(list.items.len(), 2);
+            assert_eq!(list.items[0].stock, None);
+            let limited = &list.items[1];
+            assert_eq!(
+                (limited.slot, limited.item, limited.display_id),
+                (2, 4540, 5540)
+            );
+            assert_eq!(
+                (limited.stock, limited.price, limited.buy_count),
+                (Some(3), 25, 1)
+            );
+            assert_eq!(limited.extended_cost, if build >= 8606 { 7 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn empty_vendor_lists_carry_an_error() {
+        for build in BUILDS {
+            let list = read_all(&list_inventory(build, GUID, &[]), |r| {
+                read_list_inventory(r, build)
+            });
+            assert!(list.items.is_empty());
+            assert_eq!(list.error, Some(0));
+        }
+    }
+
+    #[test]
+    fn reads_trainer_lists() {
+        let data = trainer_list(GUID, &[(3274, 100, 10), (7934, 450, 20)]);
+        let list = read_all(&data, read_trainer_list);
+        assert_eq!((list.guid, list.trainer_type), (GUID, 2));
+        assert_eq!(list.greeting, "Hello, $c.");
+        assert_eq!(
+            list.spells[1],
+            TrainerSpell {
+                spell: 7934,
+                state: 0,
+                cost: 450,
+                can_learn_primary_profession: false,
+                primary_profession_first_rank: true,
+                required_level: 20,
+                required_skill: 164,
+                required_skill_value: 75,
+                required_spells: vec![7933, 0, 0],
+            }
+        );
+
+        let mut truncated = data;
+        truncated.truncate(truncated.len() - 12);
+        assert!(read_trainer_list(&mut PacketReader::new(&truncated)).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+/// Playable races up to WotLK (9 is the unused Goblin slot).
+const RACES: &[u32] = &[1, 2, 3, 4, 5, 6, 7, 8, 10, 11];
+/// Playable classes up to WotLK (6 is Death Knight, 10 is unused).
+const CLASSES: &[u32] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 11];
+const GENDERS: &[u32] = &[0, 1, 2];
+/// `QuestGiverStatus` values; WotLK extends the vanilla range up to 10.
+const QUESTGIVER_STATUSES: &[u32] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Ping {
+    pub sequence: u32,
+    pub latency: u32,
+}
+
+pub fn read_ping(r: &mut PacketReader) -> Result<Ping, ParseError> {
+    Ok(Ping {
+        sequence: r.u32("sequence")?,
+        latency: r.u32("latency")?,
+    })
+}
+
+pub fn read_pong(r: &mut PacketReader) -> Result<u32, ParseError> {
+    r.u32("sequence")
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NameQueryResponse {
+    pub guid: u64,
+    /// `None` when the server reports the GUID as unknown (WotLK only).
+    pub name: Option<String>,
+    pub realm: String,
+    pub race: u32,
+    pub gender: u32,
+    pub class: u32,
+}
+
+pub fn read_name_query_response(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<NameQueryResponse, ParseError> {
+    if build >= 12340 {
+        let guid = r.packed_guid("guid")?;
+        if r.u8("name_unknown")? != 0 {
+            return Ok(NameQueryResponse {
+                guid,
+                name: None,
+                realm: String::new(),
+                race: 0,
+                gender: 0,
+                class: 0,
+            });
+        }
+        let name = r.cstring("character_name")?;
+        let realm = r.cstring("realm_name")?;
+        let race = r.u8_enum("race", RACES)? as u32;
+        let gender = r.u8_enum("gender", GENDERS)? as u32;
+        let class = r.u8_enum("class", CLASSES)? as u32;
+        read_declined_names(r)?;
+        return Ok(NameQueryResponse {
+            guid,
+            name: Some(name),
+            realm,
+            race,
+            gender,
+            class,
+        });
+    }
+
+    let guid = r.guid("guid")?;
+    let name = r.cstring("character_name")?;
+    let realm = r.cstring("realm_name")?;
+    let race = r.u32_enum("race", RACES)?;
+    let gender = r.u32_enum("gender", GENDERS)?;
+    let class = r.u32_enum("class", CLASSES)?;
+    if build >= 8606 {
+        read_declined_names(r)?;
+    }
+    Ok(NameQueryResponse {
+        guid,
+        name: Some(name),
+        realm,
+        race,
+        gender,
+        class,
+    })
+}
+
+fn read_declined_names(r: &mut PacketReader) -> Result<(), ParseError> {
+    if r.u8("has_declined_names")? != 0 {
+        for _ in 0..5 {
+            r.cstring("declined_name")?;
+        }
+    }
+    Ok(())
+}
+
+/// `CMSG_CREATURE_QUERY`, `CMSG_GAMEOBJECT_QUERY` and `CMSG_NPC_TEXT_QUERY`
+/// all send an id followed by the GUID of the object being asked about.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryQuery {
+    pub entry: u32,
+    pub guid: u64,
+}
+
+pub fn read_entry_query(r: &mut PacketReader) -> Result<EntryQuery, ParseError> {
+    Ok(EntryQuery {
+        entry: r.u32("entry")?,
+        guid: r.guid("guid")?,
+    })
+}
+
+/// `CMSG_ITEM_QUERY_SINGLE`. WotLK dropped the trailing GUID.
+pub fn read_item_query(r: &mut PacketReader, build: u32) -> Result<u32, ParseError> {
+    let item = r.u32("item")?;
+    if build < 12340 {
+        r.guid("guid")?;
+    }
+    Ok(item)
+}
+
+pub fn read_quest_query(r: &mut PacketReader) -> Result<u32, ParseError> {
+    r.u32("quest_id")
+}
+
+/// Client packets whose only body is the GUID of the NPC or object involved.
+pub fn read_guid_request(r: &mut PacketReader) -> Result<u64, ParseError> {
+    r.guid("guid")
+}
+
+pub fn read_query_time_response(r: &mut PacketReader, build: u32) -> Result<u32, ParseError> {
+    let time = r.u32("timestamp")?;
+    if build >= 8606 {
+        r.u32("time_to_daily_reset")?;
+    }
+    Ok(time)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuestGiverStatus {
+    pub guid: u64,
+    pub status: u32,
+}
+
+pub fn read_questgiver_status(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<QuestGiverStatus, ParseError> {
+    let guid = r.guid("guid")?;
+    let status = if build >= 12340 {
+        r.u8_enum("status", QUESTGIVER_STATUSES)? as u32
+    } else {
+        r.u32_enum("status", QUESTGIVER_STATUSES)?
+    };
+    Ok(QuestGiverStatus { guid, status })
+}
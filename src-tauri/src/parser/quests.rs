@@ -0,0 +1,893 @@
+//! Quest query responses and the questgiver dialog packets.
+//!
+//! Vanilla layouts follow the frontend definitions; TBC and WotLK add
+//! suggested players, honor, titles and (WotLK) faction rewards.
+
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestItem {
+    pub item: u32,
+    pub count: u32,
+}
+
+/// A reward or requirement as shown in a questgiver dialog. Vanilla quest
+/// details omit the display id, which is then 0.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestDialogItem {
+    pub item: u32,
+    pub count: u32,
+    pub display_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestEmote {
+    pub emote: u32,
+    pub delay: u32,
+}
+
+/// Kill or use objective. Gameobjects are sent with the high bit set and
+/// stored as negative ids, matching the emulator databases.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestObjective {
+    pub npc_or_go: i32,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestQueryResponse {
+    pub quest_id: u32,
+    pub method: u32,
+    pub level: i32,
+    /// WotLK only.
+    pub min_level: u32,
+    pub zone_or_sort: i32,
+    pub quest_type: u32,
+    /// TBC and later.
+    pub suggested_players: u32,
+    pub rep_objective_faction: u32,
+    pub rep_objective_value: i32,
+    pub required_opposite_faction: u32,
+    pub required_opposite_value: i32,
+    pub next_quest_in_chain: u32,
+    /// WotLK only.
+    pub xp_id: u32,
+    pub reward_money: i32,
+    pub reward_money_max_level: u32,
+    pub reward_spell: u32,
+    /// TBC and later.
+    pub reward_spell_cast: i32,
+    /// TBC and later.
+    pub reward_honor: u32,
+    /// WotLK only.
+    pub reward_honor_multiplier: f32,
+    pub source_item: u32,
+    pub flags: u32,
+    /// TBC and later.
+    pub reward_title: u32,
+    /// WotLK only.
+    pub players_slain: u32,
+    /// WotLK only.
+    pub bonus_talents: u32,
+    /// WotLK only.
+    pub reward_arena_points: u32,
+    pub reward_items: Vec<QuestItem>,
+    pub choice_items: Vec<QuestItem>,
+    /// WotLK only: five faction ids, value ids and value overrides.
+    pub reward_factions: Vec<u32>,
+    pub reward_faction_values: Vec<i32>,
+    pub reward_faction_overrides: Vec<i32>,
+    pub point_map: u32,
+    pub point_x: f32,
+    pub point_y: f32,
+    pub point_opt: u32,
+    pub title: String,
+    pub objectives: String,
+    pub details: String,
+    /// `EndText` before WotLK, `AreaDescription` after.
+    pub end_text: String,
+    /// WotLK only.
+    pub completed_text: String,
+    pub objectives_npc_or_go: Vec<QuestObjective>,
+    /// WotLK only: items dropped to the player to use for an objective.
+    pub source_items: Vec<QuestItem>,
+    pub required_items: Vec<QuestItem>,
+    pub objective_texts: Vec<String>,
+}
+
+fn read_quest_items(
+    r: &mut PacketReader,
+    count: usize,
+    field: &str,
+) -> Result<Vec<QuestItem>, ParseError> {
+    (0..count)
+        .map(|_| {
+            Ok(QuestItem {
+                item: r.u32(field)?,
+                count: r.u32(field)?,
+            })
+        })
+        .collect()
+}
+
+fn read_npc_or_go(r: &mut PacketReader) -> Result<i32, ParseError> {
+    let raw = r.u32("required_npc_or_go")?;
+    Ok(if raw & 0x8000_0000 != 0 {
+        -((raw & 0x7FFF_FFFF) as i32)
+    } else {
+        raw as i32
+    })
+}
+
+pub fn read_quest_query_response(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<QuestQueryResponse, ParseError> {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+
+    let quest_id = r.u32("quest_id")?;
+    let method = r.u32("method")?;
+    let level = r.i32("level")?;
+    let min_level = if wotlk { r.u32("min_level")? } else { 0 };
+    let zone_or_sort = r.i32("zone_or_sort")?;
+    let quest_type = r.u32("quest_type")?;
+    let suggested_players = if tbc { r.u32("suggested_players")? } else { 0 };
+    let rep_objective_faction = r.u32("rep_objective_faction")?;
+    let rep_objective_value = r.i32("rep_objective_value")?;
+    let required_opposite_faction = r.u32("required_opposite_faction")?;
+    let required_opposite_value = r.i32("required_opposite_value")?;
+    let next_quest_in_chain = r.u32("next_quest_in_chain")?;
+    let xp_id = if wotlk { r.u32("xp_id")? } else { 0 };
+    let reward_money = r.i32("reward_money")?;
+    let reward_money_max_level = r.u32("reward_money_max_level")?;
+    let reward_spell = r.u32("reward_spell")?;
+    let (reward_spell_cast, reward_honor) = if tbc {
+        (r.i32("reward_spell_cast")?, r.u32("reward_honor")?)
+    } else {
+        (0, 0)
+    };
+    let reward_honor_multiplier = if wotlk {
+        r.f32("reward_honor_multiplier")?
+    } else {
+        0.0
+    };
+    let source_item = r.u32("source_item")?;
+    let flags = r.u32("flags")?;
+    let reward_title = if tbc { r.u32("reward_title")? } else { 0 };
+    let (players_slain, bonus_talents, reward_arena_points) = if wotlk {
+        let values = (
+            r.u32("players_slain")?,
+            r.u32("bonus_talents")?,
+            r.u32("reward_arena_points")?,
+        );
+        r.u32("reputation_show_mask")?;
+        values
+    } else {
+        (0, 0, 0)
+    };
+    let reward_items = read_quest_items(r, 4, "reward_item")?;
+    let choice_items = read_quest_items(r, 6, "choice_item")?;
+    let (mut reward_factions, mut reward_faction_values, mut reward_faction_overrides) =
+        (Vec::new(), Vec::new(), Vec::new());
+    if wotlk {
+        for _ in 0..5 {
+            reward_factions.push(r.u32("reward_faction")?);
+        }
+        for _ in 0..5 {
+            reward_faction_values.push(r.i32("reward_faction_value")?);
+        }
+        for _ in 0..5 {
+            reward_faction_overrides.push(r.i32("reward_faction_override")?);
+        }
+    }
+    let point_map = r.u32("point_map")?;
+    let point_x = r.f32("point_x")?;
+    let point_y = r.f32("point_y")?;
+    let point_opt = r.u32("point_opt")?;
+    let title = r.cstring("title")?;
+    let objectives = r.cstring("objectives")?;
+    let details = r.cstring("details")?;
+    let end_text = r.cstring("end_text")?;
+    let completed_text = if wotlk {
+        r.cstring("completed_text")?
+    } else {
+        String::new()
+    };
+
+    let mut objectives_npc_or_go = Vec::new();
+    let mut source_items = Vec::new();
+    let mut required_items = Vec::new();
+    for _ in 0..4 {
+        objectives_npc_or_go.push(QuestObjective {
+            npc_or_go: read_npc_or_go(r)?,
+            count: r.u32("required_npc_or_go_count")?,
+        });
+        let item = QuestItem {
+            item: r.u32("required_item")?,
+            count: r.u32("required_item_count")?,
+        };
+        // WotLK moved the required items into their own list of six and
+        // reused this slot for the item handed to the player.
+        if wotlk {
+            source_items.push(item);
+        } else {
+            required_items.push(item);
+        }
+    }
+    if wotlk {
+        required_items = read_quest_items(r, 6, "required_item")?;
+    }
+    let mut objective_texts = Vec::new();
+    for _ in 0..4 {
+        objective_texts.push(r.cstring("objective_text")?);
+    }
+
+    Ok(QuestQueryResponse {
+        quest_id,
+        method,
+        level,
+        min_level,
+        zone_or_sort,
+        quest_type,
+        suggested_players,
+        rep_objective_faction,
+        rep_objective_value,
+        required_opposite_faction,
+        required_opposite_value,
+        next_quest_in_chain,
+        xp_id,
+        reward_money,
+        reward_money_max_level,
+        reward_spell,
+        reward_spell_cast,
+        reward_honor,
+        reward_honor_multiplier,
+        source_item,
+        flags,
+        reward_title,
+        players_slain,
+        bonus_talents,
+        reward_arena_points,
+        reward_items,
+        choice_items,
+        reward_factions,
+        reward_faction_values,
+        reward_faction_overrides,
+        point_map,
+        point_x,
+        point_y,
+        point_opt,
+        title,
+        objectives,
+        details,
+        end_text,
+        completed_text,
+        objectives_npc_or_go,
+        source_items,
+        required_items,
+        objective_texts,
+    })
+}
+
+/// Rewards shared by the quest details and offer reward dialogs.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct QuestDialogRewards {
+    pub choice_items: Vec<QuestDialogItem>,
+    pub reward_items: Vec<QuestDialogItem>,
+    pub money: u32,
+    /// WotLK only.
+    pub xp: u32,
+    /// TBC and later.
+    pub honor: u32,
+    pub spell: u32,
+    pub spell_cast: i32,
+    /// TBC and later.
+    pub title: u32,
+    /// WotLK only.
+    pub bonus_talents: u32,
+    /// WotLK only.
+    pub arena_points: u32,
+}
+
+fn read_dialog_items(
+    r: &mut PacketReader,
+    with_display: bool,
+    field: &str,
+) -> Result<Vec<QuestDialogItem>, ParseError> {
+    let count = r.u32(field)?;
+    (0..count)
+        .map(|_| {
+            Ok(QuestDialogItem {
+                item: r.u32(field)?,
+                count: r.u32(field)?,
+                display_id: if with_display { r.u32(field)? } else { 0 },
+            })
+        })
+        .collect()
+}
+
+fn read_faction_rewards(r: &mut PacketReader) -> Result<(), ParseError> {
+    r.skip(5 * 4, "reward_factions")?;
+    r.skip(5 * 4, "reward_faction_values")?;
+    r.skip(5 * 4, "reward_faction_overrides")
+}
+
+fn read_emotes(
+    r: &mut PacketReader,
+    delay_first: bool,
+    field: &str,
+) -> Result<Vec<QuestEmote>, ParseError> {
+    let count = r.u32(field)?;
+    (0..count)
+        .map(|_| {
+            let (emote, delay) = if delay_first {
+                let delay = r.u32("emote_delay")?;
+                (r.u32("emote")?, delay)
+            } else {
+                let emote = r.u32("emote")?;
+                (emote, r.u32("emote_delay")?)
+            };
+            Ok(QuestEmote { emote, delay })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestDetails {
+    pub npc_guid: u64,
+    pub quest_id: u32,
+    pub title: String,
+    pub details: String,
+    pub objectives: String,
+    pub auto_accept: bool,
+    /// WotLK only.
+    pub flags: u32,
+    /// TBC and later.
+    pub suggested_players: u32,
+    pub rewards: QuestDialogRewards,
+    pub emotes: Vec<QuestEmote>,
+}
+
+pub fn read_quest_details(r: &mut PacketReader, build: u32) -> Result<QuestDetails, ParseError> {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+
+    let npc_guid = r.guid("npc_guid")?;
+    if wotlk {
+        r.guid("sharer_guid")?;
+    }
+    let quest_id = r.u32("quest_id")?;
+    let title = r.cstring("title")?;
+    let details = r.cstring("details")?;
+    let objectives = r.cstring("objectives")?;
+    let auto_accept = if wotlk {
+        r.bool("auto_accept")?
+    } else {
+        r.u32("auto_accept")? != 0
+    };
+    let flags = if wotlk { r.u32("flags")? } else { 0 };
+    let suggested_players = if tbc { r.u32("suggested_players")? } else { 0 };
+    if wotlk {
+        r.u8("is_finished")?;
+    }
+
+    let mut rewards = QuestDialogRewards {
+        choice_items: read_dialog_items(r, tbc, "choice_item")?,
+        reward_items: read_dialog_items(r, tbc, "reward_item")?,
+        money: r.u32("reward_money")?,
+        ..Default::default()
+    };
+    if wotlk {
+        rewards.xp = r.u32("reward_xp")?;
+    }
+    if tbc {
+        rewards.honor = r.u32("reward_honor")?;
+    }
+    if wotlk {
+        r.f32("reward_honor_multiplier")?;
+    }
+    rewards.spell = r.u32("reward_spell")?;
+    if tbc {
+        rewards.spell_cast = r.i32("reward_spell_cast")?;
+        rewards.title = r.u32("reward_title")?;
+    }
+    if wotlk {
+        rewards.bonus_talents = r.u32("bonus_talents")?;
+        rewards.arena_points = r.u32("reward_arena_points")?;
+        r.u32("reputation_show_mask")?;
+        read_faction_rewards(r)?;
+    }
+    let emotes = read_emotes(r, false, "emote_count")?;
+
+    Ok(QuestDetails {
+        npc_guid,
+        quest_id,
+        title,
+        details,
+        objectives,
+        auto_accept,
+        flags,
+        suggested_players,
+        rewards,
+        emotes,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestRequestItems {
+    pub npc_guid: u64,
+    pub quest_id: u32,
+    pub title: String,
+    pub text: String,
+    pub emote_delay: u32,
+    /// The complete or incomplete emote, depending on `completable`.
+    pub emote: u32,
+    pub auto_finish: bool,
+    /// WotLK only.
+    pub flags: u32,
+    /// WotLK only.
+    pub suggested_players: u32,
+    pub required_money: u32,
+    pub required_items: Vec<QuestDialogItem>,
+    pub completable: bool,
+}
+
+pub fn read_quest_request_items(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<QuestRequestItems, ParseError> {
+    let wotlk = build >= 12340;
+
+    let npc_guid = r.guid("npc_guid")?;
+    let quest_id = r.u32("quest_id")?;
+    let title = r.cstring("title")?;
+    let text = r.cstring("request_items_text")?;
+    let emote_delay = r.u32("emote_delay")?;
+    let emote = r.u32("emote")?;
+    let auto_finish = r.u32("auto_finish")? != 0;
+    let (flags, suggested_players) = if wotlk {
+        (r.u32("flags")?, r.u32("suggested_players")?)
+    } else {
+        (0, 0)
+    };
+    let required_money = r.u32("required_money")?;
+    let required_items = read_dialog_items(r, true, "required_item")?;
+    if !wotlk {
+        r.u32("unknown")?;
+    }
+    let completable = r.u32("completable")? != 0;
+    r.skip(3 * 4, "status_flags")?;
+
+    Ok(QuestRequestItems {
+        npc_guid,
+        quest_id,
+        title,
+        text,
+        emote_delay,
+        emote,
+        auto_finish,
+        flags,
+        suggested_players,
+        required_money,
+        required_items,
+        completable,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuestOfferReward {
+    pub npc_guid: u64,
+    pub quest_id: u32,
+    pub title: String,
+    pub text: String,
+    pub auto_finish: bool,
+    /// WotLK only.
+    pub flags: u32,
+    /// TBC and later.
+    pub suggested_players: u32,
+    pub emotes: Vec<QuestEmote>,
+    pub rewards: QuestDialogRewards,
+}
+
+pub fn read_quest_offer_reward(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<QuestOfferReward, ParseError> {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+
+    let npc_guid = r.guid("npc_guid")?;
+    let quest_id = r.u32("quest_id")?;
+    let title = r.cstring("title")?;
+    let text = r.cstring("offer_reward_text")?;
+    let auto_finish = if wotlk {
+        r.bool("auto_finish")?
+    } else {
+        r.u32("auto_finish")? != 0
+    };
+    let flags = if wotlk { r.u32("flags")? } else { 0 };
+    let suggested_players = if tbc { r.u32("suggested_players")? } else { 0 };
+    let emotes = read_emotes(r, true, "emote_count")?;
+
+    let mut rewards = QuestDialogRewards {
+        choice_items: read_dialog_items(r, true, "choice_item")?,
+        reward_items: read_dialog_items(r, true, "reward_item")?,
+        money: r.u32("reward_money")?,
+        ..Default::default()
+    };
+    if wotlk {
+        rewards.xp = r.u32("reward_xp")?;
+    }
+    if tbc {
+        rewards.honor = r.u32("reward_honor")?;
+    }
+    if wotlk {
+        r.f32("reward_honor_multiplier")?;
+    }
+    if tbc {
+        r.u32("unknown")?;
+    }
+    rewards.spell = r.u32("reward_spell")?;
+    rewards.spell_cast = r.i32("reward_spell_cast")?;
+    if tbc {
+        rewards.title = r.u32("reward_title")?;
+    }
+    if wotlk {
+        rewards.bonus_talents = r.u32("bonus_talents")?;
+        rewards.arena_points = r.u32("reward_arena_points")?;
+        r.u32("reputation_show_mask")?;
+        read_faction_rewards(r)?;
+    }
+
+    Ok(QuestOfferReward {
+        npc_guid,
+        quest_id,
+        title,
+        text,
+        auto_finish,
+        flags,
+        suggested_players,
+        emotes,
+        rewards,
+    })
+}
+
+/// Quest packets as each build sends them, shared with the extractor tests.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::Bytes;
+
+    pub(crate) const BUILDS: [u32; 3] = [5875, 8606, 12340];
+    /// Marshal McBride, who hands out "Kobold Camp Cleanup".
+    pub(crate) const NPC: u64 = 0xF130_0000_C500_0005;
+    pub(crate) const QUEST: u32 = 7;
+    pub(crate) const DETAILS: &str = "Kobolds have been seen near the mine.";
+
+    /// A kill quest with one gameobject objective and one item objective.
+    pub(crate) fn query_response(build: u32, quest_id: u32, details: &str) -> Vec<u8> {
+        let (wotlk, tbc) = (build >= 12340, build >= 8606);
+        let mut b = Bytes::new();
+        b.u32(quest_id).u32(2).i32(5);
+        if wotlk {
+            b.u32(4);
+        }
+        b.i32(12).u32(0);
+        if tbc {
+            b.u32(0);
+        }
+        b.u32(0).i32(0).u32(0).i32(0).u32(15);
+        if wotlk {
+            b.u32(5);
+        }
+        b.i32(250).u32(60).u32(0);
+        if tbc {
+            b.i32(0).u32(0);
+        }
+        if wotlk {
+            b.f32(0.0);
+        }
+        b.u32(0).u32(8);
+        if tbc {
+            b.u32(0);
+        }
+        if wotlk {
+            b.u32(0).u32(0).u32(0).u32(0);
+        }
+        b.u32(2589).u32(1);
+        for _ in 1..4 {
+            b.u32(0).u32(0);
+        }
+        b.u32(6070).u32(1).u32(6071).u32(1);
+        for _ in 2..6 {
+            b.u32(0).u32(0);
+        }
+        if wotlk {
+            for faction in [72, 0, 0, 0, 0] {
+                b.u32(faction);
+            }
+            for _ in 0..10 {
+                b.i32(0);
+            }
+        }
+        b.u32(0).f32(-9104.5).f32(-73.25).u32(0);
+        b.cstring("Kobold Camp Cleanup")
+            .cstring("Kill 10 Kobold Vermin.")
+            .cstring(details)
+            .cstring("");
+        if wotlk {
+            b.cstring("Return to Marshal McBride.");
+        }
+        // A gameobject objective goes over the wire with the high bit set.
+        b.u32(0x8000_0000 | 1617).u32(1).u32(750).u32(8);
+        b.u32(6).u32(10).u32(0).u32(0);
+        for _ in 2..4 {
+            b.u32(0).u32(0).u32(0).u32(0);
+        }
+        if wotlk {
+            b.u32(1307).u32(3);
+            for _ in 1..6 {
+                b.u32(0).u32(0);
+            }
+        }
+        for text in ["Burn the camp", "", "", ""] {
+            b.cstring(text);
+        }
+        b.build()
+    }
+
+    pub(crate) fn details(build: u32, npc: u64, quest_id: u32, title: &str) -> Vec<u8> {
+        let (wotlk, tbc) = (build >= 12340, build >= 8606);
+        let mut b = Bytes::new();
+        b.guid(npc);
+        if wotlk {
+            b.guid(0);
+        }
+        b.u32(quest_id)
+            .cstring(title)
+            .cstring(DETAILS)
+            .cstring("Kill 10 Kobold Vermin.");
+        if wotlk {
+            b.u8(0).u32(8);
+        } else {
+            b.u32(0);
+        }
+        if tbc {
+            b.u32(0);
+        }
+        if wotlk {
+            b.u8(0);
+        }
+        b.u32(1).u32(6070).u32(1);
+        if tbc {
+            b.u32(6414);
+        }
+        b.u32(0).u32(250);
+        if wotlk {
+            b.u32(170);
+        }
+        if tbc {
+            b.u32(0);
+        }
+        if wotlk {
+            b.f32(0.0);
+        }
+        b.u32(0);
+        if tbc {
+            b.i32(0).u32(0);
+        }
+        if wotlk {
+            b.u32(0).u32(0).u32(0).bytes(&[0; 60]);
+        }
+        b.u32(2).u32(1).u32(0).u32(6).u32(500).build()
+    }
+
+    pub(crate) fn request_items(build: u32, emote: u32, completable: bool) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.guid(NPC)
+            .u32(QUEST)
+            .cstring("Kobold Camp Cleanup")
+            .cstring("Have you dealt with the kobolds?")
+            .u32(0)
+            .u32(emote)
+            .u32(0);
+        if build >= 12340 {
+            b.u32(8).u32(0);
+        }
+        b.u32(0).u32(1).u32(750).u32(8).u32(7202);
+        if build < 12340 {
+            b.u32(0);
+        }
+        b.u32(completable as u32).bytes(&[0; 12]).build()
+    }
+
+    pub(crate) fn offer_reward(build: u32, text: &str) -> Vec<u8> {
+        let (wotlk, tbc) = (build >= 12340, build >= 8606);
+        let mut b = Bytes::new();
+        b.guid(NPC)
+            .u32(QUEST)
+            .cstring("Kobold Camp Cleanup")
+            .cstring(text);
+        if wotlk {
+            b.u8(0).u32(8);
+        } else {
+            b.u32(0);
+        }
+        if tbc {
+            b.u32(0);
+        }
+        // Delay first, unlike the details emotes.
+        b.u32(1).u32(100).u32(4);
+        b.u32(1).u32(6070).u32(1).u32(6414).u32(0).u32(250);
+        if wotlk {
+            b.u32(170);
+        }
+        if tbc {
+            b.u32(0);
+        }
+        if wotlk {
+            b.f32(0.0);
+        }
+        if tbc {
+            b.u32(0);
+        }
+        b.u32(0).i32(0);
+        if tbc {
+            b.u32(0);
+        }
+        if wotlk {
+            b.u32(0).u32(0).u32(0).bytes(&[0; 60]);
+        }
+        b.build()
+    }
+
+    fn read_all<T>(
+        data: &[u8],
+        read: impl FnOnce(&mut PacketReader) -> Result<T, ParseError>,
+    ) -> T {
+        let mut r = PacketReader::new(data);
+        let value = read(&mut r).unwrap();
+        assert_eq!(r.remaining(), 0);
+        value
+    }
+
+    #[test]
+    fn reads_query_response() {
+        for build in BUILDS {
+            let data = query_response(build, QUEST, "Details");
+            let q = read_all(&data, |r| read_quest_query_response(r, build));
+            assert_eq!((q.quest_id, q.level, q.zone_or_sort), (QUEST, 5, 12));
+            assert_eq!(q.min_level, if build >= 12340 { 4 } else { 0 });
+            assert_eq!((q.reward_money, q.flags), (250, 8));
+            assert_eq!(
+                q.reward_items[0],
+                QuestItem {
+                    item: 2589,
+                    count: 1
+                }
+            );
+            assert_eq!(q.choice_items[1].item, 6071);
+            assert_eq!(
+                (q.title.as_str(), q.details.as_str()),
+                ("Kobold Camp Cleanup", "Details")
+            );
+            assert_eq!((q.point_x, q.point_y), (-9104.5, -73.25));
+            assert_eq!(
+                q.objectives_npc_or_go[0],
+                QuestObjective {
+                    npc_or_go: -1617,
+                    count: 1
+                }
+            );
+            assert_eq!(
+                q.objectives_npc_or_go[1],
+                QuestObjective {
+                    npc_or_go: 6,
+                    count: 10
+                }
+            );
+            assert_eq!(q.objective_texts[0], "Burn the camp");
+        }
+    }
+
+    #[test]
+    fn wotlk_splits_required_and_source_items() {
+        let q = read_all(&query_response(5875, QUEST, ""), |r| {
+            read_quest_query_response(r, 5875)
+        });
+        assert_eq!(
+            q.required_items[0],
+            QuestItem {
+                item: 750,
+                count: 8
+            }
+        );
+        assert!(q.source_items.is_empty() && q.reward_factions.is_empty());
+
+        let q = read_all(&query_response(12340, QUEST, ""), |r| {
+            read_quest_query_response(r, 12340)
+        });
+        assert_eq!(
+            q.source_items[0],
+            QuestItem {
+                item: 750,
+                count: 8
+            }
+        );
+        assert_eq!(q.required_items.len(), 6);
+        assert_eq!(
+            q.required_items[0],
+            QuestItem {
+                item: 1307,
+                count: 3
+            }
+        );
+        assert_eq!((q.xp_id, q.reward_factions[0]), (5, 72));
+        assert_eq!(q.completed_text, "Return to Marshal McBride.");
+    }
+
+    #[test]
+    fn reads_dialogs() {
+        for build in BUILDS {
+            let tbc = build >= 8606;
+            let d = read_all(&details(build, NPC, QUEST, "Kobold Camp Cleanup"), |r| {
+                read_quest_details(r, build)
+            });
+            assert_eq!((d.npc_guid, d.quest_id), (NPC, QUEST));
+            assert_eq!(d.objectives, "Kill 10 Kobold Vermin.");
+            // Vanilla quest details leave the display id out.
+            let display_id = if tbc { 6414 } else { 0 };
+            assert_eq!(
+                d.rewards.choice_items,
+                [QuestDialogItem {
+                    item: 6070,
+                    count: 1,
+                    display_id
+                }]
+            );
+            assert_eq!(d.rewards.money, 250);
+            assert_eq!(d.rewards.xp, if build >= 12340 { 170 } else { 0 });
+            assert_eq!(
+                d.emotes,
+                [
+                    QuestEmote { emote: 1, delay: 0 },
+                    QuestEmote {
+                        emote: 6,
+                        delay: 500
+                    }
+                ]
+            );
+
+            let q = read_all(&request_items(build, 6, true), |r| {
+                read_quest_request_items(r, build)
+            });
+            assert_eq!((q.emote, q.completable), (6, true));
+            assert_eq!(q.required_items[0].display_id, 7202);
+
+            let o = read_all(&offer_reward(build, "Thanks"), |r| {
+                read_quest_offer_reward(r, build)
+            });
+            assert_eq!((o.text.as_str(), o.rewards.money), ("Thanks", 250));
+            assert_eq!(
+                o.emotes,
+                [QuestEmote {
+                    emote: 4,
+                    delay: 100
+                }]
+            );
+            assert_eq!(o.rewards.choice_items[0].display_id, 6414);
+        }
+    }
+
+    #[test]
+    fn truncated_dialogs_fail() {
+        for build in BUILDS {
+            let mut data = offer_reward(build, "Thanks");
+            data.pop();
+            let mut r = PacketReader::new(&data);
+            assert!(read_quest_offer_reward(&mut r, build).is_err());
+        }
+    }
+}
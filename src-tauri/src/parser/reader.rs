@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Error raised when a decoder cannot read the field it asked for.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseError {
+    /// Byte offset into the payload where the failing read started.
+    pub offset: usize,
+    /// Name of the field being read.
+    pub field: String,
+    /// What the reader expected to find, e.g. `u32 (4 bytes)`.
+    pub expected: String,
+    /// Bytes left in the payload at `offset`.
+    pub available: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}`: expected {} at offset {}, but only {} bytes remain",
+            self.field, self.expected, self.offset, self.available
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// The decoder finished without consuming the whole payload.
+    TrailingBytes,
+    /// The decoder tried to read past the end of the payload.
+    Overrun,
+    /// An enum-like field held a value outside the known range.
+    UnknownEnumValue,
+    /// A compressed payload did not inflate to the size it announced.
+    BadCompression,
+}
+
+/// Something suspicious noticed while decoding a packet. Findings do not stop
+/// decoding; an overrun is recorded alongside the `ParseError` it caused.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub offset: usize,
+    pub field: String,
+    pub detail: String,
+}
+
+/// A position or direction in world space.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// Little-endian cursor over a packet payload.
+///
+/// Every read takes the name of the field being decoded so that errors can
+/// say which part of the structure ran past the end of the packet.
+pub struct PacketReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    findings: Vec<Finding>,
+}
+
+impl<'a> PacketReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            findings: Vec::new(),
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn into_findings(self) -> Vec<Finding> {
+        self.findings
+    }
+
+    pub fn add_finding(&mut self, finding: Finding) {
+        self.findings.push(finding);
+    }
+
+    fn overrun(&mut self, field: &str, expected: String) -> ParseError {
+        let err = ParseError {
+            offset: self.pos,
+            field: field.to_string(),
+            expected,
+            available: self.remaining(),
+        };
+        self.findings.push(Finding {
+            kind: FindingKind::Overrun,
+            offset: err.offset,
+            field: err.field.clone(),
+            detail: err.to_string(),
+        });
+        err
+    }
+
+    fn take(&mut self, n: usize, field: &str, ty: &str) -> Result<&'a [u8], ParseError> {
+        if n > self.remaining() {
+            return Err(self.overrun(field, format!("{} ({} bytes)", ty, n)));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self, field: &str, ty: &str) -> Result<[u8; N], ParseError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N, field, ty)?);
+        Ok(out)
+    }
+
+    pub fn u8(&mut self, field: &str) -> Result<u8, ParseError> {
+        Ok(self.take(1, field, "u8")?[0])
+    }
+
+    pub fn u16(&mut self, field: &str) -> Result<u16, ParseError> {
+        self.array(field, "u16").map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self, field: &str) -> Result<u32, ParseError> {
+        self.array(field, "u32").map(u32::from_le_bytes)
+    }
+
+    pub fn i32(&mut self, field: &str) -> Result<i32, ParseError> {
+        self.array(field, "i32").map(i32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self, field: &str) -> Result<u64, ParseError> {
+        self.array(field, "u64").map(u64::from_le_bytes)
+    }
+
+    pub fn i64(&mut self, field: &str) -> Result<i64, ParseError> {
+        self.array(field, "i64").map(i64::from_le_bytes)
+    }
+
+    pub fn f32(&mut self, field: &str) -> Result<f32, ParseError> {
+        self.array(field, "f32").map(f32::from_le_bytes)
+    }
+
+    pub fn vector3(&mut self, field: &str) -> Result<Vector3, ParseError> {
+        Ok(Vector3 {
+            x: self.f32(field)?,
+            y: self.f32(field)?,
+            z: self.f32(field)?,
+        })
+    }
+
+    pub fn bool(&mut self, field: &str) -> Result<bool, ParseError> {
+        Ok(self.u8(field)? != 0)
+    }
+
+    /// A `u8` enum. Values outside `known` are recorded as findings but still
+    /// returned so decoding can continue.
+    pub fn u8_enum(&mut self, field: &str, known: &[u32]) -> Result<u8, ParseError> {
+        let offset = self.pos;
+        let value = self.u8(field)?;
+        if !known.contains(&(value as u32)) {
+            self.unknown_enum(offset, field, value as u32);
+        }
+        Ok(value)
+    }
+
+    /// A `u32` enum, see [`PacketReader::u8_enum`].
+    pub fn u32_enum(&mut self, field: &str, known: &[u32]) -> Result<u32, ParseError> {
+        let offset = self.pos;
+        let value = self.u32(field)?;
+        if !known.contains(&value) {
+            self.unknown_enum(offset, field, value);
+        }
+        Ok(value)
+    }
+
+    fn unknown_enum(&mut self, offset: usize, field: &str, value: u32) {
+        self.findings.push(Finding {
+            kind: FindingKind::UnknownEnumValue,
+            offset,
+            field: field.to_string(),
+            detail: format!("`{}` has unknown value {} (0x{:X})", field, value, value),
+        });
+    }
+
+    /// A full 8-byte GUID.
+    pub fn guid(&mut self, field: &str) -> Result<u64, ParseError> {
+        self.u64(field)
+    }
+
+    /// A packed GUID: one mask byte followed by the non-zero GUID bytes.
+    pub fn packed_guid(&mut self, field: &str) -> Result<u64, ParseError> {
+        let start = self.pos;
+        let mask = self.u8(field)?;
+        let len = mask.count_ones() as usize;
+        if len > self.remaining() {
+            self.pos = start;
+            return Err(self.overrun(field, format!("packed guid ({} bytes)", len + 1)));
+        }
+        let mut guid = 0u64;
+        for i in 0..8 {
+            if mask & (1 << i) != 0 {
+                guid |= (self.u8(field)? as u64) << (i * 8);
+            }
+        }
+        Ok(guid)
+    }
+
+    /// A null-terminated string. Invalid UTF-8 is replaced rather than rejected.
+    pub fn cstring(&mut self, field: &str) -> Result<String, ParseError> {
+        let rest = &self.data[self.pos..];
+        let Some(len) = rest.iter().position(|&b| b == 0) else {
+            return Err(self.overrun(field, "null-terminated string".to_string()));
+        };
+        let text = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len + 1;
+        Ok(text)
+    }
+
+    pub fn bytes(&mut self, n: usize, field: &str) -> Result<&'a [u8], ParseError> {
+        self.take(n, field, "bytes")
+    }
+
+    pub fn skip(&mut self, n: usize, field: &str) -> Result<(), ParseError> {
+        self.take(n, field, "bytes").map(|_| ())
+    }
+}
@@ -0,0 +1,695 @@
+//! Creature, item and gameobject query responses.
+//!
+//! Each response starts with the entry; an entry with the high bit set means
+//! the server does not know it and nothing else follows.
+
+use super::reader::{PacketReader, ParseError};
+use serde::Serialize;
+
+const NOT_FOUND: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CreatureTemplate {
+    pub entry: u32,
+    pub name: String,
+    pub subname: String,
+    /// TBC and later.
+    pub icon_name: String,
+    pub type_flags: u32,
+    pub creature_type: u32,
+    pub family: u32,
+    pub rank: u32,
+    /// WotLK only.
+    pub kill_credits: Vec<u32>,
+    /// Vanilla and TBC.
+    pub pet_spell_data_id: u32,
+    /// One display id in vanilla, four from TBC on.
+    pub display_ids: Vec<u32>,
+    /// TBC and later.
+    pub health_multiplier: f32,
+    /// TBC and later.
+    pub power_multiplier: f32,
+    /// Vanilla only.
+    pub civilian: bool,
+    pub racial_leader: bool,
+    /// WotLK only.
+    pub quest_items: Vec<u32>,
+    /// WotLK only.
+    pub movement_id: u32,
+}
+
+/// `None` when the server answered that the entry does not exist.
+pub fn read_creature_query_response(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<Option<CreatureTemplate>, ParseError> {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+
+    let entry = r.u32("entry")?;
+    if entry & NOT_FOUND != 0 {
+        return Ok(None);
+    }
+    let name = r.cstring("name")?;
+    for _ in 0..3 {
+        r.cstring("alt_name")?;
+    }
+    let subname = r.cstring("subname")?;
+    let icon_name = if tbc {
+        r.cstring("icon_name")?
+    } else {
+        String::new()
+    };
+    let type_flags = r.u32("type_flags")?;
+    let creature_type = r.u32("creature_type")?;
+    let family = r.u32("family")?;
+    let rank = r.u32("rank")?;
+
+    let mut template = CreatureTemplate {
+        entry,
+        name,
+        subname,
+        icon_name,
+        type_flags,
+        creature_type,
+        family,
+        rank,
+        kill_credits: Vec::new(),
+        pet_spell_data_id: 0,
+        display_ids: Vec::new(),
+        health_multiplier: 1.0,
+        power_multiplier: 1.0,
+        civilian: false,
+        racial_leader: false,
+        quest_items: Vec::new(),
+        movement_id: 0,
+    };
+    if wotlk {
+        template.kill_credits = vec![r.u32("kill_credit")?, r.u32("kill_credit")?];
+    } else {
+        r.u32("unknown")?;
+        template.pet_spell_data_id = r.u32("pet_spell_data_id")?;
+    }
+    if tbc {
+        for _ in 0..4 {
+            template.display_ids.push(r.u32("display_id")?);
+        }
+        template.health_multiplier = r.f32("health_multiplier")?;
+        template.power_multiplier = r.f32("power_multiplier")?;
+    } else {
+        template.display_ids.push(r.u32("display_id")?);
+        template.civilian = r.bool("civilian")?;
+    }
+    template.racial_leader = r.bool("racial_leader")?;
+    if wotlk {
+        for _ in 0..6 {
+            template.quest_items.push(r.u32("quest_item")?);
+        }
+        template.movement_id = r.u32("movement_id")?;
+    }
+    Ok(Some(template))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemStat {
+    pub stat_type: u32,
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemDamage {
+    pub min: f32,
+    pub max: f32,
+    pub school: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemSpell {
+    pub spell: u32,
+    pub trigger: u32,
+    pub charges: i32,
+    pub cooldown: i32,
+    pub category: u32,
+    pub category_cooldown: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemSocket {
+    pub color: u32,
+    pub content: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemTemplate {
+    pub entry: u32,
+    pub class: u32,
+    pub subclass: u32,
+    /// TBC and later.
+    pub sound_override_subclass: i32,
+    pub name: String,
+    pub display_id: u32,
+    pub quality: u32,
+    pub flags: u32,
+    /// WotLK only.
+    pub flags2: u32,
+    pub buy_price: u32,
+    pub sell_price: u32,
+    pub inventory_type: u32,
+    pub allowable_class: i32,
+    pub allowable_race: i32,
+    pub item_level: u32,
+    pub required_level: u32,
+    pub required_skill: u32,
+    pub required_skill_rank: u32,
+    pub required_spell: u32,
+    pub required_honor_rank: u32,
+    pub required_city_rank: u32,
+    pub required_reputation_faction: u32,
+    pub required_reputation_rank: u32,
+    pub max_count: i32,
+    pub stackable: i32,
+    pub container_slots: u32,
+    pub stats: Vec<ItemStat>,
+    /// WotLK only.
+    pub scaling_stat_distribution: u32,
+    /// WotLK only.
+    pub scaling_stat_value: u32,
+    pub damages: Vec<ItemDamage>,
+    pub armor: i32,
+    /// Holy, fire, nature, frost, shadow and arcane.
+    pub resistances: Vec<i32>,
+    pub delay: u32,
+    pub ammo_type: u32,
+    pub ranged_mod_range: f32,
+    pub spells: Vec<ItemSpell>,
+    pub bonding: u32,
+    pub description: String,
+    pub page_text: u32,
+    pub language: u32,
+    pub page_material: u32,
+    pub start_quest: u32,
+    pub lock_id: u32,
+    pub material: i32,
+    pub sheath: u32,
+    pub random_property: u32,
+    /// TBC and later.
+    pub random_suffix: u32,
+    pub block: u32,
+    pub item_set: u32,
+    pub max_durability: u32,
+    pub area: u32,
+    pub map: u32,
+    pub bag_family: u32,
+    /// TBC and later.
+    pub totem_category: u32,
+    /// TBC and later.
+    pub sockets: Vec<ItemSocket>,
+    /// TBC and later.
+    pub socket_bonus: u32,
+    /// TBC and later.
+    pub gem_properties: u32,
+    /// TBC and later.
+    pub required_disenchant_skill: i32,
+    /// TBC and later.
+    pub armor_damage_modifier: f32,
+    /// TBC and later.
+    pub duration: u32,
+    /// WotLK only.
+    pub item_limit_category: u32,
+    /// WotLK only.
+    pub holiday_id: u32,
+}
+
+pub fn read_item_query_response(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<Option<ItemTemplate>, ParseError> {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+
+    let entry = r.u32("entry")?;
+    if entry & NOT_FOUND != 0 {
+        return Ok(None);
+    }
+    let class = r.u32("class")?;
+    let subclass = r.u32("subclass")?;
+    let sound_override_subclass = if tbc {
+        r.i32("sound_override_subclass")?
+    } else {
+        -1
+    };
+    let name = r.cstring("name")?;
+    for _ in 0..3 {
+        r.cstring("alt_name")?;
+    }
+    let display_id = r.u32("display_id")?;
+    let quality = r.u32("quality")?;
+    let flags = r.u32("flags")?;
+    let flags2 = if wotlk { r.u32("flags2")? } else { 0 };
+    let buy_price = r.u32("buy_price")?;
+    let sell_price = r.u32("sell_price")?;
+    let inventory_type = r.u32("inventory_type")?;
+    let allowable_class = r.i32("allowable_class")?;
+    let allowable_race = r.i32("allowable_race")?;
+    let item_level = r.u32("item_level")?;
+    let required_level = r.u32("required_level")?;
+    let required_skill = r.u32("required_skill")?;
+    let required_skill_rank = r.u32("required_skill_rank")?;
+    let required_spell = r.u32("required_spell")?;
+    let required_honor_rank = r.u32("required_honor_rank")?;
+    let required_city_rank = r.u32("required_city_rank")?;
+    let required_reputation_faction = r.u32("required_reputation_faction")?;
+    let required_reputation_rank = r.u32("required_reputation_rank")?;
+    let max_count = r.i32("max_count")?;
+    let stackable = r.i32("stackable")?;
+    let container_slots = r.u32("container_slots")?;
+
+    // WotLK sends only the stats in use; earlier builds always send ten.
+    let stat_count = if wotlk { r.u32("stat_count")? } else { 10 };
+    let mut stats = Vec::new();
+    for _ in 0..stat_count {
+        stats.push(ItemStat {
+            stat_type: r.u32("stat_type")?,
+            value: r.i32("stat_value")?,
+        });
+    }
+    let (scaling_stat_distribution, scaling_stat_value) = if wotlk {
+        (
+            r.u32("scaling_stat_distribution")?,
+            r.u32("scaling_stat_value")?,
+        )
+    } else {
+        (0, 0)
+    };
+    let damage_count = if wotlk { 2 } else { 5 };
+    let mut damages = Vec::new();
+    for _ in 0..damage_count {
+        damages.push(ItemDamage {
+            min: r.f32("damage_min")?,
+            max: r.f32("damage_max")?,
+            school: r.u32("damage_school")?,
+        });
+    }
+    let armor = r.i32("armor")?;
+    let mut resistances = Vec::new();
+    for _ in 0..6 {
+        resistances.push(r.i32("resistance")?);
+    }
+    let delay = r.u32("delay")?;
+    let ammo_type = r.u32("ammo_type")?;
+    let ranged_mod_range = r.f32("ranged_mod_range")?;
+    let mut spells = Vec::new();
+    for _ in 0..5 {
+        spells.push(ItemSpell {
+            spell: r.u32("spell")?,
+            trigger: r.u32("spell_trigger")?,
+            charges: r.i32("spell_charges")?,
+            cooldown: r.i32("spell_cooldown")?,
+            category: r.u32("spell_category")?,
+            category_cooldown: r.i32("spell_category_cooldown")?,
+        });
+    }
+    let bonding = r.u32("bonding")?;
+    let description = r.cstring("description")?;
+    let page_text = r.u32("page_text")?;
+    let language = r.u32("language")?;
+    let page_material = r.u32("page_material")?;
+    let start_quest = r.u32("start_quest")?;
+    let lock_id = r.u32("lock_id")?;
+    let material = r.i32("material")?;
+    let sheath = r.u32("sheath")?;
+    let random_property = r.u32("random_property")?;
+    let random_suffix = if tbc { r.u32("random_suffix")? } else { 0 };
+    let block = r.u32("block")?;
+    let item_set = r.u32("item_set")?;
+    let max_durability = r.u32("max_durability")?;
+    let area = r.u32("area")?;
+    let map = r.u32("map")?;
+    let bag_family = r.u32("bag_family")?;
+
+    let mut template = ItemTemplate {
+        entry,
+        class,
+        subclass,
+        sound_override_subclass,
+        name,
+        display_id,
+        quality,
+        flags,
+        flags2,
+        buy_price,
+        sell_price,
+        inventory_type,
+        allowable_class,
+        allowable_race,
+        item_level,
+        required_level,
+        required_skill,
+        required_skill_rank,
+        required_spell,
+        required_honor_rank,
+        required_city_rank,
+        required_reputation_faction,
+        required_reputation_rank,
+        max_count,
+        stackable,
+        container_slots,
+        stats,
+        scaling_stat_distribution,
+        scaling_stat_value,
+        damages,
+        armor,
+        resistances,
+        delay,
+        ammo_type,
+        ranged_mod_range,
+        spells,
+        bonding,
+        description,
+        page_text,
+        language,
+        page_material,
+        start_quest,
+        lock_id,
+        material,
+        sheath,
+        random_property,
+        random_suffix,
+        block,
+        item_set,
+        max_durability,
+        area,
+        map,
+        bag_family,
+        totem_category: 0,
+        sockets: Vec::new(),
+        socket_bonus: 0,
+        gem_properties: 0,
+        required_disenchant_skill: -1,
+        armor_damage_modifier: 0.0,
+        duration: 0,
+        item_limit_category: 0,
+        holiday_id: 0,
+    };
+    if tbc {
+        template.totem_category = r.u32("totem_category")?;
+        for _ in 0..3 {
+            template.sockets.push(ItemSocket {
+                color: r.u32("socket_color")?,
+                content: r.u32("socket_content")?,
+            });
+        }
+        template.socket_bonus = r.u32("socket_bonus")?;
+        template.gem_properties = r.u32("gem_properties")?;
+        template.required_disenchant_skill = r.i32("required_disenchant_skill")?;
+        template.armor_damage_modifier = r.f32("armor_damage_modifier")?;
+        template.duration = r.u32("duration")?;
+    }
+    if wotlk {
+        template.item_limit_category = r.u32("item_limit_category")?;
+        template.holiday_id = r.u32("holiday_id")?;
+    }
+    Ok(Some(template))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GameObjectTemplate {
+    pub entry: u32,
+    pub go_type: u32,
+    pub display_id: u32,
+    pub name: String,
+    /// TBC and later.
+    pub icon_name: String,
+    /// TBC and later.
+    pub cast_bar_caption: String,
+    /// TBC and later.
+    pub unk1: String,
+    /// The 24 type-specific `data` fields, sent raw.
+    pub data: Vec<u32>,
+    /// TBC and later.
+    pub size: f32,
+    /// WotLK only.
+    pub quest_items: Vec<u32>,
+}
+
+pub fn read_gameobject_query_response(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<Option<GameObjectTemplate>, ParseError> {
+    let wotlk = build >= 12340;
+    let tbc = build >= 8606;
+
+    let entry = r.u32("entry")?;
+    if entry & NOT_FOUND != 0 {
+        return Ok(None);
+    }
+    let go_type = r.u32("go_type")?;
+    let display_id = r.u32("display_id")?;
+    let name = r.cstring("name")?;
+    for _ in 0..3 {
+        r.cstring("alt_name")?;
+    }
+    let (icon_name, cast_bar_caption, unk1) = if tbc {
+        (
+            r.cstring("icon_name")?,
+            r.cstring("cast_bar_caption")?,
+            r.cstring("unk1")?,
+        )
+    } else {
+        (String::new(), String::new(), String::new())
+    };
+    let mut data = Vec::new();
+    for _ in 0..24 {
+        data.push(r.u32("data")?);
+    }
+    let size = if tbc { r.f32("size")? } else { 1.0 };
+    let mut quest_items = Vec::new();
+    if wotlk {
+        for _ in 0..6 {
+            quest_items.push(r.u32("quest_item")?);
+        }
+    }
+    Ok(Some(GameObjectTemplate {
+        entry,
+        go_type,
+        display_id,
+        name,
+        icon_name,
+        cast_bar_caption,
+        unk1,
+        data,
+        size,
+        quest_items,
+    }))
+}
+
+/// Query responses as each build sends them, shared with the extractor tests.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::fixture::Bytes;
+
+    pub(crate) const BUILDS: [u32; 3] = [5875, 8606, 12340];
+
+    pub(crate) fn not_found(entry: u32) -> Vec<u8> {
+        Bytes::new().u32(entry | NOT_FOUND).build()
+    }
+
+    /// A rare elite beast with one display, and a quest item in WotLK.
+    pub(crate) fn creature_response(build: u32, entry: u32, name: &str) -> Vec<u8> {
+        let (wotlk, tbc) = (build >= 12340, build >= 8606);
+        let mut b = Bytes::new();
+        b.u32(entry)
+            .cstring(name)
+            .cstring("")
+            .cstring("")
+            .cstring("");
+        b.cstring("Pack Leader");
+        if tbc {
+            b.cstring("");
+        }
+        // Kill credits in WotLK; an unknown field and pet spell data before.
+        b.u32(1).u32(1).u32(1).u32(2).u32(0).u32(0);
+        if tbc {
+            b.u32(11412).u32(0).u32(0).u32(0).f32(1.5).f32(1.0);
+        } else {
+            b.u32(11412).u8(0);
+        }
+        b.u8(0);
+        if wotlk {
+            b.u32(884).u32(0).u32(0).u32(0).u32(0).u32(0).u32(100);
+        }
+        b.build()
+    }
+
+    /// A green one-handed sword with one stat and one damage range.
+    pub(crate) fn item_response(build: u32, entry: u32, name: &str) -> Vec<u8> {
+        let (wotlk, tbc) = (build >= 12340, build >= 8606);
+        let mut b = Bytes::new();
+        b.u32(entry).u32(2).u32(7);
+        if tbc {
+            b.i32(-1);
+        }
+        b.cstring(name).cstring("").cstring("").cstring("");
+        b.u32(1542).u32(2).u32(0);
+        if wotlk {
+            b.u32(0);
+        }
+        b.u32(1000).u32(200).u32(13).i32(-1).i32(-1).u32(20).u32(15);
+        for _ in 0..7 {
+            b.u32(0);
+        }
+        b.i32(0).i32(1).u32(0);
+        if wotlk {
+            b.u32(1).u32(7).i32(3).u32(0).u32(0);
+        } else {
+            b.u32(7).i32(3);
+            for _ in 1..10 {
+                b.u32(0).i32(0);
+            }
+        }
+        b.f32(12.0).f32(23.0).u32(0);
+        for _ in 1..if wotlk { 2 } else { 5 } {
+            b.f32(0.0).f32(0.0).u32(0);
+        }
+        b.i32(0);
+        for _ in 0..6 {
+            b.i32(0);
+        }
+        b.u32(2600).u32(0).f32(0.0);
+        for _ in 0..5 {
+            b.u32(0).u32(0).i32(0).i32(-1).u32(0).i32(-1);
+        }
+        b.u32(2).cstring("Sharp.");
+        b.u32(0).u32(0).u32(0).u32(0).u32(0).i32(1).u32(3).u32(0);
+        if tbc {
+            b.u32(0);
+        }
+        b.u32(0).u32(0).u32(65).u32(0).u32(0).u32(0);
+        if tbc {
+            b.u32(0);
+            for _ in 0..3 {
+                b.u32(0).u32(0);
+            }
+            b.u32(0).u32(0).i32(-1).f32(0.0).u32(0);
+        }
+        if wotlk {
+            b.u32(0).u32(0);
+        }
+        b.build()
+    }
+
+    /// A chest whose first data field is its lock.
+    pub(crate) fn gameobject_response(build: u32, entry: u32, name: &str) -> Vec<u8> {
+        let mut b = Bytes::new();
+        b.u32(entry).u32(3).u32(259);
+        b.cstring(name).cstring("").cstring("").cstring("");
+        if build >= 8606 {
+            b.cstring("").cstring("Opening").cstring("");
+        }
+        b.u32(57);
+        for _ in 1..24 {
+            b.u32(0);
+        }
+        if build >= 8606 {
+            b.f32(1.25);
+        }
+        if build >= 12340 {
+            b.u32(3898).u32(0).u32(0).u32(0).u32(0).u32(0);
+        }
+        b.build()
+    }
+
+    fn read_all<T>(
+        data: &[u8],
+        read: impl FnOnce(&mut PacketReader) -> Result<Option<T>, ParseError>,
+    ) -> T {
+        let mut r = PacketReader::new(data);
+        let value = read(&mut r).unwrap().expect("template");
+        assert_eq!(r.remaining(), 0);
+        value
+    }
+
+    #[test]
+    fn reads_creature_responses() {
+        for build in BUILDS {
+            let data = creature_response(build, 2044, "Forlorn Spirit");
+            let t = read_all(&data, |r| read_creature_query_response(r, build));
+            assert_eq!((t.entry, t.name.as_str()), (2044, "Forlorn Spirit"));
+            assert_eq!((t.subname.as_str(), t.rank), ("Pack Leader", 2));
+            assert_eq!(t.display_ids[0], 11412);
+            assert_eq!(t.display_ids.len(), if build >= 8606 { 4 } else { 1 });
+            if build >= 8606 {
+                assert_eq!(t.health_multiplier, 1.5);
+            }
+            if build >= 12340 {
+                assert_eq!(
+                    (t.kill_credits.len(), t.quest_items[0], t.movement_id),
+                    (2, 884, 100)
+                );
+            } else {
+                assert!(t.quest_items.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn reads_item_responses() {
+        for build in BUILDS {
+            let data = item_response(build, 2489, "Two-Handed Sword");
+            let t = read_all(&data, |r| read_item_query_response(r, build));
+            assert_eq!((t.entry, t.class, t.subclass), (2489, 2, 7));
+            assert_eq!((t.display_id, t.quality, t.buy_price), (1542, 2, 1000));
+            assert_eq!(
+                t.stats[0],
+                ItemStat {
+                    stat_type: 7,
+                    value: 3
+                }
+            );
+            // Earlier builds always send ten stat slots and five damages.
+            let (stats, damages) = if build >= 12340 { (1, 2) } else { (10, 5) };
+            assert_eq!((t.stats.len(), t.damages.len()), (stats, damages));
+            assert_eq!(
+                t.damages[0],
+                ItemDamage {
+                    min: 12.0,
+                    max: 23.0,
+                    school: 0
+                }
+            );
+            assert_eq!(
+                (t.delay, t.description.as_str(), t.max_durability),
+                (2600, "Sharp.", 65)
+            );
+            assert_eq!(t.sockets.len(), if build >= 8606 { 3 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn reads_gameobject_responses() {
+        for build in BUILDS {
+            let data = gameobject_response(build, 2843, "Battered Chest");
+            let t = read_all(&data, |r| read_gameobject_query_response(r, build));
+            assert_eq!((t.entry, t.go_type, t.display_id), (2843, 3, 259));
+            assert_eq!((t.data.len(), t.data[0]), (24, 57));
+            let size = if build >= 8606 { 1.25 } else { 1.0 };
+            assert_eq!(t.size, size);
+            assert_eq!(t.quest_items.first(), (build >= 12340).then_some(&3898));
+        }
+    }
+
+    #[test]
+    fn unknown_entries_stop_after_the_entry() {
+        for build in BUILDS {
+            let data = not_found(2044);
+            let mut r = PacketReader::new(&data);
+            assert_eq!(read_creature_query_response(&mut r, build).unwrap(), None);
+            let mut r = PacketReader::new(&data);
+            assert_eq!(read_item_query_response(&mut r, build).unwrap(), None);
+            let mut r = PacketReader::new(&data);
+            assert_eq!(read_gameobject_query_response(&mut r, build).unwrap(), None);
+            assert_eq!(r.remaining(), 0);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Update field indices the extractors care about, per build.
+//!
+//! Only the handful of fields needed to rebuild spawn rows are listed; the
+//! full tables live in the emulator sources.
+
+use super::update_object::UpdateFields;
+
+pub const OBJECT_FIELD_ENTRY: u32 = 3;
+
+pub struct UnitFields {
+    pub health: u32,
+    pub max_health: u32,
+    pub level: u32,
+    pub faction_template: u32,
+    pub flags: u32,
+    pub display_id: u32,
+    pub dynamic_flags: u32,
+    pub npc_flags: u32,
+}
+
+const VANILLA_UNIT: UnitFields = UnitFields {
+    health: 22,
+    max_health: 28,
+    level: 34,
+    faction_template: 35,
+    flags: 46,
+    display_id: 131,
+    dynamic_flags: 143,
+    npc_flags: 147,
+};
+
+const TBC_UNIT: UnitFields = UnitFields {
+    health: 22,
+    max_health: 28,
+    level: 34,
+    faction_template: 35,
+    flags: 46,
+    display_id: 152,
+    dynamic_flags: 164,
+    npc_flags: 168,
+};
+
+const WOTLK_UNIT: UnitFields = UnitFields {
+    health: 24,
+    max_health: 32,
+    level: 54,
+    faction_template: 55,
+    flags: 59,
+    display_id: 67,
+    dynamic_flags: 79,
+    npc_flags: 82,
+};
+
+pub fn unit_fields(build: u32) -> &'static UnitFields {
+    match build {
+        5875 => &VANILLA_UNIT,
+        8606 => &TBC_UNIT,
+        _ => &WOTLK_UNIT,
+    }
+}
+
+/// Where a value lives in the update fields: a whole field, or one byte of a
+/// packed `BYTES` field.
+#[derive(Clone, Copy)]
+pub enum FieldSlot {
+    Whole(u32),
+    Byte(u32, u8),
+}
+
+impl FieldSlot {
+    /// Values absent from a create block are zero; the mask skips them.
+    pub fn get(self, fields: &UpdateFields) -> u32 {
+        match self {
+            FieldSlot::Whole(index) => fields.get(&index).copied().unwrap_or(0),
+            FieldSlot::Byte(index, byte) => {
+                (fields.get(&index).copied().unwrap_or(0) >> (byte * 8)) & 0xFF
+            }
+        }
+    }
+}
+
+pub struct GameObjectFields {
+    pub display_id: u32,
+    pub flags: u32,
+    pub faction: u32,
+    /// First of four float fields holding the rotation quaternion. `None`
+    /// from WotLK on, where the rotation is packed into the movement block.
+    pub rotation: Option<u32>,
+    pub state: FieldSlot,
+    pub type_id: FieldSlot,
+    pub anim_progress: FieldSlot,
+}
+
+/// Vanilla and TBC share the gameobject field layout.
+const CLASSIC_GAMEOBJECT: GameObjectFields = GameObjectFields {
+    display_id: 8,
+    flags: 9,
+    faction: 20,
+    rotation: Some(10),
+    state: FieldSlot::Whole(14),
+    type_id: FieldSlot::Whole(21),
+    anim_progress: FieldSlot::Whole(24),
+};
+
+const WOTLK_GAMEOBJECT: GameObjectFields = GameObjectFields {
+    display_id: 8,
+    flags: 9,
+    faction: 15,
+    rotation: None,
+    state: FieldSlot::Byte(17, 0),
+    type_id: FieldSlot::Byte(17, 1),
+    anim_progress: FieldSlot::Byte(17, 3),
+};
+
+pub fn gameobject_fields(build: u32) -> &'static GameObjectFields {
+    match build {
+        5875 | 8606 => &CLASSIC_GAMEOBJECT,
+        _ => &WOTLK_GAMEOBJECT,
+    }
+}
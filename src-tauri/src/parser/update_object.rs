@@ -0,0 +1,669 @@
+//! `SMSG_UPDATE_OBJECT`, `SMSG_COMPRESSED_UPDATE_OBJECT` and
+//! `SMSG_DESTROY_OBJECT`.
+//!
+//! The block framing is the same in every supported build; what changes is
+//! the update flag width and the contents of the movement block.
+
+use super::movement::{read_movement_info, MovementInfo};
+use super::reader::{Finding, FindingKind, PacketReader, ParseError, Vector3};
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// `TypeID` values sent with create blocks.
+const OBJECT_TYPES: &[u32] = &[0, 1, 2, 3, 4, 5, 6, 7];
+
+pub const TYPEID_UNIT: u8 = 3;
+pub const TYPEID_GAMEOBJECT: u8 = 5;
+
+/// Update field index to raw 32-bit value, as set by the update mask.
+pub type UpdateFields = BTreeMap<u32, u32>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateObject {
+    pub blocks: Vec<UpdateBlock>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpdateBlock {
+    Values {
+        guid: u64,
+        fields: UpdateFields,
+    },
+    Movement {
+        guid: u64,
+        movement: MovementBlock,
+    },
+    Create {
+        guid: u64,
+        object_type: u8,
+        /// `CREATE_OBJECT2`: the object just spawned rather than came into view.
+        spawned: bool,
+        movement: MovementBlock,
+        fields: UpdateFields,
+    },
+    OutOfRange {
+        guids: Vec<u64>,
+    },
+    Near {
+        guids: Vec<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplineInfo {
+    pub flags: u32,
+    pub time_passed: u32,
+    pub duration: u32,
+    pub id: u32,
+    pub points: Vec<Vector3>,
+    pub final_point: Vector3,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MovementBlock {
+    pub update_flags: u16,
+    /// Present for units (`UPDATEFLAG_LIVING`).
+    pub info: Option<MovementInfo>,
+    pub speeds: Vec<f32>,
+    pub spline: Option<SplineInfo>,
+    /// World position, from whichever of the living, position or stationary
+    /// parts the block carried.
+    pub position: Option<Vector3>,
+    pub orientation: f32,
+    pub target: Option<u64>,
+    /// WotLK `UPDATEFLAG_ROTATION`: the gameobject rotation packed into 64 bits.
+    pub packed_rotation: Option<i64>,
+}
+
+/// Gameobject rotation as stored in the `gameobject` table.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+/// Undo the WotLK rotation packing: 22 bits of X and 21 bits each of Y and Z,
+/// with W rebuilt from the unit length.
+pub fn unpack_rotation(packed: i64) -> Quaternion {
+    let x = (packed >> 42) as f32 / 2_097_152.0;
+    let y = ((packed << 22) >> 43) as f32 / 1_048_576.0;
+    let z = ((packed << 43) >> 43) as f32 / 1_048_576.0;
+    let length = x * x + y * y + z * z;
+    let w = if (length - 1.0).abs() >= 1.0 / 1_048_576.0 {
+        (1.0 - length).max(0.0).sqrt()
+    } else {
+        0.0
+    };
+    Quaternion { x, y, z, w }
+}
+
+pub fn read_update_object(r: &mut PacketReader, build: u32) -> Result<UpdateObject, ParseError> {
+    let count = r.u32("block_count")?;
+    if build < 12340 {
+        r.u8("has_transport")?;
+    }
+    let mut blocks = Vec::new();
+    for _ in 0..count {
+        blocks.push(read_update_block(r, build)?);
+    }
+    Ok(UpdateObject { blocks })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestroyObject {
+    pub guid: u64,
+    /// WotLK only: the object is going away because it died.
+    pub on_death: Option<bool>,
+}
+
+pub fn read_destroy_object(r: &mut PacketReader, build: u32) -> Result<DestroyObject, ParseError> {
+    let guid = r.guid("guid")?;
+    let on_death = if build >= 12340 {
+        Some(r.u8("on_death")? != 0)
+    } else {
+        None
+    };
+    Ok(DestroyObject { guid, on_death })
+}
+
+/// Most bytes to reserve up front for an inflated update packet.
+const MAX_INFLATED_PREALLOC: usize = 1 << 20;
+
+/// The compressed form is a `u32` inflated size followed by a zlib stream
+/// holding a regular update packet. Findings inside the inflated payload are
+/// reported with offsets into that payload.
+pub fn read_compressed_update_object(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<UpdateObject, ParseError> {
+    let size = r.u32("inflated_size")? as usize;
+    let offset = r.position();
+    let compressed = r.bytes(r.remaining(), "compressed_data")?;
+    // The size comes from the packet, so neither trust it for the
+    // allocation nor inflate past it.
+    let mut inflated = Vec::with_capacity(size.min(MAX_INFLATED_PREALLOC));
+    if let Err(e) = ZlibDecoder::new(compressed)
+        .take(size as u64 + 1)
+        .read_to_end(&mut inflated)
+    {
+        let expected = format!("zlib stream ({})", e);
+        return Err(bad_compression(r, offset, expected, compressed.len()));
+    }
+    if inflated.len() != size {
+        let expected = format!("{} inflated bytes", size);
+        return Err(bad_compression(r, offset, expected, inflated.len()));
+    }
+
+    let mut inner = PacketReader::new(&inflated);
+    let result = read_update_object(&mut inner, build);
+    let (stopped, left) = (inner.position(), inner.remaining());
+    for finding in inner.into_findings() {
+        r.add_finding(Finding {
+            detail: format!("inflated payload: {}", finding.detail),
+            ..finding
+        });
+    }
+    if result.is_ok() && left > 0 {
+        r.add_finding(Finding {
+            kind: FindingKind::TrailingBytes,
+            offset: stopped,
+            field: "inflated_payload".to_string(),
+            detail: format!(
+                "inflated payload: {} bytes left unread after offset {}",
+                left, stopped
+            ),
+        });
+    }
+    result
+}
+
+/// Fail on a compressed payload, recording a finding like a reader overrun
+/// does so that finding filters see the packet.
+fn bad_compression(
+    r: &mut PacketReader,
+    offset: usize,
+    expected: String,
+    available: usize,
+) -> ParseError {
+    let err = ParseError {
+        offset,
+        field: "compressed_data".to_string(),
+        expected,
+        available,
+    };
+    r.add_finding(Finding {
+        kind: FindingKind::BadCompression,
+        offset,
+        field: err.field.clone(),
+        detail: err.to_string(),
+    });
+    err
+}
+
+fn read_update_block(r: &mut PacketReader, build: u32) -> Result<UpdateBlock, ParseError> {
+    let offset = r.position();
+    let update_type = r.u8("update_type")?;
+    match update_type {
+        0 => Ok(UpdateBlock::Values {
+            guid: r.packed_guid("guid")?,
+            fields: read_update_mask(r)?,
+        }),
+        1 => {
+            // Movement-only blocks carried a full GUID until 3.1.2.
+            let guid = if build >= 12340 {
+                r.packed_guid("guid")?
+            } else {
+                r.guid("guid")?
+            };
+            Ok(UpdateBlock::Movement {
+                guid,
+                movement: read_movement_block(r, build)?,
+            })
+        }
+        2 | 3 => Ok(UpdateBlock::Create {
+            guid: r.packed_guid("guid")?,
+            object_type: r.u8_enum("object_type", OBJECT_TYPES)?,
+            spawned: update_type == 3,
+            movement: read_movement_block(r, build)?,
+            fields: read_update_mask(r)?,
+        }),
+        4 => Ok(UpdateBlock::OutOfRange {
+            guids: read_guid_list(r)?,
+        }),
+        5 => Ok(UpdateBlock::Near {
+            guids: read_guid_list(r)?,
+        }),
+        other => {
+            // Without knowing the block layout there is no way to continue.
+            r.add_finding(Finding {
+                kind: FindingKind::UnknownEnumValue,
+                offset,
+                field: "update_type".to_string(),
+                detail: format!("`update_type` has unknown value {} (0x{:X})", other, other),
+            });
+            Err(ParseError {
+                offset,
+                field: "update_type".to_string(),
+                expected: "update type 0-5".to_string(),
+                available: r.remaining() + 1,
+            })
+        }
+    }
+}
+
+fn read_guid_list(r: &mut PacketReader) -> Result<Vec<u64>, ParseError> {
+    let count = r.u32("guid_count")?;
+    (0..count).map(|_| r.packed_guid("guid")).collect()
+}
+
+/// A `u8` block count, that many `u32` mask words, then one `u32` per set bit.
+fn read_update_mask(r: &mut PacketReader) -> Result<UpdateFields, ParseError> {
+    let block_count = r.u8("mask_block_count")? as usize;
+    let mut mask = Vec::with_capacity(block_count);
+    for _ in 0..block_count {
+        mask.push(r.u32("mask_block")?);
+    }
+    let mut fields = UpdateFields::new();
+    for (block, bits) in mask.into_iter().enumerate() {
+        for bit in 0..32 {
+            if bits & (1 << bit) != 0 {
+                let index = (block * 32 + bit) as u32;
+                fields.insert(index, r.u32("field_value")?);
+            }
+        }
+    }
+    Ok(fields)
+}
+
+fn read_movement_block(r: &mut PacketReader, build: u32) -> Result<MovementBlock, ParseError> {
+    match build {
+        5875 | 8606 => read_movement_block_classic(r, build),
+        _ => read_movement_block_wotlk(r),
+    }
+}
+
+/// Vanilla and TBC share the block shape; TBC sends two extra flight speeds,
+/// moves the spline flag and splits the GUID words into two flags.
+fn read_movement_block_classic(
+    r: &mut PacketReader,
+    build: u32,
+) -> Result<MovementBlock, ParseError> {
+    const TRANSPORT: u16 = 0x02;
+    const MELEE_ATTACKING: u16 = 0x04;
+    const HIGHGUID: u16 = 0x08;
+    const ALL: u16 = 0x10;
+    const LIVING: u16 = 0x20;
+    const HAS_POSITION: u16 = 0x40;
+
+    let (speed_count, spline_enabled) = if build >= 8606 {
+        (8, 0x0800_0000)
+    } else {
+        (6, 0x0040_0000)
+    };
+
+    let mut block = MovementBlock {
+        update_flags: r.u8("update_flags")? as u16,
+        ..Default::default()
+    };
+    let flags = block.update_flags;
+    if flags & LIVING != 0 {
+        let info = read_movement_info(r, build)?;
+        for _ in 0..speed_count {
+            block.speeds.push(r.f32("speed")?);
+        }
+        if info.flags & spline_enabled != 0 {
+            block.spline = Some(read_spline(r, build)?);
+        }
+        block.position = Some(info.position);
+        block.orientation = info.orientation;
+        block.info = Some(info);
+    } else if flags & HAS_POSITION != 0 {
+        block.position = Some(r.vector3("position")?);
+        block.orientation = r.f32("orientation")?;
+    }
+    if flags & HIGHGUID != 0 {
+        r.u32("guid_low")?;
+    }
+    // TBC renamed 0x10 to HIGHGUID; both builds send a u32.
+    if flags & ALL != 0 {
+        r.u32("guid_high")?;
+    }
+    if flags & MELEE_ATTACKING != 0 {
+        block.target = Some(r.packed_guid("target")?);
+    }
+    if flags & TRANSPORT != 0 {
+        r.u32("transport_time")?;
+    }
+    Ok(block)
+}
+
+fn read_movement_block_wotlk(r: &mut PacketReader) -> Result<MovementBlock, ParseError> {
+    const TRANSPORT: u16 = 0x0002;
+    const HAS_TARGET: u16 = 0x0004;
+    const UNKNOWN: u16 = 0x0008;
+    const LOWGUID: u16 = 0x0010;
+    const LIVING: u16 = 0x0020;
+    const STATIONARY_POSITION: u16 = 0x0040;
+    const VEHICLE: u16 = 0x0080;
+    const POSITION: u16 = 0x0100;
+    const ROTATION: u16 = 0x0200;
+    const SPLINE_ENABLED: u32 = 0x0800_0000;
+
+    let mut block = MovementBlock {
+        update_flags: r.u16("update_flags")?,
+        ..Default::default()
+    };
+    let flags = block.update_flags;
+    if flags & LIVING != 0 {
+        let info = read_movement_info(r, 12340)?;
+        for _ in 0..9 {
+            block.speeds.push(r.f32("speed")?);
+        }
+        if info.flags & SPLINE_ENABLED != 0 {
+            block.spline = Some(read_spline(r, 12340)?);
+        }
+        block.position = Some(info.position);
+        block.orientation = info.orientation;
+        block.info = Some(info);
+    } else if flags & POSITION != 0 {
+        r.packed_guid("transport_guid")?;
+        block.position = Some(r.vector3("position")?);
+        r.vector3("transport_offset")?;
+        block.orientation = r.f32("orientation")?;
+        r.f32("transport_orientation")?;
+    } else if flags & STATIONARY_POSITION != 0 {
+        block.position = Some(r.vector3("position")?);
+        block.orientation = r.f32("orientation")?;
+    }
+    if flags & UNKNOWN != 0 {
+        r.u32("unknown")?;
+    }
+    if flags & LOWGUID != 0 {
+        r.u32("guid_low")?;
+    }
+    if flags & HAS_TARGET != 0 {
+        block.target = Some(r.packed_guid("target")?);
+    }
+    if flags & TRANSPORT != 0 {
+        r.u32("transport_time")?;
+    }
+    if flags & VEHICLE != 0 {
+        r.u32("vehicle_id")?;
+        r.f32("vehicle_orientation")?;
+    }
+    if flags & ROTATION != 0 {
+        block.packed_rotation = Some(r.i64("packed_rotation")?);
+    }
+    Ok(block)
+}
+
+/// Spline data sent with a unit that is mid-move when it comes into view.
+fn read_spline(r: &mut PacketReader, build: u32) -> Result<SplineInfo, ParseError> {
+    let (final_point, final_target, final_angle) = match build {
+        5875 => (0x0001_0000, 0x0002_0000, 0x0004_0000),
+        _ => (0x0000_8000, 0x0001_0000, 0x0002_0000),
+    };
+
+    let flags = r.u32("spline_flags")?;
+    if flags & final_angle != 0 {
+        r.f32("spline_facing_angle")?;
+    } else if flags & final_target != 0 {
+        r.guid("spline_facing_target")?;
+    } else if flags & final_point != 0 {
+        r.vector3("spline_facing_point")?;
+    }
+    let time_passed = r.u32("spline_time_passed")?;
+    let duration = r.u32("spline_duration")?;
+    let id = r.u32("spline_id")?;
+    if build >= 12340 {
+        r.f32("spline_duration_mod")?;
+        r.f32("spline_duration_mod_next")?;
+        r.f32("spline_vertical_acceleration")?;
+        r.u32("spline_effect_start_time")?;
+    }
+    let count = r.u32("spline_point_count")?;
+    let mut points = Vec::new();
+    for _ in 0..count {
+        points.push(r.vector3("spline_point")?);
+    }
+    if build >= 12340 {
+        r.u8("spline_mode")?;
+    }
+    let final_point = r.vector3("spline_final_point")?;
+    Ok(SplineInfo {
+        flags,
+        time_passed,
+        duration,
+        id,
+        points,
+        final_point,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::{compressed, vector, Bytes};
+
+    const BUILDS: [u32; 3] = [5875, 8606, 12340];
+    const CREATURE: u64 = 0xF130_0004_D200_0101;
+
+    /// One creature create block, then a values block for the same GUID.
+    fn creature_update(build: u32) -> Vec<u8> {
+        Bytes::new()
+            .update_header(build, 2)
+            .create_header(CREATURE, TYPEID_UNIT)
+            .living(build, vector(-8913.5, 554.25, 93.75), 1.5)
+            .update_mask(&[(0, 0x0101), (3, 1234), (40, 7)])
+            .u8(0)
+            .packed_guid(CREATURE)
+            .update_mask(&[(22, 55)])
+            .build()
+    }
+
+    fn read(build: u32, data: &[u8]) -> (Result<UpdateObject, ParseError>, usize) {
+        let mut r = PacketReader::new(data);
+        let result = read_update_object(&mut r, build);
+        (result, r.remaining())
+    }
+
+    fn read_compressed(
+        build: u32,
+        data: &[u8],
+    ) -> (Result<UpdateObject, ParseError>, Vec<Finding>) {
+        let mut r = PacketReader::new(data);
+        let result = read_compressed_update_object(&mut r, build);
+        (result, r.into_findings())
+    }
+
+    #[test]
+    fn reads_create_and_values_blocks() {
+        for build in BUILDS {
+            let (update, left) = read(build, &creature_update(build));
+            let blocks = update.unwrap().blocks;
+            assert_eq!(left, 0, "build {}", build);
+            let UpdateBlock::Create {
+                guid,
+                object_type,
+                spawned,
+                movement,
+                fields,
+            } = &blocks[0]
+            else {
+                panic!("build {}: {:?}", build, blocks[0]);
+            };
+            assert_eq!(
+                (*guid, *object_type, *spawned),
+                (CREATURE, TYPEID_UNIT, false)
+            );
+            assert_eq!(movement.position, Some(vector(-8913.5, 554.25, 93.75)));
+            assert_eq!(movement.orientation, 1.5);
+            assert!(movement.info.is_some());
+            assert_eq!(fields.get(&3), Some(&1234));
+            assert_eq!(fields.get(&40), Some(&7));
+            let UpdateBlock::Values { guid, fields } = &blocks[1] else {
+                panic!("build {}: {:?}", build, blocks[1]);
+            };
+            assert_eq!((*guid, fields.get(&22)), (CREATURE, Some(&55)));
+        }
+    }
+
+    #[test]
+    fn speed_count_follows_build() {
+        for (build, speeds) in [(5875, 6), (8606, 8), (12340, 9)] {
+            let blocks = read(build, &creature_update(build)).0.unwrap().blocks;
+            let UpdateBlock::Create { movement, .. } = &blocks[0] else {
+                panic!();
+            };
+            assert_eq!(movement.speeds.len(), speeds, "build {}", build);
+        }
+    }
+
+    #[test]
+    fn packed_guids_skip_zero_bytes() {
+        let guids = [1, 0xFF00_0000_0000_0000, 0x0001_0203_0405_0607, u64::MAX];
+        for build in BUILDS {
+            let mut data = Bytes::new();
+            data.update_header(build, 1).u8(4).u32(guids.len() as u32);
+            for guid in guids {
+                data.packed_guid(guid);
+            }
+            let blocks = read(build, &data.build()).0.unwrap().blocks;
+            let UpdateBlock::OutOfRange { guids: read } = &blocks[0] else {
+                panic!();
+            };
+            assert_eq!(read, &guids);
+        }
+        assert_eq!(
+            Bytes::new().packed_guid(0x0500_0000_0000_0001).build(),
+            [0x81, 1, 5]
+        );
+    }
+
+    #[test]
+    fn spawned_objects_use_create_object2() {
+        let data = Bytes::new()
+            .update_header(12340, 1)
+            .u8(3)
+            .packed_guid(0xF110_0000_0000_0001)
+            .u8(TYPEID_GAMEOBJECT)
+            .stationary(12340, vector(1.0, 2.0, 3.0), 0.5, Some(42))
+            .update_mask(&[])
+            .build();
+        let blocks = read(12340, &data).0.unwrap().blocks;
+        let UpdateBlock::Create {
+            spawned, movement, ..
+        } = &blocks[0]
+        else {
+            panic!();
+        };
+        assert!(spawned);
+        assert_eq!(movement.packed_rotation, Some(42));
+    }
+
+    #[test]
+    fn movement_blocks_use_a_full_guid_before_wotlk() {
+        for build in BUILDS {
+            let mut data = Bytes::new();
+            data.update_header(build, 1).u8(1);
+            if build < 12340 {
+                data.guid(CREATURE);
+            } else {
+                data.packed_guid(CREATURE);
+            }
+            data.stationary(build, vector(1.0, 2.0, 3.0), 0.0, None);
+            let (update, left) = read(build, &data.build());
+            let blocks = update.unwrap().blocks;
+            assert!(matches!(
+                blocks[0],
+                UpdateBlock::Movement { guid: CREATURE, .. }
+            ));
+            assert_eq!(left, 0);
+        }
+    }
+
+    #[test]
+    fn compressed_updates_match_the_plain_form() {
+        for build in BUILDS {
+            let plain = creature_update(build);
+            let (update, findings) = read_compressed(build, &compressed(&plain));
+            assert!(findings.is_empty(), "build {}: {:?}", build, findings);
+            assert_eq!(
+                serde_json::to_value(update.unwrap()).unwrap(),
+                serde_json::to_value(read(build, &plain).0.unwrap()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_size_must_match() {
+        let plain = creature_update(5875);
+        let mut data = compressed(&plain);
+        data[0] += 1;
+        let err = read_compressed(5875, &data).0.unwrap_err();
+        assert_eq!(err.expected, format!("{} inflated bytes", plain.len() + 1));
+        assert_eq!(err.available, plain.len());
+
+        let mut data = compressed(&plain);
+        data[0] -= 1;
+        let (err, findings) = read_compressed(5875, &data);
+        assert_eq!(err.unwrap_err().available, plain.len());
+        assert_eq!(findings[0].kind, FindingKind::BadCompression);
+    }
+
+    #[test]
+    fn compressed_garbage_fails() {
+        let mut data = compressed(&creature_update(5875));
+        data.truncate(data.len() / 2);
+        let err = read_compressed(5875, &data).0.unwrap_err();
+        assert_eq!(err.field, "compressed_data");
+
+        let data = Bytes::new()
+            .u32(16)
+            .bytes(&[0xDE, 0xAD, 0xBE, 0xEF])
+            .build();
+        assert!(read_compressed(5875, &data).0.is_err());
+    }
+
+    #[test]
+    fn inflated_leftovers_are_findings() {
+        let mut plain = creature_update(8606);
+        plain.extend([1, 2, 3]);
+        let (update, findings) = read_compressed(8606, &compressed(&plain));
+        assert!(update.is_ok());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::TrailingBytes);
+        assert_eq!(findings[0].offset, plain.len() - 3);
+    }
+
+    #[test]
+    fn destroy_object_adds_death_flag_in_wotlk() {
+        for build in BUILDS {
+            let mut data = Bytes::new();
+            data.guid(CREATURE);
+            if build >= 12340 {
+                data.u8(1);
+            }
+            let data = data.build();
+            let mut r = PacketReader::new(&data);
+            let destroy = read_destroy_object(&mut r, build).unwrap();
+            assert_eq!(destroy.guid, CREATURE);
+            assert_eq!(destroy.on_death, (build >= 12340).then_some(true));
+            assert_eq!(r.remaining(), 0);
+        }
+    }
+
+    #[test]
+    fn unknown_block_type_stops_decoding() {
+        let data = Bytes::new().update_header(5875, 1).u8(9).build();
+        let mut r = PacketReader::new(&data);
+        assert!(read_update_object(&mut r, 5875).is_err());
+        let findings = r.into_findings();
+        assert_eq!(findings[0].kind, FindingKind::UnknownEnumValue);
+    }
+}
@@ -0,0 +1,19 @@
+use super::reader::{PacketReader, ParseError, Vector3};
+use serde::Serialize;
+
+/// `SMSG_LOGIN_VERIFY_WORLD` and `SMSG_NEW_WORLD` both tell the client which
+/// map it is on and where it stands; the layout is identical in every build.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorldPosition {
+    pub map: u32,
+    pub position: Vector3,
+    pub orientation: f32,
+}
+
+pub fn read_world_position(r: &mut PacketReader) -> Result<WorldPosition, ParseError> {
+    Ok(WorldPosition {
+        map: r.u32("map")?,
+        position: r.vector3("position")?,
+        orientation: r.f32("orientation")?,
+    })
+}
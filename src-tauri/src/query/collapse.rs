@@ -0,0 +1,83 @@
+//! Runs of repeated packets, such as heartbeats from a player standing
+//! still, folded into one row.
+
+use crate::parser;
+use crate::state::Packet;
+use serde::Deserialize;
+use serde_json::Value;
+use std::ops::Range;
+
+/// Decoded fields that change between otherwise identical packets.
+const TIME_FIELDS: &[&str] = &["time", "timestamp"];
+
+/// When a packet continues the run before it. Every mode needs the same
+/// opcode and direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Collapse {
+    Opcode,
+    /// Byte-equal payloads.
+    Bytes,
+    /// Payloads equal apart from timestamps. Decoded fields are compared
+    /// where a decoder exists; other packets must be byte-equal.
+    IgnoreTime,
+}
+
+/// What a packet is compared on in `IgnoreTime` mode.
+#[derive(PartialEq)]
+enum Fingerprint<'a> {
+    Fields(Value),
+    Bytes(&'a [u8]),
+}
+
+fn fingerprint(build: Option<u32>, packet: &Packet) -> Fingerprint<'_> {
+    match parser::decode_fields(build, packet) {
+        Some(mut fields) => {
+            strip_time(&mut fields);
+            Fingerprint::Fields(fields)
+        }
+        None => Fingerprint::Bytes(&packet.data),
+    }
+}
+
+fn strip_time(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, _| !TIME_FIELDS.contains(&key.as_str()));
+            map.values_mut().for_each(strip_time);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_time),
+        _ => {}
+    }
+}
+
+/// Split `packets` into runs of consecutive repeats. Each packet is
+/// compared with the first of its run, so slow drift never adds up to one
+/// long run.
+pub(super) fn runs(packets: &[&Packet], mode: Collapse, build: Option<u32>) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut start_print = None;
+    for (index, packet) in packets.iter().enumerate().skip(1) {
+        let first = packets[start];
+        let repeats = packet.opcode == first.opcode
+            && packet.direction == first.direction
+            && match mode {
+                Collapse::Opcode => true,
+                Collapse::Bytes => packet.data == first.data,
+                Collapse::IgnoreTime => {
+                    let first_print = start_print.get_or_insert_with(|| fingerprint(build, first));
+                    *first_print == fingerprint(build, packet)
+                }
+            };
+        if !repeats {
+            runs.push(start..index);
+            start = index;
+            start_print = None;
+        }
+    }
+    if !packets.is_empty() {
+        runs.push(start..packets.len());
+    }
+    runs
+}
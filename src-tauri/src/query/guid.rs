@@ -0,0 +1,126 @@
+//! Every packet that mentions a GUID.
+
+use super::par_scan;
+use super::pattern::BytePattern;
+use super::search::pack_guid;
+use crate::parser;
+use crate::state::{Direction, Packet, Session};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GuidHit {
+    pub packet_id: usize,
+    pub timestamp: u32,
+    /// Offsets of the full 8-byte GUID.
+    pub raw_offsets: Vec<usize>,
+    /// Offsets of the mask byte of the packed form.
+    pub packed_offsets: Vec<usize>,
+    /// Dotted paths of decoded fields holding the GUID, e.g.
+    /// `blocks[0].guid`.
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GuidOpcodeGroup {
+    pub opcode: u32,
+    pub direction: Direction,
+    pub opcode_name: String,
+    pub hits: Vec<GuidHit>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GuidReport {
+    /// Hex, since JSON numbers can't hold every GUID.
+    pub guid: String,
+    pub packet_count: usize,
+    /// Most hits first.
+    pub groups: Vec<GuidOpcodeGroup>,
+}
+
+/// Opcodes whose payload is compressed, so a byte scan can't see into them.
+const COMPRESSED_OPCODES: &[&str] = &["SMSG_COMPRESSED_UPDATE_OBJECT"];
+
+/// Byte matches come from a scan of every payload. Only the packets that
+/// match are decoded, and the compressed ones, which are always decoded: a
+/// decoded GUID is otherwise always in the bytes in one of the two forms.
+pub fn find_guid(session: &Session, guid: u64) -> GuidReport {
+    let raw = BytePattern::exact(&guid.to_le_bytes());
+    let packed = BytePattern::exact(&pack_guid(guid));
+
+    let hits: Vec<(&Packet, GuidHit)> = par_scan(&session.packets, |packet| {
+        let raw_offsets = raw.find_all(&packet.data);
+        let packed_offsets = packed.find_all(&packet.data);
+        let compressed = COMPRESSED_OPCODES.contains(&parser::opcode_name(session.build, packet));
+        if raw_offsets.is_empty() && packed_offsets.is_empty() && !compressed {
+            return None;
+        }
+        let mut fields = Vec::new();
+        if let Some(decoded) = parser::decode_fields(session.build, packet) {
+            field_paths(&decoded, guid, &mut String::new(), &mut fields);
+        }
+        if raw_offsets.is_empty() && packed_offsets.is_empty() && fields.is_empty() {
+            return None;
+        }
+        Some((
+            packet,
+            GuidHit {
+                packet_id: packet.id,
+                timestamp: packet.timestamp,
+                raw_offsets,
+                packed_offsets,
+                fields,
+            },
+        ))
+    });
+
+    let packet_count = hits.len();
+    let mut groups: BTreeMap<(u32, u8), GuidOpcodeGroup> = BTreeMap::new();
+    for (packet, hit) in hits {
+        groups
+            .entry((packet.opcode, packet.direction as u8))
+            .or_insert_with(|| GuidOpcodeGroup {
+                opcode: packet.opcode,
+                direction: packet.direction,
+                opcode_name: parser::opcode_name(session.build, packet).to_string(),
+                hits: Vec::new(),
+            })
+            .hits
+            .push(hit);
+    }
+    let mut groups: Vec<GuidOpcodeGroup> = groups.into_values().collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.hits.len()));
+
+    GuidReport {
+        guid: format!("0x{:016X}", guid),
+        packet_count,
+        groups,
+    }
+}
+
+fn field_paths(value: &Value, guid: u64, path: &mut String, out: &mut Vec<String>) {
+    match value {
+        Value::Number(n) if n.as_u64() == Some(guid) => out.push(path.clone()),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{}]", i));
+                field_paths(item, guid, path, out);
+                path.truncate(len);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                field_paths(item, guid, path, out);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
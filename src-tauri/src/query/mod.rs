@@ -0,0 +1,423 @@
+//! Backend packet queries.
+//!
+//! A query combines filters with `and` (or `&&`, or just a space), `or`
+//! (`||`), `not` (`!`) and parentheses:
+//!
+//! - `SMSG_*LOOT*`, `opcode:0x15F`: opcode name glob (`*`, `?`, any case)
+//!   or number
+//! - `dir:smsg`, `dir:cmsg`
+//! - `size:>100`, `size:16..64`, `id:..5000`: inclusive ranges; `<`, `<=`,
+//!   `>`, `>=`, `=` and open-ended `a..`/`..b` forms all work
+//! - `time:2s..90s`: time since the session's first packet, in `ms`
+//!   (default), `s` or `m`
+//! - `bytes:"DE AD ?? EF"`, `bytes@4:0100`: payload bytes with `??`
+//!   wildcards, anywhere or at a fixed offset
+//! - `guid == 0xF130000000001234`, `items.item=2589`, `text ~ "hello"`:
+//!   decoded fields, by dotted path. Arrays match if any element does. `~`
+//!   is a case-insensitive substring test. Packets that don't decode, or
+//!   lack the field, never match; use `not` for the complement.
+//!
+//! An empty query matches every packet.
+
+pub mod collapse;
+pub mod guid;
+pub mod pattern;
+pub mod search;
+mod syntax;
+pub mod window;
+
+use crate::parser;
+use crate::state::{Direction, Packet, Session};
+use pattern::BytePattern;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Sessions smaller than this are scanned on the calling thread.
+const PARALLEL_MIN_PACKETS: usize = 50_000;
+
+#[derive(Debug, Clone)]
+enum Node {
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+    Opcode(OpcodeMatch),
+    Direction(Direction),
+    Size(Range),
+    Id(Range),
+    /// Milliseconds since the first packet.
+    Time(Range),
+    Bytes {
+        pattern: BytePattern,
+        at: Option<usize>,
+    },
+    Field {
+        path: Vec<String>,
+        op: CmpOp,
+        value: Literal,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum OpcodeMatch {
+    Number(u32),
+    Glob(String),
+    /// A glob resolved against the opcodes present in a session.
+    Set(HashSet<u32>),
+}
+
+/// Inclusive bounds.
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl Range {
+    fn at_least(min: u64) -> Self {
+        Range {
+            min: Some(min),
+            max: None,
+        }
+    }
+
+    fn at_most(max: u64) -> Self {
+        Range {
+            min: None,
+            max: Some(max),
+        }
+    }
+
+    fn contains(&self, value: u64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+/// A field predicate's right-hand side, read every way it could be meant.
+#[derive(Debug, Clone)]
+struct Literal {
+    text: String,
+    int: Option<i128>,
+    float: Option<f64>,
+    boolean: Option<bool>,
+}
+
+impl Literal {
+    /// Quoted values are always text.
+    fn new(text: String, quoted: bool) -> Self {
+        if quoted {
+            return Literal {
+                text,
+                int: None,
+                float: None,
+                boolean: None,
+            };
+        }
+        let int = match text.strip_prefix('-') {
+            Some(negative) => syntax::parse_u64(negative).map(|v| -i128::from(v)),
+            None => syntax::parse_u64(&text).map(i128::from),
+        };
+        Literal {
+            int,
+            float: int.map(|v| v as f64).or_else(|| text.parse().ok()),
+            boolean: text.parse().ok(),
+            text,
+        }
+    }
+}
+
+/// A parsed query, reusable across sessions.
+#[derive(Debug, Clone)]
+pub struct Query {
+    root: Node,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        syntax::parse(text).map(|root| Query { root })
+    }
+
+    /// Ids of the matching packets, in capture order.
+    pub fn matching_ids(&self, session: &Session) -> Vec<usize> {
+        self.matching(session).map(|p| p.id).collect()
+    }
+
+    /// The matching packets, in capture order.
+    pub fn matching<'a>(&self, session: &'a Session) -> impl Iterator<Item = &'a Packet> {
+        let packets = &session.packets;
+        let context = Context {
+            build: session.build,
+            start: packets.first().map_or(0, |p| p.timestamp),
+        };
+        let root = prepare(&self.root, session);
+        par_scan(packets, |packet| {
+            let mut subject = Subject {
+                packet,
+                fields: None,
+            };
+            eval(&root, &context, &mut subject).then_some(packet)
+        })
+        .into_iter()
+    }
+}
+
+/// `f` over every packet, split across threads for large sessions. Results
+/// keep capture order.
+fn par_scan<'a, T: Send>(
+    packets: &'a [Packet],
+    f: impl Fn(&'a Packet) -> Option<T> + Sync,
+) -> Vec<T> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if packets.len() < PARALLEL_MIN_PACKETS || threads == 1 {
+        return packets.iter().filter_map(f).collect();
+    }
+    let chunk = packets.len().div_ceil(threads);
+    let f = &f;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = packets
+            .chunks(chunk)
+            .map(|slice| scope.spawn(move || slice.iter().filter_map(f).collect::<Vec<T>>()))
+            .collect();
+        workers
+            .into_iter()
+            // A worker that panicked would leave its chunk out of the result.
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+struct Context {
+    build: Option<u32>,
+    start: u32,
+}
+
+/// The packet under test, decoded at most once however many field
+/// predicates look at it.
+struct Subject<'a> {
+    packet: &'a Packet,
+    fields: Option<Option<Value>>,
+}
+
+/// Resolve opcode globs against the session and order every `and`/`or`
+/// so the cheap header checks run before payload scans and decoding.
+fn prepare(node: &Node, session: &Session) -> Node {
+    let mut names: Option<HashMap<u32, &str>> = None;
+    let mut resolve = |pattern: &str| {
+        let names = names.get_or_insert_with(|| {
+            let mut names = HashMap::new();
+            for packet in &session.packets {
+                names
+                    .entry(packet.opcode)
+                    .or_insert_with(|| parser::opcode_name(session.build, packet));
+            }
+            names
+        });
+        names
+            .iter()
+            .filter(|(_, name)| glob_match(pattern, name))
+            .map(|(&opcode, _)| opcode)
+            .collect()
+    };
+    prepare_node(node, &mut resolve)
+}
+
+fn prepare_node(node: &Node, resolve: &mut impl FnMut(&str) -> HashSet<u32>) -> Node {
+    match node {
+        Node::And(terms) | Node::Or(terms) => {
+            let mut terms: Vec<Node> = terms.iter().map(|t| prepare_node(t, resolve)).collect();
+            terms.sort_by_key(cost);
+            match node {
+                Node::And(_) => Node::And(terms),
+                _ => Node::Or(terms),
+            }
+        }
+        Node::Not(inner) => Node::Not(Box::new(prepare_node(inner, resolve))),
+        Node::Opcode(OpcodeMatch::Glob(pattern)) => {
+            Node::Opcode(OpcodeMatch::Set(resolve(pattern)))
+        }
+        other => other.clone(),
+    }
+}
+
+fn cost(node: &Node) -> u8 {
+    match node {
+        Node::And(terms) | Node::Or(terms) => terms.iter().map(cost).max().unwrap_or(0),
+        Node::Not(inner) => cost(inner),
+        Node::Bytes { .. } => 1,
+        Node::Field { .. } => 2,
+        _ => 0,
+    }
+}
+
+fn eval(node: &Node, context: &Context, subject: &mut Subject) -> bool {
+    let packet = subject.packet;
+    match node {
+        Node::And(terms) => terms.iter().all(|t| eval(t, context, subject)),
+        Node::Or(terms) => terms.iter().any(|t| eval(t, context, subject)),
+        Node::Not(inner) => !eval(inner, context, subject),
+        Node::Opcode(OpcodeMatch::Number(opcode)) => packet.opcode == *opcode,
+        Node::Opcode(OpcodeMatch::Set(opcodes)) => opcodes.contains(&packet.opcode),
+        // Unresolved globs only exist before `prepare`.
+        Node::Opcode(OpcodeMatch::Glob(pattern)) => {
+            glob_match(pattern, parser::opcode_name(context.build, packet))
+        }
+        Node::Direction(direction) => packet.direction == *direction,
+        Node::Size(range) => range.contains(packet.data.len() as u64),
+        Node::Id(range) => range.contains(packet.id as u64),
+        Node::Time(range) => {
+            range.contains(u64::from(packet.timestamp.wrapping_sub(context.start)))
+        }
+        Node::Bytes { pattern, at } => match at {
+            Some(offset) => pattern.matches_at(&packet.data, *offset),
+            None => pattern.find(&packet.data, 0).is_some(),
+        },
+        Node::Field { path, op, value } => {
+            let fields = subject
+                .fields
+                .get_or_insert_with(|| parser::decode_fields(context.build, packet));
+            fields
+                .as_ref()
+                .is_some_and(|fields| field_matches(fields, path, *op, value))
+        }
+    }
+}
+
+fn field_matches(value: &Value, path: &[String], op: CmpOp, literal: &Literal) -> bool {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .any(|item| field_matches(item, path, op, literal)),
+        _ if path.is_empty() => compare(value, op, literal),
+        Value::Object(map) => map
+            .get(&path[0])
+            .is_some_and(|child| field_matches(child, &path[1..], op, literal)),
+        _ => false,
+    }
+}
+
+fn compare(value: &Value, op: CmpOp, literal: &Literal) -> bool {
+    if op == CmpOp::Contains {
+        let text = match value {
+            Value::String(s) => s.to_lowercase(),
+            other => other.to_string(),
+        };
+        return text.contains(&literal.text.to_lowercase());
+    }
+    let ordering = match value {
+        Value::Number(n) => {
+            let int = n.as_u64().map(i128::from).or(n.as_i64().map(i128::from));
+            match (int, literal.int) {
+                // Exact, so 64-bit GUIDs compare correctly.
+                (Some(got), Some(want)) => Some(got.cmp(&want)),
+                _ => n
+                    .as_f64()
+                    .zip(literal.float)
+                    .and_then(|(got, want)| got.partial_cmp(&want)),
+            }
+        }
+        Value::String(s) => Some(s.as_str().cmp(literal.text.as_str())),
+        Value::Bool(b) => literal.boolean.map(|want| b.cmp(&want)),
+        _ => None,
+    };
+    let Some(ordering) = ordering else {
+        return op == CmpOp::Ne;
+    };
+    match op {
+        CmpOp::Eq => ordering == Ordering::Equal,
+        CmpOp::Ne => ordering != Ordering::Equal,
+        CmpOp::Lt => ordering == Ordering::Less,
+        CmpOp::Le => ordering != Ordering::Greater,
+        CmpOp::Gt => ordering == Ordering::Greater,
+        CmpOp::Ge => ordering != Ordering::Less,
+        CmpOp::Contains => unreachable!(),
+    }
+}
+
+/// Case-insensitive glob with `*` and `?`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_uppercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_uppercase()).collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it has eaten.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(count: usize) -> Vec<Packet> {
+        (0..count)
+            .map(|id| Packet {
+                id,
+                timestamp: id as u32,
+                direction: Direction::ServerToClient,
+                opcode: 0,
+                opcode_name: String::new(),
+                size: 0,
+                data: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn globs_match_any_case() {
+        assert!(glob_match("SMSG_*LOOT*", "smsg_loot_response"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("?MSG_PING", "CMSG_PING"));
+        assert!(glob_match("*AB", "AAB"));
+        assert!(glob_match("A*B*C", "AXBYBZC"));
+        assert!(!glob_match("SMSG_*", "CMSG_PING"));
+        assert!(!glob_match("A?", "A"));
+    }
+
+    #[test]
+    fn par_scan_keeps_capture_order() {
+        let packets = packets(PARALLEL_MIN_PACKETS * 2 + 7);
+        let ids = par_scan(&packets, |p| (p.id % 3 == 0).then_some(p.id));
+        let expected: Vec<usize> = (0..packets.len()).filter(|id| id % 3 == 0).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "decoder bug")]
+    fn par_scan_propagates_worker_panics() {
+        let packets = packets(PARALLEL_MIN_PACKETS * 2);
+        let last = packets.len() - 1;
+        par_scan(&packets, |p| {
+            assert!(p.id != last, "decoder bug");
+            Some(p.id)
+        });
+    }
+}
@@ -0,0 +1,106 @@
+//! Byte patterns with `??` wildcards.
+
+/// A byte sequence where `None` matches any byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytePattern(Vec<Option<u8>>);
+
+impl BytePattern {
+    /// Parse hex such as `DE AD ?? EF` or `dead??ef`. Whitespace is ignored.
+    pub fn parse_hex(text: &str) -> Result<Self, String> {
+        let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if digits.is_empty() {
+            return Err("Empty byte pattern".to_string());
+        }
+        if !digits.len().is_multiple_of(2) {
+            return Err(format!("Odd number of hex digits in '{}'", text));
+        }
+        let bytes = digits
+            .chunks(2)
+            .map(|pair| match pair {
+                ['?', '?'] => Ok(None),
+                [hi, lo] => match (hi.to_digit(16), lo.to_digit(16)) {
+                    (Some(hi), Some(lo)) => Ok(Some((hi * 16 + lo) as u8)),
+                    _ => Err(format!("'{}{}' is not a hex byte or ??", hi, lo)),
+                },
+                _ => unreachable!(),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(BytePattern(bytes))
+    }
+
+    /// Exactly these bytes.
+    pub fn exact(bytes: &[u8]) -> Self {
+        BytePattern(bytes.iter().copied().map(Some).collect())
+    }
+
+    pub fn matches_at(&self, data: &[u8], offset: usize) -> bool {
+        let Some(window) = offset
+            .checked_add(self.0.len())
+            .and_then(|end| data.get(offset..end))
+        else {
+            return false;
+        };
+        self.0
+            .iter()
+            .zip(window)
+            .all(|(want, &got)| want.is_none_or(|want| want == got))
+    }
+
+    /// Offset of the first match at or after `from`.
+    pub fn find(&self, data: &[u8], from: usize) -> Option<usize> {
+        if data.len() < self.0.len() {
+            return None;
+        }
+        let last = data.len() - self.0.len();
+        // Jump between occurrences of the first fixed byte instead of
+        // trying every offset.
+        let anchor = self.0.iter().position(Option::is_some);
+        let mut offset = from;
+        while offset <= last {
+            if let Some(index) = anchor {
+                let byte = self.0[index];
+                let skip = data[offset + index..=last + index]
+                    .iter()
+                    .position(|&b| Some(b) == byte)?;
+                offset += skip;
+            }
+            if self.matches_at(data, offset) {
+                return Some(offset);
+            }
+            offset += 1;
+        }
+        None
+    }
+
+    /// Every match offset, overlapping ones included.
+    pub fn find_all(&self, data: &[u8]) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        let mut from = 0;
+        while let Some(offset) = self.find(data, from) {
+            offsets.push(offset);
+            from = offset + 1;
+        }
+        offsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_with_wildcards() {
+        let pattern = BytePattern::parse_hex("de ?? EF").unwrap();
+        let data = [0x00, 0xDE, 0x01, 0xEF, 0xDE, 0x02, 0xEF];
+        assert!(pattern.matches_at(&data, 1));
+        assert!(!pattern.matches_at(&data, 0));
+        assert_eq!(pattern.find_all(&data), [1, 4]);
+    }
+
+    #[test]
+    fn offsets_past_the_end_do_not_match() {
+        let pattern = BytePattern::exact(&[1, 2]);
+        assert!(!pattern.matches_at(&[1, 2], 1));
+        assert!(!pattern.matches_at(&[1, 2], usize::MAX));
+    }
+}
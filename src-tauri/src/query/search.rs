@@ -0,0 +1,77 @@
+//! Byte-pattern search over packet payloads.
+
+use super::par_scan;
+use super::pattern::BytePattern;
+use super::syntax::parse_u64;
+use crate::state::Session;
+use serde::{Deserialize, Serialize};
+
+/// What to look for. GUIDs are strings because JSON numbers can't hold
+/// every 64-bit value; they take decimal or `0x` hex.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum SearchPattern {
+    /// Hex with `??` wildcards, e.g. `DE AD ?? EF`.
+    Hex(String),
+    U32(u32),
+    /// Matched bit for bit, so it only finds the exact float.
+    F32(f32),
+    Guid(String),
+    /// A GUID as its mask byte and non-zero bytes.
+    PackedGuid(String),
+}
+
+impl SearchPattern {
+    pub fn compile(&self) -> Result<BytePattern, String> {
+        match self {
+            SearchPattern::Hex(text) => BytePattern::parse_hex(text),
+            SearchPattern::U32(value) => Ok(BytePattern::exact(&value.to_le_bytes())),
+            SearchPattern::F32(value) => Ok(BytePattern::exact(&value.to_le_bytes())),
+            SearchPattern::Guid(text) => Ok(BytePattern::exact(&parse_guid(text)?.to_le_bytes())),
+            SearchPattern::PackedGuid(text) => {
+                Ok(BytePattern::exact(&pack_guid(parse_guid(text)?)))
+            }
+        }
+    }
+}
+
+pub fn parse_guid(text: &str) -> Result<u64, String> {
+    parse_u64(text.trim()).ok_or_else(|| format!("'{}' is not a GUID", text))
+}
+
+pub(super) fn pack_guid(guid: u64) -> Vec<u8> {
+    let mut packed = vec![0];
+    for (i, byte) in guid.to_le_bytes().into_iter().enumerate() {
+        if byte != 0 {
+            packed[0] |= 1 << i;
+            packed.push(byte);
+        }
+    }
+    packed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub packet_id: usize,
+    /// Where the pattern starts in the payload; overlapping matches count.
+    pub offsets: Vec<usize>,
+}
+
+/// Matches in one session. Library searches name the file they read.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionHits {
+    pub session_id: String,
+    pub session_name: String,
+    pub file_path: Option<String>,
+    pub hits: Vec<SearchHit>,
+}
+
+pub fn search_session(pattern: &BytePattern, session: &Session) -> Vec<SearchHit> {
+    par_scan(&session.packets, |packet| {
+        let offsets = pattern.find_all(&packet.data);
+        (!offsets.is_empty()).then_some(SearchHit {
+            packet_id: packet.id,
+            offsets,
+        })
+    })
+}
@@ -0,0 +1,410 @@
+//! Recursive-descent parser for the query language described in the
+//! module docs.
+
+use super::pattern::BytePattern;
+use super::{CmpOp, Literal, Node, OpcodeMatch, Range};
+use crate::state::Direction;
+
+/// Characters that end a bare word.
+const DELIMITERS: &[char] = &['(', ')', ':', '@', '"', '=', '!', '<', '>', '~', '&', '|'];
+
+/// Comparison operators, longest first so `<=` wins over `<`.
+const OPERATORS: &[(&str, CmpOp)] = &[
+    ("==", CmpOp::Eq),
+    ("!=", CmpOp::Ne),
+    ("<=", CmpOp::Le),
+    (">=", CmpOp::Ge),
+    ("=", CmpOp::Eq),
+    ("<", CmpOp::Lt),
+    (">", CmpOp::Gt),
+    ("~", CmpOp::Contains),
+];
+
+pub(super) fn parse(text: &str) -> Result<Node, String> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.skip_ws();
+    if parser.at_end() {
+        return Ok(Node::And(Vec::new()));
+    }
+    let node = parser.parse_or()?;
+    parser.skip_ws();
+    if !parser.at_end() {
+        return Err(parser.error("Unexpected input"));
+    }
+    Ok(node)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, message: &str) -> String {
+        let column = self.text[..self.pos].chars().count() + 1;
+        format!("{} at column {}", message, column)
+    }
+
+    /// Consume `token` if the input continues with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek_word(&self) -> &'a str {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || DELIMITERS.contains(&c))
+            .unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    fn at_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_ws();
+        self.peek_word().eq_ignore_ascii_case(keyword)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.at_keyword(keyword) {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Node, String> {
+        let mut terms = vec![self.parse_and()?];
+        while self.eat("||") || self.keyword("or") {
+            terms.push(self.parse_and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Node::Or(terms)
+        })
+    }
+
+    /// Terms side by side are joined with an implicit `and`.
+    fn parse_and(&mut self) -> Result<Node, String> {
+        let mut terms = vec![self.parse_not()?];
+        loop {
+            if self.eat("&&") || self.keyword("and") {
+                terms.push(self.parse_not()?);
+                continue;
+            }
+            self.skip_ws();
+            let done = self.at_end()
+                || self.peek() == Some(')')
+                || self.rest().starts_with("||")
+                || self.at_keyword("or");
+            if done {
+                break;
+            }
+            terms.push(self.parse_not()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Node::And(terms)
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<Node, String> {
+        if self.eat("!") || self.keyword("not") {
+            return Ok(Node::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        if self.eat("(") {
+            let node = self.parse_or()?;
+            if !self.eat(")") {
+                return Err(self.error("Expected ')'"));
+            }
+            return Ok(node);
+        }
+        self.skip_ws();
+        let word = self.peek_word();
+        if word.is_empty() || ["and", "or"].iter().any(|k| word.eq_ignore_ascii_case(k)) {
+            return Err(self.error("Expected a filter"));
+        }
+        self.pos += word.len();
+
+        match self.peek() {
+            Some(':') => {
+                self.pos += 1;
+                return self.parse_keyed(word, None);
+            }
+            Some('@') => {
+                self.pos += 1;
+                let offset = self.peek_word();
+                let Some(offset) = parse_u64(offset) else {
+                    return Err(self.error("Expected a byte offset after '@'"));
+                };
+                self.pos += self.peek_word().len();
+                if !self.eat(":") {
+                    return Err(self.error("Expected ':' after the offset"));
+                }
+                return self.parse_keyed(word, Some(offset as usize));
+            }
+            _ => {}
+        }
+
+        self.skip_ws();
+        for &(token, op) in OPERATORS {
+            if self.rest().starts_with(token) {
+                self.pos += token.len();
+                let value = self.take_value()?;
+                return Ok(Node::Field {
+                    path: word.split('.').map(str::to_string).collect(),
+                    op,
+                    value: Literal::new(value.text, value.quoted),
+                });
+            }
+        }
+        Ok(Node::Opcode(OpcodeMatch::Glob(word.to_string())))
+    }
+
+    fn parse_keyed(&mut self, key: &str, at: Option<usize>) -> Result<Node, String> {
+        let value = self.take_value()?.text;
+        let key = key.to_ascii_lowercase();
+        if at.is_some() && key != "bytes" {
+            return Err(self.error("Only bytes filters take an '@' offset"));
+        }
+        let invalid = |what: &str| self.error(&format!("'{}' is not a valid {}", value, what));
+        match key.as_str() {
+            "opcode" | "op" => Ok(Node::Opcode(match parse_u64(&value) {
+                Some(opcode) => OpcodeMatch::Number(opcode as u32),
+                None => OpcodeMatch::Glob(value),
+            })),
+            "dir" | "direction" => match value.to_ascii_lowercase().as_str() {
+                "smsg" | "s2c" | "server" => Ok(Node::Direction(Direction::ServerToClient)),
+                "cmsg" | "c2s" | "client" => Ok(Node::Direction(Direction::ClientToServer)),
+                _ => Err(invalid("direction (smsg or cmsg)")),
+            },
+            "size" => parse_range(&value, parse_u64)
+                .map(Node::Size)
+                .ok_or_else(|| invalid("size range")),
+            "id" => parse_range(&value, parse_u64)
+                .map(Node::Id)
+                .ok_or_else(|| invalid("id range")),
+            "time" => parse_range(&value, parse_duration)
+                .map(Node::Time)
+                .ok_or_else(|| invalid("time range")),
+            "bytes" => BytePattern::parse_hex(&value)
+                .map(|pattern| Node::Bytes { pattern, at })
+                .map_err(|e| self.error(&e)),
+            _ => Err(self.error(&format!(
+                "Unknown filter '{}'; expected opcode, dir, size, time, id or bytes",
+                key
+            ))),
+        }
+    }
+
+    /// A quoted string, or everything up to whitespace or `)`.
+    fn take_value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        if self.peek() == Some('"') {
+            self.pos += 1;
+            let mut text = String::new();
+            let mut chars = self.rest().char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.pos += i + 1;
+                        return Ok(Value { text, quoted: true });
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, escaped)) => text.push(escaped),
+                        None => break,
+                    },
+                    c => text.push(c),
+                }
+            }
+            return Err(self.error("Unterminated string"));
+        }
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == ')')
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error("Expected a value"));
+        }
+        self.pos += end;
+        Ok(Value {
+            text: rest[..end].to_string(),
+            quoted: false,
+        })
+    }
+}
+
+struct Value {
+    text: String,
+    quoted: bool,
+}
+
+/// Decimal, or hex with a `0x` prefix.
+pub(super) fn parse_u64(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Milliseconds, with an optional `ms`, `s` or `m` unit.
+fn parse_duration(text: &str) -> Option<u64> {
+    let (number, scale) = if let Some(n) = text.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = text.strip_suffix('s') {
+        (n, 1000.0)
+    } else if let Some(n) = text.strip_suffix('m') {
+        (n, 60_000.0)
+    } else {
+        (text, 1.0)
+    };
+    let value: f64 = number.parse().ok()?;
+    (value >= 0.0).then_some((value * scale) as u64)
+}
+
+/// `a..b`, `a..`, `..b`, `>a`, `>=a`, `<b`, `<=b`, `=a` or `a`; bounds are
+/// inclusive.
+fn parse_range(text: &str, value: fn(&str) -> Option<u64>) -> Option<Range> {
+    if let Some((min, max)) = text.split_once("..") {
+        let bound = |s: &str| {
+            if s.is_empty() {
+                Some(None)
+            } else {
+                value(s).map(Some)
+            }
+        };
+        return Some(Range {
+            min: bound(min)?,
+            max: bound(max)?,
+        });
+    }
+    let range = if let Some(v) = text.strip_prefix(">=") {
+        Range::at_least(value(v)?)
+    } else if let Some(v) = text.strip_prefix('>') {
+        Range::at_least(value(v)?.checked_add(1)?)
+    } else if let Some(v) = text.strip_prefix("<=") {
+        Range::at_most(value(v)?)
+    } else if let Some(v) = text.strip_prefix('<') {
+        match value(v)?.checked_sub(1) {
+            Some(max) => Range::at_most(max),
+            // Nothing is below zero.
+            None => Range {
+                min: Some(1),
+                max: Some(0),
+            },
+        }
+    } else {
+        let v = value(text.strip_prefix('=').unwrap_or(text))?;
+        Range {
+            min: Some(v),
+            max: Some(v),
+        }
+    };
+    Some(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The tree's shape, with opcode globs as their text.
+    fn shape(node: &Node) -> String {
+        let list = |op: &str, nodes: &[Node]| {
+            let nodes: Vec<String> = nodes.iter().map(shape).collect();
+            format!("({} {})", op, nodes.join(" "))
+        };
+        match node {
+            Node::And(nodes) => list("and", nodes),
+            Node::Or(nodes) => list("or", nodes),
+            Node::Not(node) => format!("(not {})", shape(node)),
+            Node::Opcode(OpcodeMatch::Glob(glob)) => glob.clone(),
+            Node::Opcode(OpcodeMatch::Number(opcode)) => format!("#{}", opcode),
+            Node::Direction(_) => "dir".to_string(),
+            Node::Field { path, .. } => path.join("."),
+            _ => "?".to_string(),
+        }
+    }
+
+    fn parsed(text: &str) -> String {
+        shape(&parse(text).unwrap())
+    }
+
+    fn range(text: &str) -> Option<(Option<u64>, Option<u64>)> {
+        parse_range(text, parse_u64).map(|r| (r.min, r.max))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parsed("a or b c"), "(or a (and b c))");
+        assert_eq!(parsed("a && b || c"), "(or (and a b) c)");
+        assert_eq!(parsed("a and (b or c)"), "(and a (or b c))");
+        assert_eq!(parsed("OR_NAME_LIKE or b"), "(or OR_NAME_LIKE b)");
+    }
+
+    #[test]
+    fn not_applies_to_one_term() {
+        assert_eq!(parsed("not a b"), "(and (not a) b)");
+        assert_eq!(parsed("!!a"), "(not (not a))");
+        assert_eq!(parsed("!(a b)"), "(not (and a b))");
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(parsed(""), "(and )");
+        assert_eq!(parsed("opcode:0x15F dir:smsg"), "(and #351 dir)");
+        assert_eq!(parsed("items.item=2589"), "items.item");
+        assert!(parse("a or").is_err());
+        assert!(parse("(a b").is_err());
+        assert!(parse("size:big").is_err());
+        assert!(parse("opcode@4:1").is_err());
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(range("16..64"), Some((Some(16), Some(64))));
+        assert_eq!(range("..5000"), Some((None, Some(5000))));
+        assert_eq!(range("10.."), Some((Some(10), None)));
+        assert_eq!(range(">100"), Some((Some(101), None)));
+        assert_eq!(range(">=100"), Some((Some(100), None)));
+        assert_eq!(range("<=0x10"), Some((None, Some(16))));
+        assert_eq!(range("=7"), Some((Some(7), Some(7))));
+        assert_eq!(range("7"), Some((Some(7), Some(7))));
+        assert_eq!(range("1..x"), None);
+        assert_eq!(range(&format!(">{}", u64::MAX)), None);
+    }
+
+    #[test]
+    fn nothing_is_below_zero() {
+        let empty = parse_range("<0", parse_u64).unwrap();
+        assert!(!(0..10).any(|v| empty.contains(v)));
+        assert_eq!(range("<1"), Some((None, Some(0))));
+    }
+}
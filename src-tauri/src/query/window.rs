@@ -0,0 +1,257 @@
+//! Filtered, sorted pages of packet summaries for a virtualized list.
+
+use super::collapse::{self, Collapse};
+use super::Query;
+use crate::parser;
+use crate::state::{Packet, PacketSummary, Session};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Capture order.
+    #[default]
+    Id,
+    Timestamp,
+    Direction,
+    Opcode,
+    OpcodeName,
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct PacketSort {
+    #[serde(default)]
+    pub key: SortKey,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+impl PacketSort {
+    /// Ties fall back to capture order, whichever way the sort runs.
+    fn compare(&self, build: Option<u32>, a: &Packet, b: &Packet) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Id => Ordering::Equal,
+            SortKey::Timestamp => a.timestamp.cmp(&b.timestamp),
+            SortKey::Direction => (a.direction as u8).cmp(&(b.direction as u8)),
+            SortKey::Opcode => a.opcode.cmp(&b.opcode),
+            SortKey::OpcodeName => parser::opcode_name(build, a).cmp(parser::opcode_name(build, b)),
+            SortKey::Size => a.size.cmp(&b.size),
+        };
+        let ordering = ordering.then(a.id.cmp(&b.id));
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Several consecutive packets shown as one row.
+#[derive(Debug, Clone, Serialize)]
+pub struct Run {
+    pub count: usize,
+    pub last_id: usize,
+    pub last_timestamp: u32,
+    pub span_ms: u32,
+}
+
+/// A packet, or the first packet of a collapsed run.
+#[derive(Debug, Clone, Serialize)]
+pub struct PacketRow {
+    #[serde(flatten)]
+    pub summary: PacketSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<Run>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PacketWindow {
+    /// Rows matching the filter, not just the ones in this window.
+    pub total: usize,
+    pub offset: usize,
+    pub packets: Vec<PacketRow>,
+}
+
+/// What a set of window rows was built from. Rows stay valid while the
+/// request and the session's packets are unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowKey {
+    filter: String,
+    sort: PacketSort,
+    collapse: Option<Collapse>,
+    build: Option<u32>,
+    packets: usize,
+    next_packet_id: usize,
+}
+
+impl WindowKey {
+    pub fn new(
+        session: &Session,
+        filter: &str,
+        sort: PacketSort,
+        collapse: Option<Collapse>,
+    ) -> Self {
+        WindowKey {
+            filter: filter.to_string(),
+            sort,
+            collapse,
+            build: session.build,
+            packets: session.packets.len(),
+            next_packet_id: session.next_packet_id,
+        }
+    }
+}
+
+/// Every row of the filtered, sorted list as packet ids. Built once per
+/// request shape so scrolling only slices it.
+#[derive(Debug)]
+pub struct WindowRows {
+    key: WindowKey,
+    rows: Vec<(usize, Option<Run>)>,
+}
+
+impl WindowRows {
+    /// Filter and sort the whole session. With `collapse`, runs are found
+    /// in capture order before sorting.
+    pub fn new(session: &Session, filter: &Query, key: WindowKey) -> Self {
+        let matching: Vec<&Packet> = filter.matching(session).collect();
+        let mut rows: Vec<(&Packet, Option<Run>)> = match key.collapse {
+            None => matching.iter().map(|&p| (p, None)).collect(),
+            Some(mode) => collapse::runs(&matching, mode, session.build)
+                .into_iter()
+                .map(|range| {
+                    let (first, last) = (matching[range.start], matching[range.end - 1]);
+                    let run = (range.len() > 1).then(|| Run {
+                        count: range.len(),
+                        last_id: last.id,
+                        last_timestamp: last.timestamp,
+                        span_ms: last.timestamp.saturating_sub(first.timestamp),
+                    });
+                    (first, run)
+                })
+                .collect(),
+        };
+        let sort = key.sort;
+        if sort != PacketSort::default() {
+            rows.sort_unstable_by(|a, b| sort.compare(session.build, a.0, b.0));
+        }
+        WindowRows {
+            key,
+            rows: rows.into_iter().map(|(p, run)| (p.id, run)).collect(),
+        }
+    }
+
+    pub fn key(&self) -> &WindowKey {
+        &self.key
+    }
+
+    /// Rows `offset..offset + limit`. `session` must be the one the rows
+    /// were built from.
+    pub fn window(&self, session: &Session, offset: usize, limit: usize) -> PacketWindow {
+        let total = self.rows.len();
+        let end = offset.saturating_add(limit).min(total);
+        let offset = offset.min(end);
+        PacketWindow {
+            total,
+            offset,
+            packets: self.rows[offset..end]
+                .iter()
+                .filter_map(|(id, run)| {
+                    Some(PacketRow {
+                        summary: PacketSummary::from(session.packet(*id)?),
+                        run: run.clone(),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The packets behind a collapsed row. Runs are consecutive in the
+/// filtered list, so the filtered packets between the run's ids are
+/// exactly its members.
+pub fn expand_run(
+    session: &Session,
+    filter: &Query,
+    first_id: usize,
+    last_id: usize,
+) -> Vec<PacketSummary> {
+    filter
+        .matching(session)
+        .filter(|p| (first_id..=last_id).contains(&p.id))
+        .map(PacketSummary::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::fixture::session;
+
+    fn ids(window: &PacketWindow) -> Vec<usize> {
+        window.packets.iter().map(|p| p.summary.id).collect()
+    }
+
+    fn rows(session: &Session, filter: &str, sort: PacketSort) -> WindowRows {
+        let key = WindowKey::new(session, filter, sort, None);
+        WindowRows::new(session, &Query::parse(filter).unwrap(), key)
+    }
+
+    #[test]
+    fn opcode_names_sort_by_the_sessions_build() {
+        let mut session = session(
+            5875,
+            vec![
+                ("SMSG_UPDATE_OBJECT", Vec::new()),
+                ("CMSG_PING", Vec::new()),
+                ("SMSG_AUTH_CHALLENGE", Vec::new()),
+            ],
+        );
+        // Names stored at capture time can be stale or missing.
+        session.packets[1].opcode_name = "UNKNOWN".to_string();
+        let sort = PacketSort {
+            key: SortKey::OpcodeName,
+            descending: false,
+        };
+        let rows = rows(&session, "", sort);
+        assert_eq!(ids(&rows.window(&session, 0, 10)), [1, 2, 0]);
+    }
+
+    #[test]
+    fn windows_slice_the_sorted_rows() {
+        let packets = (0..20u8)
+            .map(|i| ("CMSG_PING", vec![0; usize::from(i % 7)]))
+            .collect();
+        let session = session(5875, packets);
+        let sort = PacketSort {
+            key: SortKey::Size,
+            descending: true,
+        };
+        let rows = rows(&session, "size:>2", sort);
+        let mut want: Vec<&Packet> = session.packets.iter().filter(|p| p.size > 2).collect();
+        want.sort_by(|a, b| b.size.cmp(&a.size).then(b.id.cmp(&a.id)));
+        for (offset, limit) in [(0, 5), (4, 3), (10, 100), (100, 5)] {
+            let window = rows.window(&session, offset, limit);
+            assert_eq!(window.total, want.len());
+            let expected: Vec<usize> = want.iter().skip(offset).take(limit).map(|p| p.id).collect();
+            assert_eq!(ids(&window), expected, "{}..+{}", offset, limit);
+        }
+    }
+
+    #[test]
+    fn keys_follow_the_request_and_packets() {
+        let mut session = session(5875, vec![("CMSG_PING", Vec::new())]);
+        let key = |session: &Session, filter: &str| {
+            WindowKey::new(session, filter, PacketSort::default(), None)
+        };
+        let before = key(&session, "");
+        assert_eq!(before, key(&session, ""));
+        assert_ne!(before, key(&session, "size:>0"));
+        session.packets.push(session.packets[0].clone());
+        session.packets[1].id = 1;
+        session.next_packet_id = 2;
+        assert_ne!(before, key(&session, ""));
+    }
+}
@@ -1,8 +1,10 @@
+use crate::analysis::timeline::Timeline;
+use crate::query::window::WindowRows;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     #[serde(rename = "SMSG")]
     ServerToClient = 0,
@@ -55,11 +57,82 @@ impl From<&Packet> for PacketSummary {
     }
 }
 
+/// Packets and payload bytes the capture DLL dropped, per direction, and
+/// what the reader lost to a corrupt ring, whose direction is unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureDrops {
+    pub server_packets: u32,
+    pub server_bytes: u32,
+    pub client_packets: u32,
+    pub client_bytes: u32,
+    #[serde(default)]
+    pub lost_packets: u32,
+    #[serde(default)]
+    pub lost_bytes: u32,
+}
+
+impl CaptureDrops {
+    pub fn packets(&self) -> u64 {
+        self.server_packets as u64 + self.client_packets as u64 + self.lost_packets as u64
+    }
+}
+
+/// A hole in a capture: packets were dropped between `before_packet_id - 1`
+/// and `before_packet_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureGap {
+    pub before_packet_id: usize,
+    pub timestamp: u32,
+    #[serde(flatten)]
+    pub drops: CaptureDrops,
+}
+
+/// What to capture on attach. Unset fields take the DLL's defaults; the
+/// effective options read back from the DLL have every field set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureOptions {
+    /// Ring buffer size in bytes.
+    pub ring_size: Option<u32>,
+    /// Largest packet payload to keep; bigger packets are dropped.
+    pub max_packet_size: Option<u32>,
+    /// Directions to capture.
+    pub directions: Option<Vec<Direction>>,
+}
+
+/// How the capture DLL's opcode filter treats its opcodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMode {
+    /// Capture every opcode.
+    #[default]
+    Off,
+    /// Capture only the listed opcodes.
+    Only,
+    /// Capture every opcode but the listed ones.
+    Except,
+}
+
+/// The capture DLL's own state and counters, as it reports them.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CaptureStatus {
+    pub paused: bool,
+    pub hooks_active: bool,
+    pub filter_mode: FilterMode,
+    pub filter_len: u32,
+    /// Packets the DLL wrote to the ring since it loaded.
+    pub captured: u32,
+    /// Packets the opcode filter kept out since it loaded.
+    pub filtered: u32,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AttachedProcess {
     pub pid: u32,
     pub build: u32,
     pub version_name: String,
+    /// The options the DLL is capturing with.
+    pub capture: CaptureOptions,
 }
 
 pub type SessionId = String;
@@ -73,6 +146,9 @@ pub struct Session {
     pub build: Option<u32>,
     pub packets: Vec<Packet>,
     pub next_packet_id: usize,
+    /// Where the capture lost packets, in capture order.
+    #[serde(default)]
+    pub gaps: Vec<CaptureGap>,
 }
 
 impl Session {
@@ -84,8 +160,25 @@ impl Session {
             build: None,
             packets: Vec::new(),
             next_packet_id: 0,
+            gaps: Vec::new(),
         }
     }
+
+    /// The packet with `id`. Ids are handed out in capture order, so this is
+    /// a direct index unless packets were dropped from the middle.
+    pub fn packet(&self, id: usize) -> Option<&Packet> {
+        let first = self.packets.first()?.id;
+        let direct = id
+            .checked_sub(first)
+            .and_then(|index| self.packets.get(index))
+            .filter(|p| p.id == id);
+        direct.or_else(|| {
+            self.packets
+                .binary_search_by_key(&id, |p| p.id)
+                .ok()
+                .map(|index| &self.packets[index])
+        })
+    }
 }
 
 /// Lightweight session metadata without packet data.
@@ -96,6 +189,8 @@ pub struct SessionInfo {
     pub created_at: String,
     pub packet_count: usize,
     pub build: Option<u32>,
+    /// Packets the capture lost, summed over every gap.
+    pub dropped_packets: u64,
 }
 
 impl From<&Session> for SessionInfo {
@@ -106,15 +201,25 @@ impl From<&Session> for SessionInfo {
             created_at: s.created_at.clone(),
             packet_count: s.packets.len(),
             build: s.build,
+            dropped_packets: s.gaps.iter().map(|g| g.drops.packets()).sum(),
         }
     }
 }
 
 pub struct AppState {
-    pub sessions: Mutex<HashMap<SessionId, Session>>,
+    /// Shared so long scans can work on a snapshot outside the lock; writers
+    /// go through `Arc::make_mut`, which copies a session only while a
+    /// snapshot of it is out.
+    pub sessions: Mutex<HashMap<SessionId, Arc<Session>>>,
     pub active_session_id: Mutex<Option<SessionId>>,
     pub attached: Mutex<Option<AttachedProcess>>,
     pub capturing: Mutex<bool>,
+    /// Traffic timelines per session, extended as packets arrive.
+    pub timelines: Mutex<HashMap<SessionId, Timeline>>,
+    /// The rows behind the last packet window requested per session.
+    pub packet_windows: Mutex<HashMap<SessionId, Arc<WindowRows>>>,
+    /// What the DLL of the current attach has dropped so far.
+    pub capture_drops: Mutex<CaptureDrops>,
 }
 
 impl AppState {
@@ -122,12 +227,15 @@ impl AppState {
         let default_session = Session::new("Untitled");
         let default_id = default_session.id.clone();
         let mut sessions = HashMap::new();
-        sessions.insert(default_id.clone(), default_session);
+        sessions.insert(default_id.clone(), Arc::new(default_session));
         AppState {
             sessions: Mutex::new(sessions),
             active_session_id: Mutex::new(Some(default_id)),
             attached: Mutex::new(None),
             capturing: Mutex::new(false),
+            timelines: Mutex::new(HashMap::new()),
+            packet_windows: Mutex::new(HashMap::new()),
+            capture_drops: Mutex::new(CaptureDrops::default()),
         }
     }
 }